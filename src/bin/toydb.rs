@@ -18,6 +18,7 @@ use toydb::Server;
 use clap::Parser as _;
 use serde::Deserialize;
 use std::collections::HashMap;
+use std::time::Duration;
 
 fn main() {
     if let Err(error) = Command::parse().run() {
@@ -50,10 +51,20 @@ struct Config {
     /// will yield much better write performance, but may lose data on
     /// host crashes which compromises Raft safety guarantees.
     fsync: bool,
+    /// If non-zero, fsync Raft log writes at most once per this many
+    /// milliseconds instead of on every write. Writes since the last fsync
+    /// may be lost on host crashes.
+    fsync_interval: u64,
     /// The garbage fraction threshold at which to trigger compaction.
     compact_threshold: f64,
     /// The minimum bytes of garbage before triggering compaction.
     compact_min_bytes: u64,
+    /// The maximum number of read-only query results to cache, or 0 to
+    /// disable the result cache.
+    result_cache_size: usize,
+    /// The directory that COPY reads and writes files in. Client paths are
+    /// relative to it. Empty disables COPY.
+    copy_dir: String,
 }
 
 impl Config {
@@ -68,8 +79,11 @@ impl Config {
             .set_default("storage_raft", "bitcask")?
             .set_default("storage_sql", "bitcask")?
             .set_default("fsync", true)?
+            .set_default("fsync_interval", 0)?
             .set_default("compact_threshold", 0.2)?
             .set_default("compact_min_bytes", 1_000_000)?
+            .set_default("result_cache_size", 0)?
+            .set_default("copy_dir", "")?
             .add_source(config::File::with_name(file))
             .add_source(config::Environment::with_prefix("TOYDB"))
             .build()?
@@ -104,11 +118,17 @@ impl Command {
         let datadir = std::path::Path::new(&cfg.data_dir);
         let mut raft_log = match cfg.storage_raft.as_str() {
             "bitcask" | "" => {
+                let durability = match (cfg.fsync, cfg.fsync_interval) {
+                    (false, _) => storage::Durability::Never,
+                    (true, 0) => storage::Durability::Always,
+                    (true, ms) => storage::Durability::Interval(Duration::from_millis(ms)),
+                };
                 let engine = storage::BitCask::new_compact(
                     datadir.join("raft"),
                     cfg.compact_threshold,
                     cfg.compact_min_bytes,
-                )?;
+                )?
+                .with_durability(durability);
                 raft::Log::new(Box::new(engine))?
             }
             "memory" => raft::Log::new(Box::new(storage::Memory::new()))?,
@@ -131,7 +151,11 @@ impl Command {
         };
 
         // Start the server.
-        Server::new(cfg.id, cfg.peers, raft_log, raft_state)?
-            .serve(&cfg.listen_raft, &cfg.listen_sql)
+        let mut server = Server::new(cfg.id, cfg.peers, raft_log, raft_state)?;
+        server.enable_result_cache(cfg.result_cache_size);
+        if !cfg.copy_dir.is_empty() {
+            server.enable_copy(cfg.copy_dir.into());
+        }
+        server.serve(&cfg.listen_raft, &cfg.listen_sql)
     }
 }
@@ -28,7 +28,7 @@ fn main() {
 #[derive(clap::Parser)]
 #[command(about = "A toyDB client.", version, propagate_version = true)]
 struct Command {
-    /// A SQL statement to execute, then exit.
+    /// SQL statements to execute, separated by semicolons, then exit.
     #[arg()]
     statement: Option<String>,
     /// Host to connect to.
@@ -116,6 +116,7 @@ exit. The following commands are also available:
 
     !headers           Toggles column headers
     !help              This help message
+    !node              Display the server's local Raft node status
     !status            Display server status
     !table NAME        Display a table schema
     !tables            List tables
@@ -123,6 +124,29 @@ exit. The following commands are also available:
             ),
             ("!help", _) => return errinput!("!help takes no arguments"),
 
+            // Displays the server's local Raft node status.
+            ("!node", []) => {
+                let status = self.client.node_status()?;
+                let last_applied = status.last_applied.and_then(|t| t.elapsed().ok());
+                println!(
+                    r#"
+Node:         n{id} is {role} in term {term} with leader {leader}
+Raft log:     {committed} committed, {applied} applied, last applied {last_applied}
+"#,
+                    id = status.id,
+                    role = status.role,
+                    term = status.term,
+                    leader = status.leader.map_or("none".to_string(), |id| format!("n{id}")),
+                    committed = status.commit_index,
+                    applied = status.applied_index,
+                    last_applied = match last_applied {
+                        Some(elapsed) => format!("{:.3}s ago", elapsed.as_secs_f64()),
+                        None => "never".to_string(),
+                    },
+                )
+            }
+            ("!node", _) => return errinput!("!node takes no arguments"),
+
             // Displays server status.
             ("!status", []) => {
                 let status = self.client.status()?;
@@ -133,6 +157,7 @@ Raft log:     {committed} committed, {applied} applied, {raft_size} MB, {raft_ga
 Replication:  {raft_match}
 SQL storage:  {sql_keys} keys, {sql_size} MB logical, {nodes}x {sql_disk_size} MB disk, {sql_garbage}% garbage ({sql_storage} engine)
 Transactions: {active_txns} active, {versions} total
+Result cache: {result_cache}
 "#,
                     server = status.server,
                     leader = status.raft.leader,
@@ -155,6 +180,13 @@ Transactions: {active_txns} active, {versions} total
                     sql_storage = status.mvcc.storage.name,
                     active_txns = status.mvcc.active_txns,
                     versions = status.mvcc.versions,
+                    result_cache = match status.result_cache {
+                        Some(c) => format!(
+                            "{}/{} entries, {} hits, {} misses",
+                            c.entries, c.capacity, c.hits, c.misses
+                        ),
+                        None => "disabled".to_string(),
+                    },
                 )
             }
             ("!status", _) => return errinput!("!status takes no arguments"),
@@ -170,25 +202,64 @@ Transactions: {active_txns} active, {versions} total
         Ok(())
     }
 
-    /// Executes a SQL statement and displays the results.
-    fn execute_sql(&mut self, statement: &str) -> Result<()> {
+    /// Executes SQL statements separated by semicolons and displays the
+    /// results. If a statement fails, the results of the prior statements are
+    /// displayed before the error, since they may have taken effect.
+    fn execute_sql(&mut self, script: &str) -> Result<()> {
+        for result in self.client.execute_script(script)? {
+            self.print_result(result?);
+        }
+        Ok(())
+    }
+
+    /// Displays a SQL statement result.
+    fn print_result(&self, result: StatementResult) {
         use StatementResult::*;
-        match self.client.execute(statement)? {
+        match result {
             Begin(state) => match state.read_only {
                 true => println!("Began read-only transaction at version {}", state.version),
                 false => println!("Began transaction {}", state.version),
             },
             Commit { version } => println!("Committed transaction {version}"),
             Rollback { version } => println!("Rolled back transaction {version}"),
+            Savepoint { name } => println!("Created savepoint {name}"),
+            RollbackToSavepoint { name } => println!("Rolled back to savepoint {name}"),
+            ReleaseSavepoint { name } => println!("Released savepoint {name}"),
+            Set { name, value } => println!("Set {name} to {value}"),
+            Copy { count } => println!("Copied {count} rows"),
             Insert { count } => println!("Inserted {count} rows"),
             Delete { count } => println!("Deleted {count} rows"),
             Update { count } => println!("Updated {count} rows"),
-            CreateTable { name } => println!("Created table {name}"),
-            DropTable { name, existed } => match existed {
-                true => println!("Dropped table {name}"),
+            Truncate { count } => println!("Truncated {count} rows"),
+            Analyze { table, rows } => println!("Analyzed table {table} with {rows} rows"),
+            CreateTable { name, existed } => match existed {
+                true => println!("Table {name} already exists"),
+                false => println!("Created table {name}"),
+            },
+            DropTable { name, existed, cascaded } => match existed {
+                true if cascaded.is_empty() => println!("Dropped table {name}"),
+                true => println!("Dropped table {name} and dependent {}", cascaded.join(", ")),
                 false => println!("Table {name} does not exist"),
             },
-            Explain(plan) => println!("{plan}"),
+            AddColumn { table, column } => println!("Added column {table}.{column}"),
+            DropColumn { table, column } => println!("Dropped column {table}.{column}"),
+            CreateIndex { table, column } => println!("Created index {table}.{column}"),
+            DropIndex { name, existed } => match existed {
+                true => println!("Dropped index {name}"),
+                false => println!("Index {name} does not exist"),
+            },
+            Explain { plan, unoptimized, profile } => {
+                let plan = match profile {
+                    Some(profile) => plan.format_profile(&profile),
+                    None => plan.to_string(),
+                };
+                match unoptimized {
+                    Some(unoptimized) => {
+                        println!("Unoptimized:\n{unoptimized}\n\nOptimized:\n{plan}")
+                    }
+                    None => println!("{plan}"),
+                }
+            }
             Select { columns, rows } => {
                 if self.show_headers {
                     println!("{}", columns.iter().map(|c| c.as_header()).join(", "));
@@ -198,7 +269,6 @@ Transactions: {active_txns} active, {versions} total
                 }
             }
         }
-        Ok(())
     }
 
     /// Prompts the user for input. Returns None if the shell should close.
@@ -29,6 +29,8 @@ fn main() {
         Subcommand::Read(read) => runner.run(read),
         Subcommand::Write(write) => runner.run(write),
         Subcommand::Bank(bank) => runner.run(bank),
+        Subcommand::Join(join) => runner.run(join),
+        Subcommand::Sort(sort) => runner.run(sort),
     };
     if let Err(error) = result {
         eprintln!("Error: {error}")
@@ -51,6 +53,8 @@ enum Subcommand {
     Read(Read),
     Write(Write),
     Bank(Bank),
+    Join(Join),
+    Sort(Sort),
 }
 
 /// Runs a workload benchmark.
@@ -442,7 +446,7 @@ impl Workload for Bank {
             "INSERT INTO account VALUES {}",
             (1..=self.customers)
                 .flat_map(|c| (1..=self.accounts).map(move |a| (c, (c - 1) * self.accounts + a)))
-                .map(|(c, a)| (format!("({}, {}, {})", a, c, self.balance)))
+                .map(|(c, a)| format!("({}, {}, {})", a, c, self.balance))
                 .join(", ")
         ))?;
         client.execute("COMMIT")?;
@@ -515,3 +519,151 @@ impl Workload for Bank {
         Ok(())
     }
 }
+
+/// A join workload. Creates two tables join_left and join_right with the given
+/// row count, where each right row references a random left row, and runs
+/// full equijoins between them:
+///
+/// SELECT COUNT(*) FROM join_left l JOIN join_right r ON l.id = r.left_id
+///
+/// The optimizer will use a hash join for this. To compare with a nested loop
+/// join, --nested-loop uses the join predicate l.id = r.left_id + 0 instead.
+#[derive(clap::Args, Clone)]
+#[command(about = "A read-only workload joining two tables")]
+struct Join {
+    /// Number of rows in each table.
+    #[arg(short, long, default_value = "1000")]
+    rows: u64,
+
+    /// Use a nested loop join instead of a hash join.
+    #[arg(short, long)]
+    nested_loop: bool,
+}
+
+impl std::fmt::Display for Join {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = if self.nested_loop { "nested-loop" } else { "hash" };
+        write!(f, "join (rows={} join={})", self.rows, join)
+    }
+}
+
+impl Workload for Join {
+    type Item = (u64, bool); // rows,nested_loop
+
+    fn prepare(&self, client: &mut Client, rng: &mut StdRng) -> Result<()> {
+        client.execute("BEGIN")?;
+        client.execute("DROP TABLE IF EXISTS join_right")?;
+        client.execute("DROP TABLE IF EXISTS join_left")?;
+        client.execute("CREATE TABLE join_left (id INT PRIMARY KEY, value INT NOT NULL)")?;
+        client.execute(
+            "CREATE TABLE join_right (
+                    id INT PRIMARY KEY,
+                    left_id INT NOT NULL REFERENCES join_left
+                )",
+        )?;
+        for chunk in (1..=self.rows).chunks(100).into_iter() {
+            client.execute(&format!(
+                "INSERT INTO join_left VALUES {}",
+                chunk.map(|id| format!("({id}, {id})")).join(", ")
+            ))?;
+        }
+        let dist = rand::distributions::Uniform::new_inclusive(1, self.rows);
+        for chunk in (1..=self.rows).chunks(100).into_iter() {
+            client.execute(&format!(
+                "INSERT INTO join_right VALUES {}",
+                chunk.map(|id| format!("({id}, {})", dist.sample(rng))).join(", ")
+            ))?;
+        }
+        client.execute("COMMIT")?;
+        Ok(())
+    }
+
+    fn generate(&self, _: StdRng) -> impl Iterator<Item = Self::Item> + 'static {
+        std::iter::repeat((self.rows, self.nested_loop))
+    }
+
+    fn execute(client: &mut Client, item: &Self::Item) -> Result<()> {
+        let (rows, nested_loop) = *item;
+        let rhs = if nested_loop { "r.left_id + 0" } else { "r.left_id" };
+        let count: i64 = client
+            .execute(&format!(
+                "SELECT COUNT(*) FROM join_left l JOIN join_right r ON l.id = {rhs}"
+            ))?
+            .try_into()?;
+        assert_eq!(count as u64, rows, "Unexpected row count");
+        Ok(())
+    }
+}
+
+/// A sort workload. Creates a sort table with the given row count and random
+/// values, and fetches the rows with the lowest values:
+///
+/// SELECT * FROM sort ORDER BY value LIMIT 10
+///
+/// The optimizer will use a top-N sort for this, which only buffers the limit
+/// rows. To compare with a full sort, --full-sort omits the LIMIT clause and
+/// discards the remaining rows client-side (this includes the cost of
+/// transferring all rows).
+#[derive(clap::Args, Clone)]
+#[command(about = "A read-only workload sorting a table")]
+struct Sort {
+    /// Number of rows in the table.
+    #[arg(short, long, default_value = "10000")]
+    rows: u64,
+
+    /// Number of rows to fetch.
+    #[arg(short, long, default_value = "10")]
+    limit: usize,
+
+    /// Sort all rows instead of using a top-N sort.
+    #[arg(short, long)]
+    full_sort: bool,
+}
+
+impl std::fmt::Display for Sort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sort = if self.full_sort { "full" } else { "top-n" };
+        write!(f, "sort (rows={} limit={} sort={})", self.rows, self.limit, sort)
+    }
+}
+
+impl Workload for Sort {
+    type Item = (usize, bool); // limit,full_sort
+
+    fn prepare(&self, client: &mut Client, rng: &mut StdRng) -> Result<()> {
+        client.execute("BEGIN")?;
+        client.execute("DROP TABLE IF EXISTS sort")?;
+        client.execute("CREATE TABLE sort (id INT PRIMARY KEY, value INT NOT NULL)")?;
+        let dist = rand::distributions::Uniform::new_inclusive(1, self.rows);
+        for chunk in (1..=self.rows).chunks(100).into_iter() {
+            client.execute(&format!(
+                "INSERT INTO sort VALUES {}",
+                chunk.map(|id| format!("({id}, {})", dist.sample(rng))).join(", ")
+            ))?;
+        }
+        client.execute("COMMIT")?;
+        Ok(())
+    }
+
+    fn generate(&self, _: StdRng) -> impl Iterator<Item = Self::Item> + 'static {
+        std::iter::repeat((self.limit, self.full_sort))
+    }
+
+    fn execute(client: &mut Client, item: &Self::Item) -> Result<()> {
+        let (limit, full_sort) = *item;
+        let query = match full_sort {
+            true => "SELECT * FROM sort ORDER BY value".to_string(),
+            false => format!("SELECT * FROM sort ORDER BY value LIMIT {limit}"),
+        };
+        let rows: Rows = client.execute(&query)?.try_into()?;
+        let rows: Vec<Row> = rows.take(limit).try_collect()?;
+        assert!(rows.windows(2).all(|w| w[0][1] <= w[1][1]), "Unsorted rows");
+        Ok(())
+    }
+
+    fn verify(&self, client: &mut Client, _: usize) -> Result<()> {
+        let count: i64 = client.execute("SELECT COUNT(*) FROM sort")?.try_into()?;
+        assert_eq!(count, self.rows as i64, "Unexpected row count");
+        Ok(())
+    }
+}
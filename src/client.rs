@@ -1,21 +1,23 @@
 use crate::encoding::Value as _;
 use crate::errdata;
-use crate::error::{Error, Result};
+use crate::error::Result;
+use crate::raft;
 use crate::server::{Request, Response, Status};
 use crate::sql::engine::StatementResult;
-use crate::sql::types::Table;
+use crate::sql::types::{Label, Row, Table};
 use crate::storage::mvcc;
 
 use rand::Rng;
 use std::io::Write as _;
+use std::sync::{Arc, Mutex};
 
 /// A toyDB client. Connects to a server via TCP and submits SQL statements and
 /// other requests.
 pub struct Client {
     /// Inbound response stream.
     reader: std::io::BufReader<std::net::TcpStream>,
-    /// Outbound request stream.
-    writer: std::io::BufWriter<std::net::TcpStream>,
+    /// Outbound request stream. Shared with cancellers.
+    writer: Arc<Mutex<std::io::BufWriter<std::net::TcpStream>>>,
     /// The current transaction, if any.
     txn: Option<mvcc::TransactionState>,
 }
@@ -25,31 +27,94 @@ impl Client {
     pub fn connect(addr: impl std::net::ToSocketAddrs) -> Result<Self> {
         let socket = std::net::TcpStream::connect(addr)?;
         let reader = std::io::BufReader::new(socket.try_clone()?);
-        let writer = std::io::BufWriter::new(socket);
+        let writer = Arc::new(Mutex::new(std::io::BufWriter::new(socket)));
         Ok(Self { reader, writer, txn: None })
     }
 
     /// Sends a request to the server, returning the response.
     fn request(&mut self, request: Request) -> Result<Response> {
-        request.encode_into(&mut self.writer)?;
-        self.writer.flush()?;
+        send(&self.writer, &[request])?;
         Result::decode_from(&mut self.reader)?
     }
 
+    /// Returns a canceller for the client's requests, which can be used from a
+    /// different thread (e.g. a Ctrl-C handler) while a request is executing.
+    pub fn canceller(&self) -> Canceller {
+        Canceller { writer: self.writer.clone() }
+    }
+
     /// Executes a SQL statement.
     pub fn execute(&mut self, statement: &str) -> Result<StatementResult> {
         let result = match self.request(Request::Execute(statement.to_string()))? {
             Response::Execute(result) => result,
             response => return errdata!("unexpected response {response:?}"),
         };
-        // Update the transaction state.
-        match &result {
+        self.update_txn(&result);
+        Ok(result)
+    }
+
+    /// Executes several SQL statements by pipelining them: all statements are
+    /// sent to the server at once, without waiting for each response, which
+    /// avoids a network roundtrip per statement. The server executes them
+    /// sequentially, and returns their results in order.
+    ///
+    /// Unlike execute_script(), each statement is a separate request, and an
+    /// error only fails that statement. Later statements are still executed.
+    /// The outer result errors on network or protocol errors.
+    pub fn execute_pipelined(
+        &mut self,
+        statements: &[&str],
+    ) -> Result<Vec<Result<StatementResult>>> {
+        let requests: Vec<_> = statements.iter().map(|s| Request::Execute(s.to_string())).collect();
+        send(&self.writer, &requests)?;
+        let mut results = Vec::with_capacity(statements.len());
+        for _ in statements {
+            let result = match Result::<Response>::decode_from(&mut self.reader)? {
+                Ok(Response::Execute(result)) => Ok(result),
+                Ok(response) => return errdata!("unexpected response {response:?}"),
+                Err(err) => Err(err),
+            };
+            if let Ok(result) = &result {
+                self.update_txn(result);
+            }
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    /// Updates the transaction state following a statement result.
+    fn update_txn(&mut self, result: &StatementResult) {
+        match result {
             StatementResult::Begin(state) => self.txn = Some(state.clone()),
             StatementResult::Commit { .. } => self.txn = None,
             StatementResult::Rollback { .. } => self.txn = None,
             _ => {}
         }
-        Ok(result)
+    }
+
+    /// Executes a script of SQL statements separated by semicolons, returning
+    /// the result of each executed statement. Execution stops at the first
+    /// error, which is the last result. The outer result errors on parse,
+    /// network, or protocol errors.
+    pub fn execute_script(&mut self, script: &str) -> Result<Vec<Result<StatementResult>>> {
+        let (results, txn) = match self.request(Request::ExecuteScript(script.to_string()))? {
+            Response::ExecuteScript { results, txn } => (results, txn),
+            response => return errdata!("unexpected response {response:?}"),
+        };
+        // The script may have begun or ended a transaction, even on errors.
+        self.txn = txn;
+        results
+    }
+
+    /// Executes a SQL query (i.e. SELECT), streaming the result rows from the
+    /// server as the returned iterator is consumed, instead of buffering all
+    /// rows like execute() does.
+    pub fn query(&mut self, query: &str) -> Result<QueryRows<'_>> {
+        let columns = match self.request(Request::Query(query.to_string()))? {
+            Response::Query(columns) => columns,
+            response => return errdata!("unexpected response {response:?}"),
+        };
+        Ok(QueryRows { client: self, columns, done: false })
     }
 
     /// Fetches a table schema.
@@ -76,15 +141,25 @@ impl Client {
         }
     }
 
+    /// Returns the status of the server's local Raft node, e.g. its role and
+    /// term. Unlike status(), this is available even when there is no leader.
+    pub fn node_status(&mut self) -> Result<raft::NodeStatus> {
+        match self.request(Request::NodeStatus)? {
+            Response::NodeStatus(status) => Ok(status),
+            response => errdata!("unexpected response: {response:?}"),
+        }
+    }
+
     /// Returns the transaction state.
     pub fn txn(&self) -> Option<&mvcc::TransactionState> {
         self.txn.as_ref()
     }
 
-    /// Runs the given closure, automatically retrying serialization and abort
-    /// errors. If a transaction is open following an error, it is automatically
-    /// rolled back. It is the caller's responsibility to use a transaction in
-    /// the closure where appropriate (i.e. when it is not idempotent).
+    /// Runs the given closure, automatically retrying retryable errors (i.e.
+    /// serialization and abort errors). If a transaction is open following an
+    /// error, it is automatically rolled back. It is the caller's
+    /// responsibility to use a transaction in the closure where appropriate
+    /// (i.e. when it is not idempotent).
     pub fn with_retry<T>(&mut self, f: impl Fn(&mut Client) -> Result<T>) -> Result<T> {
         const MAX_RETRIES: u32 = 10;
         const MIN_WAIT: u64 = 10;
@@ -93,7 +168,7 @@ impl Client {
         loop {
             match f(self) {
                 Ok(result) => return Ok(result),
-                Err(Error::Serialization | Error::Abort) if retries < MAX_RETRIES => {
+                Err(error) if error.is_retryable() && retries < MAX_RETRIES => {
                     if self.txn().is_some() {
                         self.execute("ROLLBACK")?;
                     }
@@ -115,3 +190,79 @@ impl Client {
         }
     }
 }
+
+/// Cancels the in-flight requests of a client, obtained via
+/// Client::canceller(). The requests error with Error::Cancelled, including
+/// pipelined requests that are still queued. Cancelling when no request is in
+/// flight has no effect.
+#[derive(Clone)]
+pub struct Canceller {
+    /// The client's outbound request stream.
+    writer: Arc<Mutex<std::io::BufWriter<std::net::TcpStream>>>,
+}
+
+impl Canceller {
+    /// Cancels the client's in-flight requests, if any.
+    pub fn cancel(&self) -> Result<()> {
+        send(&self.writer, &[Request::Cancel])
+    }
+}
+
+/// Sends requests to the server via the given outbound stream. The stream is
+/// locked while writing, so requests and cancellations don't interleave.
+fn send(
+    writer: &Mutex<std::io::BufWriter<std::net::TcpStream>>,
+    requests: &[Request],
+) -> Result<()> {
+    let mut writer = writer.lock()?;
+    for request in requests {
+        request.encode_into(&mut *writer)?;
+    }
+    Ok(writer.flush()?)
+}
+
+/// A streamed query result, returned by Client::query(). Yields rows as they
+/// arrive from the server. If dropped before all rows are consumed, the rest
+/// are read and discarded such that the client can be used again.
+pub struct QueryRows<'a> {
+    /// The client, exclusively borrowed while streaming.
+    client: &'a mut Client,
+    /// The result columns.
+    columns: Vec<Label>,
+    /// Whether the stream has ended, either at the last row or an error.
+    done: bool,
+}
+
+impl QueryRows<'_> {
+    /// Returns the result columns.
+    pub fn columns(&self) -> &[Label] {
+        &self.columns
+    }
+}
+
+impl Iterator for QueryRows<'_> {
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = match Result::<Response>::decode_from(&mut self.client.reader) {
+            Ok(Ok(Response::Row(Some(row)))) => Ok(row),
+            Ok(Ok(Response::Row(None))) => {
+                self.done = true;
+                return None;
+            }
+            Ok(Ok(response)) => errdata!("unexpected response {response:?}"),
+            Ok(Err(err)) | Err(err) => Err(err),
+        };
+        self.done = result.is_err();
+        Some(result)
+    }
+}
+
+impl Drop for QueryRows<'_> {
+    fn drop(&mut self) {
+        for _ in self.by_ref() {}
+    }
+}
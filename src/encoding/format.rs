@@ -75,7 +75,7 @@ impl<I: Formatter> Formatter for Raft<I> {
             return Raw::value(key, value);
         };
         match key {
-            raft::Key::CommitIndex => {
+            raft::Key::CommitIndex | raft::Key::Snapshot => {
                 match bincode::deserialize::<(raft::Index, raft::Term)>(value) {
                     Ok((index, term)) => format!("{index}@{term}"),
                     Err(_) => Raw::bytes(value),
@@ -114,16 +114,21 @@ impl<I: Formatter> Formatter for MVCC<I> {
             mvcc::Key::Unversioned(innerkey) => {
                 format!("mvcc:Unversioned({})", I::key(&innerkey))
             }
-            mvcc::Key::NextVersion | mvcc::Key::TxnActive(_) | mvcc::Key::TxnActiveSnapshot(_) => {
-                format!("mvcc:{key:?}")
+            mvcc::Key::TxnSavepointWrite(version, seq, innerkey) => {
+                format!("mvcc:TxnSavepointWrite({version}, {seq}, {})", I::key(&innerkey))
             }
+            mvcc::Key::NextVersion
+            | mvcc::Key::GCVersion
+            | mvcc::Key::TxnActive(_)
+            | mvcc::Key::TxnActiveSnapshot(_)
+            | mvcc::Key::TxnSavepoint(_, _) => format!("mvcc:{key:?}"),
         }
     }
 
     fn value(key: &[u8], value: &[u8]) -> String {
         let Ok(key) = mvcc::Key::decode(key) else { return Raw::bytes(value) };
         match key {
-            mvcc::Key::NextVersion => {
+            mvcc::Key::NextVersion | mvcc::Key::GCVersion => {
                 let Ok(version) = bincode::deserialize::<mvcc::Version>(value) else {
                     return Raw::bytes(value);
                 };
@@ -136,11 +141,17 @@ impl<I: Formatter> Formatter for MVCC<I> {
                 format!("{{{}}}", active.iter().map(|v| v.to_string()).join(","))
             }
             mvcc::Key::TxnActive(_) | mvcc::Key::TxnWrite(_, _) => Raw::bytes(value),
-            mvcc::Key::Version(userkey, _) => match bincode::deserialize(value) {
-                Ok(Some(value)) => I::value(&userkey, value),
-                Ok(None) => "None".to_string(),
+            mvcc::Key::TxnSavepoint(_, _) => match bincode::deserialize::<String>(value) {
+                Ok(name) => name,
                 Err(_) => Raw::bytes(value),
             },
+            mvcc::Key::Version(userkey, _) | mvcc::Key::TxnSavepointWrite(_, _, userkey) => {
+                match bincode::deserialize(value) {
+                    Ok(Some(value)) => I::value(&userkey, value),
+                    Ok(None) => "None".to_string(),
+                    Err(_) => Raw::bytes(value),
+                }
+            }
             mvcc::Key::Unversioned(userkey) => I::value(&userkey, value),
         }
     }
@@ -154,6 +165,13 @@ impl SQL {
         values.into_iter().join(",")
     }
 
+    fn id(id: Vec<sql::types::Value>) -> String {
+        match id.len() {
+            1 => Self::values(id),
+            _ => format!("({})", Self::values(id)),
+        }
+    }
+
     fn schema(table: sql::types::Table) -> String {
         let re = regex::Regex::new(r#"\n\s*"#).expect("regex failed");
         re.replace_all(&table.to_string(), " ").into_owned()
@@ -174,7 +192,10 @@ impl Formatter for SQL {
                 format!("sql:Index({table}.{column}, {value})")
             }
             sql::engine::Key::Row(table, id) => {
-                format!("sql:Row({table}, {id})")
+                format!("sql:Row({table}, {})", Self::id(id.into_owned()))
+            }
+            sql::engine::Key::TableStats(table, version) => {
+                format!("sql:TableStats({table}, {version})")
             }
         }
     }
@@ -196,17 +217,22 @@ impl Formatter for SQL {
                 Self::schema(table)
             }
             sql::engine::Key::Row(_, _) => {
-                let Ok(row) = bincode::deserialize::<sql::types::Row>(value) else {
+                let Ok(row) = sql::engine::decode_row(value) else {
                     return Raw::bytes(value);
                 };
                 Self::values(row)
             }
             sql::engine::Key::Index(_, _, _) => {
-                let Ok(index) = bincode::deserialize::<BTreeSet<sql::types::Value>>(value) else {
+                let Ok(index) = bincode::deserialize::<BTreeSet<Vec<sql::types::Value>>>(value)
+                else {
                     return Raw::bytes(value);
                 };
-                Self::values(index)
+                index.into_iter().map(Self::id).join(",")
             }
+            sql::engine::Key::TableStats(_, _) => match bincode::deserialize::<i64>(value) {
+                Ok(delta) => format!("rows={delta:+}"),
+                Err(_) => Raw::bytes(value),
+            },
         }
     }
 }
@@ -229,11 +255,20 @@ impl Formatter for SQLCommand {
             sql::engine::Write::Begin => None,
             sql::engine::Write::Commit(txn)
             | sql::engine::Write::Rollback(txn)
+            | sql::engine::Write::Savepoint { txn, .. }
+            | sql::engine::Write::RollbackToSavepoint { txn, .. }
+            | sql::engine::Write::ReleaseSavepoint { txn, .. }
             | sql::engine::Write::Delete { txn, .. }
             | sql::engine::Write::Insert { txn, .. }
+            | sql::engine::Write::Truncate { txn, .. }
             | sql::engine::Write::Update { txn, .. }
+            | sql::engine::Write::Analyze { txn, .. }
             | sql::engine::Write::CreateTable { txn, .. }
-            | sql::engine::Write::DropTable { txn, .. } => Some(txn),
+            | sql::engine::Write::DropTable { txn, .. }
+            | sql::engine::Write::AddColumn { txn, .. }
+            | sql::engine::Write::DropColumn { txn, .. }
+            | sql::engine::Write::CreateIndex { txn, .. }
+            | sql::engine::Write::DropIndex { txn, .. } => Some(txn),
         };
         let ftxn =
             txn.filter(|t| !t.read_only).map(|t| format!("t{} ", t.version)).unwrap_or_default();
@@ -242,8 +277,15 @@ impl Formatter for SQLCommand {
             sql::engine::Write::Begin => "BEGIN".to_string(),
             sql::engine::Write::Commit(_) => "COMMIT".to_string(),
             sql::engine::Write::Rollback(_) => "ROLLBACK".to_string(),
+            sql::engine::Write::Savepoint { name, .. } => format!("SAVEPOINT {name}"),
+            sql::engine::Write::RollbackToSavepoint { name, .. } => {
+                format!("ROLLBACK TO SAVEPOINT {name}")
+            }
+            sql::engine::Write::ReleaseSavepoint { name, .. } => {
+                format!("RELEASE SAVEPOINT {name}")
+            }
             sql::engine::Write::Delete { table, ids, .. } => {
-                format!("DELETE {table} {}", ids.iter().map(|id| id.to_string()).join(","))
+                format!("DELETE {table} {}", ids.iter().map(|id| SQL::id(id.clone())).join(","))
             }
             sql::engine::Write::Insert { table, rows, .. } => {
                 format!(
@@ -251,12 +293,36 @@ impl Formatter for SQLCommand {
                     rows.into_iter().map(|row| format!("({})", SQL::values(row))).join(" ")
                 )
             }
+            sql::engine::Write::Truncate { table, .. } => format!("TRUNCATE {table}"),
             sql::engine::Write::Update { table, rows, .. } => format!(
                 "UPDATE {table} {}",
-                rows.into_iter().map(|(id, row)| format!("{id}→({})", SQL::values(row))).join(" ")
+                rows.into_iter()
+                    .map(|(id, row)| format!("{}→({})", SQL::id(id), SQL::values(row)))
+                    .join(" ")
             ),
+            sql::engine::Write::Analyze { table, .. } => format!("ANALYZE {table}"),
             sql::engine::Write::CreateTable { schema, .. } => SQL::schema(schema),
-            sql::engine::Write::DropTable { table, .. } => format!("DROP TABLE {table}"),
+            sql::engine::Write::DropTable { table, cascade: false, .. } => {
+                format!("DROP TABLE {table}")
+            }
+            sql::engine::Write::DropTable { table, cascade: true, .. } => {
+                format!("DROP TABLE {table} CASCADE")
+            }
+            sql::engine::Write::AddColumn { table, column, .. } => {
+                format!("ALTER TABLE {table} ADD COLUMN {}", column.name)
+            }
+            sql::engine::Write::DropColumn { table, column, .. } => {
+                format!("ALTER TABLE {table} DROP COLUMN {column}")
+            }
+            sql::engine::Write::CreateIndex { table, column, name: Some(name), .. } => {
+                format!("CREATE INDEX {name} ON {table} ({column})")
+            }
+            sql::engine::Write::CreateIndex { table, column, name: None, .. } => {
+                format!("CREATE INDEX ON {table} ({column})")
+            }
+            sql::engine::Write::DropIndex { table, column, .. } => {
+                format!("DROP INDEX {table}.{column}")
+            }
         };
         format!("{ftxn}{fcommand}")
     }
@@ -15,6 +15,7 @@
 //! bool:    0x00 for false, 0x01 for true.
 //! u64:     Big-endian binary representation.
 //! i64:     Big-endian binary representation, with sign bit flipped.
+//! i128:    Like i64.
 //! f64:     Big-endian binary representation, with sign bit flipped, and rest if negative.
 //! Vec<u8>: 0x00 is escaped as 0x00ff, terminated with 0x0000.
 //! String:  Like Vec<u8>.
@@ -99,7 +100,7 @@ struct Serializer {
     output: Vec<u8>,
 }
 
-impl<'a> ser::Serializer for &'a mut Serializer {
+impl ser::Serializer for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -143,6 +144,14 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         Ok(())
     }
 
+    /// i128 uses the same encoding as i64.
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        let mut bytes = v.to_be_bytes();
+        bytes[0] ^= 1 << 7; // flip sign bit
+        self.output.extend(bytes);
+        Ok(())
+    }
+
     fn serialize_u8(self, _: u8) -> Result<()> {
         unimplemented!()
     }
@@ -296,7 +305,7 @@ impl<'a> ser::Serializer for &'a mut Serializer {
 }
 
 /// Sequences simply concatenate the serialized elements, with no external structure.
-impl<'a> ser::SerializeSeq for &'a mut Serializer {
+impl ser::SerializeSeq for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -310,7 +319,7 @@ impl<'a> ser::SerializeSeq for &'a mut Serializer {
 }
 
 /// Tuples, like sequences, simply concatenate the serialized elements.
-impl<'a> ser::SerializeTuple for &'a mut Serializer {
+impl ser::SerializeTuple for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -324,7 +333,7 @@ impl<'a> ser::SerializeTuple for &'a mut Serializer {
 }
 
 /// Tuples, like sequences, simply concatenate the serialized elements.
-impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
+impl ser::SerializeTupleVariant for &mut Serializer {
     type Ok = ();
     type Error = Error;
 
@@ -385,7 +394,7 @@ impl<'de> Deserializer<'de> {
 }
 
 /// For details on serialization formats, see Serializer.
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     type Error = Error;
 
     fn deserialize_any<V: de::Visitor<'de>>(self, _: V) -> Result<V::Value> {
@@ -418,6 +427,12 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_i64(i64::from_be_bytes(bytes.as_slice().try_into()?))
     }
 
+    fn deserialize_i128<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let mut bytes = self.take_bytes(16)?.to_vec();
+        bytes[0] ^= 1 << 7; // flip sign bit
+        visitor.visit_i128(i128::from_be_bytes(bytes.as_slice().try_into()?))
+    }
+
     fn deserialize_u8<V: de::Visitor<'de>>(self, _: V) -> Result<V::Value> {
         unimplemented!()
     }
@@ -699,6 +714,11 @@ mod tests {
         i64_65535: 65535i64 => "800000000000ffff",
         i64_max: i64::MAX => "ffffffffffffffff",
 
+        i128_min: i128::MIN => "00000000000000000000000000000000",
+        i128_neg_1: -1i128 => "7fffffffffffffffffffffffffffffff",
+        i128_0: 0i128 => "80000000000000000000000000000000",
+        i128_max: i128::MAX => "ffffffffffffffffffffffffffffffff",
+
         u64_min: u64::MIN => "0000000000000000",
         u64_1: 1_u64 => "0000000000000001",
         u64_65535: 65535_u64 => "000000000000ffff",
@@ -729,6 +749,8 @@ mod tests {
         value_int: Value::Integer(-1) => "027fffffffffffffff",
         value_float: Value::Float(PI) => "03c00921fb54442d18",
         value_string: Value::String("foo".to_string()) => "04666f6f0000",
+        value_timestamp: Value::Timestamp(-1) => "057fffffffffffffff",
+        value_decimal: Value::Decimal(-1, 2) => "067fffffffffffffffffffffffffffffff0000000000000002",
     }
 
     test_serialize_error! {
@@ -737,7 +759,6 @@ mod tests {
         i8: 0i8,
         i16: 0i16,
         i32: 0i32,
-        i128: 0i128,
         u8: 0u8,
         u16: 0u16,
         u32: 0u32,
@@ -755,7 +776,7 @@ mod tests {
         i8: "00" as i8,
         i16: "0000" as i16,
         i32: "00000000" as i32,
-        i128: "00000000000000000000000000000000" as i128,
+        i128_partial: "0000" as i128,
         u16: "0000" as u16,
         u32: "00000000" as u32,
         u64_partial: "0000" as u64,
@@ -18,6 +18,10 @@ pub enum Error {
     /// A write transaction conflicted with a different writer and lost. The
     /// transaction must be retried.
     Serialization,
+    /// A statement exceeded the session's statement timeout.
+    Timeout,
+    /// The client cancelled the statement. Unlike Abort, it's not retried.
+    Cancelled,
 }
 
 impl std::error::Error for Error {}
@@ -31,6 +35,8 @@ impl std::fmt::Display for Error {
             Error::IO(msg) => write!(f, "io error: {msg}"),
             Error::ReadOnly => write!(f, "read-only transaction"),
             Error::Serialization => write!(f, "serialization failure, retry transaction"),
+            Error::Timeout => write!(f, "statement timeout"),
+            Error::Cancelled => write!(f, "statement cancelled"),
         }
     }
 }
@@ -58,6 +64,28 @@ impl Error {
             Error::ReadOnly => true,
             // Write conflicts are determinstic.
             Error::Serialization => true,
+            // Timeouts and cancellations depend on the execution time. They're
+            // only checked session-side, not during Raft application.
+            Error::Timeout | Error::Cancelled => false,
+        }
+    }
+
+    /// Returns whether the operation can be retried, typically after rolling
+    /// back the transaction. Clients use this to decide whether to retry.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            // Aborts happen on e.g. Raft leader changes, and serialization
+            // failures on write conflicts. Both may succeed when retried.
+            Error::Abort | Error::Serialization => true,
+            // Retrying won't help for these, they'll fail the same way again.
+            // Timeouts in particular are likely to time out again.
+            Error::InvalidData(_)
+            | Error::InvalidInput(_)
+            | Error::IO(_)
+            | Error::ReadOnly
+            | Error::Timeout => false,
+            // The client asked to stop the statement, so don't run it again.
+            Error::Cancelled => false,
         }
     }
 }
@@ -31,6 +31,10 @@ pub enum Key {
     TermVote,
     /// Stores the current commit index (if any).
     CommitIndex,
+    /// Stores the index and term of the last compacted entry (if any). Entries
+    /// up to and including it have been removed from the log, and are instead
+    /// represented by a state machine snapshot.
+    Snapshot,
 }
 
 impl encoding::Key<'_> for Key {}
@@ -72,14 +76,22 @@ impl encoding::Key<'_> for Key {}
 /// indexes, then the uncommitted entries will be replaced with entries from the
 /// new leader once the old leader (or a follower) discovers it.
 ///
+/// To avoid unbounded log growth, entries that have been applied to the state
+/// machine can be compacted via [`Log::compact`]. The compacted entries are
+/// removed from the log, and the index/term of the last compacted entry is
+/// retained as the snapshot index. Lagging followers that need compacted
+/// entries are instead caught up by installing a state machine snapshot from
+/// the leader via [`Log::install_snapshot`].
+///
 /// The Raft log has the following invariants:
 ///
-/// * Entry indexes are contiguous starting at 1 (no index gaps).
+/// * Entry indexes are contiguous starting after the snapshot index (no gaps).
 /// * Entry terms never decrease from the previous entry.
 /// * Entry terms are at or below the current term.
 /// * Appended entries are durable (flushed to disk).
 /// * Appended entries use the current term.
-/// * Committed entries are never changed or removed (no log truncation).
+/// * Committed entries are never changed, only removed by compaction.
+/// * Compacted entries have been applied to the state machine.
 /// * Committed entries will eventually be replicated to all nodes.
 /// * Entries with the same index/term contain the same command.
 /// * If two logs contain a matching index/term, all previous entries
@@ -101,6 +113,10 @@ pub struct Log {
     commit_index: Index,
     /// The term of the last committed entry.
     commit_term: Term,
+    /// The index of the last compacted entry, i.e. the snapshot index.
+    snapshot_index: Index,
+    /// The term of the last compacted entry.
+    snapshot_term: Term,
     /// If true, fsync entries to disk when appended. This is mandated by Raft,
     /// but comes with a hefty performance penalty (especially since we don't
     /// optimize for it by batching entries before fsyncing). Disabling it will
@@ -119,6 +135,11 @@ impl Log {
             .map(|v| bincode::deserialize(&v))
             .transpose()?
             .unwrap_or((0, None));
+        let (snapshot_index, snapshot_term) = engine
+            .get(&Key::Snapshot.encode())?
+            .map(|v| bincode::deserialize(&v))
+            .transpose()?
+            .unwrap_or((0, 0));
         let (last_index, last_term) = engine
             .scan_dyn((Included(Key::Entry(0).encode()), Included(Key::Entry(u64::MAX).encode())))
             .last()
@@ -126,14 +147,26 @@ impl Log {
             .map(|(_, v)| Entry::decode(&v))
             .transpose()?
             .map(|e| (e.index, e.term))
-            .unwrap_or((0, 0));
+            .filter(|(index, _)| *index > snapshot_index)
+            .unwrap_or((snapshot_index, snapshot_term));
         let (commit_index, commit_term) = engine
             .get(&Key::CommitIndex.encode())?
             .map(|v| bincode::deserialize(&v))
             .transpose()?
             .unwrap_or((0, 0));
         let fsync = true; // fsync by default (NB: BitCask::flush() is a noop in tests)
-        Ok(Self { engine, term, vote, last_index, last_term, commit_index, commit_term, fsync })
+        Ok(Self {
+            engine,
+            term,
+            vote,
+            last_index,
+            last_term,
+            commit_index,
+            commit_term,
+            snapshot_index,
+            snapshot_term,
+            fsync,
+        })
     }
 
     /// Controls whether to fsync writes. Disabling this may violate Raft
@@ -142,6 +175,16 @@ impl Log {
         self.fsync = fsync
     }
 
+    /// Flushes pending writes to durable storage, if fsync is enabled. Appends
+    /// and splices flush themselves, but the storage engine may defer fsyncs
+    /// (e.g. Durability::Interval) until a later flush.
+    pub fn flush(&mut self) -> Result<()> {
+        if self.fsync {
+            self.engine.flush()?;
+        }
+        Ok(())
+    }
+
     /// Returns the commit index and term.
     pub fn get_commit_index(&self) -> (Index, Term) {
         (self.commit_index, self.commit_term)
@@ -152,6 +195,11 @@ impl Log {
         (self.last_index, self.last_term)
     }
 
+    /// Returns the snapshot index and term, i.e. the last compacted entry.
+    pub fn get_snapshot_index(&self) -> (Index, Term) {
+        (self.snapshot_index, self.snapshot_term)
+    }
+
     /// Returns the current term (0 if none) and vote.
     pub fn get_term(&self) -> (Term, Option<NodeID>) {
         (self.term, self.vote)
@@ -197,12 +245,12 @@ impl Log {
     /// Commits entries up to and including the given index. The index must
     /// exist and be at or after the current commit index.
     pub fn commit(&mut self, index: Index) -> Result<Index> {
-        let term = match self.get(index)? {
-            Some(e) if e.index < self.commit_index => {
-                panic!("commit index regression {} → {}", self.commit_index, e.index);
+        let term = match self.get_entry_term(index)? {
+            Some(_) if index < self.commit_index => {
+                panic!("commit index regression {} → {}", self.commit_index, index);
             }
-            Some(e) if e.index == self.commit_index => return Ok(index),
-            Some(e) => e.term,
+            Some(_) if index == self.commit_index => return Ok(index),
+            Some(term) => term,
             None => panic!("commit index {index} does not exist"),
         };
         self.engine.set(&Key::CommitIndex.encode(), bincode::serialize(&(index, term)))?;
@@ -218,6 +266,15 @@ impl Log {
         self.engine.get(&Key::Entry(index).encode())?.map(|v| Entry::decode(&v)).transpose()
     }
 
+    /// Fetches the term of the entry at an index, or None if it does not
+    /// exist. Unlike get(), this also returns the term of the snapshot index.
+    pub fn get_entry_term(&mut self, index: Index) -> Result<Option<Term>> {
+        if index != 0 && index == self.snapshot_index {
+            return Ok(Some(self.snapshot_term));
+        }
+        Ok(self.get(index)?.map(|e| e.term))
+    }
+
     /// Checks if the log contains an entry with the given index and term.
     pub fn has(&mut self, index: Index, term: Term) -> Result<bool> {
         // Fast path: check against last_index. This is the common case when
@@ -228,11 +285,15 @@ impl Log {
         if (index, term) == (self.last_index, self.last_term) {
             return Ok(true);
         }
-        Ok(self.get(index)?.map(|e| e.term == term).unwrap_or(false))
+        // Compacted entries are committed, and thus match any leader's log.
+        if index < self.snapshot_index {
+            return Ok(true);
+        }
+        Ok(self.get_entry_term(index)? == Some(term))
     }
 
     /// Returns an iterator over log entries in the given index range.
-    pub fn scan(&mut self, range: impl std::ops::RangeBounds<Index>) -> Iterator {
+    pub fn scan(&mut self, range: impl std::ops::RangeBounds<Index>) -> Iterator<'_> {
         use std::ops::Bound;
         let from = match range.start_bound() {
             Bound::Excluded(&index) => Bound::Excluded(Key::Entry(index).encode()),
@@ -249,7 +310,7 @@ impl Log {
 
     /// Returns an iterator over entries that are ready to apply, starting after
     /// the current applied index up to the commit index.
-    pub fn scan_apply(&mut self, applied_index: Index) -> Iterator {
+    pub fn scan_apply(&mut self, applied_index: Index) -> Iterator<'_> {
         // NB: we don't assert that commit_index >= applied_index, because the
         // local commit index is not flushed to durable storage -- if lost on
         // restart, it can be recovered from a quorum of logs.
@@ -286,17 +347,26 @@ impl Log {
         // Check that the entries connect to the existing log (if any), and that the
         // term doesn't regress.
         assert!(last.term <= self.term, "splice term {} beyond current {}", last.term, self.term);
-        match self.get(first.index - 1)? {
-            Some(base) if first.term < base.term => {
-                panic!("splice term regression {} → {}", base.term, first.term)
+        match self.get_entry_term(first.index - 1)? {
+            Some(base_term) if first.term < base_term => {
+                panic!("splice term regression {} → {}", base_term, first.term)
             }
             Some(_) => {}
-            None if first.index == 1 => {}
+            None if first.index == 1 || first.index <= self.snapshot_index => {}
             None => panic!("first index {} must touch existing log", first.index),
         }
 
-        // Skip entries that are already in the log.
+        // Skip entries that have already been compacted. These are committed,
+        // and thus identical to the given entries.
         let mut entries = entries.as_slice();
+        while entries.first().is_some_and(|e| e.index <= self.snapshot_index) {
+            entries = &entries[1..];
+        }
+        let Some(first) = entries.first() else {
+            return Ok(self.last_index);
+        };
+
+        // Skip entries that are already in the log.
         let mut scan = self.scan(first.index..=last.index);
         while let Some(entry) = scan.next().transpose()? {
             // [0] is ok, because the scan has the same size as entries.
@@ -334,6 +404,68 @@ impl Log {
         Ok(self.last_index)
     }
 
+    /// Compacts the log by removing all entries up to and including the given
+    /// index, which becomes the new snapshot index. The index must be
+    /// committed, and the entries must have been applied to the state machine
+    /// and flushed to durable storage, since they can no longer be replayed.
+    pub fn compact(&mut self, index: Index) -> Result<Index> {
+        if index <= self.snapshot_index {
+            return Ok(self.snapshot_index);
+        }
+        assert!(index <= self.commit_index, "can't compact uncommitted index {index}");
+        let term = self.get(index)?.map(|e| e.term).expect("compact index missing");
+
+        // Write the snapshot index before removing entries, such that a crash
+        // will leave behind redundant entries rather than a gap.
+        self.engine.set(&Key::Snapshot.encode(), bincode::serialize(&(index, term)))?;
+        self.engine.flush()?;
+        for index in self.snapshot_index + 1..=index {
+            self.engine.delete(&Key::Entry(index).encode())?;
+        }
+        if self.fsync {
+            self.engine.flush()?;
+        }
+        self.snapshot_index = index;
+        self.snapshot_term = term;
+        Ok(index)
+    }
+
+    /// Installs a state machine snapshot at the given index and term, received
+    /// from the leader. If the log contains the snapshot entry, entries up to
+    /// it are compacted and subsequent entries are retained. Otherwise, the
+    /// entire log is discarded. In either case, the snapshot index becomes
+    /// committed. The caller must restore the state machine snapshot first.
+    pub fn install_snapshot(&mut self, index: Index, term: Term) -> Result<()> {
+        assert!(index > self.commit_index, "snapshot index {index} already committed");
+        assert!(term <= self.term, "snapshot term {term} beyond current {}", self.term);
+
+        if self.has(index, term)? {
+            self.commit(index)?;
+            self.compact(index)?;
+            return Ok(());
+        }
+
+        // Write the snapshot and commit index before removing entries, such
+        // that a crash will leave behind redundant entries rather than a gap.
+        // Remove them in reverse order, for the same reason.
+        self.engine.set(&Key::Snapshot.encode(), bincode::serialize(&(index, term)))?;
+        self.engine.set(&Key::CommitIndex.encode(), bincode::serialize(&(index, term)))?;
+        self.engine.flush()?;
+        for index in (self.snapshot_index + 1..=self.last_index).rev() {
+            self.engine.delete(&Key::Entry(index).encode())?;
+        }
+        if self.fsync {
+            self.engine.flush()?;
+        }
+        self.snapshot_index = index;
+        self.snapshot_term = term;
+        self.last_index = index;
+        self.last_term = term;
+        self.commit_index = index;
+        self.commit_term = term;
+        Ok(())
+    }
+
     /// Returns log engine status.
     pub fn status(&mut self) -> Result<storage::Status> {
         self.engine.status()
@@ -460,6 +592,16 @@ mod tests {
                     writeln!(output, "commit → {fmtentry}")?;
                 }
 
+                // compact INDEX
+                "compact" => {
+                    let mut args = command.consume_args();
+                    let index = args.next_pos().ok_or("index not given")?.parse()?;
+                    args.reject_rest()?;
+                    let index = self.log.compact(index)?;
+                    let (_, term) = self.log.get_snapshot_index();
+                    writeln!(output, "compact → {index}@{term}")?;
+                }
+
                 // dump
                 "dump" => {
                     command.consume_args().reject_rest()?;
@@ -511,6 +653,16 @@ mod tests {
                     }
                 }
 
+                // install_snapshot INDEX@TERM
+                "install_snapshot" => {
+                    let mut args = command.consume_args();
+                    let (index, term) = Self::parse_index_term(
+                        &args.next_pos().ok_or("index/term not given")?.value,
+                    )?;
+                    args.reject_rest()?;
+                    self.log.install_snapshot(index, term)?;
+                }
+
                 // reload
                 "reload" => {
                     command.consume_args().reject_rest()?;
@@ -584,11 +736,15 @@ mod tests {
                     let (term, vote) = self.log.get_term();
                     let (last_index, last_term) = self.log.get_last_index();
                     let (commit_index, commit_term) = self.log.get_commit_index();
+                    let (snapshot_index, snapshot_term) = self.log.get_snapshot_index();
                     let vote = vote.map(|id| id.to_string()).unwrap_or("None".to_string());
                     write!(
                         output,
                         "term={term} last={last_index}@{last_term} commit={commit_index}@{commit_term} vote={vote}",
                     )?;
+                    if snapshot_index > 0 {
+                        write!(output, " snapshot={snapshot_index}@{snapshot_term}")?;
+                    }
                     if engine {
                         write!(output, " engine={:#?}", self.log.status()?)?;
                     }
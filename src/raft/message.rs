@@ -120,6 +120,27 @@ pub enum Message {
         reject_index: Index,
     },
 
+    /// Leaders send a state machine snapshot to followers that need log entries
+    /// which have been compacted away. The follower replaces its state machine
+    /// with the snapshot and discards its log up to the snapshot index, unless
+    /// it has already committed it. It responds with an AppendResponse whose
+    /// match_index is the snapshot index, after which the leader resumes
+    /// replicating log entries following it. See section 7 in the Raft paper.
+    Snapshot {
+        /// The index of the last log entry applied to the snapshot.
+        index: Index,
+        /// The term of the last log entry applied to the snapshot.
+        term: Term,
+        /// The state machine snapshot, from `State::snapshot`.
+        data: Vec<u8>,
+    },
+
+    /// Leaders transferring leadership tell the target follower to campaign
+    /// immediately, without waiting for an election timeout. This is only sent
+    /// once the target's log has caught up with the leader, so it will win the
+    /// election. See section 3.10 in the Raft thesis.
+    TimeoutNow,
+
     /// Leaders need to confirm they are still the leader before serving reads,
     /// to guarantee linearizability in case a different leader has been
     /// estalished elsewhere. Read requests are served once the sequence number
@@ -169,6 +190,11 @@ pub enum Request {
     Write(Vec<u8>),
     /// Requests Raft cluster status from the leader.
     Status,
+    /// Requests the leader to transfer leadership to the given node. Writes are
+    /// rejected while the transfer is in progress. The request completes once
+    /// the leader steps down, or is aborted if the transfer does not complete
+    /// within an election timeout.
+    TransferLeader(NodeID),
 }
 
 impl encoding::Value for Request {}
@@ -182,6 +208,8 @@ pub enum Response {
     Write(Vec<u8>),
     /// The current Raft leader status.
     Status(Status),
+    /// The leader has transferred leadership and stepped down.
+    TransferLeader,
 }
 
 impl encoding::Value for Response {}
@@ -203,3 +231,41 @@ pub struct Status {
     /// The log storage engine status.
     pub storage: storage::Status,
 }
+
+/// The status of the local Raft node, regardless of the leader.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NodeStatus {
+    /// The node ID.
+    pub id: NodeID,
+    /// The node's current role.
+    pub role: NodeRole,
+    /// The current Raft leader, if known.
+    pub leader: Option<NodeID>,
+    /// The current Raft term.
+    pub term: Term,
+    /// The node's commit index.
+    pub commit_index: Index,
+    /// The node's applied index.
+    pub applied_index: Index,
+    /// The time at which the node last applied a log entry, if any since it
+    /// started.
+    pub last_applied: Option<std::time::SystemTime>,
+}
+
+/// A Raft node role.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NodeRole {
+    Candidate,
+    Follower,
+    Leader,
+}
+
+impl std::fmt::Display for NodeRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Candidate => write!(f, "candidate"),
+            Self::Follower => write!(f, "follower"),
+            Self::Leader => write!(f, "leader"),
+        }
+    }
+}
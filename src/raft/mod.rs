@@ -133,6 +133,13 @@
 //! hold new elections by itself until the network heals, at which point a new
 //! election will be held in its term (disrupting the current leader).
 //!
+//! A leader can also hand over leadership to a specific follower, e.g. before
+//! restarting for maintenance, via `Request::TransferLeader`. The leader stops
+//! accepting writes, catches up the target's log, and then sends it a
+//! `Message::TimeoutNow`. The target immediately campaigns without waiting for
+//! an election timeout, and wins since its log is up-to-date (Raft thesis
+//! section 3.10).
+//!
 //! REPLICATION AND CONSENSUS
 //! =========================
 //!
@@ -172,6 +179,14 @@
 //! index/term pair in their log, they'll say so in the `HeartbeatResponse` and
 //! the leader can begin probing their logs as with append rejections.
 //!
+//! To avoid unbounded log growth, each node compacts its log once enough
+//! entries have been applied to the state machine, via `Log.compact()`. If a
+//! follower needs entries that the leader has already compacted (e.g. a new
+//! node or one that has been down for a while), the leader instead sends a
+//! snapshot of its state machine in a `Message::Snapshot`. The follower
+//! restores the snapshot via `State::restore()`, discards its log up to the
+//! snapshot index, and replication resumes from there (Raft paper section 7).
+//!
 //! CLIENT REQUESTS
 //! ===============
 //!
@@ -201,6 +216,14 @@
 //! quorum have confirmed a sequence number the read is executed and the result
 //! returned to the client.
 //!
+//! Additionally, the read must observe all writes that were committed before
+//! it was received. The leader records its current commit index as the read's
+//! `index`, and only executes the read once it has been applied to the state
+//! machine. A newly elected leader may not know the latest commit index until
+//! it has committed an entry from its own term, so the read index is at least
+//! the index of the leader's initial noop entry. This is known as the ReadIndex
+//! optimization (Raft thesis section 6.4).
+//!
 //! IMPLEMENTATION CAVEATS
 //! ======================
 //!
@@ -217,13 +240,11 @@
 //!   must be stopped and restarted with the new configuration, otherwise it
 //!   risks multiple leaders (Raft paper section 6).
 //!
-//! * No snapshots: new or lagging nodes must be caught up by replicating and
-//!   replaying the entire log, instead of sending a state machine snapshot
-//!   (Raft paper section 7).
-//!
-//! * No log truncation: because snapshots aren't supported, the entire Raft
-//!   log must be retained forever in order to catch up new/lagging nodes,
-//!   leading to excessive storage use (Raft paper section 7).
+//! * Naïve snapshots: once the log is compacted, new or lagging nodes are
+//!   caught up by sending a snapshot of the entire state machine as a single
+//!   message, instead of streaming it in chunks. The snapshot is taken on the
+//!   fly, rather than being retained alongside the compacted log (Raft paper
+//!   section 7).
 //!
 //! * No pre-vote or check-quorum: a node that's partially partitioned (can
 //!   reach some but not all nodes) can cause persistent unavailability with
@@ -245,7 +266,9 @@ mod node;
 mod state;
 
 pub use log::{Entry, Index, Key, Log};
-pub use message::{Envelope, Message, ReadSequence, Request, RequestID, Response, Status};
+pub use message::{
+    Envelope, Message, NodeRole, NodeStatus, ReadSequence, Request, RequestID, Response, Status,
+};
 pub use node::{Node, NodeID, Options, Term, Ticks};
 pub use state::State;
 
@@ -261,3 +284,7 @@ const ELECTION_TIMEOUT_RANGE: std::ops::Range<Ticks> = 10..20;
 
 /// The maximum number of entries to send in a single append message.
 const MAX_APPEND_ENTRIES: usize = 100;
+
+/// The number of applied entries after the last snapshot index before the log
+/// is compacted.
+const COMPACT_THRESHOLD: Index = 1000;
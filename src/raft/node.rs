@@ -1,5 +1,7 @@
 use super::log::{Index, Log};
-use super::message::{Envelope, Message, ReadSequence, Request, RequestID, Response, Status};
+use super::message::{
+    Envelope, Message, NodeRole, NodeStatus, ReadSequence, Request, RequestID, Response, Status,
+};
 use super::state::State;
 use crate::errinput;
 use crate::error::{Error, Result};
@@ -9,6 +11,7 @@ use itertools::Itertools as _;
 use log::{debug, info};
 use rand::Rng as _;
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::SystemTime;
 
 /// A node ID. Unique within a cluster. Assigned manually when started.
 pub type NodeID = u8;
@@ -28,6 +31,9 @@ pub struct Options {
     pub election_timeout_range: std::ops::Range<Ticks>,
     /// Maximum number of entries to send in a single Append message.
     pub max_append_entries: usize,
+    /// The number of applied entries after the last snapshot index before the
+    /// log is compacted. 0 disables compaction.
+    pub compact_threshold: Index,
 }
 
 impl Default for Options {
@@ -36,6 +42,7 @@ impl Default for Options {
             heartbeat_interval: super::HEARTBEAT_INTERVAL,
             election_timeout_range: super::ELECTION_TIMEOUT_RANGE,
             max_append_entries: super::MAX_APPEND_ENTRIES,
+            compact_threshold: super::COMPACT_THRESHOLD,
         }
     }
 }
@@ -139,9 +146,33 @@ impl Node {
         })
     }
 
-    /// Advances time by a tick.
+    /// Advances time by a tick. Also flushes the log, such that storage
+    /// engines with deferred fsyncs (e.g. Durability::Interval) can fsync
+    /// pending writes even when no further writes arrive.
     pub fn tick(self) -> Result<Self> {
-        with_rawnode!(self, |n| n.tick())
+        with_rawnode!(self, |mut n| {
+            n.log.flush()?;
+            n.tick()
+        })
+    }
+
+    /// Returns the local node's status. Unlike Request::Status, this doesn't
+    /// go via the leader, and is available even when there is no leader.
+    pub fn status(&self) -> NodeStatus {
+        let (role, leader) = match self {
+            Node::Candidate(_) => (NodeRole::Candidate, None),
+            Node::Follower(n) => (NodeRole::Follower, n.role.leader),
+            Node::Leader(n) => (NodeRole::Leader, Some(n.id)),
+        };
+        with_rawnode!(ref self, |n| NodeStatus {
+            id: n.id,
+            role,
+            leader,
+            term: n.term(),
+            commit_index: n.log.get_commit_index().0,
+            applied_index: n.state.get_applied_index(),
+            last_applied: n.last_applied,
+        })
     }
 }
 
@@ -185,6 +216,9 @@ pub struct RawNode<R: Role> {
     tx: Sender<Envelope>,
     /// Node options.
     opts: Options,
+    /// The time at which a log entry was last applied to the state machine,
+    /// if any since the node started. Only used for status.
+    last_applied: Option<SystemTime>,
     /// Role-specific state.
     role: R,
 }
@@ -199,6 +233,7 @@ impl<R: Role> RawNode<R> {
             state: self.state,
             tx: self.tx,
             opts: self.opts,
+            last_applied: self.last_applied,
             role,
         }
     }
@@ -249,6 +284,29 @@ impl<R: Role> RawNode<R> {
         }
         Ok(())
     }
+
+    /// Compacts the log up to the applied index, if enough entries have been
+    /// applied since the last compaction. The state machine is flushed first,
+    /// since the compacted entries can't be reapplied.
+    fn maybe_compact(&mut self) -> Result<()> {
+        let (snapshot_index, _) = self.log.get_snapshot_index();
+        let threshold = self.opts.compact_threshold;
+        if threshold == 0 || self.state.get_applied_index() < snapshot_index + threshold {
+            return Ok(());
+        }
+        self.compact()
+    }
+
+    /// Compacts the log up to the applied index. The commit index may be below
+    /// the applied index following a restart, since it isn't flushed.
+    fn compact(&mut self) -> Result<()> {
+        let (commit_index, _) = self.log.get_commit_index();
+        let index = std::cmp::min(self.state.get_applied_index(), commit_index);
+        debug!("Compacting log up to {index}");
+        self.state.flush()?;
+        self.log.compact(index)?;
+        Ok(())
+    }
 }
 
 // A follower replicates log entries from a leader and forwards client requests.
@@ -289,7 +347,7 @@ impl RawNode<Follower> {
             return errinput!("node ID {id} can't be in peers");
         }
         let role = Follower::new(None, 0);
-        let mut node = Self { id, peers, log, state, tx, opts, role };
+        let mut node = Self { id, peers, log, state, tx, opts, last_applied: None, role };
         node.role.election_timeout = node.random_election_timeout();
 
         // Apply any pending entries following restart. Unlike the Raft log,
@@ -417,6 +475,40 @@ impl RawNode<Follower> {
                 self.send(msg.from, Message::AppendResponse { reject_index, match_index })?;
             }
 
+            // The leader sent a state machine snapshot, because we need log
+            // entries that it has compacted. Restore it and discard our log up
+            // to the snapshot index, unless we've already committed it (e.g.
+            // if the snapshot was a duplicate).
+            Message::Snapshot { index, term, data } => {
+                // Make sure the snapshot is from our leader, or follow it.
+                match self.role.leader {
+                    Some(leader) => assert_eq!(msg.from, leader, "multiple leaders in term"),
+                    None => self = self.into_follower(msg.term, Some(msg.from))?,
+                }
+
+                if index > self.log.get_commit_index().0 {
+                    info!("Installing snapshot at {index}@{term} from leader {}", msg.from);
+                    self.state.restore(data)?;
+                    assert_eq!(self.state.get_applied_index(), index, "snapshot applied index");
+                    self.log.install_snapshot(index, term)?;
+                }
+                let (reject_index, match_index) = (0, index);
+                self.send(msg.from, Message::AppendResponse { reject_index, match_index })?;
+            }
+
+            // The leader is transferring leadership to us. Campaign
+            // immediately, without waiting for an election timeout.
+            Message::TimeoutNow => {
+                // Make sure the request is from our leader, or follow it.
+                match self.role.leader {
+                    Some(leader) => assert_eq!(msg.from, leader, "multiple leaders in term"),
+                    None => self = self.into_follower(msg.term, Some(msg.from))?,
+                }
+
+                info!("Leader {} is transferring leadership to us", msg.from);
+                return Ok(self.into_candidate()?.into());
+            }
+
             // Confirm the leader's read sequence number.
             Message::Read { seq } => {
                 // Make sure the read is from our leader, or follow it.
@@ -521,8 +613,10 @@ impl RawNode<Follower> {
             // This includes errors -- any non-deterministic errors (e.g. IO
             // errors) must panic instead to avoid replica divergence.
             _ = self.state.apply(entry);
+            self.last_applied = Some(SystemTime::now());
         }
-        Ok(())
+        drop(iter);
+        self.maybe_compact()
     }
 }
 
@@ -620,7 +714,11 @@ impl RawNode<Candidate> {
 
             // If we hear from a leader in this term, we lost the election.
             // Follow it and step the message.
-            Message::Heartbeat { .. } | Message::Append { .. } | Message::Read { .. } => {
+            Message::Heartbeat { .. }
+            | Message::Append { .. }
+            | Message::Snapshot { .. }
+            | Message::TimeoutNow
+            | Message::Read { .. } => {
                 return self.into_follower(msg.term, Some(msg.from))?.step(msg);
             }
 
@@ -674,13 +772,20 @@ pub struct Leader {
     /// Tracks pending read requests. For linearizability, read requests are
     /// assigned a sequence number and only executed once a quorum of nodes have
     /// confirmed it. Otherwise, an old leader may serve stale reads if a new
-    /// leader has been elected elsewhere.
+    /// leader has been elected elsewhere. They must also wait for the read
+    /// index to be applied, see `Read.index`.
     reads: VecDeque<Read>,
     /// The read sequence number used for the last read. Initialized to 0 in
     /// this term, and incremented for every read command.
     read_seq: ReadSequence,
     /// Number of ticks since last heartbeat.
     since_heartbeat: Ticks,
+    /// A pending leadership transfer, if any. Writes are rejected while the
+    /// transfer is in progress.
+    transfer: Option<Transfer>,
+    /// The index of the first entry in our term, i.e. the noop entry appended
+    /// on election. Once committed, all entries from previous terms are too.
+    term_index: Index,
 }
 
 /// Follower replication progress (in this term).
@@ -741,10 +846,27 @@ struct Write {
     id: RequestID,
 }
 
+/// A pending leadership transfer request.
+struct Transfer {
+    /// The node to transfer leadership to.
+    target: NodeID,
+    /// The node which submitted the transfer request.
+    from: NodeID,
+    /// The transfer request ID.
+    id: RequestID,
+    /// Ticks elapsed since the transfer started.
+    duration: Ticks,
+}
+
 /// A pending client read request.
 struct Read {
     /// The sequence number of this read.
     seq: ReadSequence,
+    /// The read index: the commit index when the read was received (or our
+    /// term's first index, if it wasn't committed yet). The read must observe
+    /// all writes up to it, so it's only executed once it has been applied.
+    /// See section 6.4 in the Raft thesis.
+    index: Index,
     /// The node which submitted the read.
     from: NodeID,
     /// The read request ID.
@@ -767,6 +889,8 @@ impl Leader {
             reads: VecDeque::new(),
             read_seq: 0,
             since_heartbeat: 0,
+            transfer: None,
+            term_index: last_index + 1,
         }
     }
 }
@@ -792,6 +916,12 @@ impl RawNode<Leader> {
             self.send(read.from, Message::ClientResponse { id: read.id, response })?;
         }
 
+        // If we were transferring leadership, we've now stepped down.
+        if let Some(transfer) = self.role.transfer.take() {
+            let response = Ok(Response::TransferLeader);
+            self.send(transfer.from, Message::ClientResponse { id: transfer.id, response })?;
+        }
+
         self.log.set_term(term, None)?;
         let election_timeout = self.random_election_timeout();
         Ok(self.into_role(Follower::new(None, election_timeout)))
@@ -842,6 +972,7 @@ impl RawNode<Leader> {
                 // heartbeat will trigger a probe above.
                 if self.progress(msg.from).advance(match_index) {
                     self.maybe_commit_and_apply()?;
+                    self.maybe_transfer(msg.from)?;
                 }
             }
 
@@ -853,6 +984,7 @@ impl RawNode<Leader> {
 
                 if self.progress(msg.from).advance(match_index) {
                     self.maybe_commit_and_apply()?;
+                    self.maybe_transfer(msg.from)?;
                 }
 
                 // Eagerly send any further pending entries. This may be a
@@ -896,6 +1028,14 @@ impl RawNode<Leader> {
             // AppendResponses must set either match_index or reject_index.
             Message::AppendResponse { .. } => panic!("invalid message {msg:?}"),
 
+            // A client submitted a write request while we're transferring
+            // leadership. Reject it, the client must retry with the new leader.
+            Message::ClientRequest { id, request: Request::Write(_) }
+                if self.role.transfer.is_some() =>
+            {
+                self.send(msg.from, Message::ClientResponse { id, response: Err(Error::Abort) })?;
+            }
+
             // A client submitted a write request. Propose it, and wait until
             // it's replicated and applied to the state machine before returning
             // the response to the client.
@@ -911,8 +1051,10 @@ impl RawNode<Leader> {
             // must confirm that we are still the leader by sending the read's
             // sequence number and wait for quorum confirmation.
             Message::ClientRequest { id, request: Request::Read(command) } => {
+                let (commit_index, _) = self.log.get_commit_index();
+                let index = std::cmp::max(commit_index, self.role.term_index);
                 self.role.read_seq += 1;
-                let read = Read { seq: self.role.read_seq, from: msg.from, id, command };
+                let read = Read { seq: self.role.read_seq, index, from: msg.from, id, command };
                 self.role.reads.push_back(read);
                 self.broadcast(Message::Read { seq: self.role.read_seq })?;
                 if self.cluster_size() == 1 {
@@ -926,6 +1068,26 @@ impl RawNode<Leader> {
                 self.send(msg.from, Message::ClientResponse { id, response })?;
             }
 
+            // A client submitted a leadership transfer request. Catch up the
+            // target's log, then tell it to campaign via TimeoutNow.
+            Message::ClientRequest { id, request: Request::TransferLeader(target) } => {
+                if target == self.id {
+                    let response = Ok(Response::TransferLeader);
+                    self.send(msg.from, Message::ClientResponse { id, response })?;
+                } else if !self.peers.contains(&target) {
+                    let response = errinput!("unknown node {target}");
+                    self.send(msg.from, Message::ClientResponse { id, response })?;
+                } else if self.role.transfer.is_some() {
+                    let response = errinput!("leadership transfer already in progress");
+                    self.send(msg.from, Message::ClientResponse { id, response })?;
+                } else {
+                    info!("Transferring leadership to {target}");
+                    self.role.transfer = Some(Transfer { target, from: msg.from, id, duration: 0 });
+                    self.maybe_send_append(target, true)?;
+                    self.maybe_transfer(target)?;
+                }
+            }
+
             // Don't grant any votes (we've already voted for ourself).
             Message::Campaign { .. } => {
                 self.send(msg.from, Message::CampaignResponse { vote: false })?
@@ -935,7 +1097,11 @@ impl RawNode<Leader> {
             Message::CampaignResponse { .. } => {}
 
             // There can't be another leader in this term.
-            Message::Heartbeat { .. } | Message::Append { .. } | Message::Read { .. } => {
+            Message::Heartbeat { .. }
+            | Message::Append { .. }
+            | Message::Snapshot { .. }
+            | Message::TimeoutNow
+            | Message::Read { .. } => {
                 panic!("saw other leader {} in term {}", msg.from, msg.term);
             }
 
@@ -952,6 +1118,17 @@ impl RawNode<Leader> {
         if self.role.since_heartbeat >= self.opts.heartbeat_interval {
             self.heartbeat()?;
         }
+
+        // Abort the leadership transfer if it doesn't complete within an
+        // election timeout, and resume accepting writes.
+        if let Some(transfer) = self.role.transfer.as_mut() {
+            transfer.duration += 1;
+            if transfer.duration >= self.opts.election_timeout_range.start {
+                info!("Leadership transfer to {} timed out", transfer.target);
+                let Transfer { from, id, .. } = self.role.transfer.take().unwrap();
+                self.send(from, Message::ClientResponse { id, response: Err(Error::Abort) })?;
+            }
+        }
         Ok(self.into())
     }
 
@@ -1017,6 +1194,7 @@ impl RawNode<Leader> {
             debug!("Applying {entry:?}");
             let write = self.role.writes.remove(&entry.index);
             let result = self.state.apply(entry);
+            self.last_applied = Some(SystemTime::now());
 
             if let Some(Write { id, from: to }) = write {
                 let message = Message::ClientResponse { id, response: result.map(Response::Write) };
@@ -1031,21 +1209,29 @@ impl RawNode<Leader> {
             self.maybe_read()?;
         }
 
+        self.maybe_compact()?;
+
         Ok(quorum_index)
     }
 
-    /// Executes any ready read requests (with confirmed sequence numbers).
-    fn maybe_read(&mut self) -> Result<()> {
-        if self.role.reads.is_empty() {
+    /// Sends TimeoutNow to the given peer if it's the target of a leadership
+    /// transfer and its log has caught up with ours, such that it can campaign
+    /// and win the election immediately.
+    fn maybe_transfer(&mut self, peer: NodeID) -> Result<()> {
+        if self.role.transfer.as_ref().map(|t| t.target) != Some(peer) {
+            return Ok(());
+        }
+        let (last_index, _) = self.log.get_last_index();
+        if self.progress(peer).match_index < last_index {
             return Ok(());
         }
+        debug!("Sending TimeoutNow to {peer}");
+        self.send(peer, Message::TimeoutNow)
+    }
 
-        // It's only safe to read if we've committed and applied an entry from
-        // our own term (the leader appends an entry when elected). Otherwise we
-        // may be behind on application and serve stale reads.
-        let (commit_index, commit_term) = self.log.get_commit_index();
-        let applied_index = self.state.get_applied_index();
-        if commit_term < self.term() || applied_index < commit_index {
+    /// Executes any ready read requests (with confirmed sequence numbers).
+    fn maybe_read(&mut self) -> Result<()> {
+        if self.role.reads.is_empty() {
             return Ok(());
         }
 
@@ -1054,10 +1240,14 @@ impl RawNode<Leader> {
             self.role.progress.values().map(|p| p.read_seq).chain([self.role.read_seq]).collect(),
         );
 
-        // Execute ready reads. The VecDeque is ordered by read_seq, so we
-        // can keep pulling until we hit quorum_read_seq.
+        // Execute ready reads. The VecDeque is ordered by read_seq and index,
+        // so we can keep pulling until we hit quorum_read_seq or a read index
+        // that hasn't been applied yet. Since the read index is at or after our
+        // term's first entry, this also ensures that we've committed and
+        // applied an entry in our own term, and thus all previous entries.
+        let applied_index = self.state.get_applied_index();
         while let Some(read) = self.role.reads.front() {
-            if read.seq > quorum_read_seq {
+            if read.seq > quorum_read_seq || read.index > applied_index {
                 break;
             }
             let read = self.role.reads.pop_front().unwrap();
@@ -1079,6 +1269,9 @@ impl RawNode<Leader> {
     // The probe is skipped if the follower is up-to-date (according to
     // match_index and last_index). If the probe's base_index has already been
     // confirmed via match_index, an actual append is sent instead.
+    //
+    // If the follower needs entries that have been compacted, a state machine
+    // snapshot is sent instead, and replication resumes after it.
     fn maybe_send_append(&mut self, peer: NodeID, mut probe: bool) -> Result<()> {
         let (last_index, _) = self.log.get_last_index();
         let progress = self.role.progress.get_mut(&peer).expect("unknown node");
@@ -1103,11 +1296,26 @@ impl RawNode<Leader> {
             return Ok(());
         }
 
+        // If the entries have been compacted, send a snapshot instead. Bump
+        // next_index to avoid resending it until a response.
+        let (snapshot_index, _) = self.log.get_snapshot_index();
+        if progress.next_index <= snapshot_index {
+            let index = self.state.get_applied_index();
+            let term = self.log.get_entry_term(index)?.expect("missing applied entry");
+            let data = self.state.snapshot()?;
+            progress.next_index = index + 1;
+            debug!("Sending snapshot at {index}@{term} to {peer}");
+            return self.send(peer, Message::Snapshot { index, term, data });
+        }
+
         // Fetch the base and entries.
         let (base_index, base_term) = match progress.next_index {
             0 => panic!("next_index=0 for node {peer}"),
             1 => (0, 0),
-            next => self.log.get(next - 1)?.map(|e| (e.index, e.term)).expect("missing base entry"),
+            next => {
+                let term = self.log.get_entry_term(next - 1)?.expect("missing base entry");
+                (next - 1, term)
+            }
         };
         let entries = match probe {
             false => self
@@ -1234,6 +1442,15 @@ mod tests {
             with_rawnode!(ref self, |n| n.log.get_last_index())
         }
 
+        fn get_snapshot_index(&self) -> (Index, Term) {
+            with_rawnode!(ref self, |n| n.log.get_snapshot_index())
+        }
+
+        fn compact(mut self) -> crate::error::Result<Self> {
+            with_rawnode!(ref mut self, |n| n.compact())?;
+            Ok(self)
+        }
+
         fn get_term_vote(&self) -> (Term, Option<NodeID>) {
             with_rawnode!(ref self, |n| n.log.get_term())
         }
@@ -1291,7 +1508,16 @@ mod tests {
                     self.campaign(&ids, &mut output)?;
                 }
 
-                // cluster nodes=N [leader=ID] [heartbeat_interval=N] [election_timeout=N] [max_append_entries=N]
+                // compact [ID...]
+                // Compacts the log up to the applied index on the given nodes.
+                "compact" => {
+                    let ids = self.parse_ids_or_all(&command.args)?;
+                    for id in ids {
+                        self.transition(id, |n| n.compact(), &mut output)?;
+                    }
+                }
+
+                // cluster nodes=N [leader=ID] [heartbeat_interval=N] [election_timeout=N] [max_append_entries=N] [compact_threshold=N]
                 // Creates a new Raft cluster.
                 "cluster" => {
                     let mut opts = Options::default();
@@ -1307,6 +1533,9 @@ mod tests {
                     if let Some(max_append_entries) = args.lookup_parse("max_append_entries")? {
                         opts.max_append_entries = max_append_entries;
                     }
+                    if let Some(compact_threshold) = args.lookup_parse("compact_threshold")? {
+                        opts.compact_threshold = compact_threshold;
+                    }
                     args.reject_rest()?;
                     self.cluster(nodes, leader, opts, &mut output)?;
                 }
@@ -1377,18 +1606,20 @@ mod tests {
                     self.request(id, request, &mut output)?;
                 }
 
-                // restart [commit_index=INDEX] [applied_index=INDEX] [ID...]
+                // restart [commit_index=INDEX] [applied_index=INDEX] [wipe=BOOL] [ID...]
                 // Restarts the given nodes (or all nodes). They retain their
                 // log and state, unless applied_index is given (which reverts
                 // the state machine to the given index, or 0 if empty).
                 // commit_index may be given to regress the commit index (it
-                // is not flushed to durable storage).
+                // is not flushed to durable storage). If wipe is true, the log
+                // and state machine are wiped, as if the node was new.
                 "restart" => {
                     let mut args = command.consume_args();
                     let applied_index = args.lookup_parse("applied_index")?;
                     let commit_index = args.lookup_parse("commit_index")?;
+                    let wipe = args.lookup_parse("wipe")?.unwrap_or(false);
                     let ids = self.parse_ids_or_all(&args.rest())?;
-                    self.restart(&ids, commit_index, applied_index, &mut output)?;
+                    self.restart(&ids, commit_index, applied_index, wipe, &mut output)?;
                 }
 
                 // stabilize [heartbeat=BOOL] [ID...]
@@ -1439,6 +1670,17 @@ mod tests {
                     self.transition(id, |n| n.step(msg), &mut output)?;
                 }
 
+                // transfer ID TARGET
+                // Sends a client request to the given node to transfer
+                // leadership to the target node.
+                "transfer" => {
+                    let mut args = command.consume_args();
+                    let id = args.next_pos().ok_or("must specify node ID")?.parse()?;
+                    let target = args.next_pos().ok_or("must specify target node ID")?.parse()?;
+                    args.reject_rest()?;
+                    self.request(id, Request::TransferLeader(target), &mut output)?;
+                }
+
                 // tick [ID...]
                 // Ticks the given nodes.
                 "tick" => {
@@ -1631,11 +1873,16 @@ mod tests {
                 let nodefmt = Self::format_node(node);
                 let (last_index, last_term) = node.get_last_index();
                 let (commit_index, commit_term) = node.get_commit_index();
+                let (snapshot_index, snapshot_term) = node.get_snapshot_index();
                 let (term, vote) = node.get_term_vote();
-                writeln!(
+                write!(
                     output,
                     "{nodefmt} term={term} last={last_index}@{last_term} commit={commit_index}@{commit_term} vote={vote:?}",
                 )?;
+                if snapshot_index > 0 {
+                    write!(output, " snapshot={snapshot_index}@{snapshot_term}")?;
+                }
+                writeln!(output)?;
                 for entry in node.scan_log()? {
                     writeln!(output, "{nodefmt} entry {}", Self::format_entry(&entry))?;
                 }
@@ -1724,19 +1971,33 @@ mod tests {
         }
 
         /// Restarts the given nodes. If commit_index or applied_index are
-        /// given, the log commit index or state machine will regress.
+        /// given, the log commit index or state machine will regress. If wipe
+        /// is true, the log and state machine are wiped.
         fn restart(
             &mut self,
             ids: &[NodeID],
             commit_index: Option<Index>,
             applied_index: Option<Index>,
+            wipe: bool,
             output: &mut String,
         ) -> Result<(), Box<dyn Error>> {
             for id in ids.iter().copied() {
                 let node = self.nodes.remove(&id).ok_or(format!("unknown node {id}"))?;
                 let peers = node.peers();
                 let opts = node.options();
-                let (log, mut state) = node.dismantle();
+                let (mut log, mut state) = node.dismantle();
+
+                // If requested, wipe the log and state machine.
+                if wipe {
+                    let range = (std::ops::Bound::Unbounded, std::ops::Bound::Unbounded);
+                    let keys: Vec<Vec<u8>> =
+                        log.engine.scan_dyn(range).map_ok(|(key, _)| key).try_collect()?;
+                    for key in keys {
+                        log.engine.delete(&key)?;
+                    }
+                    state = teststate::KV::new();
+                }
+
                 let mut log = Log::new(log.engine)?; // reset log
 
                 // If requested, regress the commit index.
@@ -1763,6 +2024,9 @@ mod tests {
                     if applied_index > log.get_commit_index().0 {
                         return Err(format!("applied_index={applied_index} beyond commit").into());
                     }
+                    if log.get_snapshot_index().0 > 0 {
+                        return Err("can't reapply compacted log".into());
+                    }
                     state = teststate::KV::new();
                     let mut scan = log.scan(..=applied_index);
                     while let Some(entry) = scan.next().transpose()? {
@@ -1865,7 +2129,8 @@ mod tests {
             // Fetch pre-transition info.
             let old_noderole = Self::format_node_role(&node);
             let (old_commit_index, _) = node.get_commit_index();
-            let mut old_entries = node.scan_log()?.into_iter();
+            let (old_snapshot_index, _) = node.get_snapshot_index();
+            let old_entries = node.scan_log()?.into_iter();
 
             // Apply the transition.
             node = f(node)?;
@@ -1874,7 +2139,10 @@ mod tests {
             let nodefmt = Self::format_node(&node);
             let noderole = Self::format_node_role(&node);
             let (commit_index, commit_term) = node.get_commit_index();
+            let (snapshot_index, snapshot_term) = node.get_snapshot_index();
 
+            // Ignore old entries that were compacted by the transition.
+            let mut old_entries = old_entries.skip_while(|e| e.index <= snapshot_index);
             let entries = node.scan_log()?.into_iter();
             let appended: Vec<Entry> = entries
                 .skip_while(|e| Some(e.term) == old_entries.next().map(|e| e.term))
@@ -1895,6 +2163,9 @@ mod tests {
             for entry in self.applied_rx[&id].try_iter() {
                 writeln!(output, "{nodefmt} apply {}", Self::format_entry(&entry))?
             }
+            if old_snapshot_index != snapshot_index {
+                writeln!(output, "{nodefmt} snapshot {snapshot_index}@{snapshot_term}")?;
+            }
 
             // Receive any outbound messages.
             self.receive(id, output)?;
@@ -2058,6 +2329,10 @@ mod tests {
                         (_, _) => panic!("match_index and reject_index both set"),
                     }
                 }
+                Message::Snapshot { index, term, data: _ } => {
+                    format!("Snapshot {index}@{term}")
+                }
+                Message::TimeoutNow => "TimeoutNow".to_string(),
                 Message::Read { seq } => {
                     format!("Read seq={seq}")
                 }
@@ -2072,6 +2347,7 @@ mod tests {
                             Request::Read(v) => format!("read 0x{}", hex::encode(v)),
                             Request::Write(v) => format!("write 0x{}", hex::encode(v)),
                             Request::Status => "status".to_string(),
+                            Request::TransferLeader(id) => format!("transfer {id}"),
                         }
                     )
                 }
@@ -2083,6 +2359,7 @@ mod tests {
                             Ok(Response::Read(v)) => format!("read 0x{}", hex::encode(v)),
                             Ok(Response::Write(v)) => format!("write 0x{}", hex::encode(v)),
                             Ok(Response::Status(v)) => format!("status {v:?}"),
+                            Ok(Response::TransferLeader) => "transfer".to_string(),
                             Err(error) => format!("Error::{error:#?}"),
                         }
                     )
@@ -2113,6 +2390,7 @@ mod tests {
             match request {
                 Request::Read(c) | Request::Write(c) => KVCommand::decode(c).unwrap().to_string(),
                 Request::Status => "status".to_string(),
+                Request::TransferLeader(id) => format!("transfer {id}"),
             }
         }
 
@@ -2123,6 +2401,7 @@ mod tests {
                     KVResponse::decode(r).unwrap().to_string()
                 }
                 Ok(Response::Status(status)) => format!("{status:#?}"),
+                Ok(Response::TransferLeader) => "ok".to_string(),
                 Err(error) => format!("Error::{error:?} ({error})"),
             }
         }
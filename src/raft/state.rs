@@ -16,6 +16,12 @@ use crate::error::Result;
 /// index and return it via `State::get_applied_index`. Read commands
 /// (`Request::Read`) are only executed on a single replica via `State::read`
 /// and must not make any state changes.
+///
+/// To allow Raft to compact the log, the state machine must also be able to
+/// flush applied state to durable storage via `State::flush`, and take and
+/// restore snapshots of its entire state via `State::snapshot` and
+/// `State::restore`. Snapshots are sent to followers that lag behind the
+/// compacted log.
 pub trait State: Send {
     /// Returns the last applied index from the state machine.
     ///
@@ -48,13 +54,29 @@ pub trait State: Send {
     /// This is only executed on a single replica/node, so it must not result in
     /// any state changes (i.e. it must not write).
     fn read(&self, command: Vec<u8>) -> Result<Vec<u8>>;
+
+    /// Flushes the applied state to durable storage. Raft calls this before
+    /// compacting the log, since compacted entries can't be reapplied if the
+    /// state machine loses a tail of writes.
+    fn flush(&mut self) -> Result<()>;
+
+    /// Returns a snapshot of the entire state machine at the current applied
+    /// index, which can be restored on a different replica via
+    /// `State::restore`. The snapshot must include the applied index.
+    fn snapshot(&self) -> Result<Vec<u8>>;
+
+    /// Restores the state machine from a snapshot taken via `State::snapshot`,
+    /// replacing all existing state (including the applied index). The
+    /// restored state must be flushed to durable storage, since Raft discards
+    /// the corresponding log entries afterwards.
+    fn restore(&mut self, snapshot: Vec<u8>) -> Result<()>;
 }
 
 /// Test helper state machines.
 #[cfg(test)]
 pub mod test {
     use super::*;
-    use crate::encoding::{self, Value as _};
+    use crate::encoding::{self, bincode, Value as _};
 
     use crossbeam::channel::Sender;
     use itertools::Itertools as _;
@@ -87,6 +109,18 @@ pub mod test {
         fn read(&self, command: Vec<u8>) -> Result<Vec<u8>> {
             self.inner.read(command)
         }
+
+        fn flush(&mut self) -> Result<()> {
+            self.inner.flush()
+        }
+
+        fn snapshot(&self) -> Result<Vec<u8>> {
+            self.inner.snapshot()
+        }
+
+        fn restore(&mut self, snapshot: Vec<u8>) -> Result<()> {
+            self.inner.restore(snapshot)
+        }
     }
 
     /// A simple string key/value store. Takes KVCommands.
@@ -131,6 +165,19 @@ pub mod test {
                 c @ KVCommand::Put { .. } => panic!("{c} submitted as read command"),
             }
         }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn snapshot(&self) -> Result<Vec<u8>> {
+            Ok(bincode::serialize(&(self.applied_index, &self.data)))
+        }
+
+        fn restore(&mut self, snapshot: Vec<u8>) -> Result<()> {
+            (self.applied_index, self.data) = bincode::deserialize(&snapshot)?;
+            Ok(())
+        }
     }
 
     /// A KV command. Returns the corresponding KVResponse.
@@ -206,5 +253,18 @@ pub mod test {
         fn read(&self, _: Vec<u8>) -> Result<Vec<u8>> {
             Ok(Vec::new())
         }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn snapshot(&self) -> Result<Vec<u8>> {
+            Ok(bincode::serialize(&self.applied_index))
+        }
+
+        fn restore(&mut self, snapshot: Vec<u8>) -> Result<()> {
+            self.applied_index = bincode::deserialize(&snapshot)?;
+            Ok(())
+        }
     }
 }
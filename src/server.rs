@@ -3,7 +3,7 @@ use crate::error::Result;
 use crate::raft;
 use crate::sql;
 use crate::sql::engine::{Catalog as _, Engine as _, StatementResult};
-use crate::sql::types::{Row, Table};
+use crate::sql::types::{Label, Row, Table};
 use crate::storage;
 
 use crossbeam::channel::{Receiver, Sender};
@@ -12,6 +12,9 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::io::Write as _;
 use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
 /// The outbound Raft peer channel capacity. This buffers messages when a Raft
 /// peer is slow or unavailable. Beyond this, messages will be dropped.
@@ -37,6 +40,10 @@ pub struct Server {
     node_rx: Receiver<raft::Envelope>,
     /// Raft peer IDs and addresses.
     peers: HashMap<raft::NodeID, String>,
+    /// The SQL query result cache, if enabled.
+    result_cache: Option<sql::engine::ResultCache>,
+    /// The directory for COPY files, if enabled.
+    copy_dir: Option<PathBuf>,
 }
 
 impl Server {
@@ -56,7 +63,19 @@ impl Server {
             node_tx,
             raft::Options::default(),
         )?;
-        Ok(Self { node, peers, node_rx })
+        Ok(Self { node, peers, node_rx, result_cache: None, copy_dir: None })
+    }
+
+    /// Enables a SQL query result cache shared by all client sessions, holding
+    /// up to the given number of read-only query results. 0 disables it.
+    pub fn enable_result_cache(&mut self, capacity: usize) {
+        self.result_cache = (capacity > 0).then(|| sql::engine::ResultCache::new(capacity));
+    }
+
+    /// Enables COPY for files in the given directory. Client paths are
+    /// relative to it, and can't reach outside of it.
+    pub fn enable_copy(&mut self, copy_dir: PathBuf) {
+        self.copy_dir = Some(copy_dir);
     }
 
     /// Serves Raft and SQL requests indefinitely. Consumes the server.
@@ -73,6 +92,7 @@ impl Server {
             let id = self.node.id();
             let (raft_request_tx, raft_request_rx) = crossbeam::channel::unbounded();
             let (raft_step_tx, raft_step_rx) = crossbeam::channel::unbounded();
+            let (node_status_tx, node_status_rx) = crossbeam::channel::unbounded();
 
             // Serve inbound Raft connections.
             s.spawn(move || Self::raft_accept(raft_listener, raft_step_tx));
@@ -94,12 +114,23 @@ impl Server {
                     raft_step_rx,
                     raft_peers_tx,
                     raft_request_rx,
+                    node_status_rx,
                 )
             });
 
             // Serve inbound SQL connections.
             let sql_engine = sql::engine::Raft::new(raft_request_tx);
-            s.spawn(move || Self::sql_accept(id, sql_listener, sql_engine));
+            let (result_cache, copy_dir) = (self.result_cache, self.copy_dir);
+            s.spawn(move || {
+                Self::sql_accept(
+                    id,
+                    sql_listener,
+                    sql_engine,
+                    result_cache,
+                    copy_dir,
+                    node_status_tx,
+                )
+            });
         });
 
         Ok(())
@@ -175,6 +206,9 @@ impl Server {
     /// * peers_tx: outbound per-peer channels sent via TCP connections.
     ///   Messages from the local node's node_rx are sent here.
     ///
+    /// * status_rx: inbound local node status requests from SQL clients. The
+    ///   status is returned via the provided response channel.
+    ///
     /// Panics on any errors, since the Raft node can't recover from failed
     /// state transitions.
     fn raft_route(
@@ -183,6 +217,7 @@ impl Server {
         peers_rx: Receiver<raft::Envelope>,
         mut peers_tx: HashMap<raft::NodeID, Sender<raft::Envelope>>,
         request_rx: Receiver<(raft::Request, Sender<Result<raft::Response>>)>,
+        status_rx: Receiver<Sender<raft::NodeStatus>>,
     ) {
         // Track response channels by request ID. The Raft node will emit
         // ClientResponse messages that we forward to the response channel.
@@ -238,12 +273,25 @@ impl Server {
                     node = node.step(msg).expect("step failed");
                     response_txs.insert(id, response_tx);
                 }
+
+                // Return the local node status. The client may have hung up.
+                recv(status_rx) -> result => {
+                    let response_tx = result.expect("status_rx disconnected");
+                    _ = response_tx.send(node.status());
+                }
             }
         }
     }
 
     /// Accepts new SQL client connections and spawns session threads for them.
-    fn sql_accept(id: raft::NodeID, listener: TcpListener, sql_engine: sql::engine::Raft) {
+    fn sql_accept(
+        id: raft::NodeID,
+        listener: TcpListener,
+        sql_engine: sql::engine::Raft,
+        result_cache: Option<sql::engine::ResultCache>,
+        copy_dir: Option<PathBuf>,
+        node_status_tx: Sender<Sender<raft::NodeStatus>>,
+    ) {
         std::thread::scope(|s| loop {
             let (socket, peer) = match listener.accept() {
                 Ok((socket, peer)) => (socket, peer),
@@ -252,10 +300,19 @@ impl Server {
                     continue;
                 }
             };
-            let session = sql_engine.session();
+            let cancel = Arc::new(AtomicBool::new(false));
+            let mut session = sql_engine.session().with_cancel(cancel.clone());
+            if let Some(result_cache) = &result_cache {
+                session = session.with_result_cache(result_cache);
+            }
+            if let Some(copy_dir) = &copy_dir {
+                session = session.with_copy_dir(copy_dir.clone());
+            }
+            let result_cache = result_cache.as_ref();
+            let node_status_tx = node_status_tx.clone();
             s.spawn(move || {
                 debug!("Client {peer} connected");
-                match Self::sql_session(id, socket, session) {
+                match Self::sql_session(id, socket, session, cancel, result_cache, node_status_tx) {
                     Ok(()) => debug!("Client {peer} disconnected"),
                     Err(err) => error!("Client {peer} error: {err}"),
                 }
@@ -264,44 +321,212 @@ impl Server {
     }
 
     /// Processes a client SQL session, executing SQL statements against the
-    /// Raft node.
+    /// Raft node. The session lives as long as the connection, so an explicit
+    /// transaction started with BEGIN spans subsequent requests until COMMIT
+    /// or ROLLBACK. It's rolled back if the client disconnects.
+    ///
+    /// Requests are read by a separate thread and queued, such that clients can
+    /// pipeline requests: they can send several requests without waiting for
+    /// the responses, and the requests are executed sequentially with the
+    /// responses returned in order. Errors are returned as responses, and don't
+    /// affect subsequent requests.
+    ///
+    /// This also allows the client to cancel requests with Request::Cancel.
+    /// Requests are numbered as they're received, and a cancellation applies
+    /// to all requests received before it, whether they're executing or still
+    /// queued, but not to later requests. Cancelled requests error with
+    /// Error::Cancelled, via the session's cancellation flag.
     fn sql_session(
         id: raft::NodeID,
         socket: TcpStream,
         mut session: sql::engine::Session<sql::engine::Raft>,
+        cancel: Arc<AtomicBool>,
+        result_cache: Option<&sql::engine::ResultCache>,
+        node_status_tx: Sender<Sender<raft::NodeStatus>>,
     ) -> Result<()> {
-        let mut reader = std::io::BufReader::new(socket.try_clone()?);
+        let reader = std::io::BufReader::new(socket.try_clone()?);
         let mut writer = std::io::BufWriter::new(socket);
 
+        let cancellation = Cancellation::new(cancel);
+        std::thread::scope(|s| {
+            let (request_tx, request_rx) = crossbeam::channel::unbounded();
+            let receiver = s.spawn(|| Self::sql_receive(reader, request_tx, &cancellation));
+            for (number, request) in request_rx {
+                cancellation.start(number);
+                let result = Self::sql_request(
+                    id,
+                    &mut session,
+                    request,
+                    &mut writer,
+                    result_cache,
+                    &node_status_tx,
+                );
+                if let Err(err) = result {
+                    // Shut down the socket to stop the receiver thread.
+                    writer.get_ref().shutdown(std::net::Shutdown::Both).ok();
+                    return Err(err);
+                }
+            }
+            receiver.join().expect("request receiver panicked")
+        })
+    }
+
+    /// Receives inbound client requests, numbers them, and queues them for
+    /// execution. On Request::Cancel, cancels the requests received so far
+    /// instead.
+    fn sql_receive(
+        mut reader: impl std::io::Read,
+        request_tx: Sender<(u64, Request)>,
+        cancellation: &Cancellation,
+    ) -> Result<()> {
+        let mut number = 0;
         while let Some(request) = Request::maybe_decode_from(&mut reader)? {
-            // Execute request.
-            debug!("Received request {request:?}");
-            let response = match request {
-                Request::Execute(query) => session.execute(&query).map(Response::Execute),
-                Request::GetTable(table) => {
-                    session.with_txn(true, |txn| txn.must_get_table(&table)).map(Response::GetTable)
+            match request {
+                Request::Cancel => {
+                    debug!("Received cancellation");
+                    cancellation.cancel(number);
                 }
-                Request::ListTables => session
-                    .with_txn(true, |txn| {
-                        Ok(txn.list_tables()?.into_iter().map(|t| t.name).collect())
-                    })
-                    .map(Response::ListTables),
-                Request::Status => session
-                    .status()
-                    .map(|s| Status { server: id, raft: s.raft, mvcc: s.mvcc })
-                    .map(Response::Status),
-            };
+                request => {
+                    number += 1;
+                    // The session may have errored and hung up.
+                    if request_tx.send((number, request)).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes a client request and writes the response.
+    fn sql_request(
+        id: raft::NodeID,
+        session: &mut sql::engine::Session<sql::engine::Raft>,
+        request: Request,
+        mut writer: impl std::io::Write,
+        result_cache: Option<&sql::engine::ResultCache>,
+        node_status_tx: &Sender<Sender<raft::NodeStatus>>,
+    ) -> Result<()> {
+        // Execute request.
+        debug!("Received request {request:?}");
+        let response = match request {
+            Request::Execute(query) => session.execute(&query).map(Response::Execute),
+            Request::ExecuteScript(script) => {
+                // The script may begin or end a transaction before an
+                // error, so always return the final transaction state.
+                let results = session.execute_script(&script);
+                Ok(Response::ExecuteScript { results, txn: session.txn().cloned() })
+            }
+            Request::Query(query) => return Self::sql_query(session, &query, writer),
+            Request::Cancel => return Ok(()), // handled by sql_receive()
+            Request::GetTable(table) => {
+                session.with_txn(true, |txn| txn.must_get_table(&table)).map(Response::GetTable)
+            }
+            Request::ListTables => session
+                .with_txn(true, |txn| Ok(txn.list_tables()?.into_iter().map(|t| t.name).collect()))
+                .map(Response::ListTables),
+            Request::Status => session
+                .status()
+                .and_then(|s| {
+                    let result_cache = result_cache.map(|c| c.stats()).transpose()?;
+                    Ok(Status { server: id, raft: s.raft, mvcc: s.mvcc, result_cache })
+                })
+                .map(Response::Status),
+            Request::NodeStatus => {
+                let (response_tx, response_rx) = crossbeam::channel::bounded(1);
+                node_status_tx.send(response_tx)?;
+                Ok(Response::NodeStatus(response_rx.recv()?))
+            }
+        };
+
+        // Process response.
+        debug!("Returning response {response:?}");
+        response.encode_into(&mut writer)?;
+        Ok(writer.flush()?)
+    }
 
-            // Process response.
-            debug!("Returning response {response:?}");
-            response.encode_into(&mut writer)?;
-            writer.flush()?;
+    /// Executes a query and streams the result to the client, as a
+    /// Response::Query with the result columns followed by a Response::Row for
+    /// each row and a final Response::Row(None). An error ends the stream.
+    ///
+    /// Rows are pulled lazily from the executor and written to the socket
+    /// buffer, which blocks when the client isn't reading them. The server
+    /// thus only runs a bounded distance ahead of the client.
+    fn sql_query(
+        session: &mut sql::engine::Session<sql::engine::Raft>,
+        query: &str,
+        mut writer: impl std::io::Write,
+    ) -> Result<()> {
+        debug!("Received query {query}");
+        let rows = match session.query(query) {
+            Ok((columns, rows)) => {
+                Ok(Response::Query(columns)).encode_into(&mut writer)?;
+                rows
+            }
+            Err(err) => {
+                debug!("Returning error {err}");
+                Result::<Response>::Err(err).encode_into(&mut writer)?;
+                return Ok(writer.flush()?);
+            }
+        };
+        let mut count = 0;
+        for result in rows {
+            match result {
+                Ok(row) => Ok(Response::Row(Some(row))).encode_into(&mut writer)?,
+                Err(err) => {
+                    debug!("Returning error {err}");
+                    Result::<Response>::Err(err).encode_into(&mut writer)?;
+                    return Ok(writer.flush()?);
+                }
+            }
+            count += 1;
         }
+        Ok(Response::Row(None)).encode_into(&mut writer)?;
+        writer.flush()?;
+        debug!("Streamed {count} rows");
         Ok(())
     }
 }
 
-/// A SQL client request.
+/// Tracks request cancellations for a SQL session. Requests are numbered from
+/// 1 in the order they're received, and a cancellation applies to all requests
+/// up to a given number. The session's cancellation flag is set while a
+/// cancelled request is executing.
+struct Cancellation {
+    /// The session's cancellation flag.
+    flag: Arc<AtomicBool>,
+    /// The number of the executing request, and of the last cancelled request.
+    /// These are updated together with the flag under the mutex, such that a
+    /// concurrent cancellation and request start can't lose the cancellation.
+    requests: Mutex<(u64, u64)>,
+}
+
+impl Cancellation {
+    /// Creates a new cancellation tracker using the given session flag.
+    fn new(flag: Arc<AtomicBool>) -> Self {
+        Self { flag, requests: Mutex::new((0, 0)) }
+    }
+
+    /// Cancels all requests up to and including the given request number.
+    fn cancel(&self, number: u64) {
+        let mut requests = self.requests.lock().expect("mutex failed");
+        requests.1 = number;
+        if requests.0 <= number {
+            self.flag.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Marks the given request as executing, setting the session flag if it
+    /// has been cancelled and clearing it otherwise.
+    fn start(&self, number: u64) {
+        let mut requests = self.requests.lock().expect("mutex failed");
+        requests.0 = number;
+        self.flag.store(number <= requests.1, Ordering::Relaxed);
+    }
+}
+
+/// A SQL client request. Variants are encoded by index, so new variants must
+/// be added at the end to remain compatible with existing clients.
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Request {
     /// Executes a SQL statement.
@@ -310,20 +535,42 @@ pub enum Request {
     GetTable(String),
     /// Lists all tables.
     ListTables,
-    /// Returns server status.
+    /// Returns server status. This is generated by the Raft leader.
     Status,
+    /// Cancels all previously sent requests that are still executing or
+    /// queued, which error with Error::Cancelled. Can be sent while waiting
+    /// for a response, and has no response of its own.
+    Cancel,
+    /// Returns the status of the server's local Raft node, e.g. its role and
+    /// term. Unlike Status, this doesn't go via the leader, so it is available
+    /// even when there is no leader.
+    NodeStatus,
+    /// Executes a script of SQL statements separated by semicolons.
+    ExecuteScript(String),
+    /// Executes a SQL query, streaming the result rows.
+    Query(String),
 }
 
 impl encoding::Value for Request {}
 
-/// A SQL server response.
+/// A SQL server response. As with Request, new variants must be added at the
+/// end.
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Response {
     Execute(StatementResult),
+    /// A streamed query result row, or None at the end of the result.
     Row(Option<Row>),
     GetTable(Table),
     ListTables(Vec<String>),
     Status(Status),
+    NodeStatus(raft::NodeStatus),
+    /// The results of a script, and the session's transaction state after it.
+    ExecuteScript {
+        results: Result<Vec<Result<StatementResult>>>,
+        txn: Option<storage::mvcc::TransactionState>,
+    },
+    /// The columns of a streamed query result, followed by its rows.
+    Query(Vec<Label>),
 }
 
 impl encoding::Value for Response {}
@@ -334,4 +581,5 @@ pub struct Status {
     pub server: raft::NodeID,
     pub raft: raft::Status,
     pub mvcc: storage::mvcc::Status,
+    pub result_cache: Option<sql::engine::ResultCacheStats>,
 }
@@ -0,0 +1,256 @@
+use crate::encoding::bincode;
+use crate::error::Result;
+use crate::sql::parser::ast;
+use crate::sql::types::{Label, Row, Value};
+use crate::storage::mvcc;
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// A bounded in-memory cache of read-only query results, shared by sessions.
+/// Entries are evicted in least recently used order once the cache is full.
+///
+/// Results are keyed by the parsed statement (which normalizes whitespace,
+/// keyword case, etc.), its parameter values, and the MVCC snapshot of the
+/// transaction that executed it. A given snapshot always sees the same data,
+/// so a cached result is valid for as long as the snapshot is. Any write
+/// commits a new version, and later transactions get a new snapshot that
+/// won't match stale entries -- these are never hit again, and are eventually
+/// evicted.
+///
+/// Only read-only transactions can use the cache, since read-write
+/// transactions see their own uncommitted writes.
+pub struct ResultCache {
+    inner: Mutex<Inner>,
+}
+
+/// The inner cache state, guarded by a mutex.
+struct Inner {
+    /// The maximum number of entries.
+    capacity: usize,
+    /// Cached results by key, along with their last access tick.
+    entries: HashMap<Key, (u64, CachedResult)>,
+    /// Cache keys by last access tick, in least recently used order.
+    lru: BTreeMap<u64, Key>,
+    /// The next access tick.
+    tick: u64,
+    /// The number of cache hits.
+    hits: u64,
+    /// The number of cache misses.
+    misses: u64,
+}
+
+/// A cache key.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Key {
+    /// The parsed statement.
+    statement: ast::Statement,
+    /// The statement's encoded parameter values. These are keyed by their
+    /// encoding rather than Value equality, which considers e.g. Integer(1)
+    /// and Float(1.0) equal even though they can yield different results.
+    params: Vec<u8>,
+    /// The transaction's snapshot version.
+    version: mvcc::Version,
+    /// The transaction's concurrent active transactions, whose writes are
+    /// invisible to it.
+    active: Vec<mvcc::Version>,
+}
+
+impl Key {
+    /// Creates a cache key for a statement executed in the given transaction.
+    fn new(statement: &ast::Statement, params: &[Value], state: &mvcc::TransactionState) -> Self {
+        Self {
+            statement: statement.clone(),
+            params: bincode::serialize(&params),
+            version: state.version,
+            active: state.active.iter().copied().collect(),
+        }
+    }
+}
+
+/// A cached query result: the result columns and rows.
+pub type CachedResult = (Vec<Label>, Vec<Row>);
+
+/// Result cache statistics.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ResultCacheStats {
+    /// The maximum number of entries.
+    pub capacity: usize,
+    /// The current number of entries.
+    pub entries: usize,
+    /// The number of cache hits.
+    pub hits: u64,
+    /// The number of cache misses.
+    pub misses: u64,
+}
+
+impl ResultCache {
+    /// Creates a new result cache holding at most capacity results.
+    pub fn new(capacity: usize) -> Self {
+        let inner = Inner {
+            capacity,
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        };
+        Self { inner: Mutex::new(inner) }
+    }
+
+    /// Looks up the cached result of a statement executed in the given
+    /// transaction, recording a hit or miss. Returns None for read-write
+    /// transactions, without recording a miss.
+    pub fn get(
+        &self,
+        statement: &ast::Statement,
+        params: &[Value],
+        state: &mvcc::TransactionState,
+    ) -> Result<Option<CachedResult>> {
+        if !state.read_only {
+            return Ok(None);
+        }
+        let key = Key::new(statement, params, state);
+        let mut inner = self.inner.lock()?;
+        let tick = inner.next_tick();
+        let Inner { entries, lru, .. } = &mut *inner;
+        let Some((last, result)) = entries.get_mut(&key) else {
+            inner.misses += 1;
+            return Ok(None);
+        };
+        // Bump the entry's recency.
+        let key = lru.remove(last).expect("missing LRU entry");
+        lru.insert(tick, key);
+        *last = tick;
+        let result = result.clone();
+        inner.hits += 1;
+        Ok(Some(result))
+    }
+
+    /// Caches the result of a statement executed in the given transaction,
+    /// evicting the least recently used entry if the cache is full. Ignores
+    /// read-write transactions.
+    pub fn put(
+        &self,
+        statement: &ast::Statement,
+        params: &[Value],
+        state: &mvcc::TransactionState,
+        result: CachedResult,
+    ) -> Result<()> {
+        if !state.read_only {
+            return Ok(());
+        }
+        let key = Key::new(statement, params, state);
+        let mut inner = self.inner.lock()?;
+        if inner.capacity == 0 {
+            return Ok(());
+        }
+        let tick = inner.next_tick();
+        if let Some((last, _)) = inner.entries.remove(&key) {
+            inner.lru.remove(&last);
+        }
+        while inner.entries.len() >= inner.capacity {
+            let Some((_, evict)) = inner.lru.pop_first() else { break };
+            inner.entries.remove(&evict);
+        }
+        inner.lru.insert(tick, key.clone());
+        inner.entries.insert(key, (tick, result));
+        Ok(())
+    }
+
+    /// Returns cache statistics.
+    pub fn stats(&self) -> Result<ResultCacheStats> {
+        let inner = self.inner.lock()?;
+        Ok(ResultCacheStats {
+            capacity: inner.capacity,
+            entries: inner.entries.len(),
+            hits: inner.hits,
+            misses: inner.misses,
+        })
+    }
+}
+
+impl Inner {
+    /// Returns the next access tick.
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::parser::Parser;
+
+    use std::collections::BTreeSet;
+
+    /// Returns a read-only transaction state at the given version.
+    fn state(version: mvcc::Version) -> mvcc::TransactionState {
+        mvcc::TransactionState { version, read_only: true, active: BTreeSet::new() }
+    }
+
+    /// Returns a result with a single row containing the given integer.
+    fn result(i: i64) -> CachedResult {
+        (vec![Label::None], vec![vec![Value::Integer(i)]])
+    }
+
+    /// Tests that entries are evicted in least recently used order.
+    #[test]
+    fn lru() -> Result<()> {
+        let cache = ResultCache::new(2);
+        let a = Parser::new("SELECT 1").parse()?;
+        let b = Parser::new("SELECT 2").parse()?;
+        let c = Parser::new("SELECT 3").parse()?;
+
+        cache.put(&a, &[], &state(1), result(1))?;
+        cache.put(&b, &[], &state(1), result(2))?;
+        assert_eq!(cache.get(&a, &[], &state(1))?, Some(result(1)));
+
+        // b is the least recently used entry, and is evicted.
+        cache.put(&c, &[], &state(1), result(3))?;
+        assert_eq!(cache.get(&b, &[], &state(1))?, None);
+        assert_eq!(cache.get(&a, &[], &state(1))?, Some(result(1)));
+        assert_eq!(cache.get(&c, &[], &state(1))?, Some(result(3)));
+
+        assert_eq!(
+            cache.stats()?,
+            ResultCacheStats { capacity: 2, entries: 2, hits: 3, misses: 1 }
+        );
+        Ok(())
+    }
+
+    /// Tests that entries are keyed by the snapshot, parameters, and statement,
+    /// and that read-write transactions don't use the cache.
+    #[test]
+    fn key() -> Result<()> {
+        let cache = ResultCache::new(10);
+        let select = Parser::new("SELECT $1").parse()?;
+        let one = [Value::Integer(1)];
+        cache.put(&select, &one, &state(1), result(1))?;
+
+        // Whitespace and keyword case are normalized by the parser.
+        let normalized = Parser::new("select   $1").parse()?;
+        assert_eq!(cache.get(&normalized, &one, &state(1))?, Some(result(1)));
+
+        assert_eq!(cache.get(&select, &[Value::Integer(2)], &state(1))?, None);
+        assert_eq!(cache.get(&select, &[Value::Float(1.0)], &state(1))?, None);
+        assert_eq!(cache.get(&select, &one, &state(2))?, None);
+        let mut active = state(1);
+        active.active.insert(0);
+        assert_eq!(cache.get(&select, &one, &active)?, None);
+
+        let mut read_write = state(1);
+        read_write.read_only = false;
+        assert_eq!(cache.get(&select, &one, &read_write)?, None);
+        cache.put(&select, &one, &read_write, result(2))?;
+        assert_eq!(cache.get(&select, &one, &state(1))?, Some(result(1)));
+
+        assert_eq!(
+            cache.stats()?,
+            ResultCacheStats { capacity: 10, entries: 1, hits: 2, misses: 4 }
+        );
+        Ok(())
+    }
+}
@@ -1,12 +1,16 @@
 #![allow(clippy::module_inception)]
 
 use super::Session;
+use crate::encoding;
 use crate::errinput;
-use crate::error::Result;
-use crate::sql::types::{Expression, Row, Rows, Table, Value};
+use crate::error::{Error, Result};
+use crate::sql::types::{Column, Expression, Row, Rows, Table, Value};
 use crate::storage::mvcc;
 
+use rand::Rng as _;
+use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
 
 /// A SQL engine. This provides low-level CRUD (create, read, update, delete)
 /// operations for table rows, a schema catalog for accessing and modifying
@@ -25,12 +29,60 @@ pub trait Engine<'a>: Sized {
     /// Begins a read-only transaction as of a historical version.
     fn begin_as_of(&'a self, version: mvcc::Version) -> Result<Self::Transaction>;
 
+    /// Runs a closure in a read-write transaction, committing it if the
+    /// closure succeeds and rolling it back if it fails. Serialization failures
+    /// (i.e. write conflicts) are retried up to max_retries times, using a new
+    /// transaction and randomized exponential backoff. Other errors are
+    /// returned immediately.
+    ///
+    /// The closure may be run several times, so it must be idempotent. Its
+    /// transactional writes are rolled back before retrying, but any other
+    /// side effects are not.
+    fn with_txn_retry<F, T>(&'a self, max_retries: u32, f: F) -> Result<T>
+    where
+        F: Fn(&mut Self::Transaction) -> Result<T>,
+    {
+        let mut retries = 0;
+        loop {
+            let mut txn = self.begin()?;
+            let result = match f(&mut txn) {
+                Ok(value) => txn.commit().map(|_| value),
+                Err(error) => {
+                    // Return the original error, not any rollback error.
+                    if let Err(rollback_error) = txn.rollback() {
+                        log::error!("transaction rollback failed: {rollback_error}");
+                    }
+                    Err(error)
+                }
+            };
+            match result {
+                Err(Error::Serialization) if retries < max_retries => {
+                    std::thread::sleep(retry_backoff(retries));
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
     /// Creates a session for executing SQL statements. Can't outlive engine.
     fn session(&'a self) -> Session<'a, Self> {
         Session::new(self)
     }
 }
 
+/// Returns the randomized exponential backoff before retrying a transaction
+/// the given number of times, between 1 and 100 milliseconds.
+fn retry_backoff(retries: u32) -> Duration {
+    const MIN_WAIT: u64 = 1;
+    const MAX_WAIT: u64 = 100;
+    // Cap the exponent to avoid overflow, the wait is capped anyway.
+    let wait = std::cmp::min(MIN_WAIT * 2_u64.pow(retries.min(16)), MAX_WAIT);
+    // Randomize the wait time to reduce the chance of the same transactions
+    // colliding again.
+    Duration::from_millis(rand::thread_rng().gen_range(MIN_WAIT..=wait))
+}
+
 /// A SQL transaction. Executes transactional CRUD operations on table rows.
 /// Provides snapshot isolation (see `storage::mvcc` module for details).
 ///
@@ -53,21 +105,53 @@ pub trait Transaction {
     fn commit(self) -> Result<()>;
     /// Rolls back the transaction.
     fn rollback(self) -> Result<()>;
+    /// Creates a savepoint with the given name.
+    fn savepoint(&self, name: &str) -> Result<()>;
+    /// Rolls back to the latest savepoint with the given name, undoing any
+    /// writes made after it. Errors if the savepoint does not exist.
+    fn rollback_to_savepoint(&self, name: &str) -> Result<()>;
+    /// Releases the latest savepoint with the given name, retaining its
+    /// writes. Errors if the savepoint does not exist.
+    fn release_savepoint(&self, name: &str) -> Result<()>;
 
-    /// Deletes table rows by primary key, if they exist.
-    fn delete(&self, table: &str, ids: &[Value]) -> Result<()>;
+    /// Deletes table rows by primary key, if they exist. A primary key is given
+    /// as the values of the primary key columns, in key order.
+    fn delete(&self, table: &str, ids: &[Vec<Value>]) -> Result<()>;
     /// Fetches table rows by primary key, if they exist.
-    fn get(&self, table: &str, ids: &[Value]) -> Result<Vec<Row>>;
+    fn get(&self, table: &str, ids: &[Vec<Value>]) -> Result<Vec<Row>>;
     /// Inserts new table rows.
     fn insert(&self, table: &str, rows: Vec<Row>) -> Result<()>;
     /// Looks up a set of primary keys by index values. BTreeSet for testing.
-    fn lookup_index(&self, table: &str, column: &str, values: &[Value]) -> Result<BTreeSet<Value>>;
+    fn lookup_index(
+        &self,
+        table: &str,
+        column: &str,
+        values: &[Value],
+    ) -> Result<BTreeSet<Vec<Value>>>;
     /// Scans a table's rows, optionally applying the given filter.
     fn scan(&self, table: &str, filter: Option<Expression>) -> Result<Rows>;
+    /// Returns table statistics, as maintained by writes to the table.
+    fn table_stats(&self, table: &str) -> Result<TableStats>;
+    /// Recomputes exact table statistics by scanning the table, stores them,
+    /// and returns them.
+    fn analyze(&self, table: &str) -> Result<TableStats>;
+    /// Deletes all rows in a table, along with their index entries, and
+    /// returns the number of deleted rows. Errors if other tables have rows
+    /// that reference the table.
+    fn truncate(&self, table: &str) -> Result<u64>;
     /// Updates table rows by primary key. Uses BTreeMap for testing.
-    fn update(&self, table: &str, rows: BTreeMap<Value, Row>) -> Result<()>;
+    fn update(&self, table: &str, rows: BTreeMap<Vec<Value>, Row>) -> Result<()>;
+}
+
+/// Table statistics, used for cost-based planning decisions.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TableStats {
+    /// The number of rows in the table.
+    pub rows: u64,
 }
 
+impl encoding::Value for TableStats {}
+
 /// The catalog stores table schema information. It must be implemented for
 /// Engine::Transaction, and is thus fully transactional. For simplicity, it
 /// only supports creating and dropping tables. There are no ALTER TABLE schema
@@ -81,8 +165,32 @@ pub trait Catalog {
     /// Creates a new table. Errors if it already exists.
     fn create_table(&self, table: Table) -> Result<()>;
     /// Drops a table. Errors if it does not exist, unless if_exists is true.
-    /// Returns true if the table existed and was deleted.
-    fn drop_table(&self, table: &str, if_exists: bool) -> Result<bool>;
+    /// Errors if other tables have foreign key references to it, unless
+    /// cascade is true, in which case the referencing tables are dropped too
+    /// (recursively). Returns None if the table did not exist, otherwise the
+    /// names of the referencing tables that were dropped.
+    fn drop_table(
+        &self,
+        table: &str,
+        if_exists: bool,
+        cascade: bool,
+    ) -> Result<Option<Vec<String>>>;
+    /// Adds a column to a table. Existing rows are not rewritten, and instead
+    /// take on the column's default value when read. Errors if the column
+    /// already exists, or if it has no default value.
+    fn add_column(&self, table: &str, column: Column) -> Result<()>;
+    /// Drops a column from a table, rewriting existing rows and dropping any
+    /// index. Errors if the column does not exist, or is the primary key or a
+    /// foreign key.
+    fn drop_column(&self, table: &str, column: &str) -> Result<()>;
+    /// Creates a secondary index on a table column, indexing existing rows.
+    /// The index can optionally be given a name, unique across all tables.
+    /// Errors if the index or index name already exists.
+    fn create_index(&self, table: &str, column: &str, name: Option<&str>) -> Result<()>;
+    /// Drops a secondary index on a table column. Errors if it does not exist,
+    /// unless if_exists is true. Returns true if the index existed and was
+    /// deleted.
+    fn drop_index(&self, table: &str, column: &str, if_exists: bool) -> Result<bool>;
     /// Fetches a table schema, or None if it doesn't exist.
     fn get_table(&self, table: &str) -> Result<Option<Table>>;
     /// Returns a list of all table schemas.
@@ -92,4 +200,90 @@ pub trait Catalog {
     fn must_get_table(&self, table: &str) -> Result<Table> {
         self.get_table(table)?.ok_or_else(|| errinput!("table {table} does not exist"))
     }
+
+    /// Looks up a named index, returning its table and column names, or None
+    /// if no index has the given name.
+    fn find_index(&self, name: &str) -> Result<Option<(String, String)>> {
+        for table in self.list_tables()? {
+            if let Some(column) =
+                table.columns.iter().find(|c| c.index_name.as_deref() == Some(name))
+            {
+                return Ok(Some((table.name.clone(), column.name.clone())));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sql::engine::Local;
+    use crate::storage::Memory;
+
+    use std::cell::Cell;
+
+    /// Tests that with_txn_retry() retries serialization failures, and returns
+    /// other errors immediately.
+    #[test]
+    fn with_txn_retry() -> Result<()> {
+        let engine = Local::new(Memory::new());
+        engine.session().execute("CREATE TABLE test (id INT PRIMARY KEY, value INT)")?;
+        engine.session().execute("INSERT INTO test VALUES (1, 0)")?;
+
+        // Increment the value. On the first attempt, a concurrent transaction
+        // writes the same row and commits first, causing a write conflict.
+        let attempts = Cell::new(0);
+        let value = engine.with_txn_retry(3, |txn| {
+            attempts.set(attempts.get() + 1);
+            let Some(row) = txn.get("test", &[vec![Value::Integer(1)]])?.pop() else {
+                return errinput!("row not found");
+            };
+            if attempts.get() == 1 {
+                engine.session().execute("UPDATE test SET value = 10 WHERE id = 1")?;
+            }
+            let value = i64::try_from(row[1].clone())? + 1;
+            let row = vec![Value::Integer(1), Value::Integer(value)];
+            txn.update("test", BTreeMap::from([(vec![Value::Integer(1)], row)]))?;
+            Ok(value)
+        })?;
+        assert_eq!(attempts.get(), 2);
+        assert_eq!(value, 11);
+        assert_eq!(i64::try_from(engine.session().execute("SELECT value FROM test")?)?, 11);
+
+        // Conflicts are retried up to max_retries times.
+        attempts.set(0);
+        let result = engine.with_txn_retry(2, |txn| {
+            attempts.set(attempts.get() + 1);
+            engine.session().execute("UPDATE test SET value = 0 WHERE id = 1")?;
+            let row = vec![Value::Integer(1), Value::Integer(-1)];
+            txn.update("test", BTreeMap::from([(vec![Value::Integer(1)], row)]))
+        });
+        assert_eq!(result, Err(Error::Serialization));
+        assert_eq!(attempts.get(), 3);
+
+        // Other errors are returned immediately, and writes are rolled back.
+        attempts.set(0);
+        let result: Result<()> = engine.with_txn_retry(2, |txn| {
+            attempts.set(attempts.get() + 1);
+            txn.delete("test", &[vec![Value::Integer(1)]])?;
+            errinput!("boom")
+        });
+        assert_eq!(result, errinput!("boom"));
+        assert_eq!(attempts.get(), 1);
+        assert_eq!(i64::try_from(engine.session().execute("SELECT COUNT(*) FROM test")?)?, 1);
+
+        Ok(())
+    }
+
+    /// Tests that the retry backoff grows exponentially up to 100 ms, without
+    /// overflowing for large retry counts.
+    #[test]
+    fn retry_backoff() {
+        assert_eq!(super::retry_backoff(0), Duration::from_millis(1));
+        for retries in [1, 6, 7, 63, 64, u32::MAX] {
+            let wait = super::retry_backoff(retries);
+            assert!(wait >= Duration::from_millis(1) && wait <= Duration::from_millis(100));
+        }
+    }
 }
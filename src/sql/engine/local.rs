@@ -1,14 +1,16 @@
-use super::Catalog;
-use crate::encoding::{self, Key as _, Value as _};
-use crate::errinput;
+use super::{Catalog, TableStats};
+use crate::encoding::{self, bincode, keycode, Key as _, Value as _};
 use crate::error::Result;
-use crate::sql::types::{Expression, Row, Rows, Table, Value};
+use crate::sql::types::{format_id, Column, Expression, OnDelete, Row, Rows, Table, Value};
 use crate::storage::{self, mvcc};
+use crate::{errdata, errinput};
 
 use itertools::Itertools as _;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::collections::btree_map::Entry;
 use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
 
 /// A SQL engine using local storage. This provides the main SQL storage logic,
 /// and the Raft SQL engine just dispatches to this for node-local SQL storage.
@@ -74,9 +76,38 @@ impl<E: storage::Engine> Transaction<E> {
         self.txn.state()
     }
 
+    /// Scans a page of table rows, starting after the given row key (if any).
+    /// Returns at most limit rows matching the filter, along with the row key
+    /// to continue the scan from if the page is full. Used by the Raft engine,
+    /// which pages scans to bound the size of each read.
+    pub fn scan_page(
+        &self,
+        table: &str,
+        filter: Option<&Expression>,
+        after: Option<Vec<u8>>,
+        limit: usize,
+    ) -> Result<(Vec<Row>, Option<Vec<u8>>)> {
+        let table = self.must_get_table(table)?;
+        let (start, end) = keycode::prefix_range(&KeyPrefix::Row((&table.name).into()).encode());
+        let start = after.map(Bound::Excluded).unwrap_or(start);
+        let mut scan = self.txn.scan((start, end));
+        let mut rows = Vec::new();
+        while let Some((key, value)) = scan.next().transpose()? {
+            let row = table.pad_row(decode_row(&value)?);
+            if filter.map(|filter| filter_row(filter, &row)).transpose()? == Some(false) {
+                continue;
+            }
+            rows.push(row);
+            if rows.len() >= limit {
+                return Ok((rows, Some(key)));
+            }
+        }
+        Ok((rows, None))
+    }
+
     /// Fetches the matching primary keys for the given secondary index value,
     /// or an empty set if there is none. The value must already be normalized.
-    fn get_index(&self, table: &str, column: &str, value: &Value) -> Result<BTreeSet<Value>> {
+    fn get_index(&self, table: &str, column: &str, value: &Value) -> Result<BTreeSet<Vec<Value>>> {
         debug_assert!(self.has_index(table, column)?, "no index on {table}.{column}");
         debug_assert!(value.is_normalized(), "value not normalized");
         Ok(self
@@ -88,12 +119,13 @@ impl<E: storage::Engine> Transaction<E> {
     }
 
     /// Fetches a single row by primary key, or None if it doesn't exist. The key
-    /// must already be normalized.
-    fn get_row(&self, table: &str, id: &Value) -> Result<Option<Row>> {
-        debug_assert!(id.is_normalized(), "value not normalized");
+    /// must already be normalized. The row is padded with the default values of
+    /// any columns added after it was written.
+    fn get_row(&self, table: &Table, id: &[Value]) -> Result<Option<Row>> {
+        debug_assert!(id.iter().all(|v| v.is_normalized()), "value not normalized");
         self.txn
-            .get(&Key::Row(table.into(), id.into()).encode())?
-            .map(|v| Row::decode(&v))
+            .get(&Key::Row((&table.name).into(), id.into()).encode())?
+            .map(|v| decode_row(&v).map(|row| table.pad_row(row)))
             .transpose()
     }
 
@@ -110,11 +142,11 @@ impl<E: storage::Engine> Transaction<E> {
         table: &str,
         column: &str,
         value: &Value,
-        ids: BTreeSet<Value>,
+        ids: BTreeSet<Vec<Value>>,
     ) -> Result<()> {
         debug_assert!(self.has_index(table, column)?, "no index on {table}.{column}");
         debug_assert!(value.is_normalized(), "value not normalized");
-        debug_assert!(ids.iter().all(|v| v.is_normalized()), "value not normalized");
+        debug_assert!(ids.iter().flatten().all(|v| v.is_normalized()), "value not normalized");
         let key = Key::Index(table.into(), column.into(), value.into()).encode();
         if ids.is_empty() {
             self.txn.delete(&key)
@@ -123,6 +155,47 @@ impl<E: storage::Engine> Transaction<E> {
         }
     }
 
+    /// Adjusts a table's row count by the given delta. Each transaction stores
+    /// its own delta, to avoid write conflicts between concurrent writers.
+    ///
+    /// To keep table_stats() from scanning an ever-growing number of deltas,
+    /// transactions whose version is a multiple of ROW_COUNT_FOLD_INTERVAL fold
+    /// all visible deltas into their own. Only one transaction per interval
+    /// does this, so concurrent writers rarely conflict on the old deltas.
+    fn add_row_count(&self, table: &str, delta: i64) -> Result<()> {
+        if delta == 0 {
+            return Ok(());
+        }
+        let key = Key::TableStats(table.into(), self.txn.version()).encode();
+        let count: i64 = match self.txn.get(&key)? {
+            Some(value) => bincode::deserialize(&value)?,
+            None if self.txn.version().is_multiple_of(ROW_COUNT_FOLD_INTERVAL) => {
+                self.take_row_counts(table)?
+            }
+            None => 0,
+        };
+        self.txn.set(&key, bincode::serialize(&(count + delta)))
+    }
+
+    /// Deletes all of a table's visible row count deltas, returning their sum.
+    fn take_row_counts(&self, table: &str) -> Result<i64> {
+        // storage::Engine doesn't support writing while scanning, so we buffer
+        // the keys in a vector.
+        let prefix = KeyPrefix::TableStats(table.into()).encode();
+        let mut count = 0;
+        let mut keys = Vec::new();
+        let mut scan = self.txn.scan_prefix(&prefix);
+        while let Some((key, value)) = scan.next().transpose()? {
+            count += bincode::deserialize::<i64>(&value)?;
+            keys.push(key);
+        }
+        drop(scan);
+        for key in keys {
+            self.txn.delete(&key)?;
+        }
+        Ok(count)
+    }
+
     /// Returns all tables referencing a table, as (table, column index) pairs.
     /// This includes references from the table itself.
     fn table_references(&self, table: &str) -> Result<Vec<(Table, Vec<usize>)>> {
@@ -142,6 +215,40 @@ impl<E: storage::Engine> Transaction<E> {
             .filter(|(_, references)| !references.is_empty())
             .collect())
     }
+
+    /// Deletes a table's schema entry, rows, secondary indexes, and statistics.
+    fn delete_table(&self, table: &Table) -> Result<()> {
+        // Delete the table schema entry.
+        self.txn.delete(&Key::Table((&table.name).into()).encode())?;
+
+        // Delete the table rows. storage::Engine doesn't support writing while
+        // scanning, so we buffer all keys in a vector. We could also do this in
+        // batches, although we'd want to do the batching above Raft to avoid
+        // blocking Raft processing for the duration of the drop.
+        let prefix = &KeyPrefix::Row((&table.name).into()).encode();
+        let keys: Vec<Vec<u8>> =
+            self.txn.scan_prefix(prefix).map_ok(|(key, _)| key).try_collect()?;
+        for key in keys {
+            self.txn.delete(&key)?;
+        }
+
+        // Delete any secondary indexes.
+        for column in table.columns.iter().filter(|c| c.index) {
+            let prefix = &KeyPrefix::Index((&table.name).into(), (&column.name).into()).encode();
+            let keys: Vec<_> = self.txn.scan_prefix(prefix).map_ok(|(key, _)| key).try_collect()?;
+            for key in keys {
+                self.txn.delete(&key)?;
+            }
+        }
+
+        // Delete the table statistics.
+        let prefix = &KeyPrefix::TableStats((&table.name).into()).encode();
+        let keys: Vec<_> = self.txn.scan_prefix(prefix).map_ok(|(key, _)| key).try_collect()?;
+        for key in keys {
+            self.txn.delete(&key)?;
+        }
+        Ok(())
+    }
 }
 
 impl<E: storage::Engine> super::Transaction for Transaction<E> {
@@ -157,89 +264,230 @@ impl<E: storage::Engine> super::Transaction for Transaction<E> {
         self.txn.rollback()
     }
 
-    fn delete(&self, table: &str, ids: &[Value]) -> Result<()> {
-        let table = self.must_get_table(table)?;
-        let indexes = table.columns.iter().enumerate().filter(|(_, c)| c.index).collect_vec();
+    fn savepoint(&self, name: &str) -> Result<()> {
+        self.txn.savepoint(name)
+    }
 
-        // Check for foreign key references to the deleted rows.
-        for (source, refs) in self.table_references(&table.name)? {
-            let self_reference = source.name == table.name;
-            for i in refs {
-                let column = &source.columns[i];
-                let mut source_ids = if i == source.primary_key {
-                    // If the reference is from a primary key column, do a lookup.
-                    self.get(&source.name, ids)?
-                        .into_iter()
-                        .map(|row| row.into_iter().nth(i).expect("short row"))
-                        .collect()
-                } else {
-                    // Otherwise (commonly), do a secondary index lookup.
-                    // All foreign keys have a secondary index.
-                    self.lookup_index(&source.name, &column.name, ids)?
-                };
-                // We can ignore any references between the deleted rows,
-                // including a row referring to itself.
-                if self_reference {
-                    for id in ids {
-                        source_ids.remove(id);
+    fn rollback_to_savepoint(&self, name: &str) -> Result<()> {
+        self.txn.rollback_to_savepoint(name)
+    }
+
+    fn release_savepoint(&self, name: &str) -> Result<()> {
+        self.txn.release_savepoint(name)
+    }
+
+    fn delete(&self, table: &str, ids: &[Vec<Value>]) -> Result<()> {
+        // Find all rows to delete, following ON DELETE CASCADE references
+        // recursively, along with any ON DELETE SET NULL references to them.
+        // Rows that have already been visited are skipped, which terminates
+        // reference cycles. ON DELETE RESTRICT references are checked once all
+        // deleted rows are known, since a referencing row may itself be
+        // deleted, e.g. via another cascade path. Nothing is written until
+        // then, to avoid partial deletes on errors.
+        let mut delete: BTreeMap<String, (Table, BTreeSet<Vec<Value>>)> = BTreeMap::new();
+        let mut restrict = Vec::new();
+        let mut set_null = Vec::new();
+        let ids: BTreeSet<_> = ids
+            .iter()
+            .map(|id| id.iter().map(|v| v.normalize_ref().into_owned()).collect_vec())
+            .collect();
+        let mut queue = vec![(self.must_get_table(table)?, ids)];
+        while let Some((table, ids)) = queue.pop() {
+            let (_, deleted) = delete
+                .entry(table.name.clone())
+                .or_insert_with(|| (table.clone(), BTreeSet::new()));
+            let ids: Vec<_> = ids.into_iter().filter(|id| deleted.insert(id.clone())).collect();
+            if ids.is_empty() {
+                continue;
+            }
+            for (source, refs) in self.table_references(&table.name)? {
+                for i in refs {
+                    let column = &source.columns[i];
+                    let source_ids = if source.primary_key == [i] {
+                        // If the reference is from a primary key column, do a lookup.
+                        self.get(&source.name, &ids)?.iter().map(|row| source.row_id(row)).collect()
+                    } else {
+                        // Otherwise (commonly), do a secondary index lookup.
+                        // All foreign keys have a secondary index, unless
+                        // they're the primary key. Only single-column primary
+                        // keys can be referenced, so ids are single values.
+                        let values = ids.iter().flatten().cloned().collect_vec();
+                        self.lookup_index(&source.name, &column.name, &values)?
+                    };
+                    if source_ids.is_empty() {
+                        continue;
+                    }
+                    match column.on_delete {
+                        OnDelete::Restrict => restrict.push((source.clone(), source_ids)),
+                        OnDelete::Cascade => queue.push((source.clone(), source_ids)),
+                        OnDelete::SetNull => set_null.push((source.clone(), i, source_ids)),
                     }
-                }
-                // Error if the delete would violate referential integrity.
-                if let Some(source_id) = source_ids.first() {
-                    let table = source.name;
-                    let column = &source.columns[source.primary_key].name;
-                    return errinput!("row referenced by {table}.{column}={source_id}");
                 }
             }
         }
+        let is_deleted = |table: &str, id: &Vec<Value>| {
+            delete.get(table).is_some_and(|(_, ids)| ids.contains(id))
+        };
 
-        // Delete the rows.
-        for id in ids {
-            let id = id.normalize_ref();
+        // Error if the delete would violate referential integrity. We can
+        // ignore any references between the deleted rows, including a row
+        // referring to itself.
+        for (source, source_ids) in restrict {
+            if let Some(source_id) = source_ids.iter().find(|id| !is_deleted(&source.name, id)) {
+                return errinput!("row referenced by {}", format_row(&source, source_id));
+            }
+        }
 
-            // Update any index entries.
-            if !indexes.is_empty() {
-                if let Some(row) = self.get_row(&table.name, &id)? {
+        // Delete the rows.
+        for (table, ids) in delete.values() {
+            let indexes = table.columns.iter().enumerate().filter(|(_, c)| c.index).collect_vec();
+            let mut count = 0;
+            for id in ids {
+                // Update any index entries, and count the existing rows.
+                if let Some(row) = self.get_row(table, id)? {
                     for (i, column) in indexes.iter().copied() {
                         let mut index = self.get_index(&table.name, &column.name, &row[i])?;
-                        index.remove(&id);
+                        index.remove(id);
                         self.set_index(&table.name, &column.name, &row[i], index)?;
                     }
+                    count += 1;
                 }
+
+                self.txn.delete(&Key::Row((&table.name).into(), id.into()).encode())?;
             }
+            self.add_row_count(&table.name, -count)?;
+        }
 
-            self.txn.delete(&Key::Row((&table.name).into(), id).encode())?;
+        // Set ON DELETE SET NULL references to NULL, unless the referencing
+        // rows were deleted too.
+        for (source, i, source_ids) in set_null {
+            let ids =
+                source_ids.into_iter().filter(|id| !is_deleted(&source.name, id)).collect_vec();
+            let rows = self
+                .get(&source.name, &ids)?
+                .into_iter()
+                .map(|mut row| {
+                    row[i] = Value::Null;
+                    (source.row_id(&row), row)
+                })
+                .collect();
+            self.update(&source.name, rows)?;
         }
         Ok(())
     }
 
-    fn get(&self, table: &str, ids: &[Value]) -> Result<Vec<Row>> {
-        ids.iter().filter_map(|id| self.get_row(table, &id.normalize_ref()).transpose()).collect()
+    fn get(&self, table: &str, ids: &[Vec<Value>]) -> Result<Vec<Row>> {
+        let table = self.must_get_table(table)?;
+        // String IDs may have to be coerced to the primary key datatypes, e.g.
+        // timestamps. IDs of the wrong length can't match any rows.
+        let mut rows = Vec::new();
+        for id in ids.iter().filter(|id| id.len() == table.primary_key.len()) {
+            let id: Vec<_> = id
+                .iter()
+                .zip(&table.primary_key)
+                .map(|(v, i)| match v {
+                    Value::String(_) => v.clone().coerce(table.columns[*i].datatype),
+                    v => Ok(v.clone()),
+                })
+                .map_ok(|v| v.normalize_ref().into_owned())
+                .try_collect()?;
+            rows.extend(self.get_row(&table, &id)?);
+        }
+        Ok(rows)
     }
 
     fn insert(&self, table: &str, rows: Vec<Row>) -> Result<()> {
         let table = self.must_get_table(table)?;
-        for mut row in rows {
-            // Normalize the row.
-            row.iter_mut().for_each(|v| v.normalize());
 
-            // Insert the row.
+        // Secondary index updates are buffered and written once the batch is
+        // done, to avoid rewriting an index entry for every row with that
+        // value. Rows are written immediately, such that validate_row() sees
+        // them for primary key and reference checks.
+        let mut indexes: BTreeMap<(usize, Value), BTreeSet<Vec<Value>>> = BTreeMap::new();
+        let mut unique: BTreeSet<(usize, Vec<Value>)> = BTreeSet::new();
+        let mut written = Vec::new();
+        let result = rows.into_iter().try_for_each(|row| {
+            // Coerce and normalize the row.
+            let row = table.coerce_row(row)?;
+
+            // Insert the row. validate_row() only checks unique columns
+            // against stored indexes, so check the buffered entries too.
             table.validate_row(&row, false, self)?;
-            let id = &row[table.primary_key];
-            self.txn.set(&Key::Row((&table.name).into(), id.into()).encode(), row.encode())?;
+            for (i, column) in table.columns.iter().enumerate() {
+                if column.unique
+                    && table.primary_key != [i]
+                    && !row[i].is_undefined()
+                    && indexes.get(&(i, row[i].clone())).is_some_and(|ids| !ids.is_empty())
+                {
+                    let (value, cname) = (&row[i], &column.name);
+                    return errinput!("value {value} already in unique column {cname}");
+                }
+            }
+            for (i, columns) in table.unique.iter().enumerate() {
+                let values = columns.iter().map(|c| row[*c].clone()).collect_vec();
+                if !values.iter().any(|v| v.is_undefined()) && !unique.insert((i, values.clone())) {
+                    let (values, cnames) = (format_id(&values), table.format_columns(columns));
+                    return errinput!("values {values} already in unique columns ({cnames})");
+                }
+            }
+            let id = table.row_id(&row);
+            self.txn
+                .set(&Key::Row((&table.name).into(), (&id).into()).encode(), encode_row(&row))?;
+            written.push(id.clone());
 
             // Update any secondary indexes.
             for (i, column) in table.columns.iter().enumerate().filter(|(_, c)| c.index) {
-                let mut index = self.get_index(&table.name, &column.name, &row[i])?;
-                index.insert(id.clone());
-                self.set_index(&table.name, &column.name, &row[i], index)?;
+                let ids = match indexes.entry((i, row[i].clone())) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        entry.insert(self.get_index(&table.name, &column.name, &row[i])?)
+                    }
+                };
+                ids.insert(id.clone());
             }
+            Ok(())
+        });
+
+        // If a row failed, remove the rows already written. Otherwise, a
+        // failed INSERT would leave partial writes in an explicit transaction.
+        if let Err(err) = result {
+            for id in written {
+                self.txn.delete(&Key::Row((&table.name).into(), (&id).into()).encode())?;
+            }
+            return Err(err);
         }
-        Ok(())
+
+        for ((i, value), ids) in indexes {
+            self.set_index(&table.name, &table.columns[i].name, &value, ids)?;
+        }
+        self.add_row_count(&table.name, written.len() as i64)
     }
 
-    fn lookup_index(&self, table: &str, column: &str, values: &[Value]) -> Result<BTreeSet<Value>> {
+    fn lookup_index(
+        &self,
+        table: &str,
+        column: &str,
+        values: &[Value],
+    ) -> Result<BTreeSet<Vec<Value>>> {
         debug_assert!(self.has_index(table, column)?, "no index on {table}.{column}");
+        // String values may have to be coerced to the column datatype, e.g.
+        // timestamps. Avoid the table lookup in the common case.
+        if values.iter().any(|v| matches!(v, Value::String(_))) {
+            let table = self.must_get_table(table)?;
+            let Some(column) = table.columns.iter().find(|c| c.name == column) else {
+                return errinput!("unknown column {column} in table {}", table.name);
+            };
+            let datatype = column.datatype;
+            let values: Vec<_> = values
+                .iter()
+                .map(|v| v.clone().coerce(datatype).map(|v| v.normalize_ref().into_owned()))
+                .try_collect()?;
+            return values
+                .iter()
+                .map(|v| self.get_index(&table.name, &column.name, v))
+                .flatten_ok()
+                .collect();
+        }
         values
             .iter()
             .map(|v| self.get_index(table, column, &v.normalize_ref()))
@@ -249,35 +497,122 @@ impl<E: storage::Engine> super::Transaction for Transaction<E> {
 
     fn scan(&self, table: &str, filter: Option<Expression>) -> Result<Rows> {
         // TODO: this could be simpler if process_results() implemented Clone.
-        let rows = self
-            .txn
-            .scan_prefix(&KeyPrefix::Row(table.into()).encode())
-            .map(|result| result.and_then(|(_, value)| Row::decode(&value)));
+        let table = self.must_get_table(table)?;
+        let rows = self.txn.scan_prefix(&KeyPrefix::Row((&table.name).into()).encode()).map(
+            move |result| {
+                result.and_then(|(_, value)| decode_row(&value).map(|row| table.pad_row(row)))
+            },
+        );
         let Some(filter) = filter else {
             return Ok(Box::new(rows));
         };
         let rows = rows.filter_map(move |result| {
-            result
-                .and_then(|row| match filter.evaluate(Some(&row))? {
-                    Value::Boolean(true) => Ok(Some(row)),
-                    Value::Boolean(false) | Value::Null => Ok(None),
-                    value => errinput!("filter returned {value}, expected boolean"),
-                })
-                .transpose()
+            result.and_then(|row| Ok(filter_row(&filter, &row)?.then_some(row))).transpose()
         });
         Ok(Box::new(rows))
     }
 
-    fn update(&self, table: &str, rows: BTreeMap<Value, Row>) -> Result<()> {
+    fn table_stats(&self, table: &str) -> Result<TableStats> {
+        let table = self.must_get_table(table)?;
+        let prefix = KeyPrefix::TableStats((&table.name).into()).encode();
+        let mut rows: i64 = 0;
+        let mut scan = self.txn.scan_prefix(&prefix);
+        while let Some((_, value)) = scan.next().transpose()? {
+            rows += bincode::deserialize::<i64>(&value)?;
+        }
+        // The count can be negative if rows were written before row counts
+        // were maintained. analyze() will fix this.
+        Ok(TableStats { rows: rows.max(0) as u64 })
+    }
+
+    fn analyze(&self, table: &str) -> Result<TableStats> {
+        let table = self.must_get_table(table)?;
+
+        // Count the rows by scanning their key range, without decoding them.
+        let prefix = KeyPrefix::Row((&table.name).into()).encode();
+        let rows: u64 = self.txn.scan_prefix(&prefix).map_ok(|_| 1).sum::<Result<_>>()?;
+
+        // Replace the row count deltas with a single exact count.
+        self.take_row_counts(&table.name)?;
+        self.add_row_count(&table.name, rows as i64)?;
+        Ok(TableStats { rows })
+    }
+
+    fn truncate(&self, table: &str) -> Result<u64> {
+        let table = self.must_get_table(table)?;
+
+        // Check for foreign key references from other tables. References from
+        // the table's own rows are deleted along with the rows.
+        for (source, refs) in self.table_references(&table.name)? {
+            if source.name == table.name {
+                continue;
+            }
+            for i in refs {
+                let source_id = if source.primary_key == [i] {
+                    // Primary keys can't be NULL, so any row is a reference.
+                    let prefix = KeyPrefix::Row((&source.name).into()).encode();
+                    match self.txn.scan_prefix(&prefix).next().transpose()? {
+                        Some((_, value)) => Some(source.row_id(&decode_row(&value)?)),
+                        None => None,
+                    }
+                } else {
+                    // Otherwise, look for non-NULL entries in the index. All
+                    // foreign keys have a secondary index.
+                    let column = &source.columns[i];
+                    let prefix =
+                        KeyPrefix::Index((&source.name).into(), (&column.name).into()).encode();
+                    let mut source_id = None;
+                    let mut scan = self.txn.scan_prefix(&prefix);
+                    while let Some((key, value)) = scan.next().transpose()? {
+                        let Key::Index(_, _, index_value) = Key::decode(&key)? else {
+                            return errdata!("invalid index key {key:?}");
+                        };
+                        if *index_value != Value::Null {
+                            source_id = BTreeSet::<Vec<Value>>::decode(&value)?.pop_first();
+                            break;
+                        }
+                    }
+                    source_id
+                };
+                // Error if the truncate would violate referential integrity.
+                if let Some(source_id) = source_id {
+                    return errinput!("row referenced by {}", format_row(&source, &source_id));
+                }
+            }
+        }
+
+        // Delete the rows and index entries by scanning their key ranges.
+        // Unlike delete(), this doesn't have to read and decode the rows to
+        // update the index entries one by one. storage::Engine doesn't support
+        // writing while scanning, so we buffer the keys in a vector.
+        let prefix = KeyPrefix::Row((&table.name).into()).encode();
+        let keys: Vec<_> = self.txn.scan_prefix(&prefix).map_ok(|(key, _)| key).try_collect()?;
+        let count = keys.len() as u64;
+        for key in keys {
+            self.txn.delete(&key)?;
+        }
+        for column in table.columns.iter().filter(|c| c.index) {
+            let prefix = KeyPrefix::Index((&table.name).into(), (&column.name).into()).encode();
+            let keys: Vec<_> =
+                self.txn.scan_prefix(&prefix).map_ok(|(key, _)| key).try_collect()?;
+            for key in keys {
+                self.txn.delete(&key)?;
+            }
+        }
+        self.add_row_count(&table.name, -(count as i64))?;
+        Ok(count)
+    }
+
+    fn update(&self, table: &str, rows: BTreeMap<Vec<Value>, Row>) -> Result<()> {
         let table = self.must_get_table(table)?;
-        for (mut id, mut row) in rows {
-            // Normalize the ID and row.
-            id.normalize();
-            row.iter_mut().for_each(|v| v.normalize());
+        for (mut id, row) in rows {
+            // Normalize the ID, and coerce and normalize the row.
+            id.iter_mut().for_each(|v| v.normalize());
+            let row = table.coerce_row(row)?;
 
             // If the primary key changes, we simply do a delete and insert.
             // This simplifies constraint validation.
-            if id != row[table.primary_key] {
+            if id != table.row_id(&row) {
                 self.delete(&table.name, &[id])?;
                 self.insert(&table.name, vec![row])?;
                 continue;
@@ -310,7 +645,8 @@ impl<E: storage::Engine> super::Transaction for Transaction<E> {
             }
 
             // Update the row.
-            self.txn.set(&Key::Row((&table.name).into(), (&id).into()).encode(), row.encode())?;
+            self.txn
+                .set(&Key::Row((&table.name).into(), (&id).into()).encode(), encode_row(&row))?;
         }
         Ok(())
     }
@@ -325,47 +661,202 @@ impl<E: storage::Engine> Catalog for Transaction<E> {
         self.txn.set(&Key::Table((&table.name).into()).encode(), table.encode())
     }
 
-    fn drop_table(&self, table: &str, if_exists: bool) -> Result<bool> {
+    fn drop_table(
+        &self,
+        table: &str,
+        if_exists: bool,
+        cascade: bool,
+    ) -> Result<Option<Vec<String>>> {
         let table = match self.get_table(table)? {
             Some(table) => table,
-            None if if_exists => return Ok(false),
+            None if if_exists => return Ok(None),
             None => return errinput!("table {table} does not exist"),
         };
 
-        // Check for foreign key references.
-        if let Some((source, refs)) =
-            self.table_references(&table.name)?.iter().find(|(t, _)| t.name != table.name)
-        {
-            return errinput!(
-                "table {} is referenced from {}.{}",
-                table.name,
-                source.name,
-                source.columns[refs[0]].name
-            );
+        // Find the tables to drop. Without cascade, error if other tables have
+        // foreign key references to the table. With cascade, drop them as well,
+        // along with any tables referencing them, and so on.
+        let mut tables = vec![table];
+        let mut i = 0;
+        while i < tables.len() {
+            let name = tables[i].name.clone();
+            let mut references = Vec::new();
+            for (source, refs) in self.table_references(&name)? {
+                if tables.iter().any(|t| t.name == source.name) {
+                    continue;
+                }
+                references.extend(
+                    refs.iter().map(|r| format!("{}.{}", source.name, source.columns[*r].name)),
+                );
+                if cascade {
+                    tables.push(source);
+                }
+            }
+            if !cascade && !references.is_empty() {
+                return errinput!("table {name} is referenced from {}", references.join(", "));
+            }
+            i += 1;
         }
 
-        // Delete the table schema entry.
-        self.txn.delete(&Key::Table((&table.name).into()).encode())?;
+        for table in &tables {
+            self.delete_table(table)?;
+        }
+        Ok(Some(tables.into_iter().skip(1).map(|t| t.name).collect()))
+    }
 
-        // Delete the table rows. storage::Engine doesn't support writing while
-        // scanning, so we buffer all keys in a vector. We could also do this in
-        // batches, although we'd want to do the batching above Raft to avoid
-        // blocking Raft processing for the duration of the drop.
-        let prefix = &KeyPrefix::Row((&table.name).into()).encode();
-        let keys: Vec<Vec<u8>> =
-            self.txn.scan_prefix(prefix).map_ok(|(key, _)| key).try_collect()?;
-        for key in keys {
-            self.txn.delete(&key)?;
+    fn add_column(&self, table: &str, column: Column) -> Result<()> {
+        let mut table = self.must_get_table(table)?;
+        if table.columns.iter().any(|c| c.name == column.name) {
+            return errinput!("column {} already exists in table {}", column.name, table.name);
+        }
+        // Existing rows take on the default value, so non-nullable columns
+        // must have one. Nullable columns default to NULL.
+        let Some(default) = column.default.clone() else {
+            return errinput!("non-nullable column {} must have a default value", column.name);
+        };
+        let name = column.name.clone();
+        let index = column.index;
+        let check = column.unique || column.references.is_some() || column.check.is_some();
+        table.columns.push(column);
+        table.validate(self)?;
+        self.txn.set(&Key::Table((&table.name).into()).encode(), table.encode())?;
+        if !index && !check {
+            return Ok(());
         }
 
-        // Delete any secondary indexes.
-        for column in table.columns.iter().filter(|c| c.index) {
-            let prefix = &KeyPrefix::Index((&table.name).into(), (&column.name).into()).encode();
+        // Index the existing rows, which all have the default value. Buffer
+        // the rows, since storage::Engine doesn't support writing while
+        // scanning. Unique, reference, and CHECK columns are then validated for
+        // each row, which errors if the default violates the constraint.
+        let rows: Vec<Row> = super::Transaction::scan(self, &table.name, None)?.try_collect()?;
+        if index {
+            let ids = rows.iter().map(|row| table.row_id(row)).collect();
+            self.set_index(&table.name, &name, &default.normalize_ref(), ids)?;
+        }
+        if check {
+            for row in rows {
+                table.validate_row(&row, true, self)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn drop_column(&self, table: &str, column: &str) -> Result<()> {
+        let mut table = self.must_get_table(table)?;
+        let Some(index) = table.columns.iter().position(|c| c.name == column) else {
+            return errinput!("unknown column {column} in table {}", table.name);
+        };
+        if table.primary_key.contains(&index) {
+            return errinput!("can't drop primary key column {column}");
+        }
+        if table.unique.iter().any(|unique| unique.contains(&index)) {
+            return errinput!("can't drop column {column} in unique constraint");
+        }
+        if let Some(target) = &table.columns[index].references {
+            return errinput!("can't drop column {column} referencing table {target}");
+        }
+        let references_column =
+            |expr: &Expression| matches!(expr, Expression::Column(i) if *i == index);
+        let other_checks = table.columns.iter().enumerate().filter(|(i, _)| *i != index);
+        let mut checks = other_checks.filter_map(|(_, c)| c.check.as_ref()).chain(&table.checks);
+        if checks.any(|check| check.contains(&references_column)) {
+            return errinput!("can't drop column {column} used in CHECK constraint");
+        }
+
+        // Update the schema. Buffer the existing rows first, since they're
+        // read using the schema.
+        let rows: Vec<Row> = super::Transaction::scan(self, &table.name, None)?.try_collect()?;
+        let dropped = table.columns.remove(index);
+        let indexes = table.primary_key.iter_mut().chain(table.unique.iter_mut().flatten());
+        for i in indexes.filter(|i| **i > index) {
+            *i -= 1;
+        }
+        let shift = |expr| match expr {
+            Expression::Column(i) if i > index => Ok(Expression::Column(i - 1)),
+            expr => Ok(expr),
+        };
+        let checks = table.columns.iter_mut().filter_map(|c| c.check.as_mut());
+        for check in checks.chain(table.checks.iter_mut()) {
+            *check = std::mem::replace(check, Expression::Constant(Value::Null))
+                .transform(&Ok, &shift)?;
+        }
+        table.validate(self)?;
+        self.txn.set(&Key::Table((&table.name).into()).encode(), table.encode())?;
+
+        // Rewrite the existing rows without the column.
+        for mut row in rows {
+            row.remove(index);
+            let id = table.row_id(&row);
+            self.txn
+                .set(&Key::Row((&table.name).into(), (&id).into()).encode(), encode_row(&row))?;
+        }
+
+        // Delete any index entries.
+        if dropped.index {
+            let prefix = &KeyPrefix::Index((&table.name).into(), column.into()).encode();
             let keys: Vec<_> = self.txn.scan_prefix(prefix).map_ok(|(key, _)| key).try_collect()?;
             for key in keys {
                 self.txn.delete(&key)?;
             }
         }
+        Ok(())
+    }
+
+    fn create_index(&self, table: &str, column: &str, name: Option<&str>) -> Result<()> {
+        let mut table = self.must_get_table(table)?;
+        let Some(index) = table.columns.iter().position(|c| c.name == column) else {
+            return errinput!("unknown column {column} in table {}", table.name);
+        };
+        if table.columns[index].index {
+            return errinput!("index {}.{column} already exists", table.name);
+        }
+        if let Some(name) = name {
+            if self.find_index(name)?.is_some() {
+                return errinput!("index {name} already exists");
+            }
+        }
+        table.columns[index].index = true;
+        table.columns[index].index_name = name.map(str::to_string);
+        table.validate(self)?;
+        self.txn.set(&Key::Table((&table.name).into()).encode(), table.encode())?;
+
+        // Index the existing rows. storage::Engine doesn't support writing
+        // while scanning, so we build the index entries in memory first.
+        let mut entries: BTreeMap<Value, BTreeSet<Vec<Value>>> = BTreeMap::new();
+        let mut rows = super::Transaction::scan(self, &table.name, None)?;
+        while let Some(row) = rows.next().transpose()? {
+            let id = table.row_id(&row);
+            entries.entry(row[index].clone()).or_default().insert(id);
+        }
+        for (value, ids) in entries {
+            self.set_index(&table.name, column, &value, ids)?;
+        }
+        Ok(())
+    }
+
+    fn drop_index(&self, table: &str, column: &str, if_exists: bool) -> Result<bool> {
+        let mut table = self.must_get_table(table)?;
+        let Some(index) = table.columns.iter().position(|c| c.name == column) else {
+            return errinput!("unknown column {column} in table {}", table.name);
+        };
+        if !table.columns[index].index {
+            if if_exists {
+                return Ok(false);
+            }
+            return errinput!("index {}.{column} does not exist", table.name);
+        }
+        // Unique and foreign key columns require an index, which is enforced
+        // by schema validation.
+        table.columns[index].index = false;
+        table.columns[index].index_name = None;
+        table.validate(self)?;
+        self.txn.set(&Key::Table((&table.name).into()).encode(), table.encode())?;
+
+        let prefix = &KeyPrefix::Index((&table.name).into(), column.into()).encode();
+        let keys: Vec<_> = self.txn.scan_prefix(prefix).map_ok(|(key, _)| key).try_collect()?;
+        for key in keys {
+            self.txn.delete(&key)?;
+        }
         Ok(true)
     }
 
@@ -393,12 +884,28 @@ pub enum Key<'a> {
     Table(Cow<'a, str>),
     /// An index entry, by table name, index name, and index value.
     Index(Cow<'a, str>, Cow<'a, str>, Cow<'a, Value>),
-    /// A table row, by table name and primary key value.
-    Row(Cow<'a, str>, Cow<'a, Value>),
+    /// A table row, by table name and primary key values. Composite keys are
+    /// encoded as the concatenation of the key values, which preserves the
+    /// tuple ordering, and single-column keys are encoded as the plain value.
+    Row(Cow<'a, str>, Cow<'a, [Value]>),
+    /// A table row count delta, by table name and the version of the
+    /// transaction that wrote it. The table's row count is the sum of the
+    /// visible deltas. Deltas are periodically folded together by writers, see
+    /// Transaction::add_row_count().
+    TableStats(Cow<'a, str>, mvcc::Version),
 }
 
 impl<'a> encoding::Key<'a> for Key<'a> {}
 
+/// Evaluates a scan filter for a row, returning true if the row matches.
+fn filter_row(filter: &Expression, row: &Row) -> Result<bool> {
+    match filter.evaluate(Some(row))? {
+        Value::Boolean(b) => Ok(b),
+        Value::Null => Ok(false),
+        value => errinput!("filter returned {value}, expected boolean"),
+    }
+}
+
 /// Key prefixes, allowing prefix scans of specific parts of the keyspace. These
 /// must match the keys -- in particular, the enum variant indexes must match.
 #[derive(Deserialize, Serialize)]
@@ -409,6 +916,158 @@ enum KeyPrefix<'a> {
     Index(Cow<'a, str>, Cow<'a, str>),
     /// An entire table's rows, by table name.
     Row(Cow<'a, str>),
+    /// All of a table's row count deltas, by table name.
+    TableStats(Cow<'a, str>),
 }
 
 impl<'a> encoding::Key<'a> for KeyPrefix<'a> {}
+
+/// Transactions whose version is a multiple of this fold the visible row count
+/// deltas of the tables they write into a single delta.
+const ROW_COUNT_FOLD_INTERVAL: mvcc::Version = 64;
+
+/// The current row encoding version. Rows are stored with a version prefix, so
+/// that the encoding can change while still reading rows written by earlier
+/// versions. The versions are:
+///
+/// * 1: a Bincode-encoded Vec<Value>, without a prefix. Written before rows
+///   were versioned.
+/// * 2: ROW_VERSION_MARKER and the version byte, followed by a version 1 row.
+///
+/// When changing the encoding (e.g. the Value enum's variants), bump the
+/// version and upgrade older rows in decode_row().
+const ROW_VERSION: u8 = 2;
+
+/// Marks a versioned row. Unversioned (version 1) rows begin with the row
+/// length as a Bincode varint, which never begins with 0xff.
+const ROW_VERSION_MARKER: u8 = 0xff;
+
+/// Encodes a row using the current row encoding version.
+fn encode_row(row: &Row) -> Vec<u8> {
+    let mut bytes = vec![ROW_VERSION_MARKER, ROW_VERSION];
+    bytes.extend(row.encode());
+    bytes
+}
+
+/// Decodes a row, upgrading it from earlier encoding versions as needed.
+pub fn decode_row(bytes: &[u8]) -> Result<Row> {
+    match bytes {
+        [ROW_VERSION_MARKER, ROW_VERSION, bytes @ ..] => Row::decode(bytes),
+        [ROW_VERSION_MARKER, version, ..] => errdata!("unsupported row encoding version {version}"),
+        bytes => Row::decode(bytes).map(upgrade_row_v1),
+    }
+}
+
+/// Upgrades a version 1 row to version 2. The versions only differ in the
+/// prefix, so the row is unchanged.
+fn upgrade_row_v1(row: Row) -> Row {
+    row
+}
+
+/// Formats a table row reference for error messages, e.g. table.id=1 or, for
+/// composite primary keys, table.(a, b)=(1, 2).
+fn format_row(table: &Table, id: &[Value]) -> String {
+    let columns = table.primary_key.iter().map(|i| &table.columns[*i].name);
+    let column = match table.primary_key.len() {
+        1 => columns.into_iter().join(""),
+        _ => format!("({})", columns.into_iter().join(", ")),
+    };
+    format!("{}.{column}={}", table.name, format_id(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    /// Tests that rows are encoded with the version prefix, and round-trip.
+    #[test]
+    fn row_encoding() -> Result<()> {
+        let row = vec![Value::Integer(1), Value::String("a".into()), Value::Null];
+        let bytes = encode_row(&row);
+        assert_eq!(bytes[..2], [ROW_VERSION_MARKER, ROW_VERSION]);
+        assert_eq!(decode_row(&bytes)?, row);
+        Ok(())
+    }
+
+    /// Tests that unversioned (version 1) rows are decoded by the current
+    /// decoder.
+    #[test]
+    fn row_encoding_v1() -> Result<()> {
+        let row = vec![Value::Integer(1), Value::Decimal(150, 2), Value::Timestamp(0)];
+        let v1 = bincode::serialize(&row);
+        assert_eq!(decode_row(&v1)?, row);
+
+        // Version 1 rows never begin with the version marker, regardless of
+        // the row length.
+        for len in [0, 1, 250, 251, 65_535, 65_536] {
+            let v1 = bincode::serialize(&vec![Value::Null; len]);
+            assert_ne!(v1[0], ROW_VERSION_MARKER, "length {len}");
+        }
+        Ok(())
+    }
+
+    /// Tests that rows with an unknown encoding version error.
+    #[test]
+    fn row_encoding_unknown_version() {
+        let bytes =
+            [&[ROW_VERSION_MARKER, ROW_VERSION + 1][..], &bincode::serialize(&Row::new())].concat();
+        assert_eq!(
+            decode_row(&bytes),
+            Err(Error::InvalidData(format!(
+                "unsupported row encoding version {}",
+                ROW_VERSION + 1
+            )))
+        );
+    }
+
+    /// Tests that row count deltas are periodically folded together, such
+    /// that table_stats() doesn't scan a delta for every past write.
+    #[test]
+    fn row_count_fold() -> Result<()> {
+        use crate::sql::engine::{Engine as _, Transaction as _};
+
+        let engine = Local::new(storage::Memory::new());
+        let mut session = engine.session();
+        session.execute("CREATE TABLE t (id INT PRIMARY KEY)")?;
+        for id in 0..200 {
+            session.execute(&format!("INSERT INTO t VALUES ({id})"))?;
+        }
+        session.execute("DELETE FROM t WHERE id < 10")?;
+
+        let txn = engine.begin()?;
+        assert_eq!(txn.table_stats("t")?, TableStats { rows: 190 });
+        let prefix = KeyPrefix::TableStats("t".into()).encode();
+        let deltas = txn.txn.scan_prefix(&prefix).count();
+        assert!(deltas <= ROW_COUNT_FOLD_INTERVAL as usize, "{deltas} row count deltas");
+        txn.commit()
+    }
+
+    /// Benchmarks a 10,000-row insert into a table with a low-cardinality
+    /// secondary index, where many rows share each index entry. Timings depend
+    /// on the hardware, so this is ignored by default and only prints them.
+    /// Run with:
+    ///
+    /// cargo test --release --lib -- --ignored --nocapture insert_benchmark
+    #[test]
+    #[ignore]
+    fn insert_benchmark() -> Result<()> {
+        use crate::sql::engine::{Engine as _, Transaction as _};
+        const ROWS: i64 = 10_000;
+
+        let engine = Local::new(storage::Memory::new());
+        engine.session().execute("CREATE TABLE t (id INT PRIMARY KEY, value INT INDEX)")?;
+        let rows = (0..ROWS).map(|i| vec![Value::Integer(i), Value::Integer(i % 10)]).collect();
+
+        let txn = engine.begin()?;
+        let start = std::time::Instant::now();
+        txn.insert("t", rows)?;
+        let elapsed = start.elapsed();
+        txn.commit()?;
+        println!(
+            "inserted {ROWS} rows in {elapsed:.2?} ({:.0} rows/s)",
+            ROWS as f64 / elapsed.as_secs_f64()
+        );
+        Ok(())
+    }
+}
@@ -3,12 +3,14 @@
 //! storage, while the `Raft` engine submits commands through Raft consensus
 //! before dispatching to the `Local` engine on each node.
 
+mod cache;
 mod engine;
 mod local;
 mod raft;
 mod session;
 
-pub use engine::{Catalog, Engine, Transaction};
-pub use local::{Key, Local};
+pub use cache::{ResultCache, ResultCacheStats};
+pub use engine::{Catalog, Engine, TableStats, Transaction};
+pub use local::{decode_row, Key, Local};
 pub use raft::{Raft, Status, Write};
 pub use session::{Session, StatementResult};
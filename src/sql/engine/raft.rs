@@ -1,9 +1,9 @@
-use super::{Catalog, Engine as _, Transaction as _};
+use super::{Catalog, Engine as _, TableStats, Transaction as _};
 use crate::encoding::{self, bincode, Value as _};
 use crate::errdata;
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::raft;
-use crate::sql::types::{Expression, Row, Rows, Table, Value};
+use crate::sql::types::{Column, Expression, Row, Rows, Table, Value};
 use crate::storage::{self, mvcc};
 
 use crossbeam::channel::Sender;
@@ -23,6 +23,7 @@ use std::collections::{BTreeMap, BTreeSet};
 ///
 /// For more details on how SQL statements flow through the engine, see the
 /// `sql` module documentation.
+#[derive(Clone)]
 pub struct Raft {
     /// Sends requests to the local Raft node, along with a response channel.
     tx: Sender<(raft::Request, Sender<Result<raft::Response>>)>,
@@ -60,7 +61,10 @@ impl Raft {
         }
     }
 
-    /// Reads from Raft, deserializing the response into the return type.
+    /// Reads from Raft, deserializing the response into the return type. Reads
+    /// (e.g. SELECT scans) are not appended to the Raft log, but are still
+    /// linearizable: the leader confirms its leadership with a quorum and waits
+    /// for its commit index to be applied before executing the read.
     fn read<V: DeserializeOwned>(&self, read: Read) -> Result<V> {
         match self.execute(raft::Request::Read(read.encode()))? {
             raft::Response::Read(response) => bincode::deserialize(&response),
@@ -145,7 +149,29 @@ impl<'a> super::Transaction for Transaction<'a> {
         self.engine.write(Write::Rollback(self.state.into()))
     }
 
-    fn delete(&self, table: &str, ids: &[Value]) -> Result<()> {
+    fn savepoint(&self, name: &str) -> Result<()> {
+        if self.state.read_only {
+            return Err(Error::ReadOnly);
+        }
+        self.engine.write(Write::Savepoint { txn: (&self.state).into(), name: name.into() })
+    }
+
+    fn rollback_to_savepoint(&self, name: &str) -> Result<()> {
+        if self.state.read_only {
+            return Err(Error::ReadOnly);
+        }
+        self.engine
+            .write(Write::RollbackToSavepoint { txn: (&self.state).into(), name: name.into() })
+    }
+
+    fn release_savepoint(&self, name: &str) -> Result<()> {
+        if self.state.read_only {
+            return Err(Error::ReadOnly);
+        }
+        self.engine.write(Write::ReleaseSavepoint { txn: (&self.state).into(), name: name.into() })
+    }
+
+    fn delete(&self, table: &str, ids: &[Vec<Value>]) -> Result<()> {
         self.engine.write(Write::Delete {
             txn: (&self.state).into(),
             table: table.into(),
@@ -153,7 +179,7 @@ impl<'a> super::Transaction for Transaction<'a> {
         })
     }
 
-    fn get(&self, table: &str, ids: &[Value]) -> Result<Vec<Row>> {
+    fn get(&self, table: &str, ids: &[Vec<Value>]) -> Result<Vec<Row>> {
         self.engine.read(Read::Get {
             txn: (&self.state).into(),
             table: table.into(),
@@ -165,7 +191,12 @@ impl<'a> super::Transaction for Transaction<'a> {
         self.engine.write(Write::Insert { txn: (&self.state).into(), table: table.into(), rows })
     }
 
-    fn lookup_index(&self, table: &str, column: &str, values: &[Value]) -> Result<BTreeSet<Value>> {
+    fn lookup_index(
+        &self,
+        table: &str,
+        column: &str,
+        values: &[Value],
+    ) -> Result<BTreeSet<Vec<Value>>> {
         self.engine.read(Read::LookupIndex {
             txn: (&self.state).into(),
             table: table.into(),
@@ -175,15 +206,22 @@ impl<'a> super::Transaction for Transaction<'a> {
     }
 
     fn scan(&self, table: &str, filter: Option<Expression>) -> Result<Rows> {
-        let scan: Vec<Row> = self.engine.read(Read::Scan {
-            txn: (&self.state).into(),
-            table: table.into(),
-            filter,
-        })?;
-        Ok(Box::new(scan.into_iter().map(Ok)))
+        Ok(Box::new(ScanIterator::new(self.engine.clone(), self.state.clone(), table, filter)?))
+    }
+
+    fn table_stats(&self, table: &str) -> Result<TableStats> {
+        self.engine.read(Read::TableStats { txn: (&self.state).into(), table: table.into() })
     }
 
-    fn update(&self, table: &str, rows: BTreeMap<Value, Row>) -> Result<()> {
+    fn analyze(&self, table: &str) -> Result<TableStats> {
+        self.engine.write(Write::Analyze { txn: (&self.state).into(), table: table.into() })
+    }
+
+    fn truncate(&self, table: &str) -> Result<u64> {
+        self.engine.write(Write::Truncate { txn: (&self.state).into(), table: table.into() })
+    }
+
+    fn update(&self, table: &str, rows: BTreeMap<Vec<Value>, Row>) -> Result<()> {
         self.engine.write(Write::Update { txn: (&self.state).into(), table: table.into(), rows })
     }
 }
@@ -193,11 +231,51 @@ impl<'a> Catalog for Transaction<'a> {
         self.engine.write(Write::CreateTable { txn: (&self.state).into(), schema })
     }
 
-    fn drop_table(&self, table: &str, if_exists: bool) -> Result<bool> {
+    fn drop_table(
+        &self,
+        table: &str,
+        if_exists: bool,
+        cascade: bool,
+    ) -> Result<Option<Vec<String>>> {
         self.engine.write(Write::DropTable {
             txn: (&self.state).into(),
             table: table.into(),
             if_exists,
+            cascade,
+        })
+    }
+
+    fn add_column(&self, table: &str, column: Column) -> Result<()> {
+        self.engine.write(Write::AddColumn {
+            txn: (&self.state).into(),
+            table: table.into(),
+            column,
+        })
+    }
+
+    fn drop_column(&self, table: &str, column: &str) -> Result<()> {
+        self.engine.write(Write::DropColumn {
+            txn: (&self.state).into(),
+            table: table.into(),
+            column: column.into(),
+        })
+    }
+
+    fn create_index(&self, table: &str, column: &str, name: Option<&str>) -> Result<()> {
+        self.engine.write(Write::CreateIndex {
+            txn: (&self.state).into(),
+            table: table.into(),
+            column: column.into(),
+            name: name.map(Cow::from),
+        })
+    }
+
+    fn drop_index(&self, table: &str, column: &str, if_exists: bool) -> Result<bool> {
+        self.engine.write(Write::DropIndex {
+            txn: (&self.state).into(),
+            table: table.into(),
+            column: column.into(),
+            if_exists,
         })
     }
 
@@ -215,10 +293,9 @@ impl<'a> Catalog for Transaction<'a> {
 /// work, using a `storage::Engine` for storage.
 ///
 /// For simplicity, we don't attempt to stream large requests or responses,
-/// instead simply delivering them as one large chunk. This means that e.g. a
-/// full table scan will pull the entire table into memory, serialize it, and
-/// send it across the network as one message. The simplest way to address this
-/// would likely be to send row batches, but this is fine for our purposes.
+/// instead simply delivering them as one large chunk. The exception is table
+/// scans, which are paged in row batches via ScanIterator, such that a full
+/// table scan doesn't pull the entire table into memory.
 pub struct State<E: storage::Engine + 'static> {
     /// The local SQL engine.
     local: super::Local<E>,
@@ -249,6 +326,15 @@ impl<E: storage::Engine> State<E> {
             Write::Rollback(txn) => {
                 bincode::serialize(&self.local.resume(txn.into_owned())?.rollback()?)
             }
+            Write::Savepoint { txn, name } => {
+                bincode::serialize(&self.local.resume(txn.into_owned())?.savepoint(&name)?)
+            }
+            Write::RollbackToSavepoint { txn, name } => bincode::serialize(
+                &self.local.resume(txn.into_owned())?.rollback_to_savepoint(&name)?,
+            ),
+            Write::ReleaseSavepoint { txn, name } => {
+                bincode::serialize(&self.local.resume(txn.into_owned())?.release_savepoint(&name)?)
+            }
 
             Write::Delete { txn, table, ids } => {
                 bincode::serialize(&self.local.resume(txn.into_owned())?.delete(&table, &ids)?)
@@ -256,15 +342,37 @@ impl<E: storage::Engine> State<E> {
             Write::Insert { txn, table, rows } => {
                 bincode::serialize(&self.local.resume(txn.into_owned())?.insert(&table, rows)?)
             }
+            Write::Truncate { txn, table } => {
+                bincode::serialize(&self.local.resume(txn.into_owned())?.truncate(&table)?)
+            }
             Write::Update { txn, table, rows } => {
                 bincode::serialize(&self.local.resume(txn.into_owned())?.update(&table, rows)?)
             }
+            Write::Analyze { txn, table } => {
+                self.local.resume(txn.into_owned())?.analyze(&table)?.encode()
+            }
 
             Write::CreateTable { txn, schema } => {
                 bincode::serialize(&self.local.resume(txn.into_owned())?.create_table(schema)?)
             }
-            Write::DropTable { txn, table, if_exists } => bincode::serialize(
-                &self.local.resume(txn.into_owned())?.drop_table(&table, if_exists)?,
+            Write::DropTable { txn, table, if_exists, cascade } => bincode::serialize(
+                &self.local.resume(txn.into_owned())?.drop_table(&table, if_exists, cascade)?,
+            ),
+            Write::AddColumn { txn, table, column } => bincode::serialize(
+                &self.local.resume(txn.into_owned())?.add_column(&table, column)?,
+            ),
+            Write::DropColumn { txn, table, column } => bincode::serialize(
+                &self.local.resume(txn.into_owned())?.drop_column(&table, &column)?,
+            ),
+            Write::CreateIndex { txn, table, column, name } => {
+                bincode::serialize(&self.local.resume(txn.into_owned())?.create_index(
+                    &table,
+                    &column,
+                    name.as_deref(),
+                )?)
+            }
+            Write::DropIndex { txn, table, column, if_exists } => bincode::serialize(
+                &self.local.resume(txn.into_owned())?.drop_index(&table, &column, if_exists)?,
             ),
         })
     }
@@ -317,13 +425,16 @@ impl<E: storage::Engine> raft::State for State<E> {
                 .resume(txn.into_owned())?
                 .lookup_index(&table, &column, &values)?
                 .encode(),
-            Read::Scan { txn, table, filter } => {
-                // For simplicity, buffer the entire scan. See `State` comment.
-                self.local
-                    .resume(txn.into_owned())?
-                    .scan(&table, filter)?
-                    .collect::<Result<Vec<Row>>>()?
-                    .encode()
+            Read::Scan { txn, table, filter, after, limit } => {
+                bincode::serialize(&self.local.resume(txn.into_owned())?.scan_page(
+                    &table,
+                    filter.as_ref(),
+                    after,
+                    limit,
+                )?)
+            }
+            Read::TableStats { txn, table } => {
+                self.local.resume(txn.into_owned())?.table_stats(&table)?.encode()
             }
 
             Read::GetTable { txn, table } => {
@@ -334,6 +445,117 @@ impl<E: storage::Engine> raft::State for State<E> {
             }
         })
     }
+
+    fn flush(&mut self) -> Result<()> {
+        self.local.mvcc.engine.lock()?.flush()
+    }
+
+    /// For simplicity, the snapshot is a dump of all raw key/value pairs in the
+    /// storage engine, including MVCC metadata and the applied index. Unlike
+    /// scans, this is buffered in memory and sent as a single message.
+    fn snapshot(&self) -> Result<Vec<u8>> {
+        let mut engine = self.local.mvcc.engine.lock()?;
+        let data: Vec<(Vec<u8>, Vec<u8>)> = engine.scan(..).collect::<Result<_>>()?;
+        Ok(bincode::serialize(&data))
+    }
+
+    fn restore(&mut self, snapshot: Vec<u8>) -> Result<()> {
+        let data: Vec<(Vec<u8>, Vec<u8>)> = bincode::deserialize(&snapshot)?;
+        let mut engine = self.local.mvcc.engine.lock()?;
+        let keys: Vec<Vec<u8>> =
+            engine.scan(..).map(|r| r.map(|(k, _)| k)).collect::<Result<_>>()?;
+        for key in keys {
+            engine.delete(&key)?;
+        }
+        for (key, value) in data {
+            engine.set(&key, value)?;
+        }
+        engine.flush()?;
+        drop(engine);
+
+        self.applied_index = self
+            .local
+            .get_unversioned(Raft::APPLIED_INDEX_KEY)?
+            .map(|b| bincode::deserialize(&b))
+            .transpose()?
+            .unwrap_or(0);
+        Ok(())
+    }
+}
+
+/// A paged table scan through Raft. Rather than buffering the entire scan in a
+/// single Raft read, it fetches a page of up to SCAN_PAGE_SIZE matching rows at
+/// a time as the iterator is consumed, which bounds the memory use on both the
+/// Raft leader and the SQL server. Pages are read at the transaction's MVCC
+/// snapshot, so they're consistent with each other.
+#[derive(Clone)]
+struct ScanIterator {
+    /// The Raft engine.
+    engine: Raft,
+    /// The transaction state.
+    txn: mvcc::TransactionState,
+    /// The table to scan.
+    table: String,
+    /// The scan filter.
+    filter: Option<Expression>,
+    /// The current page of rows.
+    page: std::vec::IntoIter<Row>,
+    /// The row key to continue the scan after, or None if the scan is done.
+    next: Option<Vec<u8>>,
+}
+
+impl ScanIterator {
+    /// The maximum number of rows to fetch per page.
+    #[cfg(not(test))]
+    const SCAN_PAGE_SIZE: usize = 1000;
+    /// Use a small page size in tests, to exercise the paging.
+    #[cfg(test)]
+    const SCAN_PAGE_SIZE: usize = 2;
+
+    /// Creates a new scan iterator, eagerly fetching the first page (which
+    /// e.g. errors if the table doesn't exist).
+    fn new(
+        engine: Raft,
+        txn: mvcc::TransactionState,
+        table: &str,
+        filter: Option<Expression>,
+    ) -> Result<Self> {
+        let page = Vec::new().into_iter();
+        let mut iter = Self { engine, txn, table: table.to_string(), filter, page, next: None };
+        iter.fetch(None)?;
+        Ok(iter)
+    }
+
+    /// Fetches the page of rows after the given row key.
+    fn fetch(&mut self, after: Option<Vec<u8>>) -> Result<()> {
+        let (rows, next): (Vec<Row>, Option<Vec<u8>>) = self.engine.read(Read::Scan {
+            txn: (&self.txn).into(),
+            table: (&self.table).into(),
+            filter: self.filter.clone(),
+            after,
+            limit: Self::SCAN_PAGE_SIZE,
+        })?;
+        self.page = rows.into_iter();
+        self.next = next;
+        Ok(())
+    }
+}
+
+impl Iterator for ScanIterator {
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.page.next() {
+                return Some(Ok(row));
+            }
+            // The page is exhausted. Fetch the next one, if any.
+            let after = self.next.take()?;
+            if let Err(error) = self.fetch(Some(after)) {
+                return Some(Err(error));
+            }
+        }
+    }
 }
 
 /// A Raft engine read. Values correspond to engine method parameters. Uses
@@ -348,7 +570,7 @@ pub enum Read<'a> {
     Get {
         txn: Cow<'a, mvcc::TransactionState>,
         table: Cow<'a, str>,
-        ids: Cow<'a, [Value]>,
+        ids: Cow<'a, [Vec<Value>]>,
     },
     LookupIndex {
         txn: Cow<'a, mvcc::TransactionState>,
@@ -356,10 +578,18 @@ pub enum Read<'a> {
         column: Cow<'a, str>,
         values: Cow<'a, [Value]>,
     },
+    /// Scans a page of at most limit rows after the given row key, see
+    /// `Local::scan_page()`.
     Scan {
         txn: Cow<'a, mvcc::TransactionState>,
         table: Cow<'a, str>,
         filter: Option<Expression>,
+        after: Option<Vec<u8>>,
+        limit: usize,
+    },
+    TableStats {
+        txn: Cow<'a, mvcc::TransactionState>,
+        table: Cow<'a, str>,
     },
 
     GetTable {
@@ -380,13 +610,75 @@ pub enum Write<'a> {
     Begin,
     Commit(Cow<'a, mvcc::TransactionState>),
     Rollback(Cow<'a, mvcc::TransactionState>),
+    Savepoint {
+        txn: Cow<'a, mvcc::TransactionState>,
+        name: Cow<'a, str>,
+    },
+    RollbackToSavepoint {
+        txn: Cow<'a, mvcc::TransactionState>,
+        name: Cow<'a, str>,
+    },
+    ReleaseSavepoint {
+        txn: Cow<'a, mvcc::TransactionState>,
+        name: Cow<'a, str>,
+    },
 
-    Delete { txn: Cow<'a, mvcc::TransactionState>, table: Cow<'a, str>, ids: Cow<'a, [Value]> },
-    Insert { txn: Cow<'a, mvcc::TransactionState>, table: Cow<'a, str>, rows: Vec<Row> },
-    Update { txn: Cow<'a, mvcc::TransactionState>, table: Cow<'a, str>, rows: BTreeMap<Value, Row> },
+    Delete {
+        txn: Cow<'a, mvcc::TransactionState>,
+        table: Cow<'a, str>,
+        ids: Cow<'a, [Vec<Value>]>,
+    },
+    Insert {
+        txn: Cow<'a, mvcc::TransactionState>,
+        table: Cow<'a, str>,
+        rows: Vec<Row>,
+    },
+    Truncate {
+        txn: Cow<'a, mvcc::TransactionState>,
+        table: Cow<'a, str>,
+    },
+    Update {
+        txn: Cow<'a, mvcc::TransactionState>,
+        table: Cow<'a, str>,
+        rows: BTreeMap<Vec<Value>, Row>,
+    },
+    Analyze {
+        txn: Cow<'a, mvcc::TransactionState>,
+        table: Cow<'a, str>,
+    },
 
-    CreateTable { txn: Cow<'a, mvcc::TransactionState>, schema: Table },
-    DropTable { txn: Cow<'a, mvcc::TransactionState>, table: Cow<'a, str>, if_exists: bool },
+    CreateTable {
+        txn: Cow<'a, mvcc::TransactionState>,
+        schema: Table,
+    },
+    DropTable {
+        txn: Cow<'a, mvcc::TransactionState>,
+        table: Cow<'a, str>,
+        if_exists: bool,
+        cascade: bool,
+    },
+    AddColumn {
+        txn: Cow<'a, mvcc::TransactionState>,
+        table: Cow<'a, str>,
+        column: Column,
+    },
+    DropColumn {
+        txn: Cow<'a, mvcc::TransactionState>,
+        table: Cow<'a, str>,
+        column: Cow<'a, str>,
+    },
+    CreateIndex {
+        txn: Cow<'a, mvcc::TransactionState>,
+        table: Cow<'a, str>,
+        column: Cow<'a, str>,
+        name: Option<Cow<'a, str>>,
+    },
+    DropIndex {
+        txn: Cow<'a, mvcc::TransactionState>,
+        table: Cow<'a, str>,
+        column: Cow<'a, str>,
+        if_exists: bool,
+    },
 }
 
 impl<'a> encoding::Value for Write<'a> {}
@@ -397,3 +689,81 @@ pub struct Status {
     pub raft: raft::Status,
     pub mvcc: mvcc::Status,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raft::State as _;
+    use crate::storage::Memory;
+
+    use itertools::Itertools as _;
+    use std::sync::{Arc, Mutex};
+
+    /// Tests that Raft scans are paged, fetching pages of bounded size lazily
+    /// as the rows are consumed.
+    #[test]
+    fn scan_paging() -> Result<()> {
+        let state = Raft::new_state(Memory::new())?;
+        let mut session = state.local.session();
+        session.execute("CREATE TABLE test (id INT PRIMARY KEY, value STRING)")?;
+        session
+            .execute("INSERT INTO test VALUES (1, 'a'), (2, 'b'), (3, 'c'), (4, 'd'), (5, 'e')")?;
+        drop(session);
+
+        // Run a fake Raft node which executes reads against the state machine,
+        // recording the number of rows in each scan page.
+        let pages = Arc::new(Mutex::new(Vec::new()));
+        let (tx, rx) = crossbeam::channel::unbounded::<(raft::Request, Sender<_>)>();
+        let node_pages = pages.clone();
+        std::thread::spawn(move || {
+            for (request, response_tx) in rx {
+                let raft::Request::Read(command) = request else {
+                    panic!("unexpected request {request:?}");
+                };
+                let scan = matches!(Read::decode(&command), Ok(Read::Scan { .. }));
+                let response = state.read(command);
+                if let (true, Ok(response)) = (scan, &response) {
+                    let (rows, _): (Vec<Row>, Option<Vec<u8>>) = bincode::deserialize(response)?;
+                    node_pages.lock()?.push(rows.len());
+                }
+                response_tx.send(response.map(raft::Response::Read))?;
+            }
+            Ok::<_, Error>(())
+        });
+        let engine = Raft::new(tx);
+        let txn = engine.begin_read_only()?;
+
+        // The first page is fetched eagerly, and the rest as rows are consumed.
+        // Pages never exceed the page size.
+        let mut scan = txn.scan("test", None)?;
+        assert_eq!(*pages.lock()?, vec![2]);
+        let rows: Vec<Row> = scan.by_ref().take(3).try_collect()?;
+        assert_eq!(rows.len(), 3);
+        assert_eq!(*pages.lock()?, vec![2, 2]);
+        let rows: Vec<Row> = scan.try_collect()?;
+        assert_eq!(
+            rows,
+            vec![
+                vec![Value::Integer(4), Value::String("d".into())],
+                vec![Value::Integer(5), Value::String("e".into())],
+            ]
+        );
+        assert_eq!(*pages.lock()?, vec![2, 2, 1]);
+
+        // Filtered scans page the matching rows. The last page was full, so an
+        // empty page is fetched to find the end of the scan.
+        pages.lock()?.clear();
+        let filter = Expression::GreaterThan(
+            Expression::Column(0).into(),
+            Expression::Constant(Value::Integer(1)).into(),
+        );
+        let ids: Vec<Value> =
+            txn.scan("test", Some(filter))?.map_ok(|row| row[0].clone()).try_collect()?;
+        assert_eq!(ids, [2, 3, 4, 5].map(Value::Integer));
+        assert_eq!(*pages.lock()?, vec![2, 2, 0]);
+
+        // Errors are returned when the scan is created.
+        assert!(txn.scan("missing", None).is_err());
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use super::raft::{Raft, Status};
-use super::{Engine, Transaction as _};
+use super::{Engine, ResultCache, Transaction as _};
 use crate::error::{Error, Result};
-use crate::sql::execution::ExecutionResult;
+use crate::sql::execution::{Context, ExecutionResult, Profile};
 use crate::sql::parser::{ast, Parser};
 use crate::sql::planner::Plan;
 use crate::sql::types::{Label, Row, Rows, Value};
@@ -11,6 +11,10 @@ use crate::{errdata, errinput};
 use itertools::Itertools as _;
 use log::error;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// A SQL client session. Executes raw SQL statements against a SQL engine and
 /// handles transaction control.
@@ -19,19 +23,114 @@ pub struct Session<'a, E: Engine<'a>> {
     engine: &'a E,
     /// The current transaction, if any.
     txn: Option<E::Transaction>,
+    /// The session settings, set via SET and read via SHOW.
+    settings: Settings,
+    /// The shared query result cache, if any.
+    result_cache: Option<&'a ResultCache>,
+    /// The statement cancellation flag, if any. Executing statements abort
+    /// with Error::Cancelled once it is set.
+    cancel: Option<Arc<AtomicBool>>,
+    /// The server directory for COPY files, if any. COPY errors without it.
+    copy_dir: Option<PathBuf>,
 }
 
 impl<'a, E: Engine<'a>> Session<'a, E> {
     /// Creates a new session using the given SQL engine.
     pub fn new(engine: &'a E) -> Self {
-        Self { engine, txn: None }
+        Self {
+            engine,
+            txn: None,
+            settings: Settings::default(),
+            result_cache: None,
+            cancel: None,
+            copy_dir: None,
+        }
+    }
+
+    /// Uses the given result cache for read-only queries executed via
+    /// execute(). Streamed queries via query() don't use the cache.
+    pub fn with_result_cache(mut self, result_cache: &'a ResultCache) -> Self {
+        self.result_cache = Some(result_cache);
+        self
+    }
+
+    /// Uses the given flag to cancel executing statements: once it is set,
+    /// they abort with Error::Cancelled. The caller is responsible for resetting
+    /// the flag before executing the next statement.
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Allows COPY to access files in the given server directory. COPY paths
+    /// are relative to it, and can't reach outside of it.
+    pub fn with_copy_dir(mut self, copy_dir: PathBuf) -> Self {
+        self.copy_dir = Some(copy_dir);
+        self
     }
 
     /// Executes a client statement.
     pub fn execute(&mut self, statement: &str) -> Result<StatementResult> {
-        // Parse and execute the statement. Transaction control is done here,
-        // other statements are executed by the SQL engine.
-        Ok(match Parser::new(statement).parse()? {
+        self.execute_with(statement, &[])
+    }
+
+    /// Executes a client statement with the given query parameter values,
+    /// bound to the statement's $1, $2, ... placeholders. The number of values
+    /// must match the statement's parameters.
+    pub fn execute_with(&mut self, statement: &str, params: &[Value]) -> Result<StatementResult> {
+        let statement = Parser::new(statement).parse()?;
+        self.execute_statement(statement, params)
+    }
+
+    /// Executes a script of client statements separated by semicolons,
+    /// returning their results. The whole script is parsed before any
+    /// statements are executed, so syntax errors don't leave it half-applied.
+    ///
+    /// Statements are executed sequentially as if they were given one by one,
+    /// i.e. each in its own implicit transaction unless the script uses
+    /// BEGIN/COMMIT. Execution stops at the first error, which is the last
+    /// result. The results of prior statements are returned along with it,
+    /// since their effects remain (e.g. they may have committed).
+    pub fn execute_script(&mut self, script: &str) -> Result<Vec<Result<StatementResult>>> {
+        let statements = Parser::new(script).parse_statements()?;
+        let mut results = Vec::with_capacity(statements.len());
+        for statement in statements {
+            let result = self.execute_statement(statement, &[]);
+            let failed = result.is_err();
+            results.push(result);
+            if failed {
+                break;
+            }
+        }
+        Ok(results)
+    }
+
+    /// Executes a parsed client statement with the given parameter values.
+    fn execute_statement(
+        &mut self,
+        mut statement: ast::Statement,
+        params: &[Value],
+    ) -> Result<StatementResult> {
+        // Statements are executed with the session's settings, e.g. the
+        // statement timeout and default limit.
+        let ctx = self.context();
+        self.settings.apply_default_limit(&mut statement);
+
+        // Execute the statement. Transaction control and settings are handled
+        // here, other statements are executed by the SQL engine.
+        Ok(match statement {
+            ast::Statement::Begin { .. }
+            | ast::Statement::Commit
+            | ast::Statement::Rollback
+            | ast::Statement::Savepoint(_)
+            | ast::Statement::RollbackToSavepoint(_)
+            | ast::Statement::ReleaseSavepoint(_)
+            | ast::Statement::Set { .. }
+            | ast::Statement::Show(_)
+                if !params.is_empty() =>
+            {
+                return errinput!("expected 0 parameters, got {}", params.len());
+            }
             ast::Statement::Begin { read_only, as_of } => {
                 if self.txn.is_some() {
                     return errinput!("already in a transaction");
@@ -64,27 +163,160 @@ impl<'a, E: Engine<'a>> Session<'a, E> {
                 txn.rollback()?;
                 StatementResult::Rollback { version }
             }
-            ast::Statement::Explain(statement) => self.with_txn(true, |txn| {
-                Ok(StatementResult::Explain(Plan::build(*statement, txn)?.optimize()?))
-            })?,
+            ast::Statement::Savepoint(name) => {
+                let Some(ref txn) = self.txn else {
+                    return errinput!("not in a transaction");
+                };
+                txn.savepoint(&name)?;
+                StatementResult::Savepoint { name }
+            }
+            ast::Statement::RollbackToSavepoint(name) => {
+                let Some(ref txn) = self.txn else {
+                    return errinput!("not in a transaction");
+                };
+                txn.rollback_to_savepoint(&name)?;
+                StatementResult::RollbackToSavepoint { name }
+            }
+            ast::Statement::ReleaseSavepoint(name) => {
+                let Some(ref txn) = self.txn else {
+                    return errinput!("not in a transaction");
+                };
+                txn.release_savepoint(&name)?;
+                StatementResult::ReleaseSavepoint { name }
+            }
+            ast::Statement::Set { name, value } => {
+                let value = self.settings.set(&name, value)?;
+                StatementResult::Set { name, value }
+            }
+            ast::Statement::Show(name) => {
+                let value = self.settings.get(&name)?;
+                StatementResult::Select {
+                    columns: vec![Label::Unqualified(name)],
+                    rows: vec![vec![value]],
+                }
+            }
+            ast::Statement::Explain { statement, verbose, analyze } => {
+                // EXPLAIN ANALYZE executes the query, and SELECT FOR UPDATE
+                // writes the locked rows.
+                let read_only = !analyze
+                    || !matches!(*statement, ast::Statement::Select { for_update: true, .. });
+                self.with_txn(read_only, |txn| {
+                    let plan = Plan::build(*statement, txn)?.bind(params)?;
+                    let unoptimized = verbose.then(|| Box::new(plan.clone()));
+                    let plan = plan.optimize()?;
+                    let profile = match analyze {
+                        true => Some(plan.clone().profile(txn, &ctx)?),
+                        false => None,
+                    };
+                    Ok(StatementResult::Explain { plan: Box::new(plan), unoptimized, profile })
+                })?
+            }
             statement => {
-                let read_only = matches!(statement, ast::Statement::Select { .. });
+                // SELECT FOR UPDATE writes the locked rows. COPY TO only reads
+                // from the database, unless its query is SELECT FOR UPDATE.
+                let query = match &statement {
+                    ast::Statement::CopyTo { query, .. } => query.as_ref(),
+                    statement => statement,
+                };
+                let read_only = matches!(
+                    query,
+                    ast::Statement::Select { for_update: false, .. }
+                        | ast::Statement::Values { .. }
+                        | ast::Statement::Union { .. }
+                );
+                // Read-only queries can use the result cache, but not COPY TO
+                // which writes a file.
+                let cache = self
+                    .result_cache
+                    .filter(|_| read_only && !matches!(statement, ast::Statement::CopyTo { .. }))
+                    .map(|cache| (cache, statement.clone()));
                 self.with_txn(read_only, |txn| {
-                    Plan::build(statement, txn)?.optimize()?.execute(txn)?.try_into()
+                    if let Some((cache, statement)) = &cache {
+                        if let Some((columns, rows)) = cache.get(statement, params, txn.state())? {
+                            return Ok(StatementResult::Select { columns, rows });
+                        }
+                    }
+                    let result = Plan::build(statement, txn)?
+                        .bind(params)?
+                        .optimize()?
+                        .execute(txn, &ctx)?
+                        .try_into()?;
+                    if let (Some((cache, statement)), StatementResult::Select { columns, rows }) =
+                        (&cache, &result)
+                    {
+                        let cached = (columns.clone(), rows.clone());
+                        cache.put(statement, params, txn.state(), cached)?;
+                    }
+                    Ok(result)
                 })?
             }
         })
     }
 
+    /// Executes a query (i.e. SELECT or UNION), returning the result columns
+    /// and a row iterator. Unlike execute(), which buffers all result rows, the
+    /// rows are pulled lazily from the executor as the iterator is consumed.
+    /// This allows streaming large results to clients.
+    ///
+    /// Read-only queries in implicit transactions commit the transaction
+    /// before the rows are consumed, which is fine since read-only
+    /// transactions can keep reading after they commit. SELECT FOR UPDATE
+    /// writes the locked rows, so its rows are buffered before committing.
+    pub fn query(&mut self, statement: &str) -> Result<(Vec<Label>, Rows)> {
+        let ctx = self.context();
+        let mut statement = Parser::new(statement).parse()?;
+        self.settings.apply_default_limit(&mut statement);
+        let read_only = match &statement {
+            ast::Statement::Select { for_update, .. } => !for_update,
+            ast::Statement::Values { .. } | ast::Statement::Union { .. } => true,
+            _ => return errinput!("only SELECT queries can be streamed"),
+        };
+        self.with_txn(read_only, |txn| {
+            let result = Plan::build(statement, txn)?.bind(&[])?.optimize()?.execute(txn, &ctx)?;
+            let ExecutionResult::Select { columns, rows } = result else {
+                return errdata!("expected select result");
+            };
+            if read_only {
+                return Ok((columns, rows));
+            }
+            let rows: Vec<Row> = rows.try_collect()?;
+            Ok((columns, Box::new(rows.into_iter().map(Ok))))
+        })
+    }
+
+    /// Returns an execution context for a statement, with the session's
+    /// settings, cancellation flag, and COPY directory.
+    fn context(&self) -> Context {
+        let mut ctx = self.settings.context();
+        if let Some(cancel) = &self.cancel {
+            ctx = ctx.with_cancel(cancel.clone());
+        }
+        if let Some(copy_dir) = &self.copy_dir {
+            ctx = ctx.with_copy_dir(copy_dir.clone());
+        }
+        ctx
+    }
+
+    /// Returns the state of the session's explicit transaction, if any.
+    pub fn txn(&self) -> Option<&mvcc::TransactionState> {
+        self.txn.as_ref().map(|txn| txn.state())
+    }
+
     /// Runs a closure in the session's explicit transaction, if there is one,
     /// otherwise a temporary implicit transaction. If read_only is true, uses a
-    /// read-only implicit transaction. Does not retry errors.
+    /// read-only implicit transaction. Otherwise, errors immediately if the
+    /// explicit transaction is read-only. Does not retry errors.
     pub fn with_txn<F, T>(&mut self, read_only: bool, f: F) -> Result<T>
     where
         F: FnOnce(&mut E::Transaction) -> Result<T>,
     {
-        // Use the current explicit transaction, if there is one.
+        // Use the current explicit transaction, if there is one. Reject writes
+        // in read-only transactions up front, rather than when the write is
+        // attempted (e.g. after validating constraints).
         if let Some(ref mut txn) = self.txn {
+            if !read_only && txn.state().read_only {
+                return Err(Error::ReadOnly);
+            }
             return f(txn);
         }
         // Otherwise, use an implicit transaction. Doing this session-side
@@ -122,21 +354,108 @@ impl<'a, E: Engine<'a>> Drop for Session<'a, E> {
     }
 }
 
+/// Per-session settings, set via SET and read via SHOW. They only apply to the
+/// session that set them, and are reset when the client reconnects.
+///
+/// The known settings are:
+///
+/// * statement_timeout: the statement timeout in milliseconds, where 0 or NULL
+///   disables the timeout (the default).
+///
+/// * default_limit: the LIMIT applied to top-level SELECT queries that don't
+///   have a LIMIT clause, where 0 or NULL disables it (the default).
+#[derive(Clone, Debug, Default)]
+struct Settings {
+    /// The statement timeout, if any.
+    statement_timeout: Option<Duration>,
+    /// The default query limit, if any.
+    default_limit: Option<u64>,
+}
+
+impl Settings {
+    /// Sets a setting to the given literal value, validating it. Returns the
+    /// new value, as given by get().
+    fn set(&mut self, name: &str, value: ast::Literal) -> Result<Value> {
+        match name {
+            "statement_timeout" => {
+                let ms = Self::parse_optional_u64(name, value)?;
+                self.statement_timeout = ms.map(Duration::from_millis);
+            }
+            "default_limit" => self.default_limit = Self::parse_optional_u64(name, value)?,
+            name => return errinput!("unknown setting {name}"),
+        }
+        self.get(name)
+    }
+
+    /// Returns a setting's current value, or NULL if unset.
+    fn get(&self, name: &str) -> Result<Value> {
+        let value = match name {
+            "statement_timeout" => self.statement_timeout.map(|t| t.as_millis() as u64),
+            "default_limit" => self.default_limit,
+            name => return errinput!("unknown setting {name}"),
+        };
+        Ok(value.map(|v| Value::Integer(v as i64)).unwrap_or(Value::Null))
+    }
+
+    /// Parses a non-negative integer setting value, where 0 or NULL is None.
+    fn parse_optional_u64(name: &str, value: ast::Literal) -> Result<Option<u64>> {
+        match value {
+            ast::Literal::Integer(i) if i >= 0 => Ok((i > 0).then_some(i as u64)),
+            ast::Literal::Null => Ok(None),
+            _ => errinput!("{name} must be a non-negative integer"),
+        }
+    }
+
+    /// Returns an execution context for a statement, e.g. with the statement
+    /// timeout.
+    fn context(&self) -> Context {
+        self.statement_timeout.map(Context::with_timeout).unwrap_or_default()
+    }
+
+    /// Applies the default limit to a top-level query without a LIMIT clause,
+    /// including queries under EXPLAIN.
+    fn apply_default_limit(&self, statement: &mut ast::Statement) {
+        let Some(default_limit) = self.default_limit else {
+            return;
+        };
+        match statement {
+            ast::Statement::Select { limit: limit @ None, .. }
+            | ast::Statement::Values { limit: limit @ None, .. }
+            | ast::Statement::Union { limit: limit @ None, .. } => {
+                *limit = Some(ast::Literal::Integer(default_limit as i64).into());
+            }
+            ast::Statement::Explain { statement, .. } => self.apply_default_limit(statement),
+            _ => {}
+        }
+    }
+}
+
 /// A session statement result. Sent across the wire to SQL clients.
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum StatementResult {
     Begin(mvcc::TransactionState),
     Commit { version: mvcc::Version },
     Rollback { version: mvcc::Version },
-    Explain(Plan),
-    CreateTable { name: String },
-    DropTable { name: String, existed: bool },
+    Savepoint { name: String },
+    RollbackToSavepoint { name: String },
+    ReleaseSavepoint { name: String },
+    Set { name: String, value: Value },
+    Explain { plan: Box<Plan>, unoptimized: Option<Box<Plan>>, profile: Option<Profile> },
+    CreateTable { name: String, existed: bool },
+    DropTable { name: String, existed: bool, cascaded: Vec<String> },
+    AddColumn { table: String, column: String },
+    DropColumn { table: String, column: String },
+    CreateIndex { table: String, column: String },
+    DropIndex { name: String, existed: bool },
+    Copy { count: u64 },
     Delete { count: u64 },
     Insert { count: u64 },
     Update { count: u64 },
+    Truncate { count: u64 },
+    Analyze { table: String, rows: u64 },
     // For simplicity, we buffer and send the entire set of rows as a vector
-    // instead of streaming them to the client. Streaming reads haven't been
-    // implemented from Raft either, so they're buffered all the way through.
+    // instead of streaming them to the client. Clients can use Session::query()
+    // to stream rows instead, with Raft table scans paged in row batches.
     Select { columns: Vec<Label>, rows: Vec<Row> },
 }
 
@@ -146,11 +465,20 @@ impl TryFrom<ExecutionResult> for StatementResult {
 
     fn try_from(result: ExecutionResult) -> Result<Self> {
         Ok(match result {
-            ExecutionResult::CreateTable { name } => Self::CreateTable { name },
-            ExecutionResult::DropTable { name, existed } => Self::DropTable { name, existed },
+            ExecutionResult::CreateTable { name, existed } => Self::CreateTable { name, existed },
+            ExecutionResult::DropTable { name, existed, cascaded } => {
+                Self::DropTable { name, existed, cascaded }
+            }
+            ExecutionResult::AddColumn { table, column } => Self::AddColumn { table, column },
+            ExecutionResult::DropColumn { table, column } => Self::DropColumn { table, column },
+            ExecutionResult::CreateIndex { table, column } => Self::CreateIndex { table, column },
+            ExecutionResult::DropIndex { name, existed } => Self::DropIndex { name, existed },
+            ExecutionResult::Copy { count } => Self::Copy { count },
             ExecutionResult::Delete { count } => Self::Delete { count },
             ExecutionResult::Insert { count } => Self::Insert { count },
             ExecutionResult::Update { count } => Self::Update { count },
+            ExecutionResult::Truncate { count } => Self::Truncate { count },
+            ExecutionResult::Analyze { table, rows } => Self::Analyze { table, rows },
             ExecutionResult::Select { rows, columns } => {
                 // We buffer the entire set of rows, for simplicity.
                 Self::Select { columns, rows: rows.try_collect()? }
@@ -3,7 +3,7 @@ use crate::sql::planner::Aggregate;
 use crate::sql::types::{Expression, Row, Rows, Value};
 
 use itertools::Itertools as _;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 /// Aggregates row values from the source according to the aggregates, using the
 /// group_by expressions as buckets. Emits rows with group_by buckets then
@@ -35,14 +35,9 @@ struct Aggregator {
 impl Aggregator {
     /// Creates a new aggregator for the given GROUP BY buckets and aggregates.
     fn new(group_by: Vec<Expression>, aggregates: Vec<Aggregate>) -> Self {
-        use Aggregate::*;
         let accumulators = aggregates.iter().map(Accumulator::new).collect();
-        let expressions = aggregates
-            .into_iter()
-            .map(|aggregate| match aggregate {
-                Average(expr) | Count(expr) | Max(expr) | Min(expr) | Sum(expr) => expr,
-            })
-            .collect();
+        let expressions =
+            aggregates.iter().map(|aggregate| aggregate.expression().clone()).collect();
         Self { buckets: BTreeMap::new(), empty: accumulators, group_by, expressions }
     }
 
@@ -87,11 +82,19 @@ impl Aggregator {
 /// to keep these in a vector (could use boxed trait objects too).
 #[derive(Clone)]
 enum Accumulator {
-    Average { count: i64, sum: Value },
+    Average {
+        count: i64,
+        sum: Value,
+    },
     Count(i64),
     Max(Option<Value>),
     Min(Option<Value>),
     Sum(Option<Value>),
+    /// Only passes values not seen before to the inner accumulator.
+    Distinct {
+        seen: BTreeSet<Value>,
+        accumulator: Box<Accumulator>,
+    },
 }
 
 impl Accumulator {
@@ -103,6 +106,10 @@ impl Accumulator {
             Aggregate::Max(_) => Self::Max(None),
             Aggregate::Min(_) => Self::Min(None),
             Aggregate::Sum(_) => Self::Sum(None),
+            Aggregate::Distinct(aggregate) => Self::Distinct {
+                seen: BTreeSet::new(),
+                accumulator: Box::new(Self::new(aggregate)),
+            },
         }
     }
 
@@ -123,6 +130,11 @@ impl Accumulator {
             Self::Min(Some(_)) => {}
             Self::Sum(sum @ None) => *sum = Some(Value::Integer(0).checked_add(&value)?),
             Self::Sum(Some(sum)) => *sum = sum.checked_add(&value)?,
+            Self::Distinct { seen, accumulator } => {
+                if seen.insert(value.clone()) {
+                    accumulator.add(value)?;
+                }
+            }
         }
         Ok(())
     }
@@ -131,10 +143,15 @@ impl Accumulator {
     fn value(self) -> Result<Value> {
         Ok(match self {
             Self::Average { count: 0, sum: _ } => Value::Null,
-            Self::Average { count, sum } => sum.checked_div(&Value::Integer(count))?,
+            // Decimal averages retain the decimal scale.
+            Self::Average { count, sum: sum @ Value::Decimal(..) } => {
+                sum.checked_div(&Value::Integer(count))?
+            }
+            Self::Average { count, sum } => sum.checked_div(&Value::Float(count as f64))?,
             Self::Count(count) => count.into(),
             Self::Max(Some(value)) | Self::Min(Some(value)) | Self::Sum(Some(value)) => value,
             Self::Max(None) | Self::Min(None) | Self::Sum(None) => Value::Null,
+            Self::Distinct { accumulator, .. } => accumulator.value()?,
         })
     }
 }
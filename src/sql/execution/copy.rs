@@ -0,0 +1,231 @@
+use super::write;
+use crate::errinput;
+use crate::error::Result;
+use crate::sql::engine::Transaction;
+use crate::sql::types::{Column, DataType, Label, Row, Rows, Table, Value};
+
+use itertools::Itertools as _;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write as _;
+use std::path::{Component, Path, PathBuf};
+
+/// Imports rows from a CSV file into a table, returning the number of rows
+/// inserted. The path is relative to the COPY directory (see copy_path). The
+/// first line must be a header with the table column names, in any order.
+/// Columns not given in the file get their default value. Fields are converted
+/// to the column datatypes, and empty unquoted fields are NULL.
+pub fn copy_from(
+    txn: &impl Transaction,
+    table: Table,
+    dir: Option<&Path>,
+    path: &str,
+) -> Result<u64> {
+    let input = match std::fs::read_to_string(copy_path(dir, path)?) {
+        Ok(input) => input,
+        Err(err) => return errinput!("can't read file {path}: {err}"),
+    };
+    let mut records = parse_csv(&input)?.into_iter();
+
+    // Map the header columns to table columns.
+    let Some((_, header)) = records.next() else {
+        return errinput!("no header in file {path}");
+    };
+    let mut column_map = HashMap::new();
+    let mut columns = Vec::with_capacity(header.len());
+    for (fidx, name) in header.into_iter().enumerate() {
+        let name = name.unwrap_or_default();
+        let Some(cidx) = table.columns.iter().position(|c| c.name == name) else {
+            return errinput!("unknown column {name} in table {}", table.name);
+        };
+        if column_map.insert(cidx, fidx).is_some() {
+            return errinput!("column {name} given multiple times");
+        }
+        columns.push(&table.columns[cidx]);
+    }
+
+    // Parse the records into rows, and insert them.
+    let rows: Vec<Row> = records
+        .map(|(line, fields)| {
+            if fields.len() != columns.len() {
+                return errinput!(
+                    "line {line}: expected {} fields, found {}",
+                    columns.len(),
+                    fields.len()
+                );
+            }
+            fields
+                .into_iter()
+                .zip(&columns)
+                .map(|(field, column)| parse_field(field, column, line))
+                .collect()
+        })
+        .try_collect()?;
+    let source = Box::new(rows.into_iter().map(Ok));
+    Ok(write::insert(txn, table, Some(column_map), source, false)?.0)
+}
+
+/// Exports rows to a new file, returning the number of rows written. The path
+/// is relative to the COPY directory (see copy_path), and existing files are
+/// never overwritten. If the path has a .json extension, the rows are written
+/// as a JSON array of objects keyed by column name (see Value::to_json),
+/// otherwise as CSV with a header of column names (which can be imported with
+/// copy_from). Rows are streamed to the file as they're read.
+pub fn copy_to(rows: Rows, columns: Vec<Label>, dir: Option<&Path>, path: &str) -> Result<u64> {
+    let file = match OpenOptions::new().write(true).create_new(true).open(copy_path(dir, path)?) {
+        Ok(file) => file,
+        Err(err) => return errinput!("can't create file {path}: {err}"),
+    };
+    let mut file = std::io::BufWriter::new(file);
+    let json = path.rsplit_once('.').is_some_and(|(_, ext)| ext.eq_ignore_ascii_case("json"));
+    let headers = columns.iter().map(|c| c.as_header()).collect_vec();
+
+    let mut count = 0;
+    if json {
+        let keys = headers.iter().map(|h| serde_json::Value::from(*h)).collect_vec();
+        write!(file, "[")?;
+        for row in rows {
+            let row = row?;
+            let fields = keys.iter().zip(&row).map(|(k, v)| format!("{k}: {}", v.to_json()));
+            let sep = if count > 0 { "," } else { "" };
+            write!(file, "{sep}\n  {{{}}}", fields.format(", "))?;
+            count += 1;
+        }
+        writeln!(file, "{}]", if count > 0 { "\n" } else { "" })?;
+    } else {
+        writeln!(file, "{}", headers.iter().map(|h| format_csv_field(h)).join(","))?;
+        for row in rows {
+            let row = row?;
+            writeln!(file, "{}", row.iter().map(format_csv).join(","))?;
+            count += 1;
+        }
+    }
+    file.flush()?;
+    Ok(count)
+}
+
+/// Resolves a client-given COPY path relative to the server's COPY directory.
+/// Clients can only access files below the directory, so absolute paths and
+/// .. components are rejected. Errors if no COPY directory is configured.
+fn copy_path(dir: Option<&Path>, path: &str) -> Result<PathBuf> {
+    let Some(dir) = dir else {
+        return errinput!("COPY is disabled, no COPY directory is configured");
+    };
+    let relative = Path::new(path);
+    if path.is_empty()
+        || !relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return errinput!("invalid COPY path '{path}', must be relative without ..");
+    }
+    Ok(dir.join(relative))
+}
+
+/// Formats a value as a CSV field. NULL is an empty unquoted field, which is
+/// distinct from a (quoted) empty string.
+fn format_csv(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Boolean(b) => b.to_string(),
+        Value::String(s) => format_csv_field(s),
+        Value::Timestamp(_) => format_csv_field(value.to_string().trim_matches('\'')),
+        Value::Array(_) => format_csv_field(&value.to_json().to_string()),
+        value => format_csv_field(&value.to_string()),
+    }
+}
+
+/// Formats a CSV field, quoting it if necessary. Empty fields are quoted too,
+/// since empty unquoted fields are NULL.
+fn format_csv_field(field: &str) -> String {
+    if !field.is_empty() && !field.contains([',', '"', '\n', '\r']) {
+        return field.to_string();
+    }
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+/// Parses a CSV field into a value of the column's datatype. None (an empty
+/// unquoted field) is NULL. The line number is used for error messages.
+fn parse_field(field: Option<String>, column: &Column, line: usize) -> Result<Value> {
+    let Some(field) = field else {
+        return Ok(Value::Null);
+    };
+    let value = match column.datatype {
+        DataType::Boolean => match field.to_lowercase().as_str() {
+            "true" => Some(Value::Boolean(true)),
+            "false" => Some(Value::Boolean(false)),
+            _ => None,
+        },
+        DataType::Integer => field.parse().ok().map(Value::Integer),
+        DataType::Float => field.parse().ok().map(Value::Float),
+        DataType::String => Some(Value::String(field.clone())),
+        DataType::Timestamp | DataType::Decimal(..) => {
+            Value::String(field.clone()).coerce(column.datatype).ok()
+        }
+        // Arrays are given as JSON arrays, e.g. [1,2,3].
+        DataType::Array(_) => serde_json::from_str(&field)
+            .ok()
+            .and_then(|json| Value::from_json(&json, column.datatype).ok()),
+        DataType::Json => serde_json::from_str(&field).ok().map(Value::Json),
+    };
+    match value {
+        Some(value) => Ok(value),
+        None => errinput!(
+            "line {line}: invalid {} value '{}' for column {}",
+            column.datatype,
+            field.escape_debug(),
+            column.name
+        ),
+    }
+}
+
+/// Parses CSV records, returning the fields of each record along with the line
+/// number where it starts. Fields can be quoted with ", in which case they can
+/// contain commas, newlines, and quotes escaped as "". Empty unquoted fields
+/// are returned as None, and blank lines are skipped.
+fn parse_csv(input: &str) -> Result<Vec<(usize, Vec<Option<String>>)>> {
+    let mut records = Vec::new();
+    let mut chars = input.chars().peekable();
+    let mut line = 1;
+    while chars.peek().is_some() {
+        let start = line;
+        let mut record = Vec::new();
+        loop {
+            let mut field = String::new();
+            let quoted = chars.next_if_eq(&'"').is_some();
+            if quoted {
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.next_if_eq(&'"').is_some() => field.push('"'),
+                        Some('"') => break,
+                        Some(c) => {
+                            line += (c == '\n') as usize;
+                            field.push(c)
+                        }
+                        None => return errinput!("line {start}: unterminated quoted field"),
+                    }
+                }
+            }
+            while let Some(c) = chars.next_if(|c| !matches!(c, ',' | '\n' | '\r')) {
+                if quoted {
+                    return errinput!("line {line}: unexpected character {c} after quoted field");
+                }
+                field.push(c);
+            }
+            record.push((quoted || !field.is_empty()).then_some(field));
+
+            // Handle the field terminator. \r is only valid as part of \r\n.
+            match chars.next() {
+                Some(',') => continue,
+                Some('\r') if chars.next_if_eq(&'\n').is_some() => line += 1,
+                Some('\r') => return errinput!("line {line}: unexpected carriage return"),
+                Some('\n') => line += 1,
+                Some(c) => panic!("unexpected character {c}"),
+                None => {}
+            }
+            break;
+        }
+        if record != [None] {
+            records.push((start, record));
+        }
+    }
+    Ok(records)
+}
@@ -1,8 +1,69 @@
-use super::{aggregate, join, source, transform, write};
-use crate::error::Result;
+use super::profile::{Profile, Profiler};
+use super::{aggregate, copy, join, source, transform, window, write};
+use crate::errinput;
+use crate::error::{Error, Result};
 use crate::sql::engine::{Catalog, Transaction};
 use crate::sql::planner::{Node, Plan};
-use crate::sql::types::{Label, Rows};
+use crate::sql::types::{Expression, Label, Row, Rows, Value};
+
+use itertools::Itertools as _;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A plan execution context, passed down to all executed plan nodes.
+#[derive(Clone, Default)]
+pub struct Context {
+    /// If given, execution errors with Error::Timeout once this time is
+    /// reached. It is checked before executing each plan node, and for each
+    /// row emitted by a plan node.
+    deadline: Option<Instant>,
+    /// If given, execution errors with Error::Cancelled once the flag is set,
+    /// e.g. when the client cancels the statement. It is checked along with
+    /// the deadline.
+    cancel: Option<Arc<AtomicBool>>,
+    /// If given, records per-node execution statistics.
+    profiler: Option<Profiler>,
+    /// The directory that COPY file paths are relative to. COPY errors if
+    /// None.
+    copy_dir: Option<PathBuf>,
+}
+
+impl Context {
+    /// Creates an execution context which times out after the given duration.
+    pub fn with_timeout(timeout: Duration) -> Self {
+        Self { deadline: Some(Instant::now() + timeout), ..Self::default() }
+    }
+
+    /// Aborts execution once the given flag is set.
+    pub fn with_cancel(self, cancel: Arc<AtomicBool>) -> Self {
+        Self { cancel: Some(cancel), ..self }
+    }
+
+    /// Resolves COPY file paths relative to the given directory.
+    pub fn with_copy_dir(self, copy_dir: PathBuf) -> Self {
+        Self { copy_dir: Some(copy_dir), ..self }
+    }
+
+    /// Errors with Error::Cancelled if the statement has been cancelled, or
+    /// Error::Timeout if the deadline has been reached.
+    fn check(&self) -> Result<()> {
+        if self.cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+            return Err(Error::Cancelled);
+        }
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(Error::Timeout),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns a copy of the context without the profiler, e.g. for correlated
+    /// subqueries which are executed for each row and not profiled.
+    fn unprofiled(&self) -> Self {
+        Self { profiler: None, ..self.clone() }
+    }
+}
 
 /// Executes a plan, returning an execution result.
 ///
@@ -12,45 +73,155 @@ pub fn execute_plan(
     plan: Plan,
     txn: &impl Transaction,
     catalog: &impl Catalog,
+    ctx: &Context,
 ) -> Result<ExecutionResult> {
     Ok(match plan {
-        Plan::CreateTable { schema } => {
+        Plan::CreateTable { schema, if_not_exists } => {
             let name = schema.name.clone();
-            catalog.create_table(schema)?;
-            ExecutionResult::CreateTable { name }
+            let existed = if_not_exists && catalog.get_table(&name)?.is_some();
+            if !existed {
+                catalog.create_table(schema)?;
+            }
+            ExecutionResult::CreateTable { name, existed }
+        }
+
+        Plan::CreateTableAs { name, columns, source, if_not_exists } => {
+            if if_not_exists && catalog.get_table(&name)?.is_some() {
+                return Ok(ExecutionResult::CreateTable { name, existed: true });
+            }
+            let rows: Vec<Row> = execute_node(source, txn, ctx)?.try_collect()?;
+            let schema = write::create_table_schema(name.clone(), columns, &rows)?;
+            catalog.create_table(schema.clone())?;
+            write::insert(txn, schema, None, Box::new(rows.into_iter().map(Ok)), false)?;
+            ExecutionResult::CreateTable { name, existed: false }
+        }
+
+        Plan::DropTable { table, if_exists, cascade } => {
+            match catalog.drop_table(&table, if_exists, cascade)? {
+                Some(cascaded) => {
+                    ExecutionResult::DropTable { name: table, existed: true, cascaded }
+                }
+                None => {
+                    ExecutionResult::DropTable { name: table, existed: false, cascaded: vec![] }
+                }
+            }
+        }
+
+        Plan::AddColumn { table, column } => {
+            let name = column.name.clone();
+            catalog.add_column(&table, column)?;
+            ExecutionResult::AddColumn { table, column: name }
+        }
+
+        Plan::DropColumn { table, column } => {
+            catalog.drop_column(&table, &column)?;
+            ExecutionResult::DropColumn { table, column }
         }
 
-        Plan::DropTable { table, if_exists } => {
-            let existed = catalog.drop_table(&table, if_exists)?;
-            ExecutionResult::DropTable { name: table, existed }
+        Plan::CreateIndex { name, table, column } => {
+            catalog.create_index(&table, &column, name.as_deref())?;
+            ExecutionResult::CreateIndex { table, column }
         }
 
-        Plan::Delete { table, primary_key, source } => {
-            let source = execute(source, txn)?;
-            let count = write::delete(txn, table, primary_key, source)?;
-            ExecutionResult::Delete { count }
+        Plan::DropIndex { table: Some(table), name: column, if_exists } => {
+            let existed = catalog.drop_index(&table, &column, if_exists)?;
+            ExecutionResult::DropIndex { name: format!("{table}.{column}"), existed }
         }
 
-        Plan::Insert { table, column_map, source } => {
-            let source = execute(source, txn)?;
-            let count = write::insert(txn, table, column_map, source)?;
-            ExecutionResult::Insert { count }
+        Plan::DropIndex { table: None, name, if_exists } => match catalog.find_index(&name)? {
+            Some((table, column)) => {
+                let existed = catalog.drop_index(&table, &column, false)?;
+                ExecutionResult::DropIndex { name, existed }
+            }
+            None if if_exists => ExecutionResult::DropIndex { name, existed: false },
+            None => return errinput!("index {name} does not exist"),
+        },
+
+        Plan::CopyFrom { table, path } => {
+            let count = copy::copy_from(txn, table, ctx.copy_dir.as_deref(), &path)?;
+            ExecutionResult::Copy { count }
+        }
+
+        Plan::CopyTo { source, path } => {
+            let columns = (0..source.columns()).map(|i| source.column_label(i)).collect();
+            let rows = execute_node(source, txn, ctx)?;
+            let count = copy::copy_to(rows, columns, ctx.copy_dir.as_deref(), &path)?;
+            ExecutionResult::Copy { count }
+        }
+
+        Plan::Delete { table, primary_key, source, returning } => {
+            let source = execute_node(source, txn, ctx)?;
+            let (count, rows) =
+                write::delete(txn, table, primary_key, source, !returning.is_empty())?;
+            match returning.is_empty() {
+                true => ExecutionResult::Delete { count },
+                false => execute_returning(rows, returning, txn, ctx)?,
+            }
+        }
+
+        Plan::Insert { table, column_map, source, returning } => {
+            let source = execute_node(source, txn, ctx)?;
+            let (count, rows) =
+                write::insert(txn, table, column_map, source, !returning.is_empty())?;
+            match returning.is_empty() {
+                true => ExecutionResult::Insert { count },
+                false => execute_returning(rows, returning, txn, ctx)?,
+            }
         }
 
         Plan::Select(root) => {
             let columns = (0..root.columns()).map(|i| root.column_label(i)).collect();
-            let rows = execute(root, txn)?;
+            let rows = execute_node(root, txn, ctx)?;
             ExecutionResult::Select { rows, columns }
         }
 
-        Plan::Update { table, primary_key, source, expressions } => {
-            let source = execute(source, txn)?;
-            let count = write::update(txn, table.name, primary_key, source, expressions)?;
-            ExecutionResult::Update { count }
+        Plan::Truncate { table } => {
+            let count = txn.truncate(&table)?;
+            ExecutionResult::Truncate { count }
+        }
+
+        Plan::Analyze { table } => {
+            let stats = txn.analyze(&table)?;
+            ExecutionResult::Analyze { table, rows: stats.rows }
+        }
+
+        Plan::Update { table, primary_key, source, expressions, returning } => {
+            let expressions = expressions
+                .into_iter()
+                .map(|(i, expr)| Ok((i, expr.transform(&Ok, &|e| execute_subquery(e, txn, ctx))?)))
+                .collect::<Result<_>>()?;
+            let source = execute_node(source, txn, ctx)?;
+            let (count, rows) =
+                write::update(txn, table, primary_key, source, expressions, !returning.is_empty())?;
+            match returning.is_empty() {
+                true => ExecutionResult::Update { count },
+                false => execute_returning(rows, returning, txn, ctx)?,
+            }
         }
     })
 }
 
+/// Evaluates a RETURNING clause's expressions against the rows affected by a
+/// write, returning the resulting rows as a query result.
+fn execute_returning(
+    rows: Vec<Row>,
+    returning: Vec<(Expression, Label)>,
+    txn: &impl Transaction,
+    ctx: &Context,
+) -> Result<ExecutionResult> {
+    let (expressions, columns): (Vec<_>, Vec<_>) = returning.into_iter().unzip();
+    let expressions: Vec<_> = expressions
+        .into_iter()
+        .map(|expr| expr.transform(&Ok, &|e| execute_subquery(e, txn, ctx)))
+        .try_collect()?;
+    let rows: Rows = Box::new(rows.into_iter().map(Ok));
+    let rows = match expressions.iter().any(is_correlated) {
+        true => execute_correlated(rows, expressions, txn, ctx, transform::project)?,
+        false => transform::project(rows, expressions),
+    };
+    Ok(ExecutionResult::Select { rows, columns })
+}
+
 /// Recursively executes a query plan node, returning a row iterator.
 ///
 /// Rows stream through the plan node tree from the branches to the root. Nodes
@@ -75,22 +246,85 @@ pub fn execute_plan(
 /// joins the rows from the two tables, then the Filter node discards old
 /// movies, the Projection node picks out the requested columns, and the Order
 /// node sorts them before emitting the rows to the client.
-pub fn execute(node: Node, txn: &impl Transaction) -> Result<Rows> {
+///
+/// Any subqueries in the node's expressions are executed first, and replaced by
+/// their constant values. Correlated subqueries reference the current row, so
+/// they are instead executed for each row in filters and projections.
+///
+/// If the context has a deadline or cancellation flag, it is checked before
+/// executing each node and for each row emitted by each node, erroring if it
+/// has been reached or set. If the context has a profiler, the node's execution
+/// statistics are recorded.
+fn execute_node(node: Node, txn: &impl Transaction, ctx: &Context) -> Result<Rows> {
+    ctx.check()?;
+    let rows = match &ctx.profiler {
+        Some(profiler) => profiler.profile(|| execute_inner(node, txn, ctx))?,
+        None => execute_inner(node, txn, ctx)?,
+    };
+    if ctx.deadline.is_none() && ctx.cancel.is_none() {
+        return Ok(rows);
+    }
+    let ctx = ctx.unprofiled();
+    Ok(Box::new(rows.map(move |row| ctx.check().and(row))))
+}
+
+/// Executes a query plan node. Use execute_node() to execute child nodes.
+fn execute_inner(node: Node, txn: &impl Transaction, ctx: &Context) -> Result<Rows> {
+    let node = node.transform_expressions(&Ok, &|expr| execute_subquery(expr, txn, ctx))?;
     Ok(match node {
+        // Correlated scan filters (e.g. pushed down by the optimizer) and inner
+        // join predicates are applied as filters on the unfiltered rows.
+        Node::Scan { table, alias: _, filter: Some(predicate) } if is_correlated(&predicate) => {
+            let source = source::scan(txn, table, None)?;
+            execute_correlated(source, vec![predicate], txn, ctx, |rows, mut predicate| {
+                transform::filter(rows, predicate.remove(0))
+            })?
+        }
+        Node::NestedLoopJoin { left, right, predicate: Some(predicate), outer: false }
+            if is_correlated(&predicate) =>
+        {
+            let right_size = right.columns();
+            let left = execute_node(*left, txn, ctx)?;
+            let right = execute_node(*right, txn, ctx)?;
+            let source = join::nested_loop(left, right, right_size, None, false)?;
+            execute_correlated(source, vec![predicate], txn, ctx, |rows, mut predicate| {
+                transform::filter(rows, predicate.remove(0))
+            })?
+        }
+
+        Node::Filter { source, predicate } if is_correlated(&predicate) => {
+            let source = execute_node(*source, txn, ctx)?;
+            execute_correlated(source, vec![predicate], txn, ctx, |rows, mut predicate| {
+                transform::filter(rows, predicate.remove(0))
+            })?
+        }
+
+        Node::Projection { source, expressions, aliases: _ }
+            if expressions.iter().any(is_correlated) =>
+        {
+            let source = execute_node(*source, txn, ctx)?;
+            execute_correlated(source, expressions, txn, ctx, transform::project)?
+        }
+
         Node::Aggregate { source, group_by, aggregates } => {
-            let source = execute(*source, txn)?;
+            let source = execute_node(*source, txn, ctx)?;
             aggregate::aggregate(source, group_by, aggregates)?
         }
 
+        Node::Distinct { source } => {
+            let source = execute_node(*source, txn, ctx)?;
+            transform::distinct(source)
+        }
+
         Node::Filter { source, predicate } => {
-            let source = execute(*source, txn)?;
+            let source = execute_node(*source, txn, ctx)?;
             transform::filter(source, predicate)
         }
 
         Node::HashJoin { left, left_column, right, right_column, outer } => {
             let right_size = right.columns();
-            let left = execute(*left, txn)?;
-            let right = execute(*right, txn)?;
+            let left = execute_node(*left, txn, ctx)?;
+            let right = execute_node(*right, txn, ctx)?;
             join::hash(left, left_column, right, right_column, right_size, outer)?
         }
 
@@ -103,51 +337,193 @@ pub fn execute(node: Node, txn: &impl Transaction) -> Result<Rows> {
         Node::KeyLookup { table, keys, alias: _ } => source::lookup_key(txn, table.name, keys)?,
 
         Node::Limit { source, limit } => {
-            let source = execute(*source, txn)?;
+            let source = execute_node(*source, txn, ctx)?;
             transform::limit(source, limit)
         }
 
+        Node::Lock { source, table } => {
+            let source = execute_node(*source, txn, ctx)?;
+            write::lock(txn, table, source)?
+        }
+
         Node::NestedLoopJoin { left, right, predicate, outer } => {
             let right_size = right.columns();
-            let left = execute(*left, txn)?;
-            let right = execute(*right, txn)?;
+            let left = execute_node(*left, txn, ctx)?;
+            let right = execute_node(*right, txn, ctx)?;
             join::nested_loop(left, right, right_size, predicate, outer)?
         }
 
         Node::Nothing { .. } => source::nothing(),
 
         Node::Offset { source, offset } => {
-            let source = execute(*source, txn)?;
+            let source = execute_node(*source, txn, ctx)?;
             transform::offset(source, offset)
         }
 
-        Node::Order { source, key: orders } => {
-            let source = execute(*source, txn)?;
-            transform::order(source, orders)?
+        Node::Order { source, key: orders, limit } => {
+            let source = execute_node(*source, txn, ctx)?;
+            transform::order(source, orders, limit)?
         }
 
         Node::Projection { source, expressions, aliases: _ } => {
-            let source = execute(*source, txn)?;
+            let source = execute_node(*source, txn, ctx)?;
             transform::project(source, expressions)
         }
 
         Node::Remap { source, targets } => {
-            let source = execute(*source, txn)?;
+            let source = execute_node(*source, txn, ctx)?;
             transform::remap(source, targets)
         }
 
         Node::Scan { table, filter, alias: _ } => source::scan(txn, table, filter)?,
 
+        // The rows were read from the catalog during planning.
+        Node::SchemaScan { rows, .. } => Box::new(rows.into_iter().map(Ok)),
+
+        Node::Union { left, right, all } => {
+            let left = execute_node(*left, txn, ctx)?;
+            let right = execute_node(*right, txn, ctx)?;
+            let rows = transform::union(left, right);
+            if all {
+                rows
+            } else {
+                transform::distinct(rows)
+            }
+        }
+
         Node::Values { rows } => source::values(rows),
+
+        Node::Window { source, functions } => {
+            let source = execute_node(*source, txn, ctx)?;
+            window::window(source, functions)?
+        }
     })
 }
 
+/// Executes a subquery expression, returning its value as a constant
+/// expression. Correlated subqueries (with outer column references) and other
+/// expressions are returned as-is, and executed for each row.
+fn execute_subquery(expr: Expression, txn: &impl Transaction, ctx: &Context) -> Result<Expression> {
+    match expr {
+        Expression::Subquery(_, ref outer) | Expression::Exists(_, ref outer)
+            if !outer.iter().any(|expr| expr.contains(&|e| matches!(e, Expression::Column(_)))) =>
+        {
+            execute_correlated_subquery(expr, None, txn, ctx)
+        }
+        // Correlated subqueries are executed for each row, unprofiled.
+        Expression::Subquery(ref node, _) | Expression::Exists(ref node, _) => {
+            if let Some(profiler) = &ctx.profiler {
+                profiler.skip(node);
+            }
+            Ok(expr)
+        }
+        expr => Ok(expr),
+    }
+}
+
+/// Executes a subquery expression for the given outer row, returning its value
+/// as a constant expression. Outer column references in the plan are replaced
+/// by the outer values first. A scalar subquery yields NULL if it doesn't
+/// return any rows, and errors if it returns more than one. An EXISTS subquery
+/// yields true if it returns any rows, and only fetches the first row.
+fn execute_correlated_subquery(
+    expr: Expression,
+    row: Option<&Row>,
+    txn: &impl Transaction,
+    ctx: &Context,
+) -> Result<Expression> {
+    let bind = |node: Node, outer: &[Expression]| -> Result<Node> {
+        let values: Vec<Value> = outer.iter().map(|expr| expr.evaluate(row)).try_collect()?;
+        let bind = |expr| match expr {
+            Expression::OuterColumn(index) => Ok(Expression::Constant(values[index].clone())),
+            expr => Ok(expr),
+        };
+        node.transform(&|node| node.transform_expressions(&Ok, &bind), &Ok)
+    };
+    match expr {
+        Expression::Subquery(node, outer) => {
+            let mut rows = execute_node(bind(*node, &outer)?, txn, ctx)?;
+            // The planner ensures the subquery returns a single column.
+            let value = rows.next().transpose()?.and_then(|row| row.into_iter().next());
+            if rows.next().transpose()?.is_some() {
+                return errinput!("subquery returned more than one row");
+            }
+            Ok(Expression::Constant(value.unwrap_or(Value::Null)))
+        }
+        Expression::Exists(node, outer) => {
+            let exists =
+                execute_node(bind(*node, &outer)?, txn, ctx)?.next().transpose()?.is_some();
+            Ok(Expression::Constant(Value::Boolean(exists)))
+        }
+        expr => Ok(expr),
+    }
+}
+
+/// Returns true if the expression contains a correlated subquery, i.e. one that
+/// must be executed for each row. Uncorrelated subqueries have already been
+/// executed by execute_subquery().
+fn is_correlated(expr: &Expression) -> bool {
+    expr.contains(&|expr| matches!(expr, Expression::Subquery(..) | Expression::Exists(..)))
+}
+
+/// Executes a row transformation with correlated subqueries in its expressions.
+/// For each source row, the subqueries are executed and replaced by constants,
+/// and the row is transformed using the resulting expressions. The output rows
+/// are buffered, since the subqueries must be executed using the transaction.
+fn execute_correlated(
+    source: Rows,
+    expressions: Vec<Expression>,
+    txn: &impl Transaction,
+    ctx: &Context,
+    transform: impl Fn(Rows, Vec<Expression>) -> Rows,
+) -> Result<Rows> {
+    let ctx = ctx.unprofiled();
+    let mut rows = Vec::new();
+    for row in source {
+        let row = row?;
+        let expressions = expressions
+            .iter()
+            .map(|expr| {
+                expr.clone().transform(&Ok, &|expr| {
+                    execute_correlated_subquery(expr, Some(&row), txn, &ctx)
+                })
+            })
+            .try_collect()?;
+        for row in transform(Box::new(std::iter::once(Ok(row))), expressions) {
+            rows.push(row?);
+        }
+    }
+    Ok(Box::new(rows.into_iter().map(Ok)))
+}
+
+/// Executes a SELECT plan with profiling (i.e. EXPLAIN ANALYZE), discarding the
+/// result rows and returning the execution statistics.
+pub fn execute_profiled(plan: Plan, txn: &impl Transaction, ctx: &Context) -> Result<Profile> {
+    let Plan::Select(root) = plan else {
+        return errinput!("can only profile SELECT plans");
+    };
+    let profiler = Profiler::default();
+    let ctx = Context { profiler: Some(profiler.clone()), ..ctx.clone() };
+    let start = Instant::now();
+    for row in execute_node(root, txn, &ctx)? {
+        row?;
+    }
+    Ok(profiler.finish(start.elapsed()))
+}
+
 /// A plan execution result.
 pub enum ExecutionResult {
-    CreateTable { name: String },
-    DropTable { name: String, existed: bool },
+    CreateTable { name: String, existed: bool },
+    DropTable { name: String, existed: bool, cascaded: Vec<String> },
+    AddColumn { table: String, column: String },
+    DropColumn { table: String, column: String },
+    CreateIndex { table: String, column: String },
+    DropIndex { name: String, existed: bool },
+    Copy { count: u64 },
     Delete { count: u64 },
     Insert { count: u64 },
     Update { count: u64 },
+    Truncate { count: u64 },
+    Analyze { table: String, rows: u64 },
     Select { rows: Rows, columns: Vec<Label> },
 }
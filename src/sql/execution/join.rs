@@ -89,7 +89,7 @@ impl NestedLoopIterator {
                 let row = left_row
                     .iter()
                     .cloned()
-                    .chain(std::iter::repeat(Value::Null).take(self.right_size))
+                    .chain(std::iter::repeat_n(Value::Null, self.right_size))
                     .collect();
                 self.left.next();
                 return Ok(Some(row));
@@ -138,7 +138,7 @@ pub fn hash(
     }
 
     // Set up an iterator for an empty right row in the outer case.
-    let empty = std::iter::repeat(Value::Null).take(right_size);
+    let empty = std::iter::repeat_n(Value::Null, right_size);
 
     // Set up the join iterator.
     let join = left.flat_map(move |result| -> Rows {
@@ -1,10 +1,14 @@
 //! Executes a `Plan` against a `sql::engine::Engine`.
 
 mod aggregate;
+mod copy;
 mod execute;
 mod join;
+mod profile;
 mod source;
 mod transform;
+mod window;
 mod write;
 
-pub use execute::{execute_plan, ExecutionResult};
+pub use execute::{execute_plan, execute_profiled, Context, ExecutionResult};
+pub use profile::{NodeStats, Profile};
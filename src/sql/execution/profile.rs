@@ -0,0 +1,99 @@
+use crate::error::Result;
+use crate::sql::planner::Node;
+use crate::sql::types::{Row, Rows};
+
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Query execution statistics, collected by EXPLAIN ANALYZE.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    /// Per-node statistics, in plan tree pre-order with subqueries before
+    /// child nodes (i.e. in EXPLAIN output order). None for nodes that weren't
+    /// profiled, i.e. correlated subqueries that are executed for each row.
+    pub nodes: Vec<Option<NodeStats>>,
+    /// The total execution time, including fetching all result rows.
+    pub total: Duration,
+}
+
+/// Execution statistics for a single plan node.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeStats {
+    /// The number of rows emitted by the node.
+    pub rows: u64,
+    /// The time spent executing the node, including its child nodes.
+    pub time: Duration,
+}
+
+/// Collects node statistics during execution. Nodes are registered as they're
+/// executed, and their row iterators are wrapped to count rows and time.
+/// Profiling is optional, such that regular execution has no overhead.
+#[derive(Clone, Default)]
+pub struct Profiler(Rc<RefCell<Vec<Option<NodeStats>>>>);
+
+impl Profiler {
+    /// Profiles a node execution, which must execute the node's subqueries and
+    /// child nodes (in that order) with this profiler.
+    pub fn profile(&self, execute: impl FnOnce() -> Result<Rows>) -> Result<Rows> {
+        let id = self.register(Some(NodeStats::default()));
+        let start = Instant::now();
+        let rows = execute()?;
+        self.update(id, |stats| stats.time += start.elapsed());
+        Ok(Box::new(ProfiledRows { rows, id, profiler: self.clone() }))
+    }
+
+    /// Skips a node tree that won't be profiled, to keep node positions
+    /// aligned with the plan.
+    pub fn skip(&self, node: &Node) {
+        self.register(None);
+        for subquery in node.subqueries() {
+            self.skip(subquery);
+        }
+        for child in node.children() {
+            self.skip(child);
+        }
+    }
+
+    /// Finishes profiling, returning the collected profile.
+    pub fn finish(self, total: Duration) -> Profile {
+        Profile { nodes: self.0.take(), total }
+    }
+
+    /// Registers a node, returning its position.
+    fn register(&self, stats: Option<NodeStats>) -> usize {
+        let mut nodes = self.0.borrow_mut();
+        nodes.push(stats);
+        nodes.len() - 1
+    }
+
+    /// Updates the statistics for the given node.
+    fn update(&self, id: usize, f: impl FnOnce(&mut NodeStats)) {
+        f(self.0.borrow_mut()[id].as_mut().expect("node not profiled"))
+    }
+}
+
+/// A row iterator that records row counts and time for a profiled node.
+#[derive(Clone)]
+struct ProfiledRows {
+    rows: Rows,
+    id: usize,
+    profiler: Profiler,
+}
+
+impl Iterator for ProfiledRows {
+    type Item = Result<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = Instant::now();
+        let row = self.rows.next();
+        let elapsed = start.elapsed();
+        let produced = matches!(row, Some(Ok(_)));
+        self.profiler.update(self.id, |stats| {
+            stats.time += elapsed;
+            stats.rows += produced as u64;
+        });
+        row
+    }
+}
@@ -8,7 +8,7 @@ pub fn scan(txn: &impl Transaction, table: Table, filter: Option<Expression>) ->
 }
 
 /// A primary key lookup source.
-pub fn lookup_key(txn: &impl Transaction, table: String, keys: Vec<Value>) -> Result<Rows> {
+pub fn lookup_key(txn: &impl Transaction, table: String, keys: Vec<Vec<Value>>) -> Result<Rows> {
     Ok(Box::new(txn.get(&table, &keys)?.into_iter().map(Ok)))
 }
 
@@ -1,9 +1,16 @@
 use crate::errinput;
 use crate::error::Result;
-use crate::sql::planner::Direction;
-use crate::sql::types::{Expression, Rows, Value};
+use crate::sql::planner::{Direction, Nulls};
+use crate::sql::types::{DataType, Expression, Row, Rows, Value};
 
 use itertools::{izip, Itertools as _};
+use std::collections::HashSet;
+
+/// Discards duplicate rows (i.e. SELECT DISTINCT).
+pub fn distinct(source: Rows) -> Rows {
+    let mut seen = HashSet::new();
+    Box::new(source.filter_ok(move |row| seen.insert(row.clone())))
+}
 
 /// Filters the input rows (i.e. WHERE).
 pub fn filter(source: Rows, predicate: Expression) -> Rows {
@@ -27,8 +34,17 @@ pub fn offset(source: Rows, offset: usize) -> Rows {
     Box::new(source.skip(offset))
 }
 
-/// Sorts the rows (i.e. ORDER BY).
-pub fn order(source: Rows, order: Vec<(Expression, Direction)>) -> Result<Rows> {
+/// Sorts the rows (i.e. ORDER BY). If limit is given, only the first limit
+/// rows are emitted (i.e. ORDER BY ... LIMIT).
+pub fn order(
+    source: Rows,
+    order: Vec<(Expression, Direction, Nulls)>,
+    limit: Option<usize>,
+) -> Result<Rows> {
+    if let Some(limit) = limit {
+        return top(source, order, limit);
+    }
+
     // We can't use sort_by_cached_key(), since expression evaluation is
     // fallible, and since we may have to vary the sort direction of each
     // expression. Precompute the sort values instead, and map them based on
@@ -36,23 +52,68 @@ pub fn order(source: Rows, order: Vec<(Expression, Direction)>) -> Result<Rows>
     let mut irows: Vec<_> = source.enumerate().map(|(i, r)| r.map(|row| (i, row))).try_collect()?;
     let mut sort_values = Vec::with_capacity(irows.len());
     for (_, row) in &irows {
-        let values: Vec<_> = order.iter().map(|(e, _)| e.evaluate(Some(row))).try_collect()?;
+        let values: Vec<_> = order.iter().map(|(e, _, _)| e.evaluate(Some(row))).try_collect()?;
         sort_values.push(values)
     }
 
-    irows.sort_by(|&(a, _), &(b, _)| {
-        let dirs = order.iter().map(|(_, dir)| dir);
-        for (a, b, dir) in izip!(&sort_values[a], &sort_values[b], dirs) {
-            match a.cmp(b) {
-                std::cmp::Ordering::Equal => {}
-                order if *dir == Direction::Descending => return order.reverse(),
-                order => return order,
+    irows.sort_by(|&(a, _), &(b, _)| compare(&sort_values[a], &sort_values[b], &order));
+
+    Ok(Box::new(irows.into_iter().map(|(_, row)| Ok(row))))
+}
+
+/// Emits the first limit rows in sort order (i.e. a top-N sort). Only buffers
+/// limit rows, kept sorted in a vector: each source row is either discarded if
+/// it sorts after the last buffered row, or inserted at its sorted position,
+/// evicting the last buffered row. Rows with equal sort values retain their
+/// source order, like the full sort.
+fn top(source: Rows, order: Vec<(Expression, Direction, Nulls)>, limit: usize) -> Result<Rows> {
+    let mut buffer: Vec<(Vec<Value>, Row)> = Vec::with_capacity(limit);
+    if limit > 0 {
+        for result in source {
+            let row = result?;
+            let values: Vec<_> =
+                order.iter().map(|(e, _, _)| e.evaluate(Some(&row))).try_collect()?;
+            if buffer.len() == limit {
+                let (last, _) = buffer.last().expect("empty buffer");
+                if compare(&values, last, &order) != std::cmp::Ordering::Less {
+                    continue;
+                }
+                buffer.pop();
             }
+            // Insert after any equal rows, to keep the sort stable.
+            let index = buffer.partition_point(|(v, _)| {
+                compare(v, &values, &order) != std::cmp::Ordering::Greater
+            });
+            buffer.insert(index, (values, row));
         }
-        std::cmp::Ordering::Equal
-    });
+    }
+    Ok(Box::new(buffer.into_iter().map(|(_, row)| Ok(row))))
+}
 
-    Ok(Box::new(irows.into_iter().map(|(_, row)| Ok(row))))
+/// Compares two rows' precomputed sort values, using the direction and NULL
+/// order of the given sort key.
+pub fn compare(
+    a: &[Value],
+    b: &[Value],
+    key: &[(Expression, Direction, Nulls)],
+) -> std::cmp::Ordering {
+    use std::cmp::Ordering::*;
+    let dirs = key.iter().map(|(_, dir, nulls)| (dir, nulls));
+    for (a, b, (dir, nulls)) in izip!(a, b, dirs) {
+        // NULLs are ordered separately from the direction.
+        let order = match (a, b, nulls) {
+            (Value::Null, Value::Null, _) => Equal,
+            (Value::Null, _, Nulls::First) | (_, Value::Null, Nulls::Last) => return Less,
+            (Value::Null, _, Nulls::Last) | (_, Value::Null, Nulls::First) => return Greater,
+            (a, b, _) => a.cmp(b),
+        };
+        match order {
+            Equal => {}
+            order if *dir == Direction::Descending => return order.reverse(),
+            order => return order,
+        }
+    }
+    Equal
 }
 
 /// Projects the rows using the given expressions (i.e. SELECT).
@@ -75,3 +136,26 @@ pub fn remap(source: Rows, targets: Vec<Option<usize>>) -> Rows {
         out
     }))
 }
+
+/// Emits the left rows followed by the right rows (i.e. UNION ALL). The column
+/// datatypes must be compatible across all rows: numeric types are compatible
+/// with each other, and NULL with any type.
+pub fn union(left: Rows, right: Rows) -> Rows {
+    let mut datatypes: Vec<Option<DataType>> = Vec::new();
+    Box::new(left.chain(right).map(move |result| {
+        let row = result?;
+        datatypes.resize(row.len(), None);
+        for (i, value) in row.iter().enumerate() {
+            let Some(datatype) = value.datatype() else { continue };
+            match datatypes[i] {
+                None => datatypes[i] = Some(datatype),
+                Some(expect) if datatype.is_compatible(expect) => {}
+                Some(expect) if datatype.is_numeric() && expect.is_numeric() => {}
+                Some(expect) => {
+                    return errinput!("can't combine {expect} and {datatype} in UNION column {i}")
+                }
+            }
+        }
+        Ok(row)
+    }))
+}
@@ -0,0 +1,66 @@
+use super::transform;
+use crate::error::Result;
+use crate::sql::planner::{Window, WindowFunction};
+use crate::sql::types::{Row, Rows, Value};
+
+use itertools::Itertools as _;
+use std::cmp::Ordering;
+
+/// Computes the given window functions across the source rows, appending one
+/// column per function to each row. Each function sorts the rows by its
+/// partition and order keys, and the rows are emitted in the last function's
+/// order.
+pub fn window(source: Rows, functions: Vec<Window>) -> Result<Rows> {
+    let mut rows: Vec<Row> = source.try_collect()?;
+    for window in functions {
+        // Precompute the partition and sort values of each row.
+        let mut keyed: Vec<(Vec<Value>, Vec<Value>, Row)> = rows
+            .into_iter()
+            .map(|row| {
+                let partition: Vec<_> =
+                    window.partition_by.iter().map(|e| e.evaluate(Some(&row))).try_collect()?;
+                let sort: Vec<_> =
+                    window.order_by.iter().map(|(e, _, _)| e.evaluate(Some(&row))).try_collect()?;
+                Ok((partition, sort, row))
+            })
+            .collect::<Result<_>>()?;
+
+        // Sort the rows by partition, then by the window order. The sort is
+        // stable, so rows without an order key retain their source order.
+        keyed.sort_by(|(a_partition, a_sort, _), (b_partition, b_sort, _)| {
+            a_partition
+                .cmp(b_partition)
+                .then_with(|| transform::compare(a_sort, b_sort, &window.order_by))
+        });
+
+        // Compute the function for each row, resetting at partition boundaries.
+        // Adjacent rows with equal sort values are peers, which share a rank.
+        rows = Vec::with_capacity(keyed.len());
+        let mut current: Option<Vec<Value>> = None;
+        let mut previous: Option<Vec<Value>> = None;
+        let (mut number, mut rank, mut dense_rank) = (0, 0, 0);
+        for (partition, sort, mut row) in keyed {
+            if current.as_ref() != Some(&partition) {
+                current = Some(partition);
+                previous = None;
+                (number, rank, dense_rank) = (0, 0, 0);
+            }
+            number += 1;
+            let peer = previous.as_ref().is_some_and(|previous| {
+                transform::compare(previous, &sort, &window.order_by) == Ordering::Equal
+            });
+            if !peer {
+                rank = number;
+                dense_rank += 1;
+            }
+            row.push(match window.function {
+                WindowFunction::RowNumber => Value::Integer(number),
+                WindowFunction::Rank => Value::Integer(rank),
+                WindowFunction::DenseRank => Value::Integer(dense_rank),
+            });
+            rows.push(row);
+            previous = Some(sort);
+        }
+    }
+    Ok(Box::new(rows.into_iter().map(Ok)))
+}
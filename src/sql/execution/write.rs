@@ -1,27 +1,75 @@
 use crate::errinput;
 use crate::error::Result;
 use crate::sql::engine::Transaction;
-use crate::sql::types::{Expression, Rows, Table, Value};
+use crate::sql::types::{Column, DataType, Expression, OnDelete, Row, Rows, Table, Value};
 
 use itertools::Itertools as _;
 use std::collections::{BTreeMap, HashMap};
 
+/// Builds the schema for a new table with the given columns and rows (i.e.
+/// CREATE TABLE AS). Unknown column datatypes are inferred from the first
+/// non-NULL value in the rows. The first column is the primary key, and the
+/// rest are nullable.
+pub fn create_table_schema(
+    name: String,
+    columns: Vec<(String, Option<DataType>)>,
+    rows: &[Row],
+) -> Result<Table> {
+    let mut schema = Table {
+        name,
+        primary_key: vec![0],
+        columns: Vec::with_capacity(columns.len()),
+        unique: Vec::new(),
+        checks: Vec::new(),
+    };
+    for (i, (name, datatype)) in columns.into_iter().enumerate() {
+        let Some(datatype) = datatype.or_else(|| rows.iter().find_map(|row| row[i].datatype()))
+        else {
+            return errinput!("can't infer datatype of column {name} from NULL values");
+        };
+        let primary_key = i == 0;
+        schema.columns.push(Column {
+            name,
+            datatype,
+            nullable: !primary_key,
+            default: (!primary_key).then_some(Value::Null),
+            unique: primary_key,
+            index: false,
+            index_name: None,
+            references: None,
+            on_delete: OnDelete::Restrict,
+            check: None,
+        });
+    }
+    Ok(schema)
+}
+
 /// Deletes rows, taking primary keys from the source (i.e. DELETE) using the
-/// primary_key column index. Returns the number of rows deleted.
+/// primary_key column indexes. Returns the number of rows deleted, and if
+/// returning is true the deleted rows (i.e. DELETE RETURNING).
 pub fn delete(
     txn: &impl Transaction,
     table: String,
-    primary_key: usize,
+    primary_key: Vec<usize>,
     source: Rows,
-) -> Result<u64> {
-    let ids: Vec<Value> =
-        source.map_ok(|row| row.into_iter().nth(primary_key).expect("short row")).try_collect()?;
-    let count = ids.len() as u64;
+    returning: bool,
+) -> Result<(u64, Vec<Row>)> {
+    let mut rows = Vec::new();
+    let mut ids = Vec::new();
+    for row in source {
+        let row = row?;
+        ids.push(primary_key.iter().map(|i| row[*i].clone()).collect_vec());
+        if returning {
+            rows.push(row);
+        }
+    }
     txn.delete(&table, &ids)?;
-    Ok(count)
+    Ok((ids.len() as u64, rows))
 }
 
-/// Inserts rows into a table (i.e. INSERT) from the given source.
+/// Inserts rows into a table (i.e. INSERT) from the given source. Returns the
+/// number of rows inserted, and if returning is true the inserted rows (i.e.
+/// INSERT RETURNING).
 ///
 /// If given, column_map contains the mapping of table -> source columns for all
 /// columns in source. Otherwise, every column in source is the corresponding
@@ -32,7 +80,8 @@ pub fn insert(
     table: Table,
     column_map: Option<HashMap<usize, usize>>,
     mut source: Rows,
-) -> Result<u64> {
+    returning: bool,
+) -> Result<(u64, Vec<Row>)> {
     let mut rows = Vec::new();
     while let Some(values) = source.next().transpose()? {
         // Fast path: the row is already complete, with no column mapping.
@@ -68,29 +117,50 @@ pub fn insert(
         rows.push(row);
     }
     let count = rows.len() as u64;
+    // The returned rows are coerced to the column datatypes like the written
+    // rows, e.g. integers to floats.
+    let returned = match returning {
+        true => rows.iter().map(|row| table.coerce_row(row.clone())).try_collect()?,
+        false => Vec::new(),
+    };
     txn.insert(&table.name, rows)?;
-    Ok(count)
+    Ok((count, returned))
 }
 
 /// Updates rows passed in from the source (i.e. UPDATE). Returns the number of
-/// rows updated.
+/// rows updated, and if returning is true the updated rows (i.e. UPDATE
+/// RETURNING).
 pub fn update(
     txn: &impl Transaction,
-    table: String,
-    primary_key: usize,
+    table: Table,
+    primary_key: Vec<usize>,
     mut source: Rows,
     expressions: Vec<(usize, Expression)>,
-) -> Result<u64> {
+    returning: bool,
+) -> Result<(u64, Vec<Row>)> {
     let mut updates = BTreeMap::new();
     while let Some(row) = source.next().transpose()? {
         let mut update = row.clone();
         for (column, expr) in &expressions {
             update[*column] = expr.evaluate(Some(&row))?;
         }
-        let id = row.into_iter().nth(primary_key).expect("short row");
+        let id = primary_key.iter().map(|i| row[*i].clone()).collect_vec();
         updates.insert(id, update);
     }
     let count = updates.len() as u64;
-    txn.update(&table, updates)?;
-    Ok(count)
+    let returned = match returning {
+        true => updates.values().map(|row| table.coerce_row(row.clone())).try_collect()?,
+        false => Vec::new(),
+    };
+    txn.update(&table.name, updates)?;
+    Ok((count, returned))
+}
+
+/// Locks the source rows (i.e. SELECT FOR UPDATE) by writing them back
+/// unchanged, such that concurrent writes to them conflict. Returns the rows.
+pub fn lock(txn: &impl Transaction, table: Table, source: Rows) -> Result<Rows> {
+    let rows: Vec<Row> = source.try_collect()?;
+    let locks = rows.iter().map(|row| (table.row_id(row), row.clone())).collect();
+    txn.update(&table.name, locks)?;
+    Ok(Box::new(rows.into_iter().map(Ok)))
 }
@@ -5,11 +5,11 @@
 //!    `sql::engine::Session` in `Server::sql_session`.
 //!
 //! 2. `toySQL` submits a SQL `SELECT` string, which the server executes via
-//!     `Session::execute`.
+//!    `Session::execute`.
 //!
 //! 3. `Session::execute` calls `Parser::parse` to parse the SQL `SELECT` string
-//!     into an `ast::Statement::Select` AST (Abstract Syntax Tree). The parser
-//!     uses the `Lexer` for initial tokenization.
+//!    into an `ast::Statement::Select` AST (Abstract Syntax Tree). The parser
+//!    uses the `Lexer` for initial tokenization.
 //!     
 //! 4. `Session::execute` obtains a new read-only `sql::engine::Transaction` via
 //!    `Session::with_txn`. We'll gloss over the details here.
@@ -75,12 +75,13 @@ pub mod types;
 /// SQL tests are implemented as goldenscripts under src/sql/testscripts.
 #[cfg(test)]
 mod tests {
-    use super::engine::{Catalog as _, Session};
-    use super::parser::Parser;
+    use super::engine::{Catalog as _, Session, Transaction as _};
+    use super::parser::{Lexer, Parser};
     use super::planner::{Plan, OPTIMIZERS};
     use crate::encoding::format::{self, Formatter as _};
-    use crate::sql::engine::{Engine, Local, StatementResult};
+    use crate::sql::engine::{Engine, Local, ResultCache, StatementResult};
     use crate::sql::planner::{Planner, Scope};
+    use crate::sql::types::Value;
     use crate::storage::engine::test as testengine;
     use crate::storage::{self, Engine as _};
 
@@ -90,10 +91,12 @@ mod tests {
     use std::error::Error;
     use std::fmt::Write as _;
     use std::result::Result;
+    use std::time::Duration;
     use test_each_file::test_each_path;
 
     // Run goldenscript tests in src/sql/testscripts.
     test_each_path! { in "src/sql/testscripts/expressions" as expressions => test_goldenscript_expr }
+    test_each_path! { in "src/sql/testscripts/lexer" as lexer => test_goldenscript_lexer }
     test_each_path! { in "src/sql/testscripts/optimizers" as optimizers => test_goldenscript }
     test_each_path! { in "src/sql/testscripts/queries" as queries => test_goldenscript }
     test_each_path! { in "src/sql/testscripts/schema" as schema => test_goldenscript }
@@ -105,6 +108,7 @@ mod tests {
         // The runner's Session can't borrow from an Engine in the same struct,
         // so pass an engine reference. Use both BitCask and Memory engines and
         // mirror operations across them. Emit engine operations to op_rx.
+        // Sessions share a result cache, which shouldn't affect results.
         let (op_tx, op_rx) = crossbeam::channel::unbounded();
         let tempdir = tempfile::TempDir::with_prefix("toydb").expect("tempdir failed");
         let bitcask =
@@ -112,7 +116,9 @@ mod tests {
         let memory = storage::Memory::new();
         let engine =
             Local::new(testengine::Emit::new(testengine::Mirror::new(bitcask, memory), op_tx));
-        let mut runner = SQLRunner::new(&engine, op_rx);
+        let result_cache = ResultCache::new(100);
+        let mut runner =
+            SQLRunner::new(&engine, &result_cache, op_rx, tempdir.path().join("files"));
 
         goldenscript::run(&mut runner, path).expect("goldenscript failed")
     }
@@ -122,19 +128,42 @@ mod tests {
         goldenscript::run(&mut ExpressionRunner, path).expect("goldenscript failed")
     }
 
+    /// Runs lexer goldenscripts.
+    fn test_goldenscript_lexer(path: &std::path::Path) {
+        goldenscript::run(&mut LexerRunner, path).expect("goldenscript failed")
+    }
+
     /// The SQL test runner.
     struct SQLRunner<'a> {
         engine: &'a TestEngine,
+        result_cache: &'a ResultCache,
         sessions: HashMap<String, Session<'a, TestEngine>>,
+        params: HashMap<String, Vec<Value>>,
         op_rx: Receiver<testengine::Operation>,
+        /// A temporary directory for files, substituted for $TEMPDIR.
+        tempdir: std::path::PathBuf,
     }
 
     type TestEngine =
         Local<testengine::Emit<testengine::Mirror<storage::BitCask, storage::Memory>>>;
 
     impl<'a> SQLRunner<'a> {
-        fn new(engine: &'a TestEngine, op_rx: Receiver<testengine::Operation>) -> Self {
-            Self { engine, sessions: HashMap::new(), op_rx }
+        fn new(
+            engine: &'a TestEngine,
+            result_cache: &'a ResultCache,
+            op_rx: Receiver<testengine::Operation>,
+            tempdir: std::path::PathBuf,
+        ) -> Self {
+            // The tempdir is the COPY directory. Copy the CSV test files into it.
+            let csvdir = tempdir.join("csv");
+            std::fs::create_dir_all(&csvdir).expect("tempdir failed");
+            for entry in std::fs::read_dir("src/sql/testscripts/csv").expect("csv dir failed") {
+                let path = entry.expect("csv dir failed").path();
+                let name = path.file_name().expect("no file name");
+                std::fs::copy(&path, csvdir.join(name)).expect("csv copy failed");
+            }
+            let (sessions, params) = (HashMap::new(), HashMap::new());
+            Self { engine, result_cache, sessions, params, op_rx, tempdir }
         }
     }
 
@@ -144,10 +173,24 @@ mod tests {
 
             // Obtain a session based on the command prefix ("" if none).
             let prefix = command.prefix.clone().unwrap_or_default();
-            let session = self.sessions.entry(prefix).or_insert_with(|| self.engine.session());
+            let session = self.sessions.entry(prefix.clone()).or_insert_with(|| {
+                let session = self.engine.session().with_result_cache(self.result_cache);
+                session.with_copy_dir(self.tempdir.clone())
+            });
 
             // Handle runner commands.
             match command.name.as_str() {
+                // cat FILE
+                //
+                // Outputs the contents of a file. $TEMPDIR is substituted.
+                "cat" => {
+                    let mut args = command.consume_args();
+                    let path = args.next_pos().ok_or("file not given")?.value.clone();
+                    args.reject_rest()?;
+                    let path = path.replace("$TEMPDIR", &self.tempdir.to_string_lossy());
+                    return Ok(std::fs::read_to_string(path)?);
+                }
+
                 // dump
                 "dump" => {
                     command.consume_args().reject_rest()?;
@@ -178,6 +221,52 @@ mod tests {
                     return Ok(schemas.into_iter().join("\n"));
                 }
 
+                // result_cache
+                //
+                // Outputs the shared result cache statistics.
+                "result_cache" => {
+                    command.consume_args().reject_rest()?;
+                    writeln!(output, "{:?}", self.result_cache.stats()?)?;
+                    return Ok(output);
+                }
+
+                // stats TABLE...
+                //
+                // Outputs the maintained statistics of the given tables.
+                "stats" => {
+                    let mut args = command.consume_args();
+                    let tables = args.rest_pos().iter().map(|arg| arg.value.clone()).collect_vec();
+                    args.reject_rest()?;
+                    for table in tables {
+                        let stats = session.with_txn(true, |txn| txn.table_stats(&table))?;
+                        writeln!(output, "{table}: {stats:?}")?;
+                    }
+                    return Ok(output);
+                }
+
+                // params [EXPR...]
+                //
+                // Sets query parameter values for the session's next statement.
+                "params" => {
+                    let mut args = command.consume_args();
+                    let exprs = args.rest_pos().iter().map(|arg| arg.value.clone()).collect_vec();
+                    args.reject_rest()?;
+
+                    let params = exprs
+                        .iter()
+                        .map(|expr| {
+                            let ast = Parser::new(expr).parse_expression()?;
+                            session.with_txn(true, |txn| {
+                                Planner::new(txn)
+                                    .build_expression(ast, &Scope::new())?
+                                    .evaluate(None)
+                            })
+                        })
+                        .try_collect()?;
+                    self.params.insert(prefix, params);
+                    return Ok(output);
+                }
+
                 // Otherwise, fall through to SQL execution.
                 _ => {}
             }
@@ -186,14 +275,16 @@ mod tests {
             if !command.args.is_empty() {
                 return Err("SQL statements should be given as a command with no args".into());
             }
-            let input = &command.name;
+            let input = &command.name.replace("$TEMPDIR", &self.tempdir.to_string_lossy());
             let mut tags = command.tags.clone();
+            let params = self.params.remove(&prefix).unwrap_or_default();
 
             // Output the plan if requested.
             if tags.remove("plan") {
                 let ast = Parser::new(input).parse()?;
-                let plan =
-                    session.with_txn(true, |txn| Planner::new(txn).build(ast)?.optimize())?;
+                let plan = session.with_txn(true, |txn| {
+                    Planner::new(txn).build(ast)?.bind(&params)?.optimize()
+                })?;
                 writeln!(output, "{plan}")?;
             }
 
@@ -203,7 +294,8 @@ mod tests {
                     return Err("using both plan and opt is redundant".into());
                 }
                 let ast = Parser::new(input).parse()?;
-                let plan = session.with_txn(true, |txn| Planner::new(txn).build(ast))?;
+                let plan =
+                    session.with_txn(true, |txn| Planner::new(txn).build(ast)?.bind(&params))?;
                 let Plan::Select(mut root) = plan else {
                     return Err("can only use opt with SELECT plans".into());
                 };
@@ -217,8 +309,12 @@ mod tests {
                 }
             }
 
-            // Execute the statement.
-            let result = session.execute(input)?;
+            // Execute the statement, or the whole input as a script of
+            // semicolon-separated statements if requested.
+            let results = match tags.remove("script") {
+                true => session.execute_script(input)?.into_iter().try_collect()?,
+                false => vec![session.execute_with(input, &params)?],
+            };
 
             // Output engine ops if requested.
             if tags.remove("ops") {
@@ -239,18 +335,44 @@ mod tests {
                 }
             }
 
-            // Output the result if requested. SELECT results are always output.
-            match result {
-                StatementResult::Select { columns, rows } => {
-                    if tags.remove("header") {
-                        writeln!(output, "{}", columns.into_iter().join(", "))?;
+            // Output the result if requested. SELECT and EXPLAIN results are
+            // always output.
+            let (show_header, show_result) = (tags.remove("header"), tags.remove("result"));
+            for result in results {
+                match result {
+                    StatementResult::Select { columns, rows } => {
+                        if show_header {
+                            writeln!(output, "{}", columns.into_iter().join(", "))?;
+                        }
+                        for row in rows {
+                            writeln!(output, "{}", row.into_iter().join(", "))?;
+                        }
                     }
-                    for row in rows {
-                        writeln!(output, "{}", row.into_iter().join(", "))?;
+                    StatementResult::Explain { plan, unoptimized, profile } => {
+                        // Zero out execution times, which are nondeterministic.
+                        let plan = match profile {
+                            Some(mut profile) => {
+                                profile.total = Duration::ZERO;
+                                profile
+                                    .nodes
+                                    .iter_mut()
+                                    .flatten()
+                                    .for_each(|n| n.time = Duration::ZERO);
+                                plan.format_profile(&profile)
+                            }
+                            None => plan.to_string(),
+                        };
+                        match unoptimized {
+                            Some(unoptimized) => writeln!(
+                                output,
+                                "Unoptimized:\n{unoptimized}\n\nOptimized:\n{plan}"
+                            )?,
+                            None => writeln!(output, "{plan}")?,
+                        }
                     }
+                    result if show_result => writeln!(output, "{result:?}")?,
+                    _ => {}
                 }
-                result if tags.remove("result") => writeln!(output, "{result:?}")?,
-                _ => {}
             }
 
             // Reject unknown tags.
@@ -258,7 +380,7 @@ mod tests {
                 return Err(format!("unknown tag {tag}").into());
             }
 
-            Ok(output)
+            Ok(output.replace(&*self.tempdir.to_string_lossy(), "$TEMPDIR"))
         }
 
         /// Drain unprocessed operations after each command.
@@ -268,12 +390,28 @@ mod tests {
         }
     }
 
+    /// A test runner for the lexer. Tokenizes the input and outputs the tokens.
+    /// Goldenscript commands can't contain newlines, so \n in the input is
+    /// replaced by a newline.
+    struct LexerRunner;
+
+    impl goldenscript::Runner for LexerRunner {
+        fn run(&mut self, command: &goldenscript::Command) -> Result<String, Box<dyn Error>> {
+            // The entire command is the input to tokenize. There are no args.
+            if !command.args.is_empty() {
+                return Err("input should be given as a command with no args".into());
+            }
+            let input = command.name.replace("\\n", "\n");
+            let tokens: Vec<_> = Lexer::new(&input).try_collect()?;
+            Ok(tokens.into_iter().map(|token| format!("{token:?}\n")).collect())
+        }
+    }
+
     /// A test runner for expressions. Evaluates expressions to values, and
-    /// optionally emits the expression tree.
+    /// optionally emits the expression tree. Uses an empty in-memory engine to
+    /// build the expressions.
     struct ExpressionRunner;
 
-    type Catalog<'a> = <Local<storage::Memory> as Engine<'a>>::Transaction;
-
     impl goldenscript::Runner for ExpressionRunner {
         fn run(&mut self, command: &goldenscript::Command) -> Result<String, Box<dyn Error>> {
             let mut output = String::new();
@@ -291,7 +429,9 @@ mod tests {
             if let Some(next) = parser.lexer.next().transpose()? {
                 return Err(format!("unconsumed token {next}").into());
             }
-            let expr = Planner::<Catalog>::build_expression(ast, &Scope::new())?;
+            let engine = Local::new(storage::Memory::new());
+            let txn = engine.begin_read_only()?;
+            let expr = Planner::new(&txn).build_expression(ast, &Scope::new())?;
 
             // Evaluate the expression.
             let value = expr.evaluate(None)?;
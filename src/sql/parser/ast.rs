@@ -1,4 +1,4 @@
-use crate::sql::types::DataType;
+use crate::sql::types::{DataType, OnDelete};
 
 use std::collections::BTreeMap;
 
@@ -6,7 +6,8 @@ use std::collections::BTreeMap;
 /// the syntactic structure of a SQL query. It is built from a raw SQL string by
 /// the parser, and passed on to the planner which validates it and builds an
 /// execution plan from it.
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[allow(clippy::large_enum_variant)]
 pub enum Statement {
     /// Begin a new transaction.
     Begin { read_only: bool, as_of: Option<u64> },
@@ -14,50 +15,138 @@ pub enum Statement {
     Commit,
     /// Roll back a transaction.
     Rollback,
-    /// Explain a statement.
-    Explain(Box<Statement>),
-    /// Create a new table.
-    CreateTable { name: String, columns: Vec<Column> },
-    /// Drop a table.
-    DropTable { name: String, if_exists: bool },
-    /// Delete matching rows.
-    Delete { table: String, r#where: Option<Expression> },
+    /// Create a savepoint in a transaction.
+    Savepoint(String),
+    /// Roll back a transaction to a savepoint.
+    RollbackToSavepoint(String),
+    /// Release a savepoint in a transaction.
+    ReleaseSavepoint(String),
+    /// Explain a statement. If verbose, also show the unoptimized plan. If
+    /// analyze, execute the query and show per-node execution statistics.
+    Explain { statement: Box<Statement>, verbose: bool, analyze: bool },
+    /// Set a session setting, e.g. statement_timeout.
+    Set { name: String, value: Literal },
+    /// Show the value of a session setting, e.g. statement_timeout.
+    Show(String),
+    /// Create a new table. The primary key is either given as a column
+    /// constraint, or as a table constraint listing the key columns, e.g. for
+    /// composite keys. Unique and check constraints can similarly be given as
+    /// table constraints across multiple columns.
+    CreateTable {
+        name: String,
+        columns: Vec<Column>,
+        primary_key: Option<Vec<String>>,
+        unique: Vec<Vec<String>>,
+        checks: Vec<Expression>,
+        if_not_exists: bool,
+    },
+    /// Create a new table from a query's output columns and rows.
+    CreateTableAs { name: String, query: Box<Statement>, if_not_exists: bool },
+    /// Drop a table. If cascade is true, also drop tables referencing it.
+    DropTable { name: String, if_exists: bool, cascade: bool },
+    /// Alter a table's schema.
+    AlterTable { name: String, action: AlterTable },
+    /// Create a secondary index on a table column. The index name is
+    /// optional, since indexes are identified by their table and column.
+    CreateIndex { name: Option<String>, table: String, column: String },
+    /// Drop a secondary index, either by index name or as table.column (in
+    /// which case table is set and name is the column name).
+    DropIndex { table: Option<String>, name: String, if_exists: bool },
+    /// Delete matching rows. The write statements can return the affected
+    /// rows via a RETURNING clause, like a SELECT clause (empty if none).
+    Delete {
+        table: String,
+        r#where: Option<Expression>,
+        returning: Vec<(Expression, Option<String>)>,
+    },
     /// Insert new rows into a table.
     Insert {
         table: String,
-        columns: Option<Vec<String>>, // columns given in values, using default for rest
-        values: Vec<Vec<Expression>>, // rows to insert
+        columns: Option<Vec<String>>, // columns given in source, using default for rest
+        source: InsertSource,
+        returning: Vec<(Expression, Option<String>)>,
     },
     /// Update rows in a table.
     Update {
         table: String,
         set: BTreeMap<String, Option<Expression>>, // column → value, None for default value
         r#where: Option<Expression>,
+        returning: Vec<(Expression, Option<String>)>,
     },
+    /// Delete all rows in a table.
+    Truncate { table: String },
+    /// Recompute table statistics.
+    Analyze { table: String },
+    /// Import rows into a table from a CSV file.
+    CopyFrom { table: String, path: String },
+    /// Export query results to a CSV or JSON file.
+    CopyTo { query: Box<Statement>, path: String },
     /// Select matching rows.
     Select {
         select: Vec<(Expression, Option<String>)>, // optional column aliases
+        distinct: bool,
         from: Vec<From>,
         r#where: Option<Expression>,
         group_by: Vec<Expression>,
         having: Option<Expression>,
-        order_by: Vec<(Expression, Direction)>,
+        order_by: Vec<(Expression, Direction, Option<Nulls>)>,
+        offset: Option<Expression>,
+        limit: Option<Expression>,
+        for_update: bool, // lock the table rows read by the query
+    },
+    /// A list of rows given as expressions, like a constant table. The rows
+    /// must have the same number of columns.
+    Values {
+        rows: Vec<Vec<Expression>>,
+        order_by: Vec<(Expression, Direction, Option<Nulls>)>,
+        offset: Option<Expression>,
+        limit: Option<Expression>,
+    },
+    /// Combine the rows of two queries (SELECT, VALUES, or UNION). ORDER BY,
+    /// OFFSET, and LIMIT apply to the combined rows.
+    Union {
+        left: Box<Statement>,
+        right: Box<Statement>,
+        all: bool, // if false, discard duplicate rows
+        order_by: Vec<(Expression, Direction, Option<Nulls>)>,
         offset: Option<Expression>,
         limit: Option<Expression>,
     },
 }
 
 /// A FROM item.
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum From {
-    /// A table.
-    Table { name: String, alias: Option<String> },
+    /// A table. The schema is only given for information_schema tables, e.g.
+    /// information_schema.tables.
+    Table { schema: Option<String>, name: String, alias: Option<String> },
+    /// A derived table, i.e. a subquery (SELECT, VALUES, or UNION). The alias
+    /// is mandatory, and qualifies the query's output columns.
+    Query { query: Box<Statement>, alias: String },
     /// A join of two or more tables (may be nested).
     Join { left: Box<From>, right: Box<From>, r#type: JoinType, predicate: Option<Expression> },
 }
 
+/// The rows to insert with INSERT.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum InsertSource {
+    /// A VALUES list of rows, with None for default values.
+    Values(Vec<Vec<Option<Expression>>>),
+    /// A query (e.g. SELECT) whose result rows are inserted.
+    Query(Box<Statement>),
+}
+
+/// An ALTER TABLE action.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum AlterTable {
+    /// Add a new column.
+    AddColumn(Column),
+    /// Drop a column.
+    DropColumn(String),
+}
+
 /// A CREATE TABLE column definition.
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Column {
     pub name: String,
     pub datatype: DataType,
@@ -67,10 +156,12 @@ pub struct Column {
     pub unique: bool,
     pub index: bool,
     pub references: Option<String>,
+    pub on_delete: OnDelete,
+    pub check: Option<Expression>,
 }
 
 /// JOIN types.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum JoinType {
     Cross,
     Inner,
@@ -90,12 +181,20 @@ impl JoinType {
 }
 
 /// ORDER BY direction.
-#[derive(Debug)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Direction {
     Ascending,
     Descending,
 }
 
+/// ORDER BY NULLS FIRST/LAST. Defaults to NULLS LAST for ascending order and
+/// NULLS FIRST for descending order, i.e. NULL is larger than all values.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Nulls {
+    First,
+    Last,
+}
+
 /// Expressions. Can be nested.
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Expression {
@@ -105,10 +204,41 @@ pub enum Expression {
     Column(Option<String>, String),
     /// A literal value.
     Literal(Literal),
-    /// A function call (name and parameters).
-    Function(String, Vec<Expression>),
+    /// An array constructor, e.g. [1, 2, 3].
+    Array(Vec<Expression>),
+    /// A query parameter placeholder, e.g. $1, numbered from 1.
+    Parameter(usize),
+    /// A function call (name and parameters). DISTINCT is only allowed for
+    /// aggregate functions, and only considers distinct argument values.
+    Function { name: String, args: Vec<Expression>, distinct: bool },
+    /// A window function call (name and parameters), evaluated across the
+    /// window of rows given by its OVER clause.
+    Window { function: String, args: Vec<Expression>, over: Box<Window> },
     /// An operator.
     Operator(Operator),
+    /// A CASE expression. If an operand is given, the WHEN values are compared
+    /// with it (simple form), otherwise they are boolean conditions (searched
+    /// form). Yields the THEN value of the first match, or the ELSE value.
+    Case {
+        operand: Option<Box<Expression>>,
+        when: Vec<(Expression, Expression)>,
+        r#else: Option<Box<Expression>>,
+    },
+    /// A scalar subquery, i.e. a SELECT statement that returns a single value.
+    Subquery(Box<Statement>),
+    /// An EXISTS subquery, which checks whether the SELECT statement returns
+    /// any rows. It can reference columns from the outer query.
+    Exists(Box<Statement>),
+    /// An explicit type conversion: CAST(a AS type) or a::type.
+    Cast(Box<Expression>, DataType),
+}
+
+/// A window definition, i.e. an OVER clause. Rows with equal partition_by
+/// values form a partition, ordered by order_by.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Window {
+    pub partition_by: Vec<Expression>,
+    pub order_by: Vec<(Expression, Direction, Option<Nulls>)>,
 }
 
 /// Expression literal values.
@@ -164,13 +294,17 @@ pub enum Operator {
     Not(Box<Expression>),                  // NOT a
     Or(Box<Expression>, Box<Expression>),  // a OR b
 
-    Equal(Box<Expression>, Box<Expression>),       // a = b
-    GreaterThan(Box<Expression>, Box<Expression>), // a > b
-    GreaterThanOrEqual(Box<Expression>, Box<Expression>), // a != b
-    Is(Box<Expression>, Literal),                  // IS NULL or IS NAN
-    LessThan(Box<Expression>, Box<Expression>),    // a < b
-    LessThanOrEqual(Box<Expression>, Box<Expression>), // a <= b
-    NotEqual(Box<Expression>, Box<Expression>),    // a != b
+    Between(Box<Expression>, Box<Expression>, Box<Expression>), // a BETWEEN b AND c
+    Distinct(Box<Expression>, Box<Expression>),                 // a IS DISTINCT FROM b
+    Equal(Box<Expression>, Box<Expression>),                    // a = b
+    GreaterThan(Box<Expression>, Box<Expression>),              // a > b
+    GreaterThanOrEqual(Box<Expression>, Box<Expression>),       // a != b
+    In(Box<Expression>, Vec<Expression>),                       // a IN (b, c)
+    Index(Box<Expression>, Box<Expression>),                    // a[b]
+    Is(Box<Expression>, Literal),                               // IS NULL/NAN/TRUE/FALSE
+    LessThan(Box<Expression>, Box<Expression>),                 // a < b
+    LessThanOrEqual(Box<Expression>, Box<Expression>),          // a <= b
+    NotEqual(Box<Expression>, Box<Expression>),                 // a != b
 
     Add(Box<Expression>, Box<Expression>),          // a + b
     Divide(Box<Expression>, Box<Expression>),       // a / b
@@ -182,7 +316,13 @@ pub enum Operator {
     Remainder(Box<Expression>, Box<Expression>),    // a % b
     Subtract(Box<Expression>, Box<Expression>),     // a - b
 
-    Like(Box<Expression>, Box<Expression>), // a LIKE b
+    Concat(Box<Expression>, Box<Expression>), // a || b
+    ILike(Box<Expression>, Box<Expression>),  // a ILIKE b
+    Like(Box<Expression>, Box<Expression>),   // a LIKE b
+    Regex(Box<Expression>, Box<Expression>),  // a ~ b
+
+    JsonExtract(Box<Expression>, Box<Expression>), // a -> b
+    JsonExtractText(Box<Expression>, Box<Expression>), // a ->> b
 }
 
 impl Expression {
@@ -196,17 +336,24 @@ impl Expression {
         match self {
             Self::Operator(Add(lhs, rhs))
             | Self::Operator(And(lhs, rhs))
+            | Self::Operator(Concat(lhs, rhs))
+            | Self::Operator(Distinct(lhs, rhs))
             | Self::Operator(Divide(lhs, rhs))
             | Self::Operator(Equal(lhs, rhs))
             | Self::Operator(Exponentiate(lhs, rhs))
             | Self::Operator(GreaterThan(lhs, rhs))
             | Self::Operator(GreaterThanOrEqual(lhs, rhs))
+            | Self::Operator(ILike(lhs, rhs))
+            | Self::Operator(Index(lhs, rhs))
+            | Self::Operator(JsonExtract(lhs, rhs))
+            | Self::Operator(JsonExtractText(lhs, rhs))
             | Self::Operator(LessThan(lhs, rhs))
             | Self::Operator(LessThanOrEqual(lhs, rhs))
             | Self::Operator(Like(lhs, rhs))
             | Self::Operator(Multiply(lhs, rhs))
             | Self::Operator(NotEqual(lhs, rhs))
             | Self::Operator(Or(lhs, rhs))
+            | Self::Operator(Regex(lhs, rhs))
             | Self::Operator(Remainder(lhs, rhs))
             | Self::Operator(Subtract(lhs, rhs)) => lhs.walk(visitor) && rhs.walk(visitor),
 
@@ -214,11 +361,40 @@ impl Expression {
             | Self::Operator(Identity(expr))
             | Self::Operator(Is(expr, _))
             | Self::Operator(Negate(expr))
-            | Self::Operator(Not(expr)) => expr.walk(visitor),
+            | Self::Operator(Not(expr))
+            | Self::Cast(expr, _) => expr.walk(visitor),
+
+            Self::Operator(Between(expr, low, high)) => {
+                expr.walk(visitor) && low.walk(visitor) && high.walk(visitor)
+            }
+
+            Self::Operator(In(expr, list)) => {
+                expr.walk(visitor) && list.iter().all(|expr| expr.walk(visitor))
+            }
 
-            Self::Function(_, exprs) => exprs.iter().any(|expr| expr.walk(visitor)),
+            Self::Array(items) | Self::Function { args: items, .. } => {
+                items.iter().all(|expr| expr.walk(visitor))
+            }
+
+            Self::Window { args, over, .. } => {
+                args.iter().all(|expr| expr.walk(visitor))
+                    && over.partition_by.iter().all(|expr| expr.walk(visitor))
+                    && over.order_by.iter().all(|(expr, _, _)| expr.walk(visitor))
+            }
 
-            Self::All | Self::Column(_, _) | Self::Literal(_) => true,
+            Self::Case { operand, when, r#else } => {
+                operand.as_ref().is_none_or(|expr| expr.walk(visitor))
+                    && when.iter().all(|(when, then)| when.walk(visitor) && then.walk(visitor))
+                    && r#else.as_ref().is_none_or(|expr| expr.walk(visitor))
+            }
+
+            // Subqueries have their own scope, so we don't descend into them.
+            Self::All
+            | Self::Column(_, _)
+            | Self::Exists(_)
+            | Self::Literal(_)
+            | Self::Parameter(_)
+            | Self::Subquery(_) => true,
         }
     }
 
@@ -239,17 +415,24 @@ impl Expression {
         match self {
             Self::Operator(Add(lhs, rhs))
             | Self::Operator(And(lhs, rhs))
+            | Self::Operator(Concat(lhs, rhs))
+            | Self::Operator(Distinct(lhs, rhs))
             | Self::Operator(Divide(lhs, rhs))
             | Self::Operator(Equal(lhs, rhs))
             | Self::Operator(Exponentiate(lhs, rhs))
             | Self::Operator(GreaterThan(lhs, rhs))
             | Self::Operator(GreaterThanOrEqual(lhs, rhs))
+            | Self::Operator(ILike(lhs, rhs))
+            | Self::Operator(Index(lhs, rhs))
+            | Self::Operator(JsonExtract(lhs, rhs))
+            | Self::Operator(JsonExtractText(lhs, rhs))
             | Self::Operator(LessThan(lhs, rhs))
             | Self::Operator(LessThanOrEqual(lhs, rhs))
             | Self::Operator(Like(lhs, rhs))
             | Self::Operator(Multiply(lhs, rhs))
             | Self::Operator(NotEqual(lhs, rhs))
             | Self::Operator(Or(lhs, rhs))
+            | Self::Operator(Regex(lhs, rhs))
             | Self::Operator(Remainder(lhs, rhs))
             | Self::Operator(Subtract(lhs, rhs)) => {
                 lhs.collect(visitor, c);
@@ -260,11 +443,45 @@ impl Expression {
             | Self::Operator(Identity(expr))
             | Self::Operator(Is(expr, _))
             | Self::Operator(Negate(expr))
-            | Self::Operator(Not(expr)) => expr.collect(visitor, c),
+            | Self::Operator(Not(expr))
+            | Self::Cast(expr, _) => expr.collect(visitor, c),
+
+            Self::Operator(Between(expr, low, high)) => {
+                expr.collect(visitor, c);
+                low.collect(visitor, c);
+                high.collect(visitor, c);
+            }
+
+            Self::Operator(In(expr, list)) => {
+                expr.collect(visitor, c);
+                list.iter().for_each(|expr| expr.collect(visitor, c));
+            }
 
-            Self::Function(_, exprs) => exprs.iter().for_each(|expr| expr.collect(visitor, c)),
+            Self::Array(items) | Self::Function { args: items, .. } => {
+                items.iter().for_each(|expr| expr.collect(visitor, c))
+            }
+
+            Self::Window { args, over, .. } => {
+                args.iter().for_each(|expr| expr.collect(visitor, c));
+                over.partition_by.iter().for_each(|expr| expr.collect(visitor, c));
+                over.order_by.iter().for_each(|(expr, _, _)| expr.collect(visitor, c));
+            }
+
+            Self::Case { operand, when, r#else } => {
+                operand.iter().for_each(|expr| expr.collect(visitor, c));
+                for (when, then) in when {
+                    when.collect(visitor, c);
+                    then.collect(visitor, c);
+                }
+                r#else.iter().for_each(|expr| expr.collect(visitor, c));
+            }
 
-            Self::All | Self::Column(_, _) | Self::Literal(_) => {}
+            Self::All
+            | Self::Column(_, _)
+            | Self::Exists(_)
+            | Self::Literal(_)
+            | Self::Parameter(_)
+            | Self::Subquery(_) => {}
         }
     }
 }
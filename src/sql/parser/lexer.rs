@@ -4,7 +4,9 @@ use crate::error::Result;
 /// The lexer (lexical analyzer) preprocesses raw SQL strings into a sequence of
 /// lexical tokens (e.g. keyword, number, string, etc), which are passed on to
 /// the SQL parser. In doing so, it strips away basic syntactic noise such as
-/// whitespace, case, and quotes, and performs initial symbol validation.
+/// whitespace, comments, case, and quotes, and performs initial symbol
+/// validation.
+#[derive(Clone)]
 pub struct Lexer<'a> {
     chars: std::iter::Peekable<std::str::Chars<'a>>,
 }
@@ -25,6 +27,8 @@ pub enum Token {
     Ident(String),
     /// A SQL keyword.
     Keyword(Keyword),
+    /// A query parameter placeholder, e.g. $1, numbered from 1.
+    Parameter(usize),
     Period,             // .
     Equal,              // =
     NotEqual,           // !=
@@ -33,18 +37,27 @@ pub enum Token {
     LessThan,           // <
     LessThanOrEqual,    // <=
     LessOrGreaterThan,  // <>
+    NullSafeEqual,      // <=>
+    Arrow,              // ->
+    DoubleArrow,        // ->>
     Plus,               // +
     Minus,              // -
     Asterisk,           // *
     Slash,              // /
     Caret,              // ^
     Percent,            // %
+    Concat,             // ||
+    DoubleColon,        // ::
     Exclamation,        // !
+    ExclamationTilde,   // !~
     Question,           // ?
+    Tilde,              // ~
     Comma,              // ,
     Semicolon,          // ;
     OpenParen,          // (
     CloseParen,         // )
+    OpenBracket,        // [
+    CloseBracket,       // ]
 }
 
 impl std::fmt::Display for Token {
@@ -54,6 +67,7 @@ impl std::fmt::Display for Token {
             Self::String(s) => s,
             Self::Ident(s) => s,
             Self::Keyword(k) => return k.fmt(f),
+            Self::Parameter(i) => return write!(f, "${i}"),
             Self::Period => ".",
             Self::Equal => "=",
             Self::NotEqual => "!=",
@@ -62,18 +76,27 @@ impl std::fmt::Display for Token {
             Self::LessThan => "<",
             Self::LessThanOrEqual => "<=",
             Self::LessOrGreaterThan => "<>",
+            Self::NullSafeEqual => "<=>",
+            Self::Arrow => "->",
+            Self::DoubleArrow => "->>",
             Self::Plus => "+",
             Self::Minus => "-",
             Self::Asterisk => "*",
             Self::Slash => "/",
             Self::Caret => "^",
             Self::Percent => "%",
+            Self::Concat => "||",
+            Self::DoubleColon => "::",
             Self::Exclamation => "!",
+            Self::ExclamationTilde => "!~",
             Self::Question => "?",
+            Self::Tilde => "~",
             Self::Comma => ",",
             Self::Semicolon => ";",
             Self::OpenParen => "(",
             Self::CloseParen => ")",
+            Self::OpenBracket => "[",
+            Self::CloseBracket => "]",
         })
     }
 }
@@ -87,29 +110,49 @@ impl From<Keyword> for Token {
 /// Reserved SQL keywords.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Keyword {
+    Add,
+    All,
+    Alter,
+    Analyze,
     And,
     As,
     Asc,
     Begin,
+    Between,
     Bool,
     Boolean,
     By,
+    Cascade,
+    Case,
+    Cast,
+    Check,
+    Column,
     Commit,
+    Copy,
     Create,
     Cross,
+    Date,
+    Decimal,
     Default,
     Delete,
     Desc,
+    Distinct,
     Double,
     Drop,
+    Else,
+    End,
     Exists,
     Explain,
     False,
+    First,
     Float,
+    For,
     From,
     Group,
     Having,
     If,
+    ILike,
+    In,
     Index,
     Infinity,
     Inner,
@@ -119,13 +162,17 @@ pub enum Keyword {
     Into,
     Is,
     Join,
+    Json,
     Key,
+    Last,
     Left,
     Like,
     Limit,
     NaN,
     Not,
     Null,
+    Nulls,
+    Numeric,
     Of,
     Offset,
     On,
@@ -133,24 +180,38 @@ pub enum Keyword {
     Or,
     Order,
     Outer,
+    Over,
+    Partition,
     Primary,
     Read,
     References,
+    Release,
+    Restrict,
+    Returning,
     Right,
     Rollback,
+    Savepoint,
     Select,
     Set,
+    Show,
     String,
     System,
     Table,
     Text,
+    Then,
     Time,
+    Timestamp,
+    To,
     Transaction,
     True,
+    Truncate,
+    Union,
     Unique,
     Update,
     Values,
     Varchar,
+    Verbose,
+    When,
     Where,
     Write,
 }
@@ -164,29 +225,49 @@ impl TryFrom<&str> for Keyword {
         // allocating a string to change the case. Assert this.
         debug_assert!(value.chars().all(|c| !c.is_uppercase()), "keyword must be lowercase");
         Ok(match value {
+            "add" => Self::Add,
+            "all" => Self::All,
+            "alter" => Self::Alter,
+            "analyze" => Self::Analyze,
+            "and" => Self::And,
             "as" => Self::As,
             "asc" => Self::Asc,
-            "and" => Self::And,
             "begin" => Self::Begin,
+            "between" => Self::Between,
             "bool" => Self::Bool,
             "boolean" => Self::Boolean,
             "by" => Self::By,
+            "cascade" => Self::Cascade,
+            "case" => Self::Case,
+            "cast" => Self::Cast,
+            "check" => Self::Check,
+            "column" => Self::Column,
             "commit" => Self::Commit,
+            "copy" => Self::Copy,
             "create" => Self::Create,
             "cross" => Self::Cross,
+            "date" => Self::Date,
+            "decimal" => Self::Decimal,
             "default" => Self::Default,
             "delete" => Self::Delete,
             "desc" => Self::Desc,
+            "distinct" => Self::Distinct,
             "double" => Self::Double,
             "drop" => Self::Drop,
+            "else" => Self::Else,
+            "end" => Self::End,
             "exists" => Self::Exists,
             "explain" => Self::Explain,
             "false" => Self::False,
+            "first" => Self::First,
             "float" => Self::Float,
+            "for" => Self::For,
             "from" => Self::From,
             "group" => Self::Group,
             "having" => Self::Having,
             "if" => Self::If,
+            "ilike" => Self::ILike,
+            "in" => Self::In,
             "index" => Self::Index,
             "infinity" => Self::Infinity,
             "inner" => Self::Inner,
@@ -196,13 +277,17 @@ impl TryFrom<&str> for Keyword {
             "into" => Self::Into,
             "is" => Self::Is,
             "join" => Self::Join,
+            "json" => Self::Json,
             "key" => Self::Key,
+            "last" => Self::Last,
             "left" => Self::Left,
             "like" => Self::Like,
             "limit" => Self::Limit,
             "nan" => Self::NaN,
             "not" => Self::Not,
             "null" => Self::Null,
+            "nulls" => Self::Nulls,
+            "numeric" => Self::Numeric,
             "of" => Self::Of,
             "offset" => Self::Offset,
             "on" => Self::On,
@@ -210,24 +295,38 @@ impl TryFrom<&str> for Keyword {
             "or" => Self::Or,
             "order" => Self::Order,
             "outer" => Self::Outer,
+            "over" => Self::Over,
+            "partition" => Self::Partition,
             "primary" => Self::Primary,
             "read" => Self::Read,
             "references" => Self::References,
+            "release" => Self::Release,
+            "restrict" => Self::Restrict,
+            "returning" => Self::Returning,
             "right" => Self::Right,
             "rollback" => Self::Rollback,
+            "savepoint" => Self::Savepoint,
             "select" => Self::Select,
             "set" => Self::Set,
+            "show" => Self::Show,
             "string" => Self::String,
             "system" => Self::System,
             "table" => Self::Table,
             "text" => Self::Text,
+            "then" => Self::Then,
             "time" => Self::Time,
+            "timestamp" => Self::Timestamp,
+            "to" => Self::To,
             "transaction" => Self::Transaction,
             "true" => Self::True,
+            "truncate" => Self::Truncate,
+            "union" => Self::Union,
             "unique" => Self::Unique,
             "update" => Self::Update,
             "values" => Self::Values,
             "varchar" => Self::Varchar,
+            "verbose" => Self::Verbose,
+            "when" => Self::When,
             "where" => Self::Where,
             "write" => Self::Write,
             _ => return Err("not a keyword"),
@@ -239,29 +338,49 @@ impl std::fmt::Display for Keyword {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         // Display keywords as uppercase.
         f.write_str(match self {
+            Self::Add => "ADD",
+            Self::All => "ALL",
+            Self::Alter => "ALTER",
+            Self::Analyze => "ANALYZE",
+            Self::And => "AND",
             Self::As => "AS",
             Self::Asc => "ASC",
-            Self::And => "AND",
             Self::Begin => "BEGIN",
+            Self::Between => "BETWEEN",
             Self::Bool => "BOOL",
             Self::Boolean => "BOOLEAN",
             Self::By => "BY",
+            Self::Cascade => "CASCADE",
+            Self::Case => "CASE",
+            Self::Cast => "CAST",
+            Self::Check => "CHECK",
+            Self::Column => "COLUMN",
             Self::Commit => "COMMIT",
+            Self::Copy => "COPY",
             Self::Create => "CREATE",
             Self::Cross => "CROSS",
+            Self::Date => "DATE",
+            Self::Decimal => "DECIMAL",
             Self::Default => "DEFAULT",
             Self::Delete => "DELETE",
             Self::Desc => "DESC",
+            Self::Distinct => "DISTINCT",
             Self::Double => "DOUBLE",
             Self::Drop => "DROP",
+            Self::Else => "ELSE",
+            Self::End => "END",
             Self::Exists => "EXISTS",
             Self::Explain => "EXPLAIN",
             Self::False => "FALSE",
+            Self::First => "FIRST",
             Self::Float => "FLOAT",
+            Self::For => "FOR",
             Self::From => "FROM",
             Self::Group => "GROUP",
             Self::Having => "HAVING",
             Self::If => "IF",
+            Self::ILike => "ILIKE",
+            Self::In => "IN",
             Self::Index => "INDEX",
             Self::Infinity => "INFINITY",
             Self::Inner => "INNER",
@@ -271,13 +390,17 @@ impl std::fmt::Display for Keyword {
             Self::Into => "INTO",
             Self::Is => "IS",
             Self::Join => "JOIN",
+            Self::Json => "JSON",
             Self::Key => "KEY",
+            Self::Last => "LAST",
             Self::Left => "LEFT",
             Self::Like => "LIKE",
             Self::Limit => "LIMIT",
             Self::NaN => "NAN",
             Self::Not => "NOT",
             Self::Null => "NULL",
+            Self::Nulls => "NULLS",
+            Self::Numeric => "NUMERIC",
             Self::Of => "OF",
             Self::Offset => "OFFSET",
             Self::On => "ON",
@@ -285,24 +408,38 @@ impl std::fmt::Display for Keyword {
             Self::Outer => "OUTER",
             Self::Or => "OR",
             Self::Order => "ORDER",
+            Self::Over => "OVER",
+            Self::Partition => "PARTITION",
             Self::Primary => "PRIMARY",
             Self::Read => "READ",
             Self::References => "REFERENCES",
+            Self::Release => "RELEASE",
+            Self::Restrict => "RESTRICT",
+            Self::Returning => "RETURNING",
             Self::Right => "RIGHT",
             Self::Rollback => "ROLLBACK",
+            Self::Savepoint => "SAVEPOINT",
             Self::Select => "SELECT",
             Self::Set => "SET",
+            Self::Show => "SHOW",
             Self::String => "STRING",
             Self::System => "SYSTEM",
             Self::Table => "TABLE",
             Self::Text => "TEXT",
+            Self::Then => "THEN",
             Self::Time => "TIME",
+            Self::Timestamp => "TIMESTAMP",
+            Self::To => "TO",
             Self::Transaction => "TRANSACTION",
             Self::True => "TRUE",
+            Self::Truncate => "TRUNCATE",
+            Self::Union => "UNION",
             Self::Unique => "UNIQUE",
             Self::Update => "UPDATE",
             Self::Values => "VALUES",
             Self::Varchar => "VARCHAR",
+            Self::Verbose => "VERBOSE",
+            Self::When => "WHEN",
             Self::Where => "WHERE",
             Self::Write => "WRITE",
         })
@@ -349,16 +486,28 @@ impl<'a> Lexer<'a> {
         self.next_if(|n| n == c).is_some()
     }
 
+    /// Returns true if the next characters are the given string, consuming
+    /// them.
+    fn next_are(&mut self, s: &str) -> bool {
+        let mut lookahead = self.chars.clone();
+        if !s.chars().all(|c| lookahead.next() == Some(c)) {
+            return false;
+        }
+        self.chars = lookahead;
+        true
+    }
+
     /// Scans the next token, if any.
     fn scan(&mut self) -> Result<Option<Token>> {
-        // Ignore whitespace.
-        self.skip_whitespace();
+        // Ignore whitespace and comments.
+        self.skip_whitespace_and_comments()?;
         // The first character tells us the token type.
         match self.chars.peek() {
             Some('\'') => self.scan_string(),
             Some('"') => self.scan_ident_quoted(),
-            Some(c) if c.is_ascii_digit() => Ok(self.scan_number()),
+            Some(c) if c.is_ascii_digit() => self.scan_number(),
             Some(c) if c.is_alphabetic() => Ok(self.scan_ident_or_keyword()),
+            Some('$') => self.scan_parameter(),
             Some(_) => Ok(self.scan_symbol()),
             None => Ok(None),
         }
@@ -394,13 +543,37 @@ impl<'a> Lexer<'a> {
                 None => return errinput!("unexpected end of quoted identifier"),
             }
         }
+        if ident.is_empty() {
+            return errinput!("empty quoted identifier");
+        }
         Ok(Some(Token::Ident(ident)))
     }
 
-    /// Scans the next number, if any.
-    fn scan_number(&mut self) -> Option<Token> {
+    /// Scans the next number, if any. This is either a decimal number with an
+    /// optional fractional part and exponent, e.g. 1.5e10, or a hexadecimal
+    /// integer with a 0x prefix, e.g. 0xFF. Hexadecimal integers are converted
+    /// to decimal. A number can't be immediately followed by letters, digits,
+    /// underscores, or periods, e.g. 1.2.3 or 0xZZ.
+    fn scan_number(&mut self) -> Result<Option<Token>> {
         // Scan the integer part. There must be one digit.
-        let mut number = self.next_if(|c| c.is_ascii_digit())?.to_string();
+        let Some(first) = self.next_if(|c| c.is_ascii_digit()) else {
+            return Ok(None);
+        };
+        let mut number = first.to_string();
+        // Scan a hexadecimal integer, if any.
+        if first == '0' {
+            if let Some(x) = self.next_if(|c| c == 'x' || c == 'X') {
+                number.push(x);
+                while let Some(c) = self.next_if(|c| c.is_ascii_hexdigit()) {
+                    number.push(c)
+                }
+                let trailing = self.scan_number_trailing(&mut number);
+                return match i64::from_str_radix(&number[2..], 16) {
+                    Ok(n) if !trailing => Ok(Some(Token::Number(n.to_string()))),
+                    Ok(_) | Err(_) => errinput!("invalid hexadecimal number {number}"),
+                };
+            }
+        }
         while let Some(c) = self.next_if(|c| c.is_ascii_digit()) {
             number.push(c)
         }
@@ -411,17 +584,52 @@ impl<'a> Lexer<'a> {
                 number.push(dec)
             }
         }
-        // Scan the exponent, if any.
+        // Scan the exponent, if any. It must have at least one digit.
         if let Some(exp) = self.next_if(|c| c == 'e' || c == 'E') {
             number.push(exp);
             if let Some(sign) = self.next_if(|c| c == '+' || c == '-') {
                 number.push(sign)
             }
+            if !self.chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+                self.scan_number_trailing(&mut number);
+                return errinput!("invalid number {number}");
+            }
             while let Some(c) = self.next_if(|c| c.is_ascii_digit()) {
                 number.push(c)
             }
         }
-        Some(Token::Number(number))
+        if self.scan_number_trailing(&mut number) {
+            return errinput!("invalid number {number}");
+        }
+        Ok(Some(Token::Number(number)))
+    }
+
+    /// Scans any letters, digits, underscores, or periods immediately following
+    /// a number, appending them to it for error messages. Returns true if any
+    /// were found, in which case the number is invalid.
+    fn scan_number_trailing(&mut self, number: &mut String) -> bool {
+        let mut trailing = false;
+        while let Some(c) = self.next_if(|c| c.is_alphanumeric() || c == '_' || c == '.') {
+            number.push(c);
+            trailing = true;
+        }
+        trailing
+    }
+
+    /// Scans the next query parameter, if any. It's given as $ followed by the
+    /// parameter number, starting at 1.
+    fn scan_parameter(&mut self) -> Result<Option<Token>> {
+        if !self.next_is('$') {
+            return Ok(None);
+        }
+        let mut number = String::new();
+        while let Some(c) = self.next_if(|c| c.is_ascii_digit()) {
+            number.push(c)
+        }
+        match number.parse() {
+            Ok(0) | Err(_) => errinput!("invalid parameter ${number}"),
+            Ok(index) => Ok(Some(Token::Parameter(index))),
+        }
     }
 
     /// Scans the next quoted string literal, if any.
@@ -444,6 +652,14 @@ impl<'a> Lexer<'a> {
 
     /// Scans the next symbol token, if any.
     fn scan_symbol(&mut self) -> Option<Token> {
+        // || and :: are the only symbols without a single-character prefix
+        // token.
+        if self.next_are("||") {
+            return Some(Token::Concat);
+        }
+        if self.next_are("::") {
+            return Some(Token::DoubleColon);
+        }
         let mut token = self.next_if_map(|c| {
             Some(match c {
                 '.' => Token::Period,
@@ -458,21 +674,33 @@ impl<'a> Lexer<'a> {
                 '%' => Token::Percent,
                 '!' => Token::Exclamation,
                 '?' => Token::Question,
+                '~' => Token::Tilde,
                 ',' => Token::Comma,
                 ';' => Token::Semicolon,
                 '(' => Token::OpenParen,
                 ')' => Token::CloseParen,
+                '[' => Token::OpenBracket,
+                ']' => Token::CloseBracket,
                 _ => return None,
             })
         })?;
         // Handle two-character tokens, e.g. !=.
         token = match token {
             Token::Exclamation if self.next_is('=') => Token::NotEqual,
+            Token::Exclamation if self.next_is('~') => Token::ExclamationTilde,
             Token::GreaterThan if self.next_is('=') => Token::GreaterThanOrEqual,
             Token::LessThan if self.next_is('>') => Token::LessOrGreaterThan,
             Token::LessThan if self.next_is('=') => Token::LessThanOrEqual,
+            Token::Minus if self.next_is('>') => Token::Arrow,
             token => token,
         };
+        // Handle the three-character tokens <=> and ->>.
+        if token == Token::LessThanOrEqual && self.next_is('>') {
+            token = Token::NullSafeEqual;
+        }
+        if token == Token::Arrow && self.next_is('>') {
+            token = Token::DoubleArrow;
+        }
         Some(token)
     }
 
@@ -480,6 +708,32 @@ impl<'a> Lexer<'a> {
     fn skip_whitespace(&mut self) {
         while self.next_if(|c| c.is_whitespace()).is_some() {}
     }
+
+    /// Skips any whitespace and comments. Line comments start with -- and run
+    /// to the end of the line. Block comments are enclosed in /* */ and may
+    /// span multiple lines. Like in PostgreSQL, block comments can be nested,
+    /// so /* a /* b */ c */ is a single comment.
+    fn skip_whitespace_and_comments(&mut self) -> Result<()> {
+        loop {
+            self.skip_whitespace();
+            if self.next_are("--") {
+                while self.next_if(|c| c != '\n').is_some() {}
+            } else if self.next_are("/*") {
+                let mut depth = 1;
+                while depth > 0 {
+                    if self.next_are("/*") {
+                        depth += 1;
+                    } else if self.next_are("*/") {
+                        depth -= 1;
+                    } else if self.chars.next().is_none() {
+                        return errinput!("unterminated block comment");
+                    }
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
 }
 
 /// Returns true if the entire given string is a single valid identifier.
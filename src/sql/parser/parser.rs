@@ -3,7 +3,7 @@
 use super::{ast, Keyword, Lexer, Token};
 use crate::errinput;
 use crate::error::Result;
-use crate::sql::types::DataType;
+use crate::sql::types::{DataType, ElementType, OnDelete, DECIMAL_MAX_PRECISION};
 
 /// The SQL parser takes tokens from the lexer and parses the SQL syntax into an
 /// Abstract Syntax Tree (AST). This nested structure represents the syntactic
@@ -17,7 +17,7 @@ pub struct Parser<'a> {
 
 impl<'a> Parser<'a> {
     /// Creates a new parser for the given raw SQL string.
-    pub fn new(statement: &str) -> Parser {
+    pub fn new(statement: &str) -> Parser<'_> {
         Parser { lexer: Lexer::new(statement).peekable() }
     }
 
@@ -32,6 +32,25 @@ impl<'a> Parser<'a> {
         Ok(statement)
     }
 
+    /// Parses the input string into a script of AST statements separated by
+    /// semicolons. Empty statements (i.e. repeated semicolons) and a trailing
+    /// semicolon are ignored, so an empty script yields no statements.
+    pub fn parse_statements(&mut self) -> Result<Vec<ast::Statement>> {
+        let mut statements = Vec::new();
+        loop {
+            while self.next_is(Token::Semicolon) {}
+            if self.peek()?.is_none() {
+                return Ok(statements);
+            }
+            statements.push(self.parse_statement()?);
+            if !self.next_is(Token::Semicolon) {
+                if let Some(token) = self.lexer.next().transpose()? {
+                    return errinput!("unexpected token {token}");
+                }
+            }
+        }
+    }
+
     /// Fetches the next lexer token, or errors if none is found.
     fn next(&mut self) -> Result<Token> {
         self.lexer.next().transpose()?.ok_or_else(|| errinput!("unexpected end of input"))
@@ -94,6 +113,14 @@ impl<'a> Parser<'a> {
         self.lexer.peek().map(|r| r.as_ref().map_err(|err| err.clone())).transpose()
     }
 
+    /// Peeks the lexer token after the next one, if any. This clones the lexer,
+    /// so it should only be used when a single token of lookahead isn't enough.
+    fn peek_second(&self) -> Result<Option<Token>> {
+        let mut lexer = self.lexer.clone();
+        lexer.next().transpose()?;
+        lexer.next().transpose()
+    }
+
     /// Parses a SQL statement.
     fn parse_statement(&mut self) -> Result<ast::Statement> {
         let Some(token) = self.peek()? else {
@@ -103,30 +130,53 @@ impl<'a> Parser<'a> {
             Token::Keyword(Keyword::Begin) => self.parse_begin(),
             Token::Keyword(Keyword::Commit) => self.parse_commit(),
             Token::Keyword(Keyword::Rollback) => self.parse_rollback(),
+            Token::Keyword(Keyword::Savepoint) => self.parse_savepoint(),
+            Token::Keyword(Keyword::Release) => self.parse_release(),
             Token::Keyword(Keyword::Explain) => self.parse_explain(),
-
-            Token::Keyword(Keyword::Create) => self.parse_create_table(),
-            Token::Keyword(Keyword::Drop) => self.parse_drop_table(),
-
+            Token::Keyword(Keyword::Set) => self.parse_set(),
+            Token::Keyword(Keyword::Show) => self.parse_show(),
+
+            Token::Keyword(Keyword::Alter) => self.parse_alter_table(),
+            Token::Keyword(Keyword::Analyze) => self.parse_analyze(),
+            Token::Keyword(Keyword::Create) => match self.peek_second()? {
+                Some(Token::Keyword(Keyword::Index)) => self.parse_create_index(),
+                _ => self.parse_create_table(),
+            },
+            Token::Keyword(Keyword::Drop) => match self.peek_second()? {
+                Some(Token::Keyword(Keyword::Index)) => self.parse_drop_index(),
+                _ => self.parse_drop_table(),
+            },
+
+            Token::Keyword(Keyword::Copy) => self.parse_copy(),
             Token::Keyword(Keyword::Delete) => self.parse_delete(),
             Token::Keyword(Keyword::Insert) => self.parse_insert(),
             Token::Keyword(Keyword::Select) => self.parse_select(),
+            Token::Keyword(Keyword::Values) => self.parse_select(),
+            Token::Keyword(Keyword::Truncate) => self.parse_truncate(),
             Token::Keyword(Keyword::Update) => self.parse_update(),
 
             token => errinput!("unexpected token {token}"),
         }
     }
 
+    /// Parses an ANALYZE statement. The TABLE keyword is optional.
+    fn parse_analyze(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Analyze.into())?;
+        self.next_is(Keyword::Table.into());
+        let table = self.next_ident()?;
+        Ok(ast::Statement::Analyze { table })
+    }
+
     /// Parses a BEGIN statement.
     fn parse_begin(&mut self) -> Result<ast::Statement> {
         self.expect(Keyword::Begin.into())?;
         self.skip(Keyword::Transaction.into());
 
-        let mut read_only = false;
+        let mut read_only = None;
         if self.next_is(Keyword::Read.into()) {
             match self.next()? {
-                Token::Keyword(Keyword::Only) => read_only = true,
-                Token::Keyword(Keyword::Write) => {}
+                Token::Keyword(Keyword::Only) => read_only = Some(true),
+                Token::Keyword(Keyword::Write) => read_only = Some(false),
                 token => return errinput!("unexpected token {token}"),
             }
         }
@@ -141,57 +191,227 @@ impl<'a> Parser<'a> {
                 token => return errinput!("unexpected token {token}, wanted number"),
             }
         }
+        // AS OF transactions are implicitly read-only.
+        let read_only = read_only.unwrap_or(as_of.is_some());
         Ok(ast::Statement::Begin { read_only, as_of })
     }
 
+    /// Parses a datatype name, e.g. in a column definition or CAST.
+    fn parse_datatype(&mut self) -> Result<DataType> {
+        let datatype = match self.next()? {
+            Token::Keyword(Keyword::Bool | Keyword::Boolean) => DataType::Boolean,
+            Token::Keyword(Keyword::Float | Keyword::Double) => DataType::Float,
+            Token::Keyword(Keyword::Int | Keyword::Integer) => DataType::Integer,
+            Token::Keyword(Keyword::Json) => DataType::Json,
+            Token::Keyword(Keyword::String | Keyword::Text | Keyword::Varchar) => DataType::String,
+            Token::Keyword(Keyword::Date | Keyword::Timestamp) => DataType::Timestamp,
+            Token::Keyword(Keyword::Decimal | Keyword::Numeric) => self.parse_decimal_type()?,
+            Token::Ident(name) => return errinput!("unknown datatype {name}"),
+            token => return errinput!("unexpected token {token}"),
+        };
+        self.parse_array_type(datatype)
+    }
+
+    /// Parses an optional [] suffix of a datatype, e.g. INTEGER[], returning
+    /// an array of the given element type.
+    fn parse_array_type(&mut self, datatype: DataType) -> Result<DataType> {
+        if !self.next_is(Token::OpenBracket) {
+            return Ok(datatype);
+        }
+        self.expect(Token::CloseBracket)?;
+        Ok(DataType::Array(Some(ElementType::try_from(datatype)?)))
+    }
+
+    /// Parses the optional (precision, scale) of a DECIMAL datatype, e.g.
+    /// DECIMAL(10,2). The precision defaults to the maximum, and the scale to 0.
+    fn parse_decimal_type(&mut self) -> Result<DataType> {
+        let (mut precision, mut scale) = (DECIMAL_MAX_PRECISION, 0);
+        if self.next_is(Token::OpenParen) {
+            let next_number = |parser: &mut Self| match parser.next()? {
+                Token::Number(n) if n.chars().all(|c| c.is_ascii_digit()) => {
+                    n.parse::<u8>().or_else(|_| errinput!("invalid decimal size {n}"))
+                }
+                token => errinput!("unexpected token {token}, wanted integer"),
+            };
+            precision = next_number(self)?;
+            if self.next_is(Token::Comma) {
+                scale = next_number(self)?;
+            }
+            self.expect(Token::CloseParen)?;
+        }
+        if precision == 0 || precision > DECIMAL_MAX_PRECISION {
+            return errinput!("decimal precision must be between 1 and {DECIMAL_MAX_PRECISION}");
+        }
+        if scale > precision {
+            return errinput!("decimal scale {scale} can't exceed precision {precision}");
+        }
+        Ok(DataType::Decimal(precision, scale))
+    }
+
     /// Parses a COMMIT statement.
     fn parse_commit(&mut self) -> Result<ast::Statement> {
         self.expect(Keyword::Commit.into())?;
         Ok(ast::Statement::Commit)
     }
 
-    /// Parses a ROLLBACK statement.
+    /// Parses a ROLLBACK statement, including ROLLBACK TO SAVEPOINT.
     fn parse_rollback(&mut self) -> Result<ast::Statement> {
         self.expect(Keyword::Rollback.into())?;
+        if self.next_is(Keyword::To.into()) {
+            self.skip(Keyword::Savepoint.into());
+            return Ok(ast::Statement::RollbackToSavepoint(self.next_ident()?));
+        }
         Ok(ast::Statement::Rollback)
     }
 
+    /// Parses a SAVEPOINT statement.
+    fn parse_savepoint(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Savepoint.into())?;
+        Ok(ast::Statement::Savepoint(self.next_ident()?))
+    }
+
+    /// Parses a RELEASE SAVEPOINT statement.
+    fn parse_release(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Release.into())?;
+        self.skip(Keyword::Savepoint.into());
+        Ok(ast::Statement::ReleaseSavepoint(self.next_ident()?))
+    }
+
     /// Parses an EXPLAIN statement.
     fn parse_explain(&mut self) -> Result<ast::Statement> {
         self.expect(Keyword::Explain.into())?;
+        let analyze = self.next_is(Keyword::Analyze.into());
+        let verbose = self.next_is(Keyword::Verbose.into());
         if self.next_is(Keyword::Explain.into()) {
             return errinput!("cannot nest EXPLAIN statements");
         }
-        Ok(ast::Statement::Explain(Box::new(self.parse_statement()?)))
+        let statement = self.parse_statement()?;
+        if let ast::Statement::Begin { .. }
+        | ast::Statement::Commit
+        | ast::Statement::Rollback
+        | ast::Statement::Savepoint(_)
+        | ast::Statement::RollbackToSavepoint(_)
+        | ast::Statement::ReleaseSavepoint(_) = statement
+        {
+            return errinput!("cannot explain transaction statements");
+        }
+        if let ast::Statement::Set { .. } = statement {
+            return errinput!("cannot explain SET statements");
+        }
+        if let ast::Statement::Show(_) = statement {
+            return errinput!("cannot explain SHOW statements");
+        }
+        if analyze
+            && !matches!(
+                statement,
+                ast::Statement::Select { .. }
+                    | ast::Statement::Values { .. }
+                    | ast::Statement::Union { .. }
+            )
+        {
+            return errinput!("EXPLAIN ANALYZE only supports SELECT queries");
+        }
+        Ok(ast::Statement::Explain { statement: Box::new(statement), verbose, analyze })
+    }
+
+    /// Parses a SET statement, e.g. SET statement_timeout = 1000. The value
+    /// must be a literal, and TO can be used instead of =.
+    fn parse_set(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Set.into())?;
+        let name = self.next_ident()?;
+        if !self.next_is(Keyword::To.into()) {
+            self.expect(Token::Equal)?;
+        }
+        let value = match self.parse_expression()? {
+            ast::Expression::Literal(literal) => literal,
+            _ => return errinput!("SET value must be a literal"),
+        };
+        Ok(ast::Statement::Set { name, value })
     }
 
-    /// Parses a CREATE TABLE statement.
+    /// Parses a SHOW statement, e.g. SHOW statement_timeout.
+    fn parse_show(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Show.into())?;
+        Ok(ast::Statement::Show(self.next_ident()?))
+    }
+
+    /// Parses a CREATE TABLE statement, or CREATE TABLE AS with a query.
     fn parse_create_table(&mut self) -> Result<ast::Statement> {
         self.expect(Keyword::Create.into())?;
         self.expect(Keyword::Table.into())?;
+        let mut if_not_exists = false;
+        if self.next_is(Keyword::If.into()) {
+            self.expect(Keyword::Not.into())?;
+            self.expect(Keyword::Exists.into())?;
+            if_not_exists = true;
+        }
         let name = self.next_ident()?;
+        if self.next_is(Keyword::As.into()) {
+            let query = Box::new(self.parse_select()?);
+            return Ok(ast::Statement::CreateTableAs { name, query, if_not_exists });
+        }
+        self.expect(Token::OpenParen)?;
+        let mut columns = Vec::new();
+        let mut primary_key = None;
+        let mut unique = Vec::new();
+        let mut checks = Vec::new();
+        loop {
+            // PRIMARY KEY (column, ...), UNIQUE (column, ...), and CHECK (expr)
+            // table constraints, or a column definition.
+            if self.next_is(Keyword::Primary.into()) {
+                self.expect(Keyword::Key.into())?;
+                if primary_key.is_some() {
+                    return errinput!("multiple primary keys for table {name}");
+                }
+                primary_key = Some(self.parse_create_table_constraint_columns()?);
+            } else if self.next_is(Keyword::Unique.into()) {
+                unique.push(self.parse_create_table_constraint_columns()?);
+            } else if self.next_is(Keyword::Check.into()) {
+                checks.push(self.parse_check()?);
+            } else {
+                columns.push(self.parse_create_table_column()?);
+            }
+            if !self.next_is(Token::Comma) {
+                break;
+            }
+        }
+        self.expect(Token::CloseParen)?;
+        Ok(ast::Statement::CreateTable {
+            name,
+            columns,
+            primary_key,
+            unique,
+            checks,
+            if_not_exists,
+        })
+    }
+
+    /// Parses a parenthesized CHECK constraint expression, after CHECK.
+    fn parse_check(&mut self) -> Result<ast::Expression> {
+        self.expect(Token::OpenParen)?;
+        let expr = self.parse_expression()?;
+        self.expect(Token::CloseParen)?;
+        Ok(expr)
+    }
+
+    /// Parses the parenthesized column list of a CREATE TABLE constraint.
+    fn parse_create_table_constraint_columns(&mut self) -> Result<Vec<String>> {
         self.expect(Token::OpenParen)?;
         let mut columns = Vec::new();
         loop {
-            columns.push(self.parse_create_table_column()?);
+            columns.push(self.next_ident()?);
             if !self.next_is(Token::Comma) {
                 break;
             }
         }
         self.expect(Token::CloseParen)?;
-        Ok(ast::Statement::CreateTable { name, columns })
+        Ok(columns)
     }
 
     /// Parses a CREATE TABLE column definition.
     fn parse_create_table_column(&mut self) -> Result<ast::Column> {
         let name = self.next_ident()?;
-        let datatype = match self.next()? {
-            Token::Keyword(Keyword::Bool | Keyword::Boolean) => DataType::Boolean,
-            Token::Keyword(Keyword::Float | Keyword::Double) => DataType::Float,
-            Token::Keyword(Keyword::Int | Keyword::Integer) => DataType::Integer,
-            Token::Keyword(Keyword::String | Keyword::Text | Keyword::Varchar) => DataType::String,
-            token => return errinput!("unexpected token {token}"),
-        };
+        let datatype = self.parse_datatype()?;
         let mut column = ast::Column {
             name,
             datatype,
@@ -201,6 +421,8 @@ impl<'a> Parser<'a> {
             unique: false,
             index: false,
             references: None,
+            on_delete: OnDelete::Restrict,
+            check: None,
         };
         while let Some(keyword) = self.next_if_keyword() {
             match keyword {
@@ -223,8 +445,32 @@ impl<'a> Parser<'a> {
                 }
                 Keyword::Default => column.default = Some(self.parse_expression()?),
                 Keyword::Unique => column.unique = true,
+                Keyword::Check => {
+                    if column.check.is_some() {
+                        return errinput!("CHECK already set for column {}", column.name);
+                    }
+                    column.check = Some(self.parse_check()?)
+                }
                 Keyword::Index => column.index = true,
-                Keyword::References => column.references = Some(self.next_ident()?),
+                Keyword::References => {
+                    column.references = Some(self.next_ident()?);
+                    if self.next_is(Keyword::On.into()) {
+                        self.expect(Keyword::Delete.into())?;
+                        column.on_delete = match self.next()? {
+                            Token::Keyword(Keyword::Cascade) => OnDelete::Cascade,
+                            Token::Keyword(Keyword::Restrict) => OnDelete::Restrict,
+                            Token::Keyword(Keyword::Set) => {
+                                self.expect(Keyword::Null.into())?;
+                                OnDelete::SetNull
+                            }
+                            token => {
+                                return errinput!(
+                                    "expected CASCADE, RESTRICT, or SET NULL, found {token}"
+                                )
+                            }
+                        };
+                    }
+                }
                 keyword => return errinput!("unexpected keyword {keyword}"),
             }
         }
@@ -241,7 +487,93 @@ impl<'a> Parser<'a> {
             if_exists = true;
         }
         let name = self.next_ident()?;
-        Ok(ast::Statement::DropTable { name, if_exists })
+        // RESTRICT is the default.
+        let cascade = self.next_is(Keyword::Cascade.into());
+        if !cascade {
+            self.next_is(Keyword::Restrict.into());
+        }
+        Ok(ast::Statement::DropTable { name, if_exists, cascade })
+    }
+
+    /// Parses an ALTER TABLE statement.
+    fn parse_alter_table(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Alter.into())?;
+        self.expect(Keyword::Table.into())?;
+        let name = self.next_ident()?;
+        let action = match self.next()? {
+            Token::Keyword(Keyword::Add) => {
+                self.next_is(Keyword::Column.into());
+                ast::AlterTable::AddColumn(self.parse_create_table_column()?)
+            }
+            Token::Keyword(Keyword::Drop) => {
+                self.next_is(Keyword::Column.into());
+                ast::AlterTable::DropColumn(self.next_ident()?)
+            }
+            token => return errinput!("unexpected token {token}"),
+        };
+        Ok(ast::Statement::AlterTable { name, action })
+    }
+
+    /// Parses a CREATE INDEX statement.
+    fn parse_create_index(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Create.into())?;
+        self.expect(Keyword::Index.into())?;
+        let name = match self.next_is(Keyword::On.into()) {
+            true => None,
+            false => {
+                let name = self.next_ident()?;
+                self.expect(Keyword::On.into())?;
+                Some(name)
+            }
+        };
+        let table = self.next_ident()?;
+        self.expect(Token::OpenParen)?;
+        let column = self.next_ident()?;
+        self.expect(Token::CloseParen)?;
+        Ok(ast::Statement::CreateIndex { name, table, column })
+    }
+
+    /// Parses a DROP INDEX statement. The index is given either by name or as
+    /// table.column.
+    fn parse_drop_index(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Drop.into())?;
+        self.expect(Keyword::Index.into())?;
+        let mut if_exists = false;
+        if self.next_is(Keyword::If.into()) {
+            self.expect(Keyword::Exists.into())?;
+            if_exists = true;
+        }
+        let mut table = None;
+        let mut name = self.next_ident()?;
+        if self.next_is(Token::Period) {
+            table = Some(name);
+            name = self.next_ident()?;
+        }
+        Ok(ast::Statement::DropIndex { table, name, if_exists })
+    }
+
+    /// Parses a COPY statement, either COPY table FROM or COPY (query) TO.
+    fn parse_copy(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Copy.into())?;
+        if self.next_is(Token::OpenParen) {
+            let query = self.parse_select()?;
+            self.expect(Token::CloseParen)?;
+            self.expect(Keyword::To.into())?;
+            let path = self.parse_path()?;
+            return Ok(ast::Statement::CopyTo { query: Box::new(query), path });
+        }
+        let table = self.next_ident()?;
+        self.expect(Keyword::From.into())?;
+        let path = self.parse_path()?;
+        Ok(ast::Statement::CopyFrom { table, path })
+    }
+
+    /// Parses a file path, given as a string literal.
+    fn parse_path(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::String(path) => Ok(path),
+            token => errinput!("expected file path, got {token}"),
+        }
     }
 
     /// Parses a DELETE statement.
@@ -249,7 +581,9 @@ impl<'a> Parser<'a> {
         self.expect(Keyword::Delete.into())?;
         self.expect(Keyword::From.into())?;
         let table = self.next_ident()?;
-        Ok(ast::Statement::Delete { table, r#where: self.parse_where_clause()? })
+        let r#where = self.parse_where_clause()?;
+        let returning = self.parse_returning_clause()?;
+        Ok(ast::Statement::Delete { table, r#where, returning })
     }
 
     /// Parses an INSERT statement.
@@ -270,26 +604,45 @@ impl<'a> Parser<'a> {
             self.expect(Token::CloseParen)?;
         }
 
-        self.expect(Keyword::Values.into())?;
+        let source = match self.next_is(Keyword::Values.into()) {
+            true => ast::InsertSource::Values(self.parse_values_rows()?),
+            false => ast::InsertSource::Query(Box::new(self.parse_select()?)),
+        };
+        let returning = self.parse_returning_clause()?;
+        Ok(ast::Statement::Insert { table, columns, source, returning })
+    }
 
-        let mut values = Vec::new();
+    /// Parses the parenthesized rows of a VALUES list, after VALUES. None
+    /// is used for DEFAULT values.
+    fn parse_values_rows(&mut self) -> Result<Vec<Vec<Option<ast::Expression>>>> {
+        let mut rows = Vec::new();
         loop {
             let mut row = Vec::new();
             self.expect(Token::OpenParen)?;
             loop {
-                row.push(self.parse_expression()?);
+                let expr = (!self.next_is(Keyword::Default.into()))
+                    .then(|| self.parse_expression())
+                    .transpose()?;
+                row.push(expr);
                 if !self.next_is(Token::Comma) {
                     break;
                 }
             }
             self.expect(Token::CloseParen)?;
-            values.push(row);
+            rows.push(row);
             if !self.next_is(Token::Comma) {
                 break;
             }
         }
+        Ok(rows)
+    }
 
-        Ok(ast::Statement::Insert { table, columns, values })
+    /// Parses a TRUNCATE statement. The TABLE keyword is optional.
+    fn parse_truncate(&mut self) -> Result<ast::Statement> {
+        self.expect(Keyword::Truncate.into())?;
+        self.next_is(Keyword::Table.into());
+        let table = self.next_ident()?;
+        Ok(ast::Statement::Truncate { table })
     }
 
     /// Parses an UPDATE statement.
@@ -312,34 +665,95 @@ impl<'a> Parser<'a> {
                 break;
             }
         }
-        Ok(ast::Statement::Update { table, set, r#where: self.parse_where_clause()? })
+        let r#where = self.parse_where_clause()?;
+        let returning = self.parse_returning_clause()?;
+        Ok(ast::Statement::Update { table, set, r#where, returning })
     }
 
-    /// Parses a SELECT statement.
+    /// Parses a SELECT statement, including any UNIONs with other SELECTs.
+    /// ORDER BY, LIMIT, and OFFSET must follow the last SELECT, and apply to
+    /// the entire UNION.
     fn parse_select(&mut self) -> Result<ast::Statement> {
+        let mut statement = self.parse_select_query()?;
+        while self.next_is(Keyword::Union.into()) {
+            statement = ast::Statement::Union {
+                left: Box::new(statement),
+                all: self.next_is(Keyword::All.into()),
+                right: Box::new(self.parse_select_query()?),
+                order_by: Vec::new(),
+                offset: None,
+                limit: None,
+            };
+        }
+        match &mut statement {
+            ast::Statement::Select { order_by, offset, limit, .. }
+            | ast::Statement::Values { order_by, offset, limit, .. }
+            | ast::Statement::Union { order_by, offset, limit, .. } => {
+                *order_by = self.parse_order_by_clause()?;
+                // LIMIT ALL is the same as no limit.
+                *limit = self
+                    .next_is(Keyword::Limit.into())
+                    .then(|| match self.next_is(Keyword::All.into()) {
+                        true => Ok(None),
+                        false => self.parse_expression().map(Some),
+                    })
+                    .transpose()?
+                    .flatten();
+                *offset = self
+                    .next_is(Keyword::Offset.into())
+                    .then(|| self.parse_expression())
+                    .transpose()?;
+            }
+            _ => unreachable!(),
+        }
+        if self.next_is(Keyword::For.into()) {
+            self.expect(Keyword::Update.into())?;
+            match &mut statement {
+                ast::Statement::Select { for_update, .. } => *for_update = true,
+                ast::Statement::Values { .. } => {
+                    return errinput!("FOR UPDATE is not allowed with VALUES")
+                }
+                _ => return errinput!("FOR UPDATE is not allowed with UNION"),
+            }
+        }
+        Ok(statement)
+    }
+
+    /// Parses a single SELECT or VALUES query, without ORDER BY, LIMIT, and
+    /// OFFSET.
+    fn parse_select_query(&mut self) -> Result<ast::Statement> {
+        if self.next_is(Keyword::Values.into()) {
+            let mut rows = Vec::new();
+            for row in self.parse_values_rows()? {
+                let Some(row) = row.into_iter().collect() else {
+                    return errinput!("DEFAULT is only allowed in INSERT");
+                };
+                rows.push(row);
+            }
+            return Ok(ast::Statement::Values {
+                rows,
+                order_by: Vec::new(),
+                offset: None,
+                limit: None,
+            });
+        }
+        self.expect(Keyword::Select.into())?;
         Ok(ast::Statement::Select {
+            distinct: self.next_is(Keyword::Distinct.into()),
             select: self.parse_select_clause()?,
             from: self.parse_from_clause()?,
             r#where: self.parse_where_clause()?,
             group_by: self.parse_group_by_clause()?,
             having: self.parse_having_clause()?,
-            order_by: self.parse_order_by_clause()?,
-            limit: self
-                .next_is(Keyword::Limit.into())
-                .then(|| self.parse_expression())
-                .transpose()?,
-            offset: self
-                .next_is(Keyword::Offset.into())
-                .then(|| self.parse_expression())
-                .transpose()?,
+            order_by: Vec::new(),
+            offset: None,
+            limit: None,
+            for_update: false,
         })
     }
 
-    /// Parses a SELECT clause, if present.
+    /// Parses the SELECT clause expressions, following SELECT [DISTINCT].
     fn parse_select_clause(&mut self) -> Result<Vec<(ast::Expression, Option<String>)>> {
-        if !self.next_is(Keyword::Select.into()) {
-            return Ok(Vec::new());
-        }
         let mut select = Vec::new();
         loop {
             let expr = self.parse_expression()?;
@@ -384,14 +798,27 @@ impl<'a> Parser<'a> {
         Ok(from)
     }
 
-    // Parses a FROM table.
+    // Parses a FROM table, or a parenthesized derived table query.
     fn parse_from_table(&mut self) -> Result<ast::From> {
-        let name = self.next_ident()?;
+        if self.next_is(Token::OpenParen) {
+            let query = Box::new(self.parse_select()?);
+            self.expect(Token::CloseParen)?;
+            if !self.next_is(Keyword::As.into()) && !matches!(self.peek()?, Some(Token::Ident(_))) {
+                return errinput!("subquery in FROM must have an alias");
+            }
+            let alias = self.next_ident()?;
+            return Ok(ast::From::Query { query, alias });
+        }
+        let mut schema = None;
+        let mut name = self.next_ident()?;
+        if self.next_is(Token::Period) {
+            schema = Some(std::mem::replace(&mut name, self.next_ident()?));
+        }
         let mut alias = None;
         if self.next_is(Keyword::As.into()) || matches!(self.peek()?, Some(Token::Ident(_))) {
             alias = Some(self.next_ident()?)
         };
-        Ok(ast::From::Table { name, alias })
+        Ok(ast::From::Table { schema, name, alias })
     }
 
     // Parses a FROM JOIN type, if present.
@@ -428,6 +855,15 @@ impl<'a> Parser<'a> {
         Ok(Some(self.parse_expression()?))
     }
 
+    /// Parses a RETURNING clause for INSERT, UPDATE, and DELETE, if present.
+    /// It takes the same expressions and aliases as a SELECT clause.
+    fn parse_returning_clause(&mut self) -> Result<Vec<(ast::Expression, Option<String>)>> {
+        if !self.next_is(Keyword::Returning.into()) {
+            return Ok(Vec::new());
+        }
+        self.parse_select_clause()
+    }
+
     /// Parses a GROUP BY clause, if present.
     fn parse_group_by_clause(&mut self) -> Result<Vec<ast::Expression>> {
         if !self.next_is(Keyword::Group.into()) {
@@ -453,7 +889,9 @@ impl<'a> Parser<'a> {
     }
 
     /// Parses an ORDER BY clause, if present.
-    fn parse_order_by_clause(&mut self) -> Result<Vec<(ast::Expression, ast::Direction)>> {
+    fn parse_order_by_clause(
+        &mut self,
+    ) -> Result<Vec<(ast::Expression, ast::Direction, Option<ast::Nulls>)>> {
         if !self.next_is(Keyword::Order.into()) {
             return Ok(Vec::new());
         }
@@ -468,7 +906,15 @@ impl<'a> Parser<'a> {
                     _ => None,
                 })
                 .unwrap_or(ast::Direction::Ascending);
-            order_by.push((expr, order));
+            let mut nulls = None;
+            if self.next_is(Keyword::Nulls.into()) {
+                nulls = Some(match self.next()? {
+                    Token::Keyword(Keyword::First) => ast::Nulls::First,
+                    Token::Keyword(Keyword::Last) => ast::Nulls::Last,
+                    token => return errinput!("expected FIRST or LAST, found {token}"),
+                });
+            }
+            order_by.push((expr, order, nulls));
             if !self.next_is(Token::Comma) {
                 break;
             }
@@ -476,6 +922,25 @@ impl<'a> Parser<'a> {
         Ok(order_by)
     }
 
+    /// Parses a window definition following OVER, i.e. a parenthesized and
+    /// optional PARTITION BY and ORDER BY clause.
+    fn parse_window(&mut self) -> Result<ast::Window> {
+        self.expect(Token::OpenParen)?;
+        let mut partition_by = Vec::new();
+        if self.next_is(Keyword::Partition.into()) {
+            self.expect(Keyword::By.into())?;
+            loop {
+                partition_by.push(self.parse_expression()?);
+                if !self.next_is(Token::Comma) {
+                    break;
+                }
+            }
+        }
+        let order_by = self.parse_order_by_clause()?;
+        self.expect(Token::CloseParen)?;
+        Ok(ast::Window { partition_by, order_by })
+    }
+
     /// Parses an expression consisting of at least one atom operated on by any
     /// number of operators, using the precedence climbing algorithm.
     ///
@@ -499,7 +964,7 @@ impl<'a> Parser<'a> {
             lhs = postfix.build(lhs)
         }
         // Apply any binary infix operators, parsing the right-hand operand.
-        while let Some(infix) = self.parse_infix_operator(min_precedence) {
+        while let Some(infix) = self.parse_infix_operator(min_precedence)? {
             let at_precedence = infix.precedence() + infix.associativity();
             let rhs = self.parse_expression_at(at_precedence)?;
             lhs = infix.build(lhs, rhs);
@@ -515,8 +980,12 @@ impl<'a> Parser<'a> {
     /// Parses an expression atom. This is either:
     ///
     /// * A literal value.
+    /// * An array constructor.
+    /// * A query parameter.
     /// * A column name.
-    /// * A function call.
+    /// * A function call, optionally a window function with OVER.
+    /// * A scalar or EXISTS subquery.
+    /// * A CASE or CAST expression.
     /// * A parenthesized expression.
     fn parse_expression_atom(&mut self) -> Result<ast::Expression> {
         Ok(match self.next()? {
@@ -531,12 +1000,36 @@ impl<'a> Parser<'a> {
             Token::String(s) => ast::Literal::String(s).into(),
             Token::Keyword(Keyword::True) => ast::Literal::Boolean(true).into(),
             Token::Keyword(Keyword::False) => ast::Literal::Boolean(false).into(),
-            Token::Keyword(Keyword::Infinity) => ast::Literal::Float(std::f64::INFINITY).into(),
-            Token::Keyword(Keyword::NaN) => ast::Literal::Float(std::f64::NAN).into(),
+            Token::Keyword(Keyword::Infinity) => ast::Literal::Float(f64::INFINITY).into(),
+            Token::Keyword(Keyword::NaN) => ast::Literal::Float(f64::NAN).into(),
             Token::Keyword(Keyword::Null) => ast::Literal::Null.into(),
 
+            // JSON literal, i.e. a string parsed as JSON.
+            Token::Keyword(Keyword::Json) => match self.next()? {
+                Token::String(s) => {
+                    ast::Expression::Cast(Box::new(ast::Literal::String(s).into()), DataType::Json)
+                }
+                token => return errinput!("expected JSON string, found {token}"),
+            },
+
+            // Array constructor.
+            Token::OpenBracket => {
+                let mut items = Vec::new();
+                while !self.next_is(Token::CloseBracket) {
+                    if !items.is_empty() {
+                        self.expect(Token::Comma)?;
+                    }
+                    items.push(self.parse_expression()?);
+                }
+                ast::Expression::Array(items)
+            }
+
+            // Query parameter.
+            Token::Parameter(index) => ast::Expression::Parameter(index),
+
             // Function call.
             Token::Ident(name) if self.next_is(Token::OpenParen) => {
+                let distinct = self.next_is(Keyword::Distinct.into());
                 let mut args = Vec::new();
                 while !self.next_is(Token::CloseParen) {
                     if !args.is_empty() {
@@ -544,7 +1037,18 @@ impl<'a> Parser<'a> {
                     }
                     args.push(self.parse_expression()?);
                 }
-                ast::Expression::Function(name, args)
+                if distinct && args.is_empty() {
+                    return errinput!("expected expression after DISTINCT");
+                }
+                if self.next_is(Keyword::Over.into()) {
+                    if distinct {
+                        return errinput!("DISTINCT is not supported for window functions");
+                    }
+                    let over = Box::new(self.parse_window()?);
+                    ast::Expression::Window { function: name, args, over }
+                } else {
+                    ast::Expression::Function { name, args, distinct }
+                }
             }
 
             // Column name, either qualified as table.column or unqualified.
@@ -553,6 +1057,59 @@ impl<'a> Parser<'a> {
             }
             Token::Ident(column) => ast::Expression::Column(None, column),
 
+            // Scalar subquery.
+            Token::OpenParen
+                if matches!(
+                    self.peek()?,
+                    Some(Token::Keyword(Keyword::Select | Keyword::Values))
+                ) =>
+            {
+                let statement = self.parse_select()?;
+                self.expect(Token::CloseParen)?;
+                ast::Expression::Subquery(Box::new(statement))
+            }
+
+            // EXISTS subquery.
+            Token::Keyword(Keyword::Exists) => {
+                self.expect(Token::OpenParen)?;
+                let statement = self.parse_select()?;
+                self.expect(Token::CloseParen)?;
+                ast::Expression::Exists(Box::new(statement))
+            }
+
+            // CAST expression.
+            Token::Keyword(Keyword::Cast) => {
+                self.expect(Token::OpenParen)?;
+                let expr = self.parse_expression()?;
+                self.expect(Keyword::As.into())?;
+                let datatype = self.parse_datatype()?;
+                self.expect(Token::CloseParen)?;
+                ast::Expression::Cast(Box::new(expr), datatype)
+            }
+
+            // CASE expression.
+            Token::Keyword(Keyword::Case) => {
+                let mut operand = None;
+                if self.peek()? != Some(&Keyword::When.into()) {
+                    operand = Some(Box::new(self.parse_expression()?));
+                }
+                let mut when = Vec::new();
+                while self.next_is(Keyword::When.into()) {
+                    let condition = self.parse_expression()?;
+                    self.expect(Keyword::Then.into())?;
+                    when.push((condition, self.parse_expression()?));
+                }
+                if when.is_empty() {
+                    return errinput!("CASE must have at least one WHEN clause");
+                }
+                let mut r#else = None;
+                if self.next_is(Keyword::Else.into()) {
+                    r#else = Some(Box::new(self.parse_expression()?));
+                }
+                self.expect(Keyword::End.into())?;
+                ast::Expression::Case { operand, when, r#else }
+            }
+
             // Parenthesized expression.
             Token::OpenParen => {
                 let expr = self.parse_expression()?;
@@ -580,15 +1137,38 @@ impl<'a> Parser<'a> {
 
     /// Parses an infix operator, if there is one and its precedence is at least
     /// min_precedence.
-    fn parse_infix_operator(&mut self, min_precedence: Precedence) -> Option<InfixOperator> {
-        self.next_if_map(|token| {
+    fn parse_infix_operator(
+        &mut self,
+        min_precedence: Precedence,
+    ) -> Result<Option<InfixOperator>> {
+        // Handle NOT LIKE and NOT ILIKE separately, since they're two tokens.
+        // NOT may also be followed by e.g. IN, so look at the following token.
+        if let Some(Token::Keyword(Keyword::Not)) = self.peek()? {
+            let operator = match self.peek_second()? {
+                Some(Token::Keyword(Keyword::Like)) => InfixOperator::NotLike,
+                Some(Token::Keyword(Keyword::ILike)) => InfixOperator::NotILike,
+                _ => return Ok(None),
+            };
+            if operator.precedence() < min_precedence {
+                return Ok(None);
+            }
+            self.next()?;
+            self.next()?;
+            return Ok(Some(operator));
+        }
+
+        Ok(self.next_if_map(|token| {
             let operator = match token {
                 Token::Asterisk => InfixOperator::Multiply,
                 Token::Caret => InfixOperator::Exponentiate,
+                Token::Concat => InfixOperator::Concat,
                 Token::Equal => InfixOperator::Equal,
                 Token::GreaterThan => InfixOperator::GreaterThan,
                 Token::GreaterThanOrEqual => InfixOperator::GreaterThanOrEqual,
+                Token::Arrow => InfixOperator::JsonExtract,
+                Token::DoubleArrow => InfixOperator::JsonExtractText,
                 Token::Keyword(Keyword::And) => InfixOperator::And,
+                Token::Keyword(Keyword::ILike) => InfixOperator::ILike,
                 Token::Keyword(Keyword::Like) => InfixOperator::Like,
                 Token::Keyword(Keyword::Or) => InfixOperator::Or,
                 Token::LessOrGreaterThan => InfixOperator::NotEqual,
@@ -596,13 +1176,16 @@ impl<'a> Parser<'a> {
                 Token::LessThanOrEqual => InfixOperator::LessThanOrEqual,
                 Token::Minus => InfixOperator::Subtract,
                 Token::NotEqual => InfixOperator::NotEqual,
+                Token::NullSafeEqual => InfixOperator::NullSafeEqual,
                 Token::Percent => InfixOperator::Remainder,
                 Token::Plus => InfixOperator::Add,
                 Token::Slash => InfixOperator::Divide,
+                Token::Tilde => InfixOperator::Regex,
+                Token::ExclamationTilde => InfixOperator::NotRegex,
                 _ => return None,
             };
             Some(operator).filter(|op| op.precedence() >= min_precedence)
-        })
+        }))
     }
 
     /// Parses a postfix operator, if there is one and its precedence is at
@@ -611,7 +1194,18 @@ impl<'a> Parser<'a> {
         &mut self,
         min_precedence: Precedence,
     ) -> Result<Option<PostfixOperator>> {
-        // Handle IS (NOT) NULL/NAN separately, since it's multiple tokens.
+        // Handle ::type casts separately, since the datatype can be multiple
+        // tokens, e.g. DECIMAL(5,2).
+        if let Some(Token::DoubleColon) = self.peek()? {
+            if PostfixOperator::Cast(DataType::Boolean).precedence() < min_precedence {
+                return Ok(None);
+            }
+            self.expect(Token::DoubleColon)?;
+            return Ok(Some(PostfixOperator::Cast(self.parse_datatype()?)));
+        }
+
+        // Handle IS (NOT) NULL/NAN/TRUE/FALSE/UNKNOWN and IS (NOT) DISTINCT
+        // FROM separately, since they're multiple tokens.
         if let Some(Token::Keyword(Keyword::Is)) = self.peek()? {
             // We can't consume tokens unless the precedence is satisfied, so we
             // assume IS NULL (they all have the same precedence).
@@ -620,9 +1214,25 @@ impl<'a> Parser<'a> {
             }
             self.expect(Keyword::Is.into())?;
             let not = self.next_is(Keyword::Not.into());
+            if self.next_is(Keyword::Distinct.into()) {
+                self.expect(Keyword::From.into())?;
+                // The operand can't contain operators with a lower precedence
+                // than IS, e.g. a IS DISTINCT FROM b = c.
+                let at_precedence = PostfixOperator::Is(ast::Literal::Null).precedence() + 1;
+                let operator =
+                    PostfixOperator::DistinctFrom(self.parse_expression_at(at_precedence)?);
+                return Ok(Some(match not {
+                    false => operator,
+                    true => PostfixOperator::Not(Box::new(operator)),
+                }));
+            }
             let value = match self.next()? {
                 Token::Keyword(Keyword::NaN) => ast::Literal::Float(f64::NAN),
                 Token::Keyword(Keyword::Null) => ast::Literal::Null,
+                // UNKNOWN isn't a keyword, so it can be used as an identifier.
+                Token::Ident(ident) if ident == "unknown" => ast::Literal::Null,
+                Token::Keyword(Keyword::True) => ast::Literal::Boolean(true),
+                Token::Keyword(Keyword::False) => ast::Literal::Boolean(false),
                 token => return errinput!("unexpected token {token}"),
             };
             let operator = match not {
@@ -632,6 +1242,65 @@ impl<'a> Parser<'a> {
             return Ok(Some(operator));
         }
 
+        // Handle (NOT) IN and (NOT) BETWEEN separately, since they take
+        // multiple operands. NOT may also be followed by e.g. NULL in column
+        // constraints, so we have to look at the following token too.
+        let keyword = match self.peek()? {
+            Some(Token::Keyword(keyword @ (Keyword::In | Keyword::Between))) => Some(*keyword),
+            Some(Token::Keyword(Keyword::Not)) => match self.peek_second()? {
+                Some(Token::Keyword(keyword @ (Keyword::In | Keyword::Between))) => Some(keyword),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(keyword) = keyword {
+            // IN and BETWEEN have the same precedence.
+            if PostfixOperator::In(Vec::new()).precedence() < min_precedence {
+                return Ok(None);
+            }
+            let not = self.next_is(Keyword::Not.into());
+            self.expect(keyword.into())?;
+            let operator = match keyword {
+                Keyword::In => {
+                    self.expect(Token::OpenParen)?;
+                    let mut list = Vec::new();
+                    loop {
+                        list.push(self.parse_expression()?);
+                        if !self.next_is(Token::Comma) {
+                            break;
+                        }
+                    }
+                    self.expect(Token::CloseParen)?;
+                    PostfixOperator::In(list)
+                }
+                Keyword::Between => {
+                    // The bounds can't contain operators with a precedence
+                    // lower than BETWEEN, to avoid consuming the AND.
+                    let at_precedence = PostfixOperator::In(Vec::new()).precedence() + 1;
+                    let low = self.parse_expression_at(at_precedence)?;
+                    self.expect(Keyword::And.into())?;
+                    let high = self.parse_expression_at(at_precedence)?;
+                    PostfixOperator::Between(low, high)
+                }
+                keyword => panic!("unexpected keyword {keyword}"),
+            };
+            return Ok(Some(match not {
+                false => operator,
+                true => PostfixOperator::Not(Box::new(operator)),
+            }));
+        }
+
+        // Handle array indexing separately, since the index is an expression.
+        if let Some(Token::OpenBracket) = self.peek()? {
+            if PostfixOperator::Index(ast::Literal::Null.into()).precedence() < min_precedence {
+                return Ok(None);
+            }
+            self.expect(Token::OpenBracket)?;
+            let index = self.parse_expression()?;
+            self.expect(Token::CloseBracket)?;
+            return Ok(Some(PostfixOperator::Index(index)));
+        }
+
         Ok(self.next_if_map(|token| {
             let operator = match token {
                 Token::Exclamation => PostfixOperator::Factorial,
@@ -660,7 +1329,7 @@ impl PrefixOperator {
     fn precedence(&self) -> Precedence {
         match self {
             Self::Not => 3,
-            Self::Minus | Self::Plus => 10,
+            Self::Minus | Self::Plus => 11,
         }
     }
 
@@ -685,17 +1354,26 @@ impl PrefixOperator {
 enum InfixOperator {
     Add,                // a + b
     And,                // a AND b
+    Concat,             // a || b
     Divide,             // a / b
     Equal,              // a = b
     Exponentiate,       // a ^ b
     GreaterThan,        // a > b
     GreaterThanOrEqual, // a >= b
+    ILike,              // a ILIKE b
+    JsonExtract,        // a -> b
+    JsonExtractText,    // a ->> b
     LessThan,           // a < b
     LessThanOrEqual,    // a <= b
     Like,               // a LIKE b
     Multiply,           // a * b
     NotEqual,           // a != b
+    NullSafeEqual,      // a <=> b
+    NotILike,           // a NOT ILIKE b
+    NotLike,            // a NOT LIKE b
+    NotRegex,           // a !~ b
     Or,                 // a OR b
+    Regex,              // a ~ b
     Remainder,          // a % b
     Subtract,           // a - b
 }
@@ -703,21 +1381,31 @@ enum InfixOperator {
 impl InfixOperator {
     /// The operator precedence.
     ///
-    /// Mostly follows Postgres, except IS and LIKE having same precedence as =.
+    /// Mostly follows Postgres, except IS, IN, and (I)LIKE having same
+    /// precedence as =.
     /// This is similar to SQLite and MySQL.
     fn precedence(&self) -> Precedence {
         match self {
             Self::Or => 1,
             Self::And => 2,
             // Self::Not => 3
-            Self::Equal | Self::NotEqual | Self::Like => 4, // and Self::Is
+            Self::Equal
+            | Self::NotEqual
+            | Self::NullSafeEqual
+            | Self::Like
+            | Self::ILike
+            | Self::NotLike
+            | Self::NotILike
+            | Self::Regex
+            | Self::NotRegex => 4, // and Self::Is
             Self::GreaterThan
             | Self::GreaterThanOrEqual
             | Self::LessThan
             | Self::LessThanOrEqual => 5,
-            Self::Add | Self::Subtract => 6,
-            Self::Multiply | Self::Divide | Self::Remainder => 7,
-            Self::Exponentiate => 8,
+            Self::Concat | Self::JsonExtract | Self::JsonExtractText => 6,
+            Self::Add | Self::Subtract => 7,
+            Self::Multiply | Self::Divide | Self::Remainder => 8,
+            Self::Exponentiate => 9,
         }
     }
 
@@ -735,17 +1423,28 @@ impl InfixOperator {
         match self {
             Self::Add => ast::Operator::Add(lhs, rhs).into(),
             Self::And => ast::Operator::And(lhs, rhs).into(),
+            Self::Concat => ast::Operator::Concat(lhs, rhs).into(),
             Self::Divide => ast::Operator::Divide(lhs, rhs).into(),
             Self::Equal => ast::Operator::Equal(lhs, rhs).into(),
             Self::Exponentiate => ast::Operator::Exponentiate(lhs, rhs).into(),
             Self::GreaterThan => ast::Operator::GreaterThan(lhs, rhs).into(),
             Self::GreaterThanOrEqual => ast::Operator::GreaterThanOrEqual(lhs, rhs).into(),
+            Self::ILike => ast::Operator::ILike(lhs, rhs).into(),
+            Self::JsonExtract => ast::Operator::JsonExtract(lhs, rhs).into(),
+            Self::JsonExtractText => ast::Operator::JsonExtractText(lhs, rhs).into(),
             Self::LessThan => ast::Operator::LessThan(lhs, rhs).into(),
             Self::LessThanOrEqual => ast::Operator::LessThanOrEqual(lhs, rhs).into(),
             Self::Like => ast::Operator::Like(lhs, rhs).into(),
             Self::Multiply => ast::Operator::Multiply(lhs, rhs).into(),
             Self::NotEqual => ast::Operator::NotEqual(lhs, rhs).into(),
+            Self::NullSafeEqual => {
+                ast::Operator::Not(ast::Operator::Distinct(lhs, rhs).into()).into()
+            }
+            Self::NotILike => ast::Operator::Not(ast::Operator::ILike(lhs, rhs).into()).into(),
+            Self::NotLike => ast::Operator::Not(ast::Operator::Like(lhs, rhs).into()).into(),
+            Self::NotRegex => ast::Operator::Not(ast::Operator::Regex(lhs, rhs).into()).into(),
             Self::Or => ast::Operator::Or(lhs, rhs).into(),
+            Self::Regex => ast::Operator::Regex(lhs, rhs).into(),
             Self::Remainder => ast::Operator::Remainder(lhs, rhs).into(),
             Self::Subtract => ast::Operator::Subtract(lhs, rhs).into(),
         }
@@ -754,27 +1453,48 @@ impl InfixOperator {
 
 /// Postfix operators.
 enum PostfixOperator {
-    Factorial,           // a!
-    Is(ast::Literal),    // a IS NULL | NAN
-    IsNot(ast::Literal), // a IS NOT NULL | NAN
+    Between(ast::Expression, ast::Expression), // a BETWEEN b AND c
+    Cast(DataType),                            // a::type
+    DistinctFrom(ast::Expression),             // a IS DISTINCT FROM b
+    Factorial,                                 // a!
+    In(Vec<ast::Expression>),                  // a IN (b, c)
+    Index(ast::Expression),                    // a[b]
+    Is(ast::Literal),                          // a IS NULL | NAN | TRUE | FALSE
+    IsNot(ast::Literal),                       // a IS NOT NULL | NAN | TRUE | FALSE
+    Not(Box<PostfixOperator>),                 // a NOT IN | BETWEEN ...
 }
 
 impl PostfixOperator {
     // The operator precedence.
     fn precedence(&self) -> Precedence {
         match self {
-            Self::Is(_) | Self::IsNot(_) => 4,
-            Self::Factorial => 9,
+            Self::Between(_, _)
+            | Self::DistinctFrom(_)
+            | Self::In(_)
+            | Self::Is(_)
+            | Self::IsNot(_)
+            | Self::Not(_) => 4,
+            Self::Factorial => 10,
+            Self::Cast(_) | Self::Index(_) => 12,
         }
     }
 
     /// Builds an AST expression for the operator.
     fn build(self, lhs: ast::Expression) -> ast::Expression {
+        if let Self::Not(operator) = self {
+            return ast::Operator::Not(operator.build(lhs).into()).into();
+        }
         let lhs = Box::new(lhs);
         match self {
+            Self::Between(low, high) => ast::Operator::Between(lhs, low.into(), high.into()).into(),
+            Self::Cast(datatype) => ast::Expression::Cast(lhs, datatype),
+            Self::DistinctFrom(rhs) => ast::Operator::Distinct(lhs, rhs.into()).into(),
             Self::Factorial => ast::Operator::Factorial(lhs).into(),
+            Self::In(list) => ast::Operator::In(lhs, list).into(),
+            Self::Index(index) => ast::Operator::Index(lhs, index.into()).into(),
             Self::Is(v) => ast::Operator::Is(lhs, v).into(),
             Self::IsNot(v) => ast::Operator::Not(ast::Operator::Is(lhs, v).into()).into(),
+            Self::Not(_) => unreachable!("handled above"),
         }
     }
 }
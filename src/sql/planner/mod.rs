@@ -5,7 +5,7 @@ mod optimizer;
 mod plan;
 mod planner;
 
-pub use plan::{Aggregate, Direction, Node, Plan};
+pub use plan::{Aggregate, Direction, Node, Nulls, Plan, Window, WindowFunction};
 pub use planner::{Planner, Scope};
 
 #[cfg(test)]
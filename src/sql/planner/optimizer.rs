@@ -1,8 +1,9 @@
 use super::Node;
 use crate::error::Result;
-use crate::sql::types::{Expression, Label, Value};
+use crate::sql::types::{DataType, Expression, Label, Value};
 
-use std::collections::HashMap;
+use itertools::Itertools as _;
+use std::collections::{HashMap, HashSet};
 
 /// A plan optimizer, which recursively transforms a plan node to make plan
 /// execution more efficient where possible.
@@ -13,8 +14,10 @@ pub static OPTIMIZERS: &[(&str, Optimizer)] = &[
     ("Constant folding", fold_constants),
     ("Filter pushdown", push_filters),
     ("Index lookup", index_lookup),
+    ("Join order", join_order),
     ("Join type", join_type),
     ("Short circuit", short_circuit),
+    ("Limit pushdown", push_limits),
 ];
 
 /// Folds constant (sub)expressions by pre-evaluating them, instead of
@@ -34,7 +37,12 @@ pub fn fold_constants(node: Node) -> Result<Node> {
         // expression as 1 - 2 + a to evaluate the 1 - 2 branch).
         //
         // TODO: consider doing something better.
-        if !expr.contains(&|expr| matches!(expr, Column(_))) {
+        //
+        // Subqueries are only executed during plan execution, so they're not
+        // considered constant here. Neither are unbound parameters.
+        if !expr.contains(&|e| {
+            matches!(e, Column(_) | OuterColumn(_) | Parameter(_) | Subquery(..) | Exists(..))
+        }) {
             return expr.evaluate(None).map(Constant);
         }
 
@@ -59,6 +67,46 @@ pub fn fold_constants(node: Node) -> Result<Node> {
                 (Constant(Boolean(false)), expr) | (expr, Constant(Boolean(false))) => expr,
                 (lhs, rhs) => Or(lhs.into(), rhs.into()),
             },
+            // CASE branches with a false or NULL condition never match, and a
+            // true condition makes the following branches unreachable.
+            Case(when, mut r#else) => {
+                let mut branches = Vec::new();
+                for (when, then) in when {
+                    match when {
+                        Constant(Boolean(false) | Null) => {}
+                        Constant(Boolean(true)) => {
+                            r#else = Some(then.into());
+                            break;
+                        }
+                        when => branches.push((when, then)),
+                    }
+                }
+                match (branches.is_empty(), r#else) {
+                    (true, Some(r#else)) => *r#else,
+                    (true, None) => Constant(Null),
+                    (false, r#else) => Case(branches, r#else),
+                }
+            }
+            // COALESCE values that are constant NULL are skipped, and a
+            // constant non-NULL value makes the following values unreachable.
+            Coalesce(exprs) => {
+                let mut values = Vec::new();
+                for expr in exprs {
+                    match expr {
+                        Constant(Null) => {}
+                        expr @ Constant(_) => {
+                            values.push(expr);
+                            break;
+                        }
+                        expr => values.push(expr),
+                    }
+                }
+                match values.len() {
+                    0 => Constant(Null),
+                    1 => values.remove(0),
+                    _ => Coalesce(values),
+                }
+            }
             expr => expr,
         };
         Ok(expr)
@@ -80,6 +128,25 @@ pub fn push_filters(node: Node) -> Result<Node> {
                 let rhs = std::mem::replace(predicate, Expression::Constant(Value::Null));
                 *predicate = Expression::And(expr.into(), rhs.into());
             }
+            // Filters can't be pushed into an outer join predicate, since
+            // unmatched left rows would then be emitted rather than filtered.
+            // Expressions that only reference the left source can be pushed
+            // into it instead.
+            Node::NestedLoopJoin { left, outer: true, .. } => {
+                let right_index = left.columns();
+                let (push_left, mut remaining): (Vec<_>, Vec<_>) =
+                    expr.into_cnf_vec().into_iter().partition(|expr| {
+                        !expr.contains(
+                            &|expr| matches!(expr, Expression::Column(i) if *i >= right_index),
+                        )
+                    });
+                if let Some(expr) = Expression::and_vec(push_left) {
+                    if let Some(expr) = push_into(expr, left) {
+                        remaining.push(expr);
+                    }
+                }
+                return Expression::and_vec(remaining);
+            }
             Node::NestedLoopJoin { predicate, .. } => {
                 *predicate = match predicate.take() {
                     Some(predicate) => Some(Expression::And(expr.into(), predicate.into())),
@@ -92,6 +159,9 @@ pub fn push_filters(node: Node) -> Result<Node> {
                     None => Some(expr),
                 };
             }
+            // Lock nodes lock the source rows, so push into the source to
+            // only lock the filtered rows.
+            Node::Lock { source, .. } => return push_into(expr, source),
             // We don't handle HashJoin here, since we assume the join_type()
             // optimizer runs after this.
             Node::HashJoin { .. } => panic!("filter pushdown must run before join optimizer"),
@@ -140,8 +210,12 @@ pub fn push_filters(node: Node) -> Result<Node> {
             });
             match (ref_left, ref_right) {
                 (true, true) => predicate.push(expr),
+                // For outer joins, all left rows must be emitted regardless of
+                // the join predicate, so we can't filter the left source.
+                (true, false) if outer => predicate.push(expr),
                 (true, false) => push_left.push(expr),
                 (false, true) => push_right.push(expr),
+                (false, false) if outer => push_right.push(expr),
                 (false, false) => {
                     push_left.push(expr.clone());
                     push_right.push(expr);
@@ -182,6 +256,9 @@ pub fn push_filters(node: Node) -> Result<Node> {
             if let Some(expr) = left_lookups.get(&l).map(|i| push_left[*i].clone()) {
                 push_right.push(expr.replace_column(l, r));
             }
+            if outer {
+                continue;
+            }
             if let Some(expr) = right_lookups.get(&r).map(|i| push_right[*i].clone()) {
                 push_left.push(expr.replace_column(r, l));
             }
@@ -229,24 +306,77 @@ pub fn index_lookup(node: Node) -> Result<Node> {
         // Convert the filter into conjunctive normal form (a list of ANDs).
         let mut cnf = filter.clone().into_cnf_vec();
 
-        // Find the first expression that's either a primary key or secondary
-        // index lookup. We could be more clever here, but this is fine.
-        let Some((i, column)) = cnf.iter().enumerate().find_map(|(i, expr)| {
-            expr.is_column_lookup()
-                .filter(|c| *c == table.primary_key || table.columns[*c].index)
-                .map(|column| (i, column))
-        }) else {
-            return Node::Scan { table, alias, filter: Some(filter) };
-        };
+        // Extracts the lookup values for a column from a CNF lookup expression.
+        let lookup_values = |expr: Expression, column: usize| -> Vec<Value> {
+            let mut values = expr.into_column_values(column);
+
+            // Decimal keys are stored with the column's scale, so lookup values
+            // must be rescaled to match, e.g. 1 to 1.00. Values that can't be
+            // coerced (e.g. out of range) can't match anyway, so leave them.
+            if let datatype @ DataType::Decimal(..) = table.columns[column].datatype {
+                values =
+                    values.into_iter().map(|v| v.clone().coerce(datatype).unwrap_or(v)).collect();
+            }
 
-        // Extract the lookup values and expression from the cnf vector.
-        let values = cnf.remove(i).into_column_values(column);
+            // Remove duplicate values, e.g. id IN (1, 1), to avoid duplicate rows.
+            values.into_iter().unique().collect()
+        };
 
-        // Build the primary key or secondary index lookup node.
-        if column == table.primary_key {
-            node = Node::KeyLookup { table, keys: values, alias };
+        // A composite primary key can only be used if all key columns have
+        // lookups, in which case we look up all combinations of their values.
+        // This is preferred over any secondary index lookups.
+        let key_lookups: Option<Vec<usize>> = (table.primary_key.len() > 1)
+            .then(|| {
+                table
+                    .primary_key
+                    .iter()
+                    .map(|c| cnf.iter().position(|expr| expr.is_column_lookup() == Some(*c)))
+                    .collect()
+            })
+            .flatten();
+        if let Some(positions) = key_lookups {
+            let values = positions
+                .iter()
+                .zip(&table.primary_key)
+                .map(|(i, column)| lookup_values(cnf[*i].clone(), *column))
+                .collect_vec();
+            for i in positions.into_iter().sorted().rev() {
+                cnf.remove(i);
+            }
+            let keys = values.into_iter().multi_cartesian_product().collect();
+            node = Node::KeyLookup { table, keys, alias };
         } else {
-            node = Node::IndexLookup { table, column, values, alias };
+            // Find the most selective expression that's either a primary key or
+            // secondary index lookup. Table statistics only have row counts,
+            // which don't tell us the selectivity of a lookup (and optimizers
+            // don't have catalog access anyway), so prefer primary keys, then
+            // unique indexes, then the fewest lookup values.
+            let Some((i, column)) = cnf
+                .iter()
+                .enumerate()
+                .filter_map(|(i, expr)| {
+                    expr.is_column_lookup()
+                        .filter(|c| table.primary_key == [*c] || table.columns[*c].index)
+                        .map(|column| (i, column))
+                })
+                .min_by_key(|(i, column)| {
+                    let values = cnf[*i].clone().into_column_values(*column).len();
+                    (table.primary_key != [*column], !table.columns[*column].unique, values)
+                })
+            else {
+                return Node::Scan { table, alias, filter: Some(filter) };
+            };
+
+            // Extract the lookup values and expression from the cnf vector.
+            let values = lookup_values(cnf.remove(i), column);
+
+            // Build the primary key or secondary index lookup node.
+            if table.primary_key == [column] {
+                let keys = values.into_iter().map(|v| vec![v]).collect();
+                node = Node::KeyLookup { table, keys, alias };
+            } else {
+                node = Node::IndexLookup { table, column, values, alias };
+            }
         }
 
         // If there's any remaining CNF expressions, add a filter node for them.
@@ -259,7 +389,199 @@ pub fn index_lookup(node: Node) -> Result<Node> {
     node.transform(&Ok, &|n| Ok(transform(n)))
 }
 
+/// Estimates the number of rows emitted by a node, or None if unknown.
+/// Optimizers don't have access to the table row counts in the catalog's
+/// table statistics, so this only handles nodes with a known upper bound,
+/// e.g. primary key lookups. Unknown row counts, e.g. table scans, are assumed
+/// to be larger than any known row count.
+fn estimate_rows(node: &Node) -> Option<usize> {
+    match node {
+        Node::KeyLookup { keys, .. } => Some(keys.len()),
+        Node::Values { rows } => Some(rows.len()),
+        Node::SchemaScan { rows, .. } => Some(rows.len()),
+        Node::Nothing { .. } => Some(0),
+        Node::Aggregate { group_by, .. } if group_by.is_empty() => Some(1),
+        Node::Limit { source, limit } => {
+            Some(estimate_rows(source).map_or(*limit, |rows| rows.min(*limit)))
+        }
+        Node::Aggregate { source, .. }
+        | Node::Distinct { source }
+        | Node::Filter { source, .. }
+        | Node::Lock { source, .. }
+        | Node::Offset { source, .. }
+        | Node::Order { source, .. }
+        | Node::Projection { source, .. }
+        | Node::Remap { source, .. }
+        | Node::Window { source, .. } => estimate_rows(source),
+        Node::HashJoin { .. }
+        | Node::IndexLookup { .. }
+        | Node::NestedLoopJoin { .. }
+        | Node::Scan { .. }
+        | Node::Union { .. } => None,
+    }
+}
+
+/// Reorders chains of three or more inner joins into a left-deep join tree,
+/// joining the smallest source first. Each following source is the smallest
+/// source that is connected to the already joined sources by a join predicate,
+/// to avoid cross joins where possible. The joined columns are remapped back
+/// to the original order. If no row counts are known, the original (textual)
+/// join order is kept.
+pub fn join_order(node: Node) -> Result<Node> {
+    /// Collects the sources of an inner join chain, in column order.
+    fn collect_sources<'a>(node: &'a Node, sources: &mut Vec<&'a Node>) {
+        match node {
+            Node::NestedLoopJoin { left, right, outer: false, .. } => {
+                collect_sources(left, sources);
+                collect_sources(right, sources);
+            }
+            node => sources.push(node),
+        }
+    }
+
+    /// Flattens an inner join chain into its sources and join predicate
+    /// conjuncts, with column indexes in the joined row of all sources. The
+    /// offset is the index of the node's first column in the joined row.
+    fn flatten(
+        node: Node,
+        offset: usize,
+        sources: &mut Vec<Node>,
+        predicates: &mut Vec<Expression>,
+    ) {
+        match node {
+            Node::NestedLoopJoin { left, right, predicate, outer: false } => {
+                let right_offset = offset + left.columns();
+                flatten(*left, offset, sources, predicates);
+                flatten(*right, right_offset, sources, predicates);
+                if let Some(predicate) = predicate {
+                    predicates.extend(predicate.shift_column(offset as isize).into_cnf_vec());
+                }
+            }
+            node => sources.push(node),
+        }
+    }
+
+    let xform = |node: Node| {
+        let mut estimates = Vec::new();
+        collect_sources(&node, &mut estimates);
+        if estimates.len() < 3 {
+            return node;
+        }
+        let estimates = estimates.into_iter().map(estimate_rows).collect_vec();
+        if estimates.iter().all(|rows| rows.is_none()) {
+            return node;
+        }
+
+        let (mut sources, mut predicates) = (Vec::new(), Vec::new());
+        flatten(node.clone(), 0, &mut sources, &mut predicates);
+
+        // Map joined row columns to their source, and find the sources
+        // referenced by each predicate.
+        let source_of = sources
+            .iter()
+            .enumerate()
+            .flat_map(|(i, source)| std::iter::repeat_n(i, source.columns()))
+            .collect_vec();
+        let references = predicates
+            .iter()
+            .map(|expr| {
+                let mut references = HashSet::new();
+                expr.walk(&mut |expr| {
+                    if let Expression::Column(index) = expr {
+                        references.insert(source_of[*index]);
+                    }
+                    true
+                });
+                references
+            })
+            .collect_vec();
+
+        // Greedily pick the smallest source, preferring sources connected to
+        // the joined sources via a predicate. Unknown row counts are larger
+        // than any known row count. Ties retain the original order.
+        let mut order: Vec<usize> = Vec::with_capacity(sources.len());
+        let mut remaining: Vec<usize> = (0..sources.len()).collect();
+        while !remaining.is_empty() {
+            let connected = remaining
+                .iter()
+                .copied()
+                .filter(|i| {
+                    references
+                        .iter()
+                        .any(|refs| refs.contains(i) && order.iter().any(|j| refs.contains(j)))
+                })
+                .collect_vec();
+            let candidates = if connected.is_empty() { &remaining } else { &connected };
+            let next = *candidates
+                .iter()
+                .min_by_key(|i| (estimates[**i].is_none(), estimates[**i]))
+                .expect("no candidates");
+            remaining.retain(|i| *i != next);
+            order.push(next);
+        }
+        if order.iter().copied().eq(0..sources.len()) {
+            return node;
+        }
+
+        // Build a left-deep join tree in the new order. Each predicate is
+        // placed in the first join where all of its sources are available,
+        // with column references remapped to the new joined row.
+        let offsets = sources
+            .iter()
+            .scan(0, |offset, source| {
+                let start = *offset;
+                *offset += source.columns();
+                Some(start)
+            })
+            .collect_vec();
+        let mut targets = Vec::with_capacity(source_of.len()); // new → original column
+        let mut columns = vec![0; source_of.len()]; // original → new column
+        let mut sources = sources.into_iter().map(Some).collect_vec();
+        let mut predicates = predicates.into_iter().map(Some).collect_vec();
+        let mut joined = HashSet::new();
+        let mut result: Option<Node> = None;
+        for i in order {
+            let source = sources[i].take().expect("source already joined");
+            for column in offsets[i]..offsets[i] + source.columns() {
+                columns[column] = targets.len();
+                targets.push(Some(column));
+            }
+            joined.insert(i);
+            let Some(left) = result.take() else {
+                result = Some(source);
+                continue;
+            };
+            let predicate = Expression::and_vec(
+                predicates
+                    .iter_mut()
+                    .zip(&references)
+                    .filter(|(expr, refs)| expr.is_some() && refs.is_subset(&joined))
+                    .map(|(expr, _)| expr.take().expect("predicate already used"))
+                    .map(|expr| {
+                        let xform = |expr| match expr {
+                            Expression::Column(i) => Expression::Column(columns[i]),
+                            expr => expr,
+                        };
+                        expr.transform(&|e| Ok(xform(e)), &Ok).unwrap() // infallible
+                    })
+                    .collect(),
+            );
+            result = Some(Node::NestedLoopJoin {
+                left: Box::new(left),
+                right: Box::new(source),
+                predicate,
+                outer: false,
+            });
+        }
+        let source = result.expect("no join sources");
+        Node::Remap { source: Box::new(source), targets }
+    };
+    node.transform(&|node| Ok(xform(node)), &Ok)
+}
+
 /// Uses a hash join instead of a nested loop join for single-column equijoins.
+/// The hash table is built from the right source, so for inner joins the
+/// sources are swapped if the left source is estimated to be smaller.
 pub fn join_type(node: Node) -> Result<Node> {
     let xform = |node| match node {
         Node::NestedLoopJoin {
@@ -277,7 +599,31 @@ pub fn join_type(node: Node) -> Result<Node> {
                 // joined row, while the HashJoin uses column indexes in each
                 // individual table. Adjust the RHS column reference.
                 right_column -= left.columns();
-                Node::HashJoin { left, left_column, right, right_column, outer }
+
+                // If the left source of an inner join is smaller, build the
+                // hash table from it instead, and remap the joined columns
+                // back to the original order. Outer joins must retain all
+                // left rows, so they can't be swapped.
+                let (left_rows, right_rows) = (estimate_rows(&left), estimate_rows(&right));
+                let smaller_left = match (left_rows, right_rows) {
+                    (Some(left_rows), Some(right_rows)) => left_rows < right_rows,
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                };
+                if outer || !smaller_left {
+                    return Node::HashJoin { left, left_column, right, right_column, outer };
+                }
+                let (left_size, right_size) = (left.columns(), right.columns());
+                let targets = (0..right_size).map(|i| Some(left_size + i));
+                let targets = targets.chain((0..left_size).map(Some)).collect();
+                let source = Node::HashJoin {
+                    left: right,
+                    left_column: right_column,
+                    right: left,
+                    right_column: left_column,
+                    outer,
+                };
+                Node::Remap { source: source.into(), targets }
             }
             (lhs, rhs) => {
                 let predicate = Some(Expression::Equal(lhs.into(), rhs.into()));
@@ -326,19 +672,28 @@ pub fn short_circuit(node: Node) -> Result<Node> {
             nothing(node)
         }
         ref node @ Node::Values { ref rows } if rows.is_empty() => nothing(node),
+        ref node @ Node::Union { ref left, ref right, .. }
+            if matches!(**left, Node::Nothing { .. })
+                && matches!(**right, Node::Nothing { .. }) =>
+        {
+            nothing(node)
+        }
 
         // Short-circuit nodes that pull from a Nothing node.
         //
         // NB: does not short-circuit aggregation, since an aggregation over 0
         // rows should produce a result.
-        ref node @ (Node::Filter { ref source, .. }
+        ref node @ (Node::Distinct { ref source }
+        | Node::Filter { ref source, .. }
         | Node::HashJoin { left: ref source, .. }
         | Node::HashJoin { right: ref source, .. }
+        | Node::Lock { ref source, .. }
         | Node::NestedLoopJoin { left: ref source, .. }
         | Node::NestedLoopJoin { right: ref source, .. }
         | Node::Offset { ref source, .. }
         | Node::Order { ref source, .. }
-        | Node::Projection { ref source, .. })
+        | Node::Projection { ref source, .. }
+        | Node::Window { ref source, .. })
             if matches!(**source, Node::Nothing { .. }) =>
         {
             nothing(node)
@@ -362,3 +717,29 @@ pub fn short_circuit(node: Node) -> Result<Node> {
     // Transform after descending, to pull Nothing nodes upwards.
     node.transform(&Ok, &|node| Ok(xform(node)))
 }
+
+/// Pushes limits into Order nodes, such that they only need to buffer and sort
+/// the first limit rows (i.e. a top-N sort) instead of the entire row set. An
+/// Offset in between is retained, but its rows are included in the Order limit.
+pub fn push_limits(node: Node) -> Result<Node> {
+    let xform = |node| match node {
+        Node::Limit { source, limit } => match *source {
+            Node::Order { source, key, limit: order_limit } => {
+                let limit = order_limit.map_or(limit, |l| l.min(limit));
+                Node::Order { source, key, limit: Some(limit) }
+            }
+            Node::Offset { source, offset } if matches!(*source, Node::Order { .. }) => {
+                let Node::Order { source, key, limit: order_limit } = *source else {
+                    unreachable!()
+                };
+                let limit = offset.saturating_add(limit);
+                let limit = order_limit.map_or(limit, |l| l.min(limit));
+                let source = Box::new(Node::Order { source, key, limit: Some(limit) });
+                Node::Offset { source, offset }
+            }
+            source => Node::Limit { source: Box::new(source), limit },
+        },
+        node => node,
+    };
+    node.transform(&Ok, &|node| Ok(xform(node)))
+}
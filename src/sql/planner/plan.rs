@@ -1,14 +1,17 @@
 use super::optimizer::OPTIMIZERS;
 use super::planner::Planner;
+use crate::errinput;
 use crate::error::Result;
 use crate::sql::engine::{Catalog, Transaction};
-use crate::sql::execution::{self, ExecutionResult};
+use crate::sql::execution::{self, Context, ExecutionResult, NodeStats, Profile};
 use crate::sql::parser::ast;
-use crate::sql::types::{Expression, Label, Table, Value};
+use crate::sql::types::{format_id, Column, DataType, Expression, Label, Row, Table, Value};
 
 use itertools::Itertools as _;
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::collections::HashMap;
+use std::time::Duration;
 
 /// A statement execution plan. The root nodes can perform data modifications or
 /// schema changes, in addition to SELECT queries. Beyond the root, the plan is
@@ -34,25 +37,89 @@ use std::collections::HashMap;
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Plan {
     /// A CREATE TABLE plan. Creates a new table with the given schema. Errors
-    /// if the table already exists or the schema is invalid.
-    CreateTable { schema: Table },
+    /// if the table already exists, unless if_not_exists is true, or if the
+    /// schema is invalid.
+    CreateTable { schema: Table, if_not_exists: bool },
+    /// A CREATE TABLE AS plan. Creates a new table with the given column names
+    /// and inserts the rows from source. Column datatypes are taken from the
+    /// source table columns if known, otherwise inferred from the source rows
+    /// during execution. The first column is the primary key. If the table
+    /// already exists and if_not_exists is true, source isn't executed.
+    CreateTableAs {
+        name: String,
+        columns: Vec<(String, Option<DataType>)>,
+        source: Node,
+        if_not_exists: bool,
+    },
     /// A DROP TABLE plan. Drops the given table. Errors if the table does not
     /// exist, unless if_exists is true.
-    DropTable { table: String, if_exists: bool },
+    DropTable { table: String, if_exists: bool, cascade: bool },
+    /// An ALTER TABLE ADD COLUMN plan. Appends the column to the table schema.
+    /// Existing rows are not rewritten, but take on the column's default value.
+    AddColumn { table: String, column: Column },
+    /// An ALTER TABLE DROP COLUMN plan. Removes the column from the table
+    /// schema and existing rows, along with any index. Errors if the column
+    /// is the primary key or a foreign key.
+    DropColumn { table: String, column: String },
+    /// A CREATE INDEX plan. Creates a secondary index on the given table
+    /// column, with an optional index name, and indexes existing rows. Errors
+    /// if the index or index name already exists.
+    CreateIndex { name: Option<String>, table: String, column: String },
+    /// A DROP INDEX plan. Drops the secondary index with the given name, or on
+    /// the given table column if table is set (name is then the column).
+    /// Errors if the index does not exist, unless if_exists is true, or if the
+    /// column is unique or a foreign key (which require an index).
+    DropIndex { table: Option<String>, name: String, if_exists: bool },
+    /// A COPY FROM plan. Reads rows from the given CSV file and inserts them
+    /// into table. The first line is a header with column names. Table columns
+    /// not present in the file will get the column's default value if set, or
+    /// error.
+    CopyFrom { table: Table, path: String },
+    /// A COPY TO plan. Streams rows from source into the given file, as JSON if
+    /// the path has a .json extension or CSV otherwise.
+    CopyTo { source: Node, path: String },
     /// A DELETE plan. Deletes rows in table that match the rows from source.
-    /// primary_key specifies the primary key column index in the source rows.
-    Delete { table: String, primary_key: usize, source: Node },
+    /// primary_key specifies the primary key column indexes in the source rows.
+    ///
+    /// For DELETE, INSERT, and UPDATE, a non-empty returning list evaluates
+    /// the given expressions against each affected table row (the deleted row,
+    /// or the inserted or updated row) and returns them as a query result with
+    /// the given column labels, instead of a row count (i.e. RETURNING).
+    Delete {
+        table: String,
+        primary_key: Vec<usize>,
+        source: Node,
+        returning: Vec<(Expression, Label)>,
+    },
     /// An INSERT plan. Inserts rows from source (typically a Values node) into
     /// table. If column_map is given, it maps table → source column indexes and
     /// must have one entry for every column in source. Table columns not
     /// present in source will get the column's default value if set, or error.
-    Insert { table: Table, column_map: Option<HashMap<usize, usize>>, source: Node },
+    Insert {
+        table: Table,
+        column_map: Option<HashMap<usize, usize>>,
+        source: Node,
+        returning: Vec<(Expression, Label)>,
+    },
     /// An UPDATE plan. Updates rows in table that match the rows from source,
-    /// where primary_key specifies the primary key column index in the source
+    /// where primary_key specifies the primary key column indexes in the source
     /// rows. The given column/expression pairs specify the row updates to make,
     /// evaluated using the existing source row, which must be a complete row
     /// from the update table.
-    Update { table: Table, primary_key: usize, source: Node, expressions: Vec<(usize, Expression)> },
+    Update {
+        table: Table,
+        primary_key: Vec<usize>,
+        source: Node,
+        expressions: Vec<(usize, Expression)>,
+        returning: Vec<(Expression, Label)>,
+    },
+    /// A TRUNCATE plan. Deletes all rows in the table, along with their index
+    /// entries, without reading them. Errors if other tables have rows that
+    /// reference the table.
+    Truncate { table: String },
+    /// An ANALYZE plan. Recomputes exact table statistics by scanning the
+    /// table, replacing the statistics maintained by writes.
+    Analyze { table: String },
     /// A SELECT plan. Recursively executes the query plan tree and returns the
     /// resulting rows.
     Select(Node),
@@ -64,29 +131,201 @@ impl Plan {
         Planner::new(catalog).build(statement)
     }
 
-    /// Executes the plan, consuming it.
-    pub fn execute(self, txn: &(impl Transaction + Catalog)) -> Result<ExecutionResult> {
-        execution::execute_plan(self, txn, txn)
+    /// Executes the plan in the given context, consuming it.
+    pub fn execute(
+        self,
+        txn: &(impl Transaction + Catalog),
+        ctx: &Context,
+    ) -> Result<ExecutionResult> {
+        execution::execute_plan(self, txn, txn, ctx)
+    }
+
+    /// Executes a SELECT plan with profiling, discarding the result rows and
+    /// returning per-node execution statistics (i.e. EXPLAIN ANALYZE).
+    pub fn profile(self, txn: &impl Transaction, ctx: &Context) -> Result<Profile> {
+        execution::execute_profiled(self, txn, ctx)
+    }
+
+    /// Formats the plan as an EXPLAIN ANALYZE tree, annotating each node with
+    /// its execution statistics from the given profile.
+    pub fn format_profile(&self, profile: &Profile) -> String {
+        struct Profiled<'a>(&'a Node, &'a Profile);
+
+        impl std::fmt::Display for Profiled<'_> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                let Self(root, profile) = self;
+                root.format(f, "", true, true, &mut profile.nodes.iter())?;
+                write!(f, "\nTotal: {}", format_duration(profile.total))
+            }
+        }
+
+        match self {
+            Self::Select(root) => Profiled(root, profile).to_string(),
+            plan => plan.to_string(),
+        }
     }
 
     /// Optimizes the plan, consuming it. See OPTIMIZERS for the list of
     /// optimizers.
     pub fn optimize(self) -> Result<Self> {
-        let optimize = |node| OPTIMIZERS.iter().try_fold(node, |node, (_, opt)| opt(node));
+        // Optimizes a node, including any subquery plans in its expressions.
+        fn optimize(node: Node) -> Result<Node> {
+            let node =
+                node.transform(&|n| n.transform_expressions(&Ok, &optimize_subquery), &Ok)?;
+            OPTIMIZERS.iter().try_fold(node, |node, (_, opt)| opt(node))
+        }
+        fn optimize_subquery(expr: Expression) -> Result<Expression> {
+            match expr {
+                Expression::Subquery(node, outer) => {
+                    Ok(Expression::Subquery(optimize(*node)?.into(), outer))
+                }
+                Expression::Exists(node, outer) => {
+                    Ok(Expression::Exists(optimize(*node)?.into(), outer))
+                }
+                expr => Ok(expr),
+            }
+        }
+        fn optimize_returning(
+            returning: Vec<(Expression, Label)>,
+        ) -> Result<Vec<(Expression, Label)>> {
+            returning
+                .into_iter()
+                .map(|(expr, label)| Ok((expr.transform(&Ok, &optimize_subquery)?, label)))
+                .collect()
+        }
+
         Ok(match self {
-            Self::CreateTable { .. } | Self::DropTable { .. } => self,
-            Self::Delete { table, primary_key, source } => {
-                Self::Delete { table, primary_key, source: optimize(source)? }
+            Self::CreateTable { .. }
+            | Self::DropTable { .. }
+            | Self::AddColumn { .. }
+            | Self::DropColumn { .. }
+            | Self::CreateIndex { .. }
+            | Self::DropIndex { .. }
+            | Self::CopyFrom { .. }
+            | Self::Truncate { .. }
+            | Self::Analyze { .. } => self,
+            Self::CreateTableAs { name, columns, source, if_not_exists } => {
+                Self::CreateTableAs { name, columns, source: optimize(source)?, if_not_exists }
             }
-            Self::Insert { table, column_map, source } => {
-                Self::Insert { table, column_map, source: optimize(source)? }
+            Self::CopyTo { source, path } => Self::CopyTo { source: optimize(source)?, path },
+            Self::Delete { table, primary_key, source, returning } => {
+                let returning = optimize_returning(returning)?;
+                Self::Delete { table, primary_key, source: optimize(source)?, returning }
             }
-            Self::Update { table, primary_key, source, expressions } => {
-                Self::Update { table, primary_key, source: optimize(source)?, expressions }
+            Self::Insert { table, column_map, source, returning } => {
+                let returning = optimize_returning(returning)?;
+                Self::Insert { table, column_map, source: optimize(source)?, returning }
+            }
+            Self::Update { table, primary_key, source, expressions, returning } => {
+                let expressions = expressions
+                    .into_iter()
+                    .map(|(i, expr)| Ok((i, expr.transform(&Ok, &optimize_subquery)?)))
+                    .collect::<Result<_>>()?;
+                let returning = optimize_returning(returning)?;
+                let source = optimize(source)?;
+                Self::Update { table, primary_key, source, expressions, returning }
             }
             Self::Select(root) => Self::Select(optimize(root)?),
         })
     }
+
+    /// Binds the plan's query parameters ($1, $2, ...) to the given values,
+    /// replacing them with constants. Errors if the number of values doesn't
+    /// match the highest parameter number in the plan. Should be called before
+    /// optimizing the plan, such that the optimizers can make use of the
+    /// parameter values.
+    pub fn bind(self, params: &[Value]) -> Result<Self> {
+        // Binds a node's parameters, including in any subquery plans. Records
+        // the highest parameter number seen in count.
+        fn bind(node: Node, params: &[Value], count: &Cell<usize>) -> Result<Node> {
+            node.transform(&|n| n.transform_expressions(&Ok, &|e| bind_expr(e, params, count)), &Ok)
+        }
+        fn bind_expr(
+            expr: Expression,
+            params: &[Value],
+            count: &Cell<usize>,
+        ) -> Result<Expression> {
+            Ok(match expr {
+                Expression::Parameter(index) => {
+                    count.set(count.get().max(index));
+                    Expression::Constant(params.get(index - 1).cloned().unwrap_or(Value::Null))
+                }
+                Expression::Subquery(node, outer) => {
+                    Expression::Subquery(bind(*node, params, count)?.into(), outer)
+                }
+                Expression::Exists(node, outer) => {
+                    Expression::Exists(bind(*node, params, count)?.into(), outer)
+                }
+                expr => expr,
+            })
+        }
+        fn bind_returning(
+            returning: Vec<(Expression, Label)>,
+            params: &[Value],
+            count: &Cell<usize>,
+        ) -> Result<Vec<(Expression, Label)>> {
+            returning
+                .into_iter()
+                .map(|(expr, label)| {
+                    Ok((expr.transform(&Ok, &|e| bind_expr(e, params, count))?, label))
+                })
+                .collect()
+        }
+
+        let count = Cell::new(0);
+        let plan = match self {
+            Self::CreateTable { .. }
+            | Self::DropTable { .. }
+            | Self::AddColumn { .. }
+            | Self::DropColumn { .. }
+            | Self::CreateIndex { .. }
+            | Self::DropIndex { .. }
+            | Self::CopyFrom { .. }
+            | Self::Truncate { .. }
+            | Self::Analyze { .. } => self,
+            Self::CreateTableAs { name, columns, source, if_not_exists } => {
+                let source = bind(source, params, &count)?;
+                Self::CreateTableAs { name, columns, source, if_not_exists }
+            }
+            Self::CopyTo { source, path } => {
+                Self::CopyTo { source: bind(source, params, &count)?, path }
+            }
+            Self::Delete { table, primary_key, source, returning } => {
+                let returning = bind_returning(returning, params, &count)?;
+                Self::Delete {
+                    table,
+                    primary_key,
+                    source: bind(source, params, &count)?,
+                    returning,
+                }
+            }
+            Self::Insert { table, column_map, source, returning } => {
+                let returning = bind_returning(returning, params, &count)?;
+                Self::Insert { table, column_map, source: bind(source, params, &count)?, returning }
+            }
+            Self::Update { table, primary_key, source, expressions, returning } => {
+                let expressions = expressions
+                    .into_iter()
+                    .map(|(i, expr)| {
+                        Ok((i, expr.transform(&Ok, &|e| bind_expr(e, params, &count))?))
+                    })
+                    .collect::<Result<_>>()?;
+                let returning = bind_returning(returning, params, &count)?;
+                Self::Update {
+                    table,
+                    primary_key,
+                    source: bind(source, params, &count)?,
+                    expressions,
+                    returning,
+                }
+            }
+            Self::Select(root) => Self::Select(bind(root, params, &count)?),
+        };
+        if count.get() != params.len() {
+            return errinput!("expected {} parameters, got {}", count.get(), params.len());
+        }
+        Ok(plan)
+    }
 }
 
 /// A query plan node. Returns a row iterator, and can be nested.
@@ -96,6 +335,10 @@ pub enum Node {
     /// across all rows in the source node. The group_by columns are emitted
     /// first, followed by the aggregate columns, in the given order.
     Aggregate { source: Box<Node>, group_by: Vec<Expression>, aggregates: Vec<Aggregate> },
+    /// Discards duplicate rows from the source, only emitting the first
+    /// occurrence of each row. NULLs are considered equal for this purpose.
+    /// Buffers all seen rows in memory.
+    Distinct { source: Box<Node> },
     /// Filters source rows, by discarding rows for which the predicate
     /// evaluates to false.
     Filter { source: Box<Node>, predicate: Expression },
@@ -114,10 +357,16 @@ pub enum Node {
     /// NULL and NaN values are considered equal, to allow IS NULL and IS NAN
     /// index lookups, as is -0.0 and 0.0.
     IndexLookup { table: Table, column: usize, values: Vec<Value>, alias: Option<String> },
-    /// Looks up the given primary keys and emits their rows.
-    KeyLookup { table: Table, keys: Vec<Value>, alias: Option<String> },
+    /// Looks up the given primary keys and emits their rows. Each key is the
+    /// values of the primary key columns, in key order.
+    KeyLookup { table: Table, keys: Vec<Vec<Value>>, alias: Option<String> },
     /// Only emits the first limit rows from the source, discards the rest.
     Limit { source: Box<Node>, limit: usize },
+    /// Locks the source rows of the given table (i.e. SELECT FOR UPDATE) by
+    /// writing them back unchanged, such that concurrent writes to them will
+    /// conflict. Buffers the entire row set in memory, since we can't write
+    /// while scanning.
+    Lock { source: Box<Node>, table: Table },
     /// Joins the left and right sources on the given predicate by buffering the
     /// right source and iterating over it for every row in the left source.
     /// When outer is true (e.g. LEFT JOIN), a left row without a right match is
@@ -130,8 +379,10 @@ pub enum Node {
     /// Discards the first offset rows from source, emits the rest.
     Offset { source: Box<Node>, offset: usize },
     /// Sorts the source rows by the given sort key. Buffers the entire row set
-    /// in memory.
-    Order { source: Box<Node>, key: Vec<(Expression, Direction)> },
+    /// in memory. If limit is given, only the first limit rows are emitted, and
+    /// only that many rows are buffered (i.e. a top-N sort). This is set by the
+    /// optimizer when fusing a Limit into the Order.
+    Order { source: Box<Node>, key: Vec<(Expression, Direction, Nulls)>, limit: Option<usize> },
     /// Projects the input rows by evaluating the given expressions. Aliases are
     /// only used when displaying the plan.
     Projection { source: Box<Node>, expressions: Vec<Expression>, aliases: Vec<Label> },
@@ -143,8 +394,22 @@ pub enum Node {
     /// A full table scan, with an optional pushed-down filter. The schema is
     /// used during plan optimization. The alias is only used for formatting.
     Scan { table: Table, filter: Option<Expression>, alias: Option<String> },
+    /// A scan of an information_schema virtual table, e.g.
+    /// information_schema.tables. The table describes the virtual table's
+    /// columns. The rows are read from the catalog's table schemas during
+    /// planning, since the catalog isn't used during execution.
+    SchemaScan { table: Table, alias: Option<String>, rows: Vec<Row> },
+    /// Emits all rows from the left source followed by all rows from the right
+    /// source, which must have the same number of columns. Unless all is true
+    /// (i.e. UNION rather than UNION ALL), duplicate rows are discarded like
+    /// Distinct. Column names are taken from the left source.
+    Union { left: Box<Node>, right: Box<Node>, all: bool },
     /// A constant set of values.
     Values { rows: Vec<Vec<Expression>> },
+    /// Computes window functions across the source rows, emitting the source
+    /// columns followed by one column per window function. Buffers the entire
+    /// row set in memory, and emits rows in the last function's window order.
+    Window { source: Box<Node>, functions: Vec<Window> },
 }
 
 impl Node {
@@ -154,11 +419,13 @@ impl Node {
             // Source nodes emit all table columns.
             Self::IndexLookup { table, .. }
             | Self::KeyLookup { table, .. }
-            | Self::Scan { table, .. } => table.columns.len(),
+            | Self::Scan { table, .. }
+            | Self::SchemaScan { table, .. } => table.columns.len(),
 
             // Some nodes modify the column set.
             Self::Aggregate { aggregates, group_by, .. } => aggregates.len() + group_by.len(),
             Self::Projection { expressions, .. } => expressions.len(),
+            Self::Window { source, functions } => source.columns() + functions.len(),
             Self::Remap { targets, .. } => {
                 targets.iter().filter_map(|v| *v).map(|i| i + 1).max().unwrap_or(0)
             }
@@ -169,11 +436,17 @@ impl Node {
             }
 
             // Simple nodes just pass through the source columns.
-            Self::Filter { source, .. }
+            Self::Distinct { source }
+            | Self::Filter { source, .. }
             | Self::Limit { source, .. }
+            | Self::Lock { source, .. }
             | Self::Offset { source, .. }
             | Self::Order { source, .. } => source.columns(),
 
+            // Unions emit the left source's columns, and the right source
+            // must match them.
+            Self::Union { left, .. } => left.columns(),
+
             // And some are trivial.
             Self::Nothing { columns } => columns.len(),
             Self::Values { rows } => rows.first().map(|row| row.len()).unwrap_or(0),
@@ -188,7 +461,8 @@ impl Node {
             // Source nodes use the table/column name.
             Self::IndexLookup { table, alias, .. }
             | Self::KeyLookup { table, alias, .. }
-            | Self::Scan { table, alias, .. } => Label::Qualified(
+            | Self::Scan { table, alias, .. }
+            | Self::SchemaScan { table, alias, .. } => Label::Qualified(
                 alias.as_ref().unwrap_or(&table.name).clone(),
                 table.columns[index].name.clone(),
             ),
@@ -214,6 +488,10 @@ impl Node {
                 .position(|t| t == &Some(index))
                 .map(|i| source.column_label(i))
                 .unwrap_or(Label::None),
+            Self::Window { source, .. } => match index < source.columns() {
+                true => source.column_label(index),
+                false => Label::None,
+            },
 
             // Joins dispatch to the appropriate source.
             Self::HashJoin { left, right, .. } | Self::NestedLoopJoin { left, right, .. } => {
@@ -225,16 +503,79 @@ impl Node {
             }
 
             // Simple nodes just dispatch to the source.
-            Self::Filter { source, .. }
+            Self::Distinct { source }
+            | Self::Filter { source, .. }
             | Self::Limit { source, .. }
+            | Self::Lock { source, .. }
             | Self::Offset { source, .. }
             | Self::Order { source, .. } => source.column_label(index),
 
+            // Unions use the left source's column names.
+            Self::Union { left, .. } => left.column_label(index),
+
             // Nothing nodes contain the original columns of replaced nodes.
             Self::Nothing { columns } => columns.get(index).cloned().unwrap_or(Label::None),
 
-            // And some don't have any names at all.
-            Self::Values { .. } => Label::None,
+            // Values columns are named column1, column2, etc.
+            Self::Values { .. } => Label::Unqualified(format!("column{}", index + 1)),
+        }
+    }
+
+    /// Returns a column's datatype, if known, by tracing the column through the
+    /// plan tree to a table column. The datatypes of computed expressions
+    /// aren't known until execution.
+    pub fn column_datatype(&self, index: usize) -> Option<DataType> {
+        match self {
+            // Source nodes use the table column's datatype.
+            Self::IndexLookup { table, .. }
+            | Self::KeyLookup { table, .. }
+            | Self::Scan { table, .. }
+            | Self::SchemaScan { table, .. } => Some(table.columns[index].datatype),
+
+            // Column references route to the source column.
+            Self::Aggregate { source, group_by, .. } => match group_by.get(index) {
+                Some(Expression::Column(index)) => source.column_datatype(*index),
+                Some(_) | None => None,
+            },
+            Self::Projection { source, expressions, .. } => match expressions.get(index) {
+                Some(Expression::Column(index)) => source.column_datatype(*index),
+                Some(Expression::Cast(_, datatype)) => Some(*datatype),
+                Some(_) | None => None,
+            },
+            Self::Remap { source, targets } => targets
+                .iter()
+                .position(|t| t == &Some(index))
+                .and_then(|i| source.column_datatype(i)),
+            Self::Window { source, .. } => match index < source.columns() {
+                true => source.column_datatype(index),
+                false => Some(DataType::Integer),
+            },
+
+            // Joins dispatch to the appropriate source.
+            Self::HashJoin { left, right, .. } | Self::NestedLoopJoin { left, right, .. } => {
+                if index < left.columns() {
+                    left.column_datatype(index)
+                } else {
+                    right.column_datatype(index - left.columns())
+                }
+            }
+
+            // Simple nodes just dispatch to the source.
+            Self::Distinct { source }
+            | Self::Filter { source, .. }
+            | Self::Limit { source, .. }
+            | Self::Lock { source, .. }
+            | Self::Offset { source, .. }
+            | Self::Order { source, .. } => source.column_datatype(index),
+
+            // Unions may mix numeric types, so only use matching datatypes.
+            Self::Union { left, right, .. } => {
+                let datatype = left.column_datatype(index)?;
+                (right.column_datatype(index)? == datatype).then_some(datatype)
+            }
+
+            // The rest are unknown.
+            Self::Nothing { .. } | Self::Values { .. } => None,
         }
     }
 
@@ -256,6 +597,7 @@ impl Node {
             Self::Aggregate { source, group_by, aggregates } => {
                 Self::Aggregate { source: xform(source)?, group_by, aggregates }
             }
+            Self::Distinct { source } => Self::Distinct { source: xform(source)? },
             Self::Filter { source, predicate } => {
                 Self::Filter { source: xform(source)?, predicate }
             }
@@ -267,20 +609,30 @@ impl Node {
                 outer,
             },
             Self::Limit { source, limit } => Self::Limit { source: xform(source)?, limit },
+            Self::Lock { source, table } => Self::Lock { source: xform(source)?, table },
             Self::NestedLoopJoin { left, right, predicate, outer } => {
                 Self::NestedLoopJoin { left: xform(left)?, right: xform(right)?, predicate, outer }
             }
             Self::Offset { source, offset } => Self::Offset { source: xform(source)?, offset },
-            Self::Order { source, key } => Self::Order { source: xform(source)?, key },
+            Self::Order { source, key, limit } => {
+                Self::Order { source: xform(source)?, key, limit }
+            }
             Self::Projection { source, expressions, aliases } => {
                 Self::Projection { source: xform(source)?, expressions, aliases }
             }
             Self::Remap { source, targets } => Self::Remap { source: xform(source)?, targets },
+            Self::Union { left, right, all } => {
+                Self::Union { left: xform(left)?, right: xform(right)?, all }
+            }
+            Self::Window { source, functions } => {
+                Self::Window { source: xform(source)?, functions }
+            }
 
             Self::IndexLookup { .. }
             | Self::KeyLookup { .. }
             | Self::Nothing { .. }
             | Self::Scan { .. }
+            | Self::SchemaScan { .. }
             | Self::Values { .. } => self,
         };
         self = after(self)?;
@@ -295,6 +647,13 @@ impl Node {
         after: &impl Fn(Expression) -> Result<Expression>,
     ) -> Result<Self> {
         Ok(match self {
+            Self::Aggregate { source, mut group_by, mut aggregates } => {
+                group_by =
+                    group_by.into_iter().map(|expr| expr.transform(before, after)).try_collect()?;
+                aggregates =
+                    aggregates.into_iter().map(|agg| agg.transform(before, after)).try_collect()?;
+                Self::Aggregate { source, group_by, aggregates }
+            }
             Self::Filter { source, mut predicate } => {
                 predicate = predicate.transform(before, after)?;
                 Self::Filter { source, predicate }
@@ -303,12 +662,12 @@ impl Node {
                 let predicate = Some(predicate.transform(before, after)?);
                 Self::NestedLoopJoin { left, right, predicate, outer }
             }
-            Self::Order { source, mut key } => {
+            Self::Order { source, mut key, limit } => {
                 key = key
                     .into_iter()
-                    .map(|(expr, dir)| Ok((expr.transform(before, after)?, dir)))
+                    .map(|(expr, dir, nulls)| Ok((expr.transform(before, after)?, dir, nulls)))
                     .collect::<Result<_>>()?;
-                Self::Order { source, key }
+                Self::Order { source, key, limit }
             }
             Self::Projection { source, mut expressions, aliases } => {
                 expressions = expressions
@@ -328,19 +687,94 @@ impl Node {
                     .try_collect()?;
                 Self::Values { rows }
             }
+            Self::Window { source, mut functions } => {
+                functions = functions
+                    .into_iter()
+                    .map(|window| window.transform(before, after))
+                    .try_collect()?;
+                Self::Window { source, functions }
+            }
 
-            Self::Aggregate { .. }
+            Self::Distinct { .. }
             | Self::HashJoin { .. }
             | Self::IndexLookup { .. }
             | Self::KeyLookup { .. }
             | Self::Limit { .. }
+            | Self::Lock { .. }
             | Self::NestedLoopJoin { predicate: None, .. }
             | Self::Nothing { .. }
             | Self::Offset { .. }
             | Self::Remap { .. }
-            | Self::Scan { filter: None, .. } => self,
+            | Self::Scan { filter: None, .. }
+            | Self::SchemaScan { .. }
+            | Self::Union { .. } => self,
         })
     }
+
+    /// Returns the node's child nodes, in execution order.
+    pub fn children(&self) -> Vec<&Node> {
+        match self {
+            Self::Aggregate { source, .. }
+            | Self::Distinct { source }
+            | Self::Filter { source, .. }
+            | Self::Limit { source, .. }
+            | Self::Lock { source, .. }
+            | Self::Offset { source, .. }
+            | Self::Order { source, .. }
+            | Self::Projection { source, .. }
+            | Self::Remap { source, .. }
+            | Self::Window { source, .. } => vec![source],
+            Self::HashJoin { left, right, .. }
+            | Self::NestedLoopJoin { left, right, .. }
+            | Self::Union { left, right, .. } => vec![left, right],
+            Self::IndexLookup { .. }
+            | Self::KeyLookup { .. }
+            | Self::Nothing { .. }
+            | Self::Scan { .. }
+            | Self::SchemaScan { .. }
+            | Self::Values { .. } => Vec::new(),
+        }
+    }
+
+    /// Returns the subquery plans in the node's expressions, but not in its
+    /// child nodes.
+    pub fn subqueries(&self) -> Vec<&Node> {
+        let expressions: Vec<&Expression> = match self {
+            Self::Aggregate { group_by, aggregates, .. } => {
+                group_by.iter().chain(aggregates.iter().map(|agg| agg.expression())).collect()
+            }
+            Self::Filter { predicate, .. } => vec![predicate],
+            Self::NestedLoopJoin { predicate, .. } => predicate.iter().collect(),
+            Self::Order { key, .. } => key.iter().map(|(expr, _, _)| expr).collect(),
+            Self::Projection { expressions, .. } => expressions.iter().collect(),
+            Self::Scan { filter, .. } => filter.iter().collect(),
+            Self::Values { rows } => rows.iter().flatten().collect(),
+            Self::Window { functions, .. } => {
+                functions.iter().flat_map(|window| window.expressions()).collect()
+            }
+            Self::Distinct { .. }
+            | Self::HashJoin { .. }
+            | Self::IndexLookup { .. }
+            | Self::KeyLookup { .. }
+            | Self::Limit { .. }
+            | Self::Lock { .. }
+            | Self::Nothing { .. }
+            | Self::Offset { .. }
+            | Self::Remap { .. }
+            | Self::SchemaScan { .. }
+            | Self::Union { .. } => Vec::new(),
+        };
+        let mut subqueries = Vec::new();
+        for expr in expressions {
+            expr.walk(&mut |expr| {
+                if let Expression::Subquery(node, _) | Expression::Exists(node, _) = expr {
+                    subqueries.push(node.as_ref());
+                }
+                true
+            });
+        }
+        subqueries
+    }
 }
 
 /// An aggregate function.
@@ -351,16 +785,136 @@ pub enum Aggregate {
     Max(Expression),
     Min(Expression),
     Sum(Expression),
+    /// Only passes distinct non-NULL values to the inner aggregate.
+    Distinct(Box<Aggregate>),
 }
 
 impl Aggregate {
+    /// Returns the aggregate function's argument expression.
+    pub fn expression(&self) -> &Expression {
+        match self {
+            Self::Average(expr)
+            | Self::Count(expr)
+            | Self::Max(expr)
+            | Self::Min(expr)
+            | Self::Sum(expr) => expr,
+            Self::Distinct(aggregate) => aggregate.expression(),
+        }
+    }
+
+    /// Returns the aggregate function's name.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Average(_) => "avg",
+            Self::Count(_) => "count",
+            Self::Max(_) => "max",
+            Self::Min(_) => "min",
+            Self::Sum(_) => "sum",
+            Self::Distinct(aggregate) => aggregate.name(),
+        }
+    }
+
+    /// Transforms the aggregate function's argument expression.
+    fn transform(
+        self,
+        before: &impl Fn(Expression) -> Result<Expression>,
+        after: &impl Fn(Expression) -> Result<Expression>,
+    ) -> Result<Self> {
+        Ok(match self {
+            Self::Average(expr) => Self::Average(expr.transform(before, after)?),
+            Self::Count(expr) => Self::Count(expr.transform(before, after)?),
+            Self::Max(expr) => Self::Max(expr.transform(before, after)?),
+            Self::Min(expr) => Self::Min(expr.transform(before, after)?),
+            Self::Sum(expr) => Self::Sum(expr.transform(before, after)?),
+            Self::Distinct(aggregate) => {
+                Self::Distinct(Box::new(aggregate.transform(before, after)?))
+            }
+        })
+    }
+
+    fn format(&self, node: &Node) -> String {
+        let expr = self.expression().format(node);
+        match self {
+            Self::Distinct(_) => format!("{}(distinct {expr})", self.name()),
+            _ => format!("{}({expr})", self.name()),
+        }
+    }
+}
+
+/// A window function, evaluated for each row across the rows of its partition
+/// in the given order. Rows with equal partition_by values share a partition.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Window {
+    pub function: WindowFunction,
+    pub partition_by: Vec<Expression>,
+    pub order_by: Vec<(Expression, Direction, Nulls)>,
+}
+
+/// A window function kind.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum WindowFunction {
+    /// The row's 1-based position within its partition.
+    RowNumber,
+    /// The row number of the row's first peer, i.e. rows with equal sort
+    /// values get the same rank, leaving gaps after ties.
+    Rank,
+    /// Like Rank, but without gaps: the 1-based number of distinct sort values
+    /// up to and including the row's.
+    DenseRank,
+}
+
+impl Window {
+    /// Returns the window's partition and sort expressions.
+    fn expressions(&self) -> impl Iterator<Item = &Expression> {
+        self.partition_by.iter().chain(self.order_by.iter().map(|(expr, _, _)| expr))
+    }
+
+    /// Transforms the window's partition and sort expressions.
+    fn transform(
+        mut self,
+        before: &impl Fn(Expression) -> Result<Expression>,
+        after: &impl Fn(Expression) -> Result<Expression>,
+    ) -> Result<Self> {
+        self.partition_by = self
+            .partition_by
+            .into_iter()
+            .map(|expr| expr.transform(before, after))
+            .try_collect()?;
+        self.order_by = self
+            .order_by
+            .into_iter()
+            .map(|(expr, dir, nulls)| Ok((expr.transform(before, after)?, dir, nulls)))
+            .collect::<Result<_>>()?;
+        Ok(self)
+    }
+
     fn format(&self, node: &Node) -> String {
+        let mut over = Vec::new();
+        if !self.partition_by.is_empty() {
+            let partition_by = self.partition_by.iter().map(|expr| expr.format(node)).join(", ");
+            over.push(format!("partition by {partition_by}"));
+        }
+        if !self.order_by.is_empty() {
+            let order_by = self
+                .order_by
+                .iter()
+                .map(|(expr, dir, nulls)| match nulls == &Nulls::new(dir, None) {
+                    true => format!("{} {dir}", expr.format(node)),
+                    false => format!("{} {dir} {nulls}", expr.format(node)),
+                })
+                .join(", ");
+            over.push(format!("order by {order_by}"));
+        }
+        format!("{}() over ({})", self.function, over.join(" "))
+    }
+}
+
+impl std::fmt::Display for WindowFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Average(expr) => format!("avg({})", expr.format(node)),
-            Self::Count(expr) => format!("count({})", expr.format(node)),
-            Self::Max(expr) => format!("max({})", expr.format(node)),
-            Self::Min(expr) => format!("min({})", expr.format(node)),
-            Self::Sum(expr) => format!("sum({})", expr.format(node)),
+            Self::RowNumber => f.write_str("row_number"),
+            Self::Rank => f.write_str("rank"),
+            Self::DenseRank => f.write_str("dense_rank"),
         }
     }
 }
@@ -390,49 +944,117 @@ impl From<ast::Direction> for Direction {
     }
 }
 
+/// The sort order of NULL values.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Nulls {
+    First,
+    Last,
+}
+
+impl Nulls {
+    /// Resolves the NULL order for the given direction. If not given, NULLs
+    /// are ordered last when ascending and first when descending, like in
+    /// PostgreSQL.
+    pub fn new(dir: &Direction, nulls: Option<ast::Nulls>) -> Self {
+        match (dir, nulls) {
+            (_, Some(ast::Nulls::First)) => Self::First,
+            (_, Some(ast::Nulls::Last)) => Self::Last,
+            (Direction::Ascending, None) => Self::Last,
+            (Direction::Descending, None) => Self::First,
+        }
+    }
+}
+
+impl std::fmt::Display for Nulls {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::First => f.write_str("nulls first"),
+            Self::Last => f.write_str("nulls last"),
+        }
+    }
+}
+
 /// Formats the plan as an EXPLAIN tree.
 impl std::fmt::Display for Plan {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::CreateTable { schema } => write!(f, "CreateTable: {}", schema.name),
-            Self::DropTable { table, .. } => write!(f, "DropTable: {table}"),
-            Self::Delete { table, source, .. } => {
-                write!(f, "Delete: {table}")?;
-                source.format(f, "", false, true)
+            Self::CreateTable { schema, .. } => write!(f, "CreateTable: {}", schema.name),
+            Self::CreateTableAs { name, source, .. } => {
+                write!(f, "CreateTableAs: {name}")?;
+                source.format(f, "", false, true, &mut [].iter())
+            }
+            Self::DropTable { table, cascade: false, .. } => write!(f, "DropTable: {table}"),
+            Self::DropTable { table, cascade: true, .. } => {
+                write!(f, "DropTable: {table} (cascade)")
+            }
+            Self::AddColumn { table, column } => write!(f, "AddColumn: {table}.{}", column.name),
+            Self::DropColumn { table, column } => write!(f, "DropColumn: {table}.{column}"),
+            Self::CreateIndex { name: Some(name), table, column } => {
+                write!(f, "CreateIndex: {name} on {table}.{column}")
+            }
+            Self::CreateIndex { name: None, table, column } => {
+                write!(f, "CreateIndex: {table}.{column}")
+            }
+            Self::DropIndex { table: Some(table), name, .. } => {
+                write!(f, "DropIndex: {table}.{name}")
+            }
+            Self::DropIndex { table: None, name, .. } => write!(f, "DropIndex: {name}"),
+            Self::CopyFrom { table, path } => {
+                write!(f, "CopyFrom: {} ('{}')", table.name, path.escape_debug())
             }
-            Self::Insert { table, source, .. } => {
-                write!(f, "Insert: {}", table.name)?;
-                source.format(f, "", false, true)
+            Self::CopyTo { source, path } => {
+                write!(f, "CopyTo: '{}'", path.escape_debug())?;
+                source.format(f, "", false, true, &mut [].iter())
             }
-            Self::Update { table, source, expressions, .. } => {
+            Self::Delete { table, source, returning, .. } => {
+                write!(f, "Delete: {table}{}", format_returning(returning, source))?;
+                source.format(f, "", false, true, &mut [].iter())
+            }
+            Self::Insert { table, source, returning, .. } => {
+                // The returning expressions refer to the table row, not the
+                // source row.
+                let columns = (0..table.columns.len())
+                    .map(|i| Label::Qualified(table.name.clone(), table.columns[i].name.clone()))
+                    .collect();
+                let returning = format_returning(returning, &Node::Nothing { columns });
+                write!(f, "Insert: {}{returning}", table.name)?;
+                source.format(f, "", false, true, &mut [].iter())
+            }
+            Self::Update { table, source, expressions, returning, .. } => {
                 let expressions = expressions
                     .iter()
                     .map(|(i, expr)| format!("{}={}", table.columns[*i].name, expr.format(source)))
                     .join(", ");
-                write!(f, "Update: {} ({expressions})", table.name)?;
-                source.format(f, "", false, true)
+                let returning = format_returning(returning, source);
+                write!(f, "Update: {} ({expressions}){returning}", table.name)?;
+                source.format(f, "", false, true, &mut [].iter())
             }
-            Self::Select(root) => root.format(f, "", true, true),
+            Self::Truncate { table } => write!(f, "Truncate: {table}"),
+            Self::Analyze { table } => write!(f, "Analyze: {table}"),
+            Self::Select(root) => root.format(f, "", true, true, &mut [].iter()),
         }
     }
 }
 
 impl std::fmt::Display for Node {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.format(f, "", true, true)
+        self.format(f, "", true, true, &mut [].iter())
     }
 }
 
 impl Node {
     /// Recursively formats the node. Prefix is used for tree branch lines. root
     /// is true if this is the root (first) node, and last_child is true if this
-    /// is the last child node of the parent.
-    pub fn format(
+    /// is the last child node of the parent. If given, stats contains execution
+    /// statistics for the node tree in pre-order, which are appended to the
+    /// nodes (see Profile).
+    pub fn format<'a>(
         &self,
         f: &mut std::fmt::Formatter<'_>,
         prefix: &str,
         root: bool,
         last_child: bool,
+        stats: &mut impl Iterator<Item = &'a Option<NodeStats>>,
     ) -> std::fmt::Result {
         // If this is not the root node, emit a newline after the previous node.
         // This avoids a spurious newline at the end of the plan.
@@ -462,11 +1084,12 @@ impl Node {
                     .chain(aggregates.iter().map(|agg| agg.format(source)))
                     .join(", ");
                 write!(f, "Aggregate: {aggregates}")?;
-                source.format(f, &prefix, false, true)?;
+            }
+            Self::Distinct { .. } => {
+                write!(f, "Distinct")?;
             }
             Self::Filter { source, predicate } => {
                 write!(f, "Filter: {}", predicate.format(source))?;
-                source.format(f, &prefix, false, true)?;
             }
             Self::HashJoin { left, left_column, right, right_column, outer } => {
                 let kind = if *outer { "outer" } else { "inner" };
@@ -479,8 +1102,6 @@ impl Node {
                     label => format!("{label}"),
                 };
                 write!(f, "HashJoin: {kind} on {left_column} = {right_column}")?;
-                left.format(f, &prefix, false, false)?;
-                right.format(f, &prefix, false, true)?;
             }
             Self::IndexLookup { table, column, alias, values } => {
                 let column = &table.columns[*column].name;
@@ -500,36 +1121,40 @@ impl Node {
                     write!(f, " as {alias}")?;
                 }
                 if !keys.is_empty() && keys.len() < 10 {
-                    write!(f, " ({})", keys.iter().join(", "))?;
+                    write!(f, " ({})", keys.iter().map(|key| format_id(key)).join(", "))?;
                 } else {
                     write!(f, " ({} keys)", keys.len())?;
                 }
             }
-            Self::Limit { source, limit } => {
+            Self::Limit { limit, .. } => {
                 write!(f, "Limit: {limit}")?;
-                source.format(f, &prefix, false, true)?;
             }
-            Self::NestedLoopJoin { left, right, predicate, outer, .. } => {
+            Self::Lock { table, .. } => {
+                write!(f, "Lock: {}", table.name)?;
+            }
+            Self::NestedLoopJoin { predicate, outer, .. } => {
                 let kind = if *outer { "outer" } else { "inner" };
                 write!(f, "NestedLoopJoin: {kind}")?;
                 if let Some(predicate) = predicate {
                     write!(f, " on {}", predicate.format(self))?;
                 }
-                left.format(f, &prefix, false, false)?;
-                right.format(f, &prefix, false, true)?;
             }
             Self::Nothing { .. } => write!(f, "Nothing")?,
-            Self::Offset { source, offset } => {
+            Self::Offset { offset, .. } => {
                 write!(f, "Offset: {offset}")?;
-                source.format(f, &prefix, false, true)?;
             }
-            Self::Order { source, key: orders } => {
+            Self::Order { source, key: orders, limit } => {
                 let orders = orders
                     .iter()
-                    .map(|(expr, dir)| format!("{} {dir}", expr.format(source)))
+                    .map(|(expr, dir, nulls)| match nulls == &Nulls::new(dir, None) {
+                        true => format!("{} {dir}", expr.format(source)),
+                        false => format!("{} {dir} {nulls}", expr.format(source)),
+                    })
                     .join(", ");
                 write!(f, "Order: {orders}")?;
-                source.format(f, &prefix, false, true)?;
+                if let Some(limit) = limit {
+                    write!(f, " (limit {limit})")?;
+                }
             }
             Self::Projection { source, expressions, aliases } => {
                 let expressions = expressions
@@ -541,7 +1166,6 @@ impl Node {
                     })
                     .join(", ");
                 write!(f, "Projection: {expressions}")?;
-                source.format(f, &prefix, false, true)?;
             }
             Self::Remap { source, targets } => {
                 let remap = remap_sources(targets)
@@ -568,7 +1192,6 @@ impl Node {
                 if !dropped.is_empty() {
                     write!(f, " (dropped: {dropped})")?;
                 }
-                source.format(f, &prefix, false, true)?;
             }
             Self::Scan { table, alias, filter } => {
                 write!(f, "Scan: {}", table.name)?;
@@ -579,6 +1202,15 @@ impl Node {
                     write!(f, " ({})", filter.format(self))?;
                 }
             }
+            Self::SchemaScan { table, alias, .. } => {
+                write!(f, "SchemaScan: information_schema.{}", table.name)?;
+                if let Some(alias) = alias {
+                    write!(f, " as {alias}")?;
+                }
+            }
+            Self::Union { all, .. } => {
+                write!(f, "Union: {}", if *all { "all" } else { "distinct" })?;
+            }
             Self::Values { rows, .. } => {
                 write!(f, "Values: ")?;
                 match rows.len() {
@@ -587,11 +1219,64 @@ impl Node {
                     n => write!(f, "{n} rows")?,
                 }
             }
+            Self::Window { source, functions } => {
+                let functions = functions.iter().map(|window| window.format(source)).join(", ");
+                write!(f, "Window: {functions}")?;
+            }
         };
+
+        // Append any execution statistics.
+        if let Some(Some(NodeStats { rows, time })) = stats.next() {
+            write!(f, " [rows={rows} time={}]", format_duration(*time))?;
+        }
+
+        // Format any subqueries, followed by the child nodes.
+        let subqueries = self.subqueries();
+        let children = self.children();
+        let mut remaining = subqueries.len() + children.len();
+        for subquery in subqueries {
+            remaining -= 1;
+            writeln!(f)?;
+            let prefix = if remaining > 0 {
+                write!(f, "{prefix}├─ Subquery")?;
+                format!("{prefix}│  ")
+            } else {
+                write!(f, "{prefix}└─ Subquery")?;
+                format!("{prefix}   ")
+            };
+            subquery.format(f, &prefix, false, true, stats)?;
+        }
+        for child in children {
+            remaining -= 1;
+            child.format(f, &prefix, false, remaining == 0, stats)?;
+        }
         Ok(())
     }
 }
 
+/// Formats a write plan's RETURNING clause, if any, using the given node to
+/// look up column labels. Aliases are only shown if they differ from the label
+/// of a column reference.
+fn format_returning(returning: &[(Expression, Label)], node: &Node) -> String {
+    if returning.is_empty() {
+        return String::new();
+    }
+    let returning = returning
+        .iter()
+        .map(|(expr, label)| match (expr, label) {
+            (_, Label::None) => expr.format(node),
+            (Expression::Column(i), label) if node.column_label(*i) == *label => expr.format(node),
+            (expr, label) => format!("{} as {label}", expr.format(node)),
+        })
+        .join(", ");
+    format!(" returning {returning}")
+}
+
+/// Formats a duration as milliseconds, e.g. for EXPLAIN ANALYZE.
+fn format_duration(duration: Duration) -> String {
+    format!("{:.3}ms", duration.as_secs_f64() * 1000.0)
+}
+
 /// Inverts a Remap targets vector to a vector of source indexes, with None
 /// for columns that weren't targeted.
 pub fn remap_sources(targets: &[Option<usize>]) -> Vec<Option<usize>> {
@@ -1,88 +1,267 @@
 #![allow(clippy::module_inception)]
 
-use super::plan::{remap_sources, Aggregate, Node, Plan};
+use super::plan::{remap_sources, Aggregate, Node, Nulls, Plan, Window, WindowFunction};
 use crate::errinput;
 use crate::error::Result;
 use crate::sql::engine::Catalog;
 use crate::sql::parser::ast;
-use crate::sql::types::{Column, Expression, Label, Table, Value};
+use crate::sql::types::{Column, DataType, Expression, Label, Row, Table, Value};
 
 use itertools::Itertools as _;
+use std::cell::RefCell;
 use std::collections::{BTreeMap, HashMap, HashSet};
 
 /// The planner builds an execution plan from a parsed Abstract Syntax Tree,
 /// using the catalog for schema information.
 pub struct Planner<'a, C: Catalog> {
     catalog: &'a C,
+    /// The outer query, when planning a correlated subquery.
+    outer: Option<&'a Outer<'a>>,
 }
 
 impl<'a, C: Catalog> Planner<'a, C> {
     /// Creates a new planner.
     pub fn new(catalog: &'a C) -> Self {
-        Self { catalog }
+        Self { catalog, outer: None }
     }
 
     /// Builds a plan for an AST statement.
     pub fn build(&mut self, statement: ast::Statement) -> Result<Plan> {
         use ast::Statement::*;
         match statement {
-            CreateTable { name, columns } => self.build_create_table(name, columns),
-            DropTable { name, if_exists } => Ok(Plan::DropTable { table: name, if_exists }),
-            Delete { table, r#where } => self.build_delete(table, r#where),
-            Insert { table, columns, values } => self.build_insert(table, columns, values),
-            Update { table, set, r#where } => self.build_update(table, set, r#where),
-            Select { select, from, r#where, group_by, having, order_by, offset, limit } => {
-                self.build_select(select, from, r#where, group_by, having, order_by, offset, limit)
+            CreateTable { name, columns, primary_key, unique, checks, if_not_exists } => {
+                self.build_create_table(name, columns, primary_key, unique, checks, if_not_exists)
+            }
+            CreateTableAs { name, query, if_not_exists } => {
+                self.build_create_table_as(name, *query, if_not_exists)
+            }
+            DropTable { name, if_exists, cascade } => {
+                Ok(Plan::DropTable { table: name, if_exists, cascade })
+            }
+            AlterTable { name, action } => self.build_alter_table(name, action),
+            CreateIndex { name, table, column } => Ok(Plan::CreateIndex { name, table, column }),
+            DropIndex { table, name, if_exists } => Ok(Plan::DropIndex { table, name, if_exists }),
+            CopyFrom { table, path } => {
+                Ok(Plan::CopyFrom { table: self.catalog.must_get_table(&table)?, path })
+            }
+            CopyTo { query, path } => Ok(Plan::CopyTo { source: self.build_query(*query)?, path }),
+            Delete { table, r#where, returning } => self.build_delete(table, r#where, returning),
+            Insert { table, columns, source, returning } => {
+                self.build_insert(table, columns, source, returning)
+            }
+            Update { table, set, r#where, returning } => {
+                self.build_update(table, set, r#where, returning)
+            }
+            Truncate { table } => Ok(Plan::Truncate { table }),
+            Analyze { table } => Ok(Plan::Analyze { table }),
+            Select { .. } | Values { .. } | Union { .. } => {
+                Ok(Plan::Select(self.build_query(statement)?))
             }
 
-            // Transaction and explain statements are handled by Session.
-            Begin { .. } | Commit | Rollback | Explain(_) => {
+            // Transaction, explain, and settings statements are handled by Session.
+            Begin { .. }
+            | Commit
+            | Rollback
+            | Savepoint(_)
+            | RollbackToSavepoint(_)
+            | ReleaseSavepoint(_)
+            | Explain { .. }
+            | Set { .. }
+            | Show(_) => {
                 panic!("unexpected statement {statement:?}")
             }
         }
     }
 
     /// Builds a CREATE TABLE plan.
-    fn build_create_table(&self, name: String, columns: Vec<ast::Column>) -> Result<Plan> {
-        // Most schema validation happens during execution via Table.validate().
-        // However, the AST specifies the primary key as a column field, while
-        // the schema stores it as a column index, so we have to map that here.
-        let Some(primary_key) = columns.iter().position(|c| c.primary_key) else {
-            return errinput!("no primary key for table {name}");
-        };
-        if columns.iter().filter(|c| c.primary_key).count() > 1 {
+    fn build_create_table(
+        &self,
+        name: String,
+        mut columns: Vec<ast::Column>,
+        primary_key: Option<Vec<String>>,
+        unique: Vec<Vec<String>>,
+        checks: Vec<ast::Expression>,
+        if_not_exists: bool,
+    ) -> Result<Plan> {
+        // Most schema validation happens via Table.validate() once the schema
+        // is built. However, the AST specifies the primary key and unique
+        // constraints as column fields or lists of column names, while the
+        // schema stores column indexes, so we have to map that here.
+        if columns.iter().filter(|c| c.primary_key).count() + primary_key.iter().len() > 1 {
             return errinput!("multiple primary keys for table {name}");
         }
+        if let Some(key) = &primary_key {
+            for cname in key {
+                let Some(column) = columns.iter_mut().find(|c| &c.name == cname) else {
+                    return errinput!("unknown primary key column {cname} in table {name}");
+                };
+                column.primary_key = true;
+            }
+        }
+        let primary_key: Vec<usize> = match primary_key {
+            Some(key) => {
+                key.iter().filter_map(|k| columns.iter().position(|c| &c.name == k)).collect()
+            }
+            None => columns.iter().positions(|c| c.primary_key).collect(),
+        };
+        if primary_key.is_empty() {
+            return errinput!("no primary key for table {name}");
+        }
+
+        // Single-column unique constraints are equivalent to UNIQUE column
+        // constraints. Multi-column constraints need an index on the first
+        // column to look up conflicting rows.
+        let mut unique_columns = Vec::new();
+        for constraint in unique {
+            let mut indexes = Vec::with_capacity(constraint.len());
+            for cname in &constraint {
+                let Some(index) = columns.iter().position(|c| &c.name == cname) else {
+                    return errinput!("unknown unique column {cname} in table {name}");
+                };
+                indexes.push(index);
+            }
+            match indexes[..] {
+                [index] => columns[index].unique = true,
+                [index, ..] => {
+                    columns[index].index = true;
+                    unique_columns.push(indexes);
+                }
+                [] => {}
+            }
+        }
+
+        // CHECK constraints can reference any table column, so they're built
+        // once the table's columns are known.
+        let column_checks = columns.iter_mut().map(|c| c.check.take()).collect_vec();
+        let composite_key = primary_key.len() > 1;
         let columns = columns
             .into_iter()
-            .map(|c| {
-                let nullable = c.nullable.unwrap_or(!c.primary_key);
-                Ok(Column {
-                    name: c.name,
-                    datatype: c.datatype,
-                    nullable,
-                    default: match c.default {
-                        Some(expr) => Some(Self::evaluate_constant(expr)?),
-                        None if nullable => Some(Value::Null),
-                        None => None,
-                    },
-                    unique: c.unique || c.primary_key,
-                    index: (c.index || c.unique || c.references.is_some()) && !c.primary_key,
-                    references: c.references,
-                })
-            })
+            .map(|c| self.build_column(c, composite_key))
             .collect::<Result<_>>()?;
-        Ok(Plan::CreateTable { schema: Table { name, primary_key, columns } })
+        let mut schema =
+            Table { name, primary_key, columns, unique: unique_columns, checks: Vec::new() };
+        let scope = Scope::from_table(&schema)?;
+        for (i, check) in column_checks.into_iter().enumerate() {
+            if let Some(check) = check {
+                schema.columns[i].check = Some(self.build_check(check, &scope)?);
+            }
+        }
+        for check in checks {
+            schema.checks.push(self.build_check(check, &scope)?);
+        }
+        // Validate the schema, such that EXPLAIN CREATE TABLE can be used as a
+        // dry run. It is validated again when creating the table.
+        schema.validate(self.catalog)?;
+        Ok(Plan::CreateTable { schema, if_not_exists })
+    }
+
+    /// Builds a CREATE TABLE AS plan. The table's column names are taken from
+    /// the query's output columns, which must be named and unique. Datatypes
+    /// are taken from the source table columns where possible, otherwise they
+    /// are inferred from the rows during execution.
+    fn build_create_table_as(
+        &self,
+        name: String,
+        query: ast::Statement,
+        if_not_exists: bool,
+    ) -> Result<Plan> {
+        let source = self.build_query(query)?;
+        let mut columns: Vec<(String, Option<DataType>)> = Vec::new();
+        for index in 0..source.columns() {
+            let column = match source.column_label(index) {
+                Label::Qualified(_, column) | Label::Unqualified(column) => column,
+                Label::None => {
+                    return errinput!("column {} has no name, use AS to name it", index + 1)
+                }
+            };
+            if columns.iter().any(|(name, _)| name == &column) {
+                return errinput!("duplicate column {column}");
+            }
+            columns.push((column, source.column_datatype(index)));
+        }
+        Ok(Plan::CreateTableAs { name, columns, source, if_not_exists })
+    }
+
+    /// Builds a CHECK constraint expression. It can only reference columns in
+    /// the given scope, and can't contain subqueries or parameters.
+    fn build_check(&self, expr: ast::Expression, scope: &Scope) -> Result<Expression> {
+        let expr = self.build_expression(expr, scope)?;
+        if expr.contains(&|expr| {
+            matches!(
+                expr,
+                Expression::Subquery(..) | Expression::Exists(..) | Expression::Parameter(..)
+            )
+        }) {
+            return errinput!("CHECK expression can't contain subqueries or parameters");
+        }
+        Ok(expr)
+    }
+
+    /// Builds a schema column from an AST column definition. If composite_key
+    /// is true, primary key columns are part of a composite primary key.
+    fn build_column(&self, c: ast::Column, composite_key: bool) -> Result<Column> {
+        // Primary key columns are non-nullable by default. A single-column
+        // primary key is the table's primary index, so it's implicitly unique
+        // and doesn't have a secondary index. Composite key columns are only
+        // unique as a tuple, and are otherwise regular columns.
+        let nullable = c.nullable.unwrap_or(!c.primary_key);
+        let primary_index = c.primary_key && !composite_key;
+        Ok(Column {
+            name: c.name,
+            datatype: c.datatype,
+            nullable,
+            default: match c.default {
+                Some(expr) => Some(self.evaluate_constant(expr)?.coerce(c.datatype)?),
+                None if nullable => Some(Value::Null),
+                None => None,
+            },
+            unique: c.unique || primary_index,
+            index: (c.index || c.unique || c.references.is_some()) && !primary_index,
+            index_name: None,
+            references: c.references,
+            on_delete: c.on_delete,
+            check: None,
+        })
+    }
+
+    /// Builds an ALTER TABLE plan.
+    fn build_alter_table(&self, name: String, action: ast::AlterTable) -> Result<Plan> {
+        let table = self.catalog.must_get_table(&name)?;
+        match action {
+            ast::AlterTable::AddColumn(mut column) => {
+                if column.primary_key {
+                    return errinput!("can't add primary key column {}", column.name);
+                }
+                let check = column.check.take();
+                let mut column = self.build_column(column, false)?;
+                if let Some(check) = check {
+                    // The check can reference existing columns and the new one.
+                    let mut scope = Scope::from_table(&table)?;
+                    scope.add_column(Label::Qualified(table.name.clone(), column.name.clone()));
+                    column.check = Some(self.build_check(check, &scope)?);
+                }
+                Ok(Plan::AddColumn { table: table.name, column })
+            }
+            ast::AlterTable::DropColumn(column) => {
+                Ok(Plan::DropColumn { table: table.name, column })
+            }
+        }
     }
 
     /// Builds a DELETE plan.
-    fn build_delete(&self, table: String, r#where: Option<ast::Expression>) -> Result<Plan> {
+    fn build_delete(
+        &self,
+        table: String,
+        r#where: Option<ast::Expression>,
+        returning: Vec<(ast::Expression, Option<String>)>,
+    ) -> Result<Plan> {
         let table = self.catalog.must_get_table(&table)?;
         let scope = Scope::from_table(&table)?;
-        let filter = r#where.map(|expr| Self::build_expression(expr, &scope)).transpose()?;
+        let filter = r#where.map(|expr| self.build_expression(expr, &scope)).transpose()?;
         Ok(Plan::Delete {
             table: table.name.clone(),
-            primary_key: table.primary_key,
+            primary_key: table.primary_key.clone(),
+            returning: self.build_returning(&table, returning)?,
             source: Node::Scan { table, alias: None, filter },
         })
     }
@@ -92,7 +271,8 @@ impl<'a, C: Catalog> Planner<'a, C> {
         &self,
         table: String,
         columns: Option<Vec<String>>,
-        values: Vec<Vec<ast::Expression>>,
+        source: ast::InsertSource,
+        returning: Vec<(ast::Expression, Option<String>)>,
     ) -> Result<Plan> {
         let table = self.catalog.must_get_table(&table)?;
         let mut column_map = None;
@@ -107,14 +287,83 @@ impl<'a, C: Catalog> Planner<'a, C> {
                 }
             }
         }
+        let values = match source {
+            ast::InsertSource::Values(values) => values,
+            ast::InsertSource::Query(query) => {
+                let source = self.build_query(*query)?;
+                Self::check_insert_source(&table, column_map.as_ref(), &source)?;
+                let returning = self.build_returning(&table, returning)?;
+                return Ok(Plan::Insert { table, column_map, source, returning });
+            }
+        };
+        // DEFAULT values are looked up in the table schema, using the column
+        // that the value index maps to.
+        let default = |vidx: usize| -> Result<Expression> {
+            let column = match &column_map {
+                Some(column_map) => match column_map.iter().find(|(_, v)| **v == vidx) {
+                    Some((cidx, _)) => &table.columns[*cidx],
+                    None => return errinput!("column and value counts do not match"),
+                },
+                None => match table.columns.get(vidx) {
+                    Some(column) => column,
+                    None => return errinput!("too many values for table {}", table.name),
+                },
+            };
+            match &column.default {
+                Some(default) => Ok(Expression::Constant(default.clone())),
+                None => errinput!("column {} has no default value", column.name),
+            }
+        };
         let scope = Scope::new();
         let rows = values
             .into_iter()
             .map(|exprs| {
-                exprs.into_iter().map(|expr| Self::build_expression(expr, &scope)).collect()
+                exprs
+                    .into_iter()
+                    .enumerate()
+                    .map(|(vidx, expr)| match expr {
+                        Some(expr) => self.build_expression(expr, &scope),
+                        None => default(vidx),
+                    })
+                    .collect()
             })
             .try_collect()?;
-        Ok(Plan::Insert { table, column_map, source: Node::Values { rows } })
+        let returning = self.build_returning(&table, returning)?;
+        Ok(Plan::Insert { table, column_map, source: Node::Values { rows }, returning })
+    }
+
+    /// Checks that the columns of an INSERT source query match the table
+    /// columns they're inserted into, such that we error before writing any
+    /// rows. Datatypes are only checked if known, otherwise they're checked
+    /// as the rows are written.
+    fn check_insert_source(
+        table: &Table,
+        column_map: Option<&HashMap<usize, usize>>,
+        source: &Node,
+    ) -> Result<()> {
+        if let Some(column_map) = column_map {
+            if column_map.len() != source.columns() {
+                return errinput!("column and value counts do not match");
+            }
+        } else if source.columns() > table.columns.len() {
+            return errinput!("too many values for table {}", table.name);
+        }
+        for vidx in 0..source.columns() {
+            let cidx = match column_map {
+                Some(column_map) => *column_map.iter().find(|(_, v)| **v == vidx).unwrap().0,
+                None => vidx,
+            };
+            let column = &table.columns[cidx];
+            let Some(datatype) = source.column_datatype(vidx) else { continue };
+            if !datatype.is_compatible(column.datatype) {
+                return errinput!(
+                    "invalid datatype {datatype} for {} column {}",
+                    column.datatype,
+                    column.name
+                );
+            }
+        }
+        Ok(())
     }
 
     /// Builds an UPDATE plan.
@@ -123,15 +372,16 @@ impl<'a, C: Catalog> Planner<'a, C> {
         table: String,
         set: BTreeMap<String, Option<ast::Expression>>,
         r#where: Option<ast::Expression>,
+        returning: Vec<(ast::Expression, Option<String>)>,
     ) -> Result<Plan> {
         let table = self.catalog.must_get_table(&table)?;
         let scope = Scope::from_table(&table)?;
-        let filter = r#where.map(|expr| Self::build_expression(expr, &scope)).transpose()?;
+        let filter = r#where.map(|expr| self.build_expression(expr, &scope)).transpose()?;
         let mut expressions = Vec::with_capacity(set.len());
         for (column, expr) in set {
             let index = scope.lookup_column(None, &column)?;
             let expr = match expr {
-                Some(expr) => Self::build_expression(expr, &scope)?,
+                Some(expr) => self.build_expression(expr, &scope)?,
                 None => match &table.columns[index].default {
                     Some(default) => Expression::Constant(default.clone()),
                     None => return errinput!("column {column} has no default value"),
@@ -141,30 +391,112 @@ impl<'a, C: Catalog> Planner<'a, C> {
         }
         Ok(Plan::Update {
             table: table.clone(),
-            primary_key: table.primary_key,
+            primary_key: table.primary_key.clone(),
+            returning: self.build_returning(&table, returning)?,
             source: Node::Scan { table, alias: None, filter },
             expressions,
         })
     }
 
-    /// Builds a SELECT plan.
+    /// Builds a RETURNING clause for an INSERT, UPDATE, or DELETE plan. The
+    /// expressions are evaluated against the affected table rows, and * expands
+    /// to all table columns. Unaliased column references are labeled by the
+    /// column, like in a SELECT clause.
+    fn build_returning(
+        &self,
+        table: &Table,
+        returning: Vec<(ast::Expression, Option<String>)>,
+    ) -> Result<Vec<(Expression, Label)>> {
+        let scope = Scope::from_table(table)?;
+        let mut output = Vec::with_capacity(returning.len());
+        for (expr, alias) in returning {
+            if expr == ast::Expression::All {
+                output.extend(
+                    scope
+                        .columns
+                        .iter()
+                        .cloned()
+                        .enumerate()
+                        .map(|(i, label)| (Expression::Column(i), label)),
+                );
+                continue;
+            }
+            if expr.contains(&Self::is_aggregate_function) {
+                return errinput!("aggregate functions are not allowed in RETURNING");
+            }
+            if expr.contains(&|expr| matches!(expr, ast::Expression::Window { .. })) {
+                return errinput!("window functions are not allowed in RETURNING");
+            }
+            let expr = self.build_expression(expr, &scope)?;
+            let label = match (&expr, alias) {
+                (_, Some(alias)) => Label::Unqualified(alias),
+                (Expression::Column(i), None) => scope.columns[*i].clone(),
+                (_, None) => Label::None,
+            };
+            output.push((expr, label));
+        }
+        Ok(output)
+    }
+
+    /// Builds a query plan node for a SELECT, VALUES, or UNION statement.
+    fn build_query(&self, statement: ast::Statement) -> Result<Node> {
+        use ast::Statement::*;
+        match statement {
+            Select {
+                select,
+                distinct,
+                from,
+                r#where,
+                group_by,
+                having,
+                order_by,
+                offset,
+                limit,
+                for_update,
+            } => self.build_select(
+                select, distinct, from, r#where, group_by, having, order_by, offset, limit,
+                for_update,
+            ),
+            Values { rows, order_by, offset, limit } => {
+                self.build_values(rows, order_by, offset, limit)
+            }
+            Union { left, right, all, order_by, offset, limit } => {
+                self.build_union(*left, *right, all, order_by, offset, limit)
+            }
+            statement => panic!("unexpected query statement {statement:?}"),
+        }
+    }
+
+    /// Builds a SELECT plan node.
     #[allow(clippy::too_many_arguments)]
     fn build_select(
         &self,
         mut select: Vec<(ast::Expression, Option<String>)>,
+        distinct: bool,
         from: Vec<ast::From>,
         r#where: Option<ast::Expression>,
         group_by: Vec<ast::Expression>,
         having: Option<ast::Expression>,
-        order_by: Vec<(ast::Expression, ast::Direction)>,
+        order_by: Vec<(ast::Expression, ast::Direction, Option<ast::Nulls>)>,
         offset: Option<ast::Expression>,
         limit: Option<ast::Expression>,
-    ) -> Result<Plan> {
+        for_update: bool,
+    ) -> Result<Node> {
         let mut scope = Scope::new();
 
-        // Build FROM clause.
+        // Build FROM clause. For FOR UPDATE, lock the rows of each table.
         let mut node = if !from.is_empty() {
-            self.build_from_clause(from, &mut scope)?
+            let node = self.build_from_clause(from, &mut scope)?;
+            match for_update {
+                true => node.transform(&Ok, &|node| match node {
+                    Node::Scan { ref table, .. } => {
+                        let table = table.clone();
+                        Ok(Node::Lock { source: Box::new(node), table })
+                    }
+                    node => Ok(node),
+                })?,
+                false => node,
+            }
         } else {
             // For a constant SELECT, emit a single empty row to project with.
             // This allows using aggregate functions and WHERE as normal.
@@ -191,19 +523,32 @@ impl<'a, C: Catalog> Planner<'a, C> {
                     .collect();
             }
         }
+        let output_columns = match select.as_slice() {
+            [(ast::Expression::All, None)] => node.columns(),
+            select => select.len(),
+        };
 
         // Build WHERE clause.
         if let Some(r#where) = r#where {
-            let predicate = Self::build_expression(r#where, &scope)?;
+            let predicate = self.build_expression(r#where, &scope)?;
             node = Node::Filter { source: Box::new(node), predicate };
         }
 
-        // Build aggregate functions and GROUP BY clause.
+        // Build aggregate functions and GROUP BY clause. GROUP BY can reference
+        // SELECT aliases, but FROM columns take precedence like in PostgreSQL.
+        let group_by = Self::resolve_group_by(group_by, &select, &scope)?;
         let aggregates = Self::collect_aggregates(&select, &having, &order_by);
         if !group_by.is_empty() || !aggregates.is_empty() {
             node = self.build_aggregate(node, group_by, aggregates, &mut scope)?;
         }
 
+        // Build window functions. These are computed after aggregation, and
+        // can be used in SELECT and ORDER BY.
+        let windows = Self::collect_windows(&select, &order_by);
+        if !windows.is_empty() {
+            node = self.build_window(node, windows, &mut scope)?;
+        }
+
         // Build SELECT clause. We can omit this for a trivial SELECT *.
         if select.as_slice() != [(ast::Expression::All, None)] {
             // Prepare the post-projection scope.
@@ -213,13 +558,19 @@ impl<'a, C: Catalog> Planner<'a, C> {
             let mut expressions = Vec::with_capacity(select.len());
             let mut aliases = Vec::with_capacity(select.len());
             for (expr, alias) in select {
-                expressions.push(Self::build_expression(expr, &scope)?);
+                expressions.push(self.build_expression(expr, &scope)?);
                 aliases.push(Label::from(alias));
             }
 
             // Add hidden columns for HAVING and ORDER BY columns not in SELECT.
-            let hidden = self.build_select_hidden(&having, &order_by, &scope, &mut child_scope);
-            aliases.extend(std::iter::repeat(Label::None).take(hidden.len()));
+            // DISTINCT can't deduplicate rows with hidden ORDER BY columns.
+            let mut hidden = self.build_select_hidden(&having, &[], &scope, &mut child_scope);
+            let order_hidden = self.build_select_hidden(&None, &order_by, &scope, &mut child_scope);
+            if distinct && !order_hidden.is_empty() {
+                return errinput!("ORDER BY expressions must be in SELECT list with DISTINCT");
+            }
+            hidden.extend(order_hidden);
+            aliases.extend(std::iter::repeat_n(Label::None, hidden.len()));
             expressions.extend(hidden);
 
             scope = child_scope;
@@ -231,43 +582,163 @@ impl<'a, C: Catalog> Planner<'a, C> {
             if scope.aggregates.is_empty() {
                 return errinput!("HAVING requires GROUP BY or aggregate function");
             }
-            let predicate = Self::build_expression(having, &scope)?;
+            if having.contains(&|expr| matches!(expr, ast::Expression::Window { .. })) {
+                return errinput!("window functions are not allowed in HAVING");
+            }
+            let predicate = self.build_expression(having, &scope)?;
             node = Node::Filter { source: Box::new(node), predicate };
         }
 
+        // Build DISTINCT clause. Any hidden HAVING columns must be removed
+        // first, to avoid considering them when deduplicating rows.
+        if distinct {
+            if let Some(targets) = scope.remap_hidden() {
+                node = Node::Remap { source: Box::new(node), targets }
+            }
+            node = Node::Distinct { source: Box::new(node) };
+        }
+
         // Build ORDER BY clause.
-        if !order_by.is_empty() {
-            let key = order_by
-                .into_iter()
-                .map(|(expr, dir)| Ok((Self::build_expression(expr, &scope)?, dir.into())))
-                .collect::<Result<_>>()?;
-            node = Node::Order { source: Box::new(node), key };
+        node = self.build_order_by(node, order_by, &scope, output_columns)?;
+
+        // Build OFFSET and LIMIT clauses.
+        node = self.build_offset_limit(node, offset, limit)?;
+
+        // Remove any hidden columns before emitting the result.
+        if let Some(targets) = scope.remap_hidden() {
+            node = Node::Remap { source: Box::new(node), targets }
         }
 
-        // Build OFFSET clause.
+        Ok(node)
+    }
+
+    /// Builds a VALUES plan node. All rows must have the same number of
+    /// columns, named column1, column2, etc. The datatypes of constant values
+    /// must be compatible with those of earlier rows in the same column.
+    fn build_values(
+        &self,
+        rows: Vec<Vec<ast::Expression>>,
+        order_by: Vec<(ast::Expression, ast::Direction, Option<ast::Nulls>)>,
+        offset: Option<ast::Expression>,
+        limit: Option<ast::Expression>,
+    ) -> Result<Node> {
+        let scope = Scope::new();
+        let rows: Vec<Vec<Expression>> = rows
+            .into_iter()
+            .map(|row| row.into_iter().map(|expr| self.build_expression(expr, &scope)).collect())
+            .try_collect()?;
+        let columns = rows.first().map(|row| row.len()).unwrap_or(0);
+        if rows.iter().any(|row| row.len() != columns) {
+            return errinput!("VALUES lists must all be the same length");
+        }
+        for i in 0..columns {
+            let what = format!("VALUES column {} values", i + 1);
+            Self::check_constant_types(&what, rows.iter().map(|row| &row[i]))?;
+        }
+
+        let node = Node::Values { rows };
+        let mut scope = Scope::new();
+        for i in 0..columns {
+            scope.add_column(node.column_label(i));
+        }
+        let node = self.build_order_by(node, order_by, &scope, columns)?;
+        self.build_offset_limit(node, offset, limit)
+    }
+
+    /// Builds a UNION plan node. Both sides must have the same number of
+    /// columns, named after the left side's columns. Column datatypes are
+    /// checked during execution, since they aren't known until then.
+    fn build_union(
+        &self,
+        left: ast::Statement,
+        right: ast::Statement,
+        all: bool,
+        order_by: Vec<(ast::Expression, ast::Direction, Option<ast::Nulls>)>,
+        offset: Option<ast::Expression>,
+        limit: Option<ast::Expression>,
+    ) -> Result<Node> {
+        let left = self.build_query(left)?;
+        let right = self.build_query(right)?;
+        if left.columns() != right.columns() {
+            return errinput!(
+                "UNION queries must have the same number of columns, got {} and {}",
+                left.columns(),
+                right.columns()
+            );
+        }
+
+        // The ORDER BY clause can reference the output columns by name, but
+        // not by table name since the rows come from different queries.
+        let mut scope = Scope::new();
+        for i in 0..left.columns() {
+            scope.add_column(match left.column_label(i) {
+                Label::Qualified(_, name) => Label::Unqualified(name),
+                label => label,
+            });
+        }
+
+        let columns = left.columns();
+        let node = Node::Union { left: Box::new(left), right: Box::new(right), all };
+        let node = self.build_order_by(node, order_by, &scope, columns)?;
+        self.build_offset_limit(node, offset, limit)
+    }
+
+    /// Builds an ORDER BY clause, if given. A bare integer literal is an
+    /// ordinal referring to the given 1-based output column, e.g. ORDER BY 2
+    /// orders by the second SELECT column. Other expressions are evaluated,
+    /// including constant ones such as 1 + 1.
+    fn build_order_by(
+        &self,
+        node: Node,
+        order_by: Vec<(ast::Expression, ast::Direction, Option<ast::Nulls>)>,
+        scope: &Scope,
+        output_columns: usize,
+    ) -> Result<Node> {
+        if order_by.is_empty() {
+            return Ok(node);
+        }
+        let key = order_by
+            .into_iter()
+            .map(|(expr, dir, nulls)| {
+                let dir = dir.into();
+                let nulls = Nulls::new(&dir, nulls);
+                let expr = match expr {
+                    ast::Expression::Literal(ast::Literal::Integer(position)) => {
+                        if position < 1 || position as usize > output_columns {
+                            return errinput!("ORDER BY position {position} is not in select list");
+                        }
+                        Expression::Column(position as usize - 1)
+                    }
+                    expr => self.build_expression(expr, scope)?,
+                };
+                Ok((expr, dir, nulls))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Node::Order { source: Box::new(node), key, limit: None })
+    }
+
+    /// Builds OFFSET and LIMIT clauses, if given.
+    fn build_offset_limit(
+        &self,
+        mut node: Node,
+        offset: Option<ast::Expression>,
+        limit: Option<ast::Expression>,
+    ) -> Result<Node> {
         if let Some(offset) = offset {
-            let offset = match Self::evaluate_constant(offset)? {
+            let offset = match self.evaluate_constant(offset)? {
                 Value::Integer(offset) if offset >= 0 => offset as usize,
                 offset => return errinput!("invalid offset {offset}"),
             };
             node = Node::Offset { source: Box::new(node), offset }
         }
-
-        // Build LIMIT clause.
         if let Some(limit) = limit {
-            let limit = match Self::evaluate_constant(limit)? {
+            let limit = match self.evaluate_constant(limit)? {
                 Value::Integer(limit) if limit >= 0 => limit as usize,
                 limit => return errinput!("invalid limit {limit}"),
             };
             node = Node::Limit { source: Box::new(node), limit }
         }
-
-        // Remove any hidden columns before emitting the result.
-        if let Some(targets) = scope.remap_hidden() {
-            node = Node::Remap { source: Box::new(node), targets }
-        }
-
-        Ok(Plan::Select(node))
+        Ok(node)
     }
 
     /// Builds a FROM clause consisting of one or more items. Each item is
@@ -303,12 +774,39 @@ impl<'a, C: Catalog> Planner<'a, C> {
 
         let node = match from {
             // A full table scan.
-            ast::From::Table { name, alias } => {
+            ast::From::Table { schema: None, name, alias } => {
                 let table = self.catalog.must_get_table(&name)?;
                 scope.add_table(&table, alias.as_deref())?;
                 Node::Scan { table, alias, filter: None }
             }
 
+            // An information_schema virtual table scan.
+            ast::From::Table { schema: Some(schema), name, alias } => {
+                if schema != "information_schema" {
+                    return errinput!("unknown schema {schema}");
+                }
+                let (table, rows) = self.build_information_schema(&name)?;
+                scope.add_table(&table, alias.as_deref())?;
+                Node::SchemaScan { table, alias, rows }
+            }
+
+            // A derived table. Its output columns are qualified by the alias
+            // via a projection.
+            ast::From::Query { query, alias } => {
+                let source = self.build_query(*query)?;
+                let aliases = (0..source.columns())
+                    .map(|i| match source.column_label(i) {
+                        Label::Qualified(_, name) | Label::Unqualified(name) => {
+                            Label::Qualified(alias.clone(), name)
+                        }
+                        Label::None => Label::None,
+                    })
+                    .collect_vec();
+                scope.add_query(&alias, &aliases)?;
+                let expressions = (0..source.columns()).map(Expression::Column).collect();
+                Node::Projection { source: Box::new(source), expressions, aliases }
+            }
+
             // A two-way join. The left or right nodes may be chained joins.
             ast::From::Join { mut left, mut right, r#type, predicate } => {
                 // Right joins are built as a left join then column swap.
@@ -322,7 +820,7 @@ impl<'a, C: Catalog> Planner<'a, C> {
                 let (left_size, right_size) = (left.columns(), right.columns());
 
                 // Build the join node.
-                let predicate = predicate.map(|e| Self::build_expression(e, &scope)).transpose()?;
+                let predicate = predicate.map(|e| self.build_expression(e, &scope)).transpose()?;
                 let outer = r#type.is_outer();
                 let mut node = Node::NestedLoopJoin { left, right, predicate, outer };
 
@@ -341,6 +839,142 @@ impl<'a, C: Catalog> Planner<'a, C> {
         Ok(node)
     }
 
+    /// Builds an information_schema virtual table, returning its schema and
+    /// rows. The rows are read from the catalog's table schemas:
+    ///
+    /// * tables: one row per table, with its column count and primary key.
+    /// * columns: one row per table column, with its datatype, nullability,
+    ///   default value, and key, index, and foreign key info.
+    fn build_information_schema(&self, name: &str) -> Result<(Table, Vec<Row>)> {
+        use DataType::*;
+        let column = |name: &str, datatype, nullable| Column {
+            name: name.to_string(),
+            datatype,
+            nullable,
+            default: nullable.then_some(Value::Null),
+            unique: false,
+            index: false,
+            index_name: None,
+            references: None,
+            on_delete: Default::default(),
+            check: None,
+        };
+        let string = |s: &str| Value::String(s.to_string());
+
+        let tables = self.catalog.list_tables()?;
+        let (columns, primary_key, rows): (_, _, Vec<Row>) = match name {
+            "tables" => (
+                vec![
+                    column("table_name", String, false),
+                    column("column_count", Integer, false),
+                    column("primary_key", String, false),
+                ],
+                vec![0],
+                tables
+                    .iter()
+                    .map(|table| {
+                        vec![
+                            string(&table.name),
+                            Value::Integer(table.columns.len() as i64),
+                            string(&table.format_columns(&table.primary_key)),
+                        ]
+                    })
+                    .collect(),
+            ),
+            "columns" => (
+                vec![
+                    column("table_name", String, false),
+                    column("column_name", String, false),
+                    column("ordinal_position", Integer, false),
+                    column("data_type", String, false),
+                    column("is_nullable", Boolean, false),
+                    column("column_default", String, true),
+                    column("is_primary_key", Boolean, false),
+                    column("is_unique", Boolean, false),
+                    column("is_indexed", Boolean, false),
+                    column("referenced_table", String, true),
+                ],
+                vec![0, 1],
+                tables
+                    .iter()
+                    .flat_map(|table| {
+                        table.columns.iter().enumerate().map(|(i, column)| {
+                            vec![
+                                string(&table.name),
+                                string(&column.name),
+                                Value::Integer(i as i64 + 1),
+                                string(&column.datatype.to_string()),
+                                Value::Boolean(column.nullable),
+                                column
+                                    .default
+                                    .as_ref()
+                                    .map_or(Value::Null, |v| string(&v.to_string())),
+                                Value::Boolean(table.primary_key.contains(&i)),
+                                Value::Boolean(column.unique),
+                                Value::Boolean(column.index),
+                                column.references.as_deref().map_or(Value::Null, string),
+                            ]
+                        })
+                    })
+                    .collect(),
+            ),
+            name => return errinput!("unknown table information_schema.{name}"),
+        };
+        let table = Table {
+            name: name.to_string(),
+            primary_key,
+            columns,
+            unique: Vec::new(),
+            checks: Vec::new(),
+        };
+        Ok((table, rows))
+    }
+
+    /// Resolves GROUP BY references to SELECT expressions. A bare integer is
+    /// an ordinal referring to the 1-based SELECT expression at that position,
+    /// and an unqualified column name that doesn't refer to a FROM column
+    /// resolves to the SELECT expression with that alias, if any. FROM columns
+    /// take precedence over aliases, like in PostgreSQL. For example:
+    ///
+    /// SELECT released / 10 * 10 AS decade, COUNT(*) FROM movies GROUP BY decade
+    ///
+    /// Groups by released / 10 * 10, which the SELECT then looks up. GROUP BY 1
+    /// is equivalent.
+    fn resolve_group_by(
+        group_by: Vec<ast::Expression>,
+        select: &[(ast::Expression, Option<String>)],
+        scope: &Scope,
+    ) -> Result<Vec<ast::Expression>> {
+        let resolve = |expr: ast::Expression| -> Result<ast::Expression> {
+            if let ast::Expression::Literal(ast::Literal::Integer(position)) = expr {
+                return match select.get((position as usize).wrapping_sub(1)) {
+                    Some((expr, _)) => Ok(expr.clone()),
+                    None => errinput!("GROUP BY position {position} is not in select list"),
+                };
+            }
+            let ast::Expression::Column(None, name) = &expr else { return Ok(expr) };
+            if scope.unqualified.contains_key(name) {
+                return Ok(expr);
+            }
+            let mut aliased = select.iter().filter(|(_, alias)| alias.as_ref() == Some(name));
+            match (aliased.next(), aliased.next()) {
+                (Some((expr, _)), None) => Ok(expr.clone()),
+                (Some(_), Some(_)) => errinput!("ambiguous column {name}"),
+                (None, _) => Ok(expr),
+            }
+        };
+        group_by
+            .into_iter()
+            .map(|expr| {
+                let expr = resolve(expr)?;
+                if expr.contains(&Self::is_aggregate_function) {
+                    return errinput!("aggregate functions are not allowed in GROUP BY");
+                }
+                Ok(expr)
+            })
+            .collect()
+    }
+
     /// Builds an aggregate node, which computes aggregates for a set of GROUP
     /// BY buckets. The aggregate functions have been collected from the SELECT,
     /// HAVING, and ORDER BY clauses.
@@ -370,10 +1004,10 @@ impl<'a, C: Catalog> Planner<'a, C> {
 
         // Build the node from the remaining unique expressions.
         let group_by =
-            group_by.into_iter().map(|expr| Self::build_expression(expr, scope)).try_collect()?;
+            group_by.into_iter().map(|expr| self.build_expression(expr, scope)).try_collect()?;
         let aggregates = aggregates
             .into_iter()
-            .map(|expr| Self::build_aggregate_function(expr, scope))
+            .map(|expr| self.build_aggregate_function(expr, scope))
             .try_collect()?;
 
         *scope = child_scope;
@@ -381,8 +1015,8 @@ impl<'a, C: Catalog> Planner<'a, C> {
     }
 
     /// Builds an aggregate function from an AST expression.
-    fn build_aggregate_function(expr: ast::Expression, scope: &Scope) -> Result<Aggregate> {
-        let ast::Expression::Function(name, mut args) = expr else {
+    fn build_aggregate_function(&self, expr: ast::Expression, scope: &Scope) -> Result<Aggregate> {
+        let ast::Expression::Function { name, mut args, distinct } = expr else {
             panic!("aggregate expression must be function");
         };
         if args.len() != 1 {
@@ -392,23 +1026,28 @@ impl<'a, C: Catalog> Planner<'a, C> {
             return errinput!("aggregate functions can't be nested");
         }
         // Special-case COUNT(*) since expressions don't support tuples.
-        let expr = match (name.as_str(), args.remove(0)) {
-            ("count", ast::Expression::All) => Expression::Constant(Value::Boolean(true)),
-            (_, arg) => Self::build_expression(arg, scope)?,
+        let expr = match (name.as_str(), args.remove(0), distinct) {
+            ("count", ast::Expression::All, false) => Expression::Constant(Value::Boolean(true)),
+            (_, ast::Expression::All, true) => return errinput!("can't use DISTINCT with *"),
+            (_, arg, _) => self.build_expression(arg, scope)?,
         };
-        Ok(match name.as_str() {
+        let aggregate = match name.as_str() {
             "avg" => Aggregate::Average(expr),
             "count" => Aggregate::Count(expr),
             "min" => Aggregate::Min(expr),
             "max" => Aggregate::Max(expr),
             "sum" => Aggregate::Sum(expr),
             name => return errinput!("unknown aggregate function {name}"),
-        })
+        };
+        match distinct {
+            true => Ok(Aggregate::Distinct(Box::new(aggregate))),
+            false => Ok(aggregate),
+        }
     }
 
     /// Checks whether a given AST expression is an aggregate function.
     fn is_aggregate_function(expr: &ast::Expression) -> bool {
-        if let ast::Expression::Function(name, _) = expr {
+        if let ast::Expression::Function { name, .. } = expr {
             return ["avg", "count", "max", "min", "sum"].contains(&name.as_str());
         }
         false
@@ -418,11 +1057,11 @@ impl<'a, C: Catalog> Planner<'a, C> {
     fn collect_aggregates(
         select: &[(ast::Expression, Option<String>)],
         having: &Option<ast::Expression>,
-        order_by: &[(ast::Expression, ast::Direction)],
+        order_by: &[(ast::Expression, ast::Direction, Option<ast::Nulls>)],
     ) -> Vec<ast::Expression> {
         let select = select.iter().map(|(expr, _)| expr);
         let having = having.iter();
-        let order_by = order_by.iter().map(|(expr, _)| expr);
+        let order_by = order_by.iter().map(|(expr, _, _)| expr);
         let mut aggregates = Vec::new();
         for expr in select.chain(having).chain(order_by) {
             expr.collect(&|expr| Self::is_aggregate_function(expr), &mut aggregates)
@@ -430,6 +1069,75 @@ impl<'a, C: Catalog> Planner<'a, C> {
         aggregates
     }
 
+    /// Builds a window node, which computes window functions collected from
+    /// the SELECT and ORDER BY clauses. The node appends one column per window
+    /// function to the source columns. Like aggregates, the ast::Expression of
+    /// each window function is tracked in the scope and mapped to its column
+    /// index, such that SELECT and ORDER BY expressions can look it up.
+    fn build_window(
+        &self,
+        source: Node,
+        mut windows: Vec<ast::Expression>,
+        scope: &mut Scope,
+    ) -> Result<Node> {
+        // Discard duplicate window functions, which share a column.
+        windows.retain(|expr| scope.add_window(expr).is_some());
+        let functions = windows
+            .into_iter()
+            .map(|expr| self.build_window_function(expr, scope))
+            .try_collect()?;
+        Ok(Node::Window { source: Box::new(source), functions })
+    }
+
+    /// Builds a window function from an AST expression.
+    fn build_window_function(&self, expr: ast::Expression, scope: &Scope) -> Result<Window> {
+        let ast::Expression::Window { function, args, over } = expr else {
+            panic!("window expression must be window function");
+        };
+        let ast::Window { partition_by, order_by } = *over;
+        let function = match (function.as_str(), args.len()) {
+            ("dense_rank", 0) => WindowFunction::DenseRank,
+            ("rank", 0) => WindowFunction::Rank,
+            ("row_number", 0) => WindowFunction::RowNumber,
+            (name, n) => return errinput!("unknown window function {name} with {n} arguments"),
+        };
+        let is_window = |expr: &ast::Expression| matches!(expr, ast::Expression::Window { .. });
+        if partition_by
+            .iter()
+            .chain(order_by.iter().map(|(expr, _, _)| expr))
+            .any(|expr| expr.contains(&is_window))
+        {
+            return errinput!("window functions can't be nested");
+        }
+        let partition_by = partition_by
+            .into_iter()
+            .map(|expr| self.build_expression(expr, scope))
+            .try_collect()?;
+        let order_by = order_by
+            .into_iter()
+            .map(|(expr, dir, nulls)| {
+                let dir = dir.into();
+                let nulls = Nulls::new(&dir, nulls);
+                Ok((self.build_expression(expr, scope)?, dir, nulls))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Window { function, partition_by, order_by })
+    }
+
+    /// Collects window functions from SELECT and ORDER BY clauses.
+    fn collect_windows(
+        select: &[(ast::Expression, Option<String>)],
+        order_by: &[(ast::Expression, ast::Direction, Option<ast::Nulls>)],
+    ) -> Vec<ast::Expression> {
+        let select = select.iter().map(|(expr, _)| expr);
+        let order_by = order_by.iter().map(|(expr, _, _)| expr);
+        let mut windows = Vec::new();
+        for expr in select.chain(order_by) {
+            expr.collect(&|expr| matches!(expr, ast::Expression::Window { .. }), &mut windows)
+        }
+        windows
+    }
+
     /// Builds hidden columns for a projection to pass through columns that are
     /// used by downstream nodes. Consider e.g.:
     ///
@@ -445,17 +1153,20 @@ impl<'a, C: Catalog> Planner<'a, C> {
     fn build_select_hidden(
         &self,
         having: &Option<ast::Expression>,
-        order_by: &[(ast::Expression, ast::Direction)],
+        order_by: &[(ast::Expression, ast::Direction, Option<ast::Nulls>)],
         scope: &Scope,
         child_scope: &mut Scope,
     ) -> Vec<Expression> {
         let mut hidden = Vec::new();
-        for expr in having.iter().chain(order_by.iter().map(|(expr, _)| expr)) {
+        for expr in having.iter().chain(order_by.iter().map(|(expr, _, _)| expr)) {
             expr.walk(&mut |expr| {
-                // If this is an aggregate or GROUP BY expression that isn't
-                // already available in the child scope, add a hidden column.
-                if let Some(index) = scope.lookup_aggregate(expr) {
-                    if child_scope.lookup_aggregate(expr).is_none() {
+                // If this is an aggregate, GROUP BY, or window expression that
+                // isn't already available in the child scope, add a hidden
+                // column.
+                if let Some(index) = scope.lookup_aggregate(expr).or(scope.lookup_window(expr)) {
+                    if child_scope.lookup_aggregate(expr).is_none()
+                        && child_scope.lookup_window(expr).is_none()
+                    {
                         child_scope.add_passthrough(scope, index, true);
                         hidden.push(Expression::Column(index));
                         return true;
@@ -483,9 +1194,9 @@ impl<'a, C: Catalog> Planner<'a, C> {
         hidden
     }
 
-    /// Builds an expression from an AST expression, looking up columns and
-    /// aggregate expressions in the scope.
-    pub fn build_expression(expr: ast::Expression, scope: &Scope) -> Result<Expression> {
+    /// Builds an expression from an AST expression, looking up columns,
+    /// aggregate expressions, and window functions in the scope.
+    pub fn build_expression(&self, expr: ast::Expression, scope: &Scope) -> Result<Expression> {
         use Expression::*;
 
         // Look up aggregate functions or GROUP BY expressions. These were added
@@ -494,9 +1205,15 @@ impl<'a, C: Catalog> Planner<'a, C> {
             return Ok(Column(index));
         }
 
+        // Look up window functions. These were added to the scope when
+        // building the Window node, if any.
+        if let Some(index) = scope.lookup_window(&expr) {
+            return Ok(Column(index));
+        }
+
         // Helper for building a boxed expression.
         let build = |expr: Box<ast::Expression>| -> Result<Box<Expression>> {
-            Ok(Box::new(Self::build_expression(*expr, scope)?))
+            Ok(Box::new(self.build_expression(*expr, scope)?))
         };
 
         Ok(match expr {
@@ -504,6 +1221,24 @@ impl<'a, C: Catalog> Planner<'a, C> {
             // values, not compound types like tuples. Support for * is
             // therefore special-cased in SELECT and COUNT(*).
             ast::Expression::All => return errinput!("unsupported use of *"),
+            // Subqueries are built with their own scope, but can reference
+            // columns in the outer query. These are collected as outer values
+            // of the Subquery or Exists expression.
+            ast::Expression::Subquery(statement) => {
+                let outer = Outer { scope, parent: self.outer, values: RefCell::default() };
+                let node = Planner { catalog: self.catalog, outer: Some(&outer) }
+                    .build_query(*statement)?;
+                if node.columns() != 1 {
+                    return errinput!("subquery must return 1 column, got {}", node.columns());
+                }
+                Subquery(Box::new(node), outer.values.into_inner())
+            }
+            ast::Expression::Exists(statement) => {
+                let outer = Outer { scope, parent: self.outer, values: RefCell::default() };
+                let node = Planner { catalog: self.catalog, outer: Some(&outer) }
+                    .build_query(*statement)?;
+                Exists(Box::new(node), outer.values.into_inner())
+            }
             ast::Expression::Literal(l) => Constant(match l {
                 ast::Literal::Null => Value::Null,
                 ast::Literal::Boolean(b) => Value::Boolean(b),
@@ -511,19 +1246,112 @@ impl<'a, C: Catalog> Planner<'a, C> {
                 ast::Literal::Float(f) => Value::Float(f),
                 ast::Literal::String(s) => Value::String(s),
             }),
+            ast::Expression::Array(items) => Array(
+                items.into_iter().map(|item| self.build_expression(item, scope)).try_collect()?,
+            ),
             ast::Expression::Column(table, name) => {
-                Column(scope.lookup_column(table.as_deref(), &name)?)
+                match scope.lookup_column(table.as_deref(), &name) {
+                    Ok(index) => Column(index),
+                    Err(err) => match self.outer.and_then(|o| o.lookup(table.as_deref(), &name)) {
+                        Some(index) => OuterColumn(index),
+                        None => return Err(err),
+                    },
+                }
             }
-            ast::Expression::Function(name, mut args) => match (name.as_str(), args.len()) {
+            ast::Expression::Parameter(index) => Parameter(index),
+            // CASE expressions. The simple form CASE a WHEN b is built as
+            // CASE WHEN a = b. Constant results must have compatible types,
+            // other result types aren't known until execution.
+            ast::Expression::Case { operand, when, r#else } => {
+                let operand = operand.map(build).transpose()?;
+                let when: Vec<_> = when
+                    .into_iter()
+                    .map(|(when, then)| {
+                        let mut when = self.build_expression(when, scope)?;
+                        if let Some(operand) = &operand {
+                            when = Equal(operand.clone(), when.into());
+                        }
+                        Ok((when, self.build_expression(then, scope)?))
+                    })
+                    .collect::<Result<_>>()?;
+                let r#else = r#else.map(build).transpose()?;
+                let results = when.iter().map(|(_, then)| then).chain(r#else.as_deref());
+                Self::check_constant_types("CASE results", results)?;
+                Case(when, r#else)
+            }
+            ast::Expression::Cast(expr, datatype) => Cast(build(expr)?, datatype),
+            ast::Expression::Function { name, distinct: true, .. } => {
+                return errinput!("DISTINCT is only allowed in aggregate functions, not {name}");
+            }
+            ast::Expression::Function { name, mut args, .. } => match (name.as_str(), args.len()) {
                 // NB: aggregate functions are processed above.
+                ("abs", 1) => Absolute(build(Box::new(args.remove(0)))?),
+                ("ceil", 1) => Ceil(build(Box::new(args.remove(0)))?),
+                ("coalesce", 1..) => {
+                    let args: Vec<_> = args
+                        .into_iter()
+                        .map(|arg| self.build_expression(arg, scope))
+                        .try_collect()?;
+                    Self::check_constant_types("COALESCE arguments", args.iter())?;
+                    Coalesce(args)
+                }
+                ("concat", 1..) => ConcatAll(
+                    args.into_iter().map(|arg| self.build_expression(arg, scope)).try_collect()?,
+                ),
+                ("contains", 2) => {
+                    let value = build(Box::new(args.remove(1)))?;
+                    Contains(build(Box::new(args.remove(0)))?, value)
+                }
+                ("floor", 1) => Floor(build(Box::new(args.remove(0)))?),
+                ("length", 1) => Length(build(Box::new(args.remove(0)))?),
+                ("lower", 1) => Lower(build(Box::new(args.remove(0)))?),
+                ("nullif", 2) => {
+                    let rhs = build(Box::new(args.remove(1)))?;
+                    NullIf(build(Box::new(args.remove(0)))?, rhs)
+                }
+                ("power", 2) => {
+                    let exp = build(Box::new(args.remove(1)))?;
+                    Exponentiate(build(Box::new(args.remove(0)))?, exp)
+                }
+                ("round", 1 | 2) => {
+                    let places =
+                        args.get(1).cloned().map(|arg| build(Box::new(arg))).transpose()?;
+                    Round(build(Box::new(args.remove(0)))?, places)
+                }
                 ("sqrt", 1) => SquareRoot(build(Box::new(args.remove(0)))?),
+                ("substring", 2 | 3) => {
+                    let length =
+                        args.get(2).cloned().map(|arg| build(Box::new(arg))).transpose()?;
+                    let start = build(Box::new(args.remove(1)))?;
+                    Substring(build(Box::new(args.remove(0)))?, start, length)
+                }
+                ("upper", 1) => Upper(build(Box::new(args.remove(0)))?),
                 (name, n) => return errinput!("unknown function {name} with {n} arguments"),
             },
+            // NB: window functions are processed above, and only allowed in
+            // SELECT and ORDER BY.
+            ast::Expression::Window { function, .. } => {
+                return errinput!("window function {function} is not allowed here")
+            }
             ast::Expression::Operator(op) => match op {
                 ast::Operator::And(lhs, rhs) => And(build(lhs)?, build(rhs)?),
                 ast::Operator::Not(expr) => Not(build(expr)?),
                 ast::Operator::Or(lhs, rhs) => Or(build(lhs)?, build(rhs)?),
 
+                // a BETWEEN b AND c is a >= b AND a <= c.
+                ast::Operator::Between(expr, low, high) => And(
+                    Or(
+                        GreaterThan(build(expr.clone())?, build(low.clone())?).into(),
+                        Equal(build(expr.clone())?, build(low)?).into(),
+                    )
+                    .into(),
+                    Or(
+                        LessThan(build(expr.clone())?, build(high.clone())?).into(),
+                        Equal(build(expr)?, build(high)?).into(),
+                    )
+                    .into(),
+                ),
+                ast::Operator::Distinct(lhs, rhs) => Distinct(build(lhs)?, build(rhs)?),
                 ast::Operator::Equal(lhs, rhs) => Equal(build(lhs)?, build(rhs)?),
                 ast::Operator::GreaterThan(lhs, rhs) => GreaterThan(build(lhs)?, build(rhs)?),
                 ast::Operator::GreaterThanOrEqual(lhs, rhs) => Or(
@@ -534,6 +1362,7 @@ impl<'a, C: Catalog> Planner<'a, C> {
                     let expr = build(expr)?;
                     let value = match literal {
                         ast::Literal::Null => Value::Null,
+                        ast::Literal::Boolean(b) => Value::Boolean(b),
                         ast::Literal::Float(f) if f.is_nan() => Value::Float(f),
                         value => panic!("invalid IS value {value:?}"), // enforced by parser
                     };
@@ -544,8 +1373,22 @@ impl<'a, C: Catalog> Planner<'a, C> {
                     LessThan(build(lhs.clone())?, build(rhs.clone())?).into(),
                     Equal(build(lhs)?, build(rhs)?).into(),
                 ),
+                ast::Operator::Concat(lhs, rhs) => Concat(build(lhs)?, build(rhs)?),
+                ast::Operator::ILike(lhs, rhs) => ILike(build(lhs)?, build(rhs)?),
+                ast::Operator::JsonExtract(lhs, rhs) => JsonExtract(build(lhs)?, build(rhs)?),
+                ast::Operator::JsonExtractText(lhs, rhs) => {
+                    JsonExtractText(build(lhs)?, build(rhs)?)
+                }
+                ast::Operator::Index(expr, index) => Index(build(expr)?, build(index)?),
+                ast::Operator::In(expr, list) => In(
+                    build(expr)?,
+                    list.into_iter()
+                        .map(|expr| self.build_expression(expr, scope))
+                        .try_collect()?,
+                ),
                 ast::Operator::Like(lhs, rhs) => Like(build(lhs)?, build(rhs)?),
                 ast::Operator::NotEqual(lhs, rhs) => Not(Equal(build(lhs)?, build(rhs)?).into()),
+                ast::Operator::Regex(lhs, rhs) => Regex(build(lhs)?, build(rhs)?),
 
                 ast::Operator::Add(lhs, rhs) => Add(build(lhs)?, build(rhs)?),
                 ast::Operator::Divide(lhs, rhs) => Divide(build(lhs)?, build(rhs)?),
@@ -560,15 +1403,68 @@ impl<'a, C: Catalog> Planner<'a, C> {
         })
     }
 
-    /// Builds and evaluates a constant AST expression. Errors on column refs.
-    fn evaluate_constant(expr: ast::Expression) -> Result<Value> {
-        Self::build_expression(expr, &Scope::new())?.evaluate(None)
+    /// Checks that the datatypes of any constant expressions are compatible,
+    /// e.g. for CASE results. Numeric types can be mixed, and NULL is
+    /// compatible with anything. Other types aren't known until execution.
+    fn check_constant_types<'e>(
+        what: &str,
+        exprs: impl IntoIterator<Item = &'e Expression>,
+    ) -> Result<()> {
+        let mut datatype: Option<DataType> = None;
+        for expr in exprs {
+            let Expression::Constant(value) = expr else { continue };
+            let Some(dt) = value.datatype() else { continue };
+            match datatype {
+                None => datatype = Some(dt),
+                Some(prev) if prev.is_compatible(dt) => {}
+                Some(prev) if prev.is_numeric() && dt.is_numeric() => {}
+                Some(prev) => return errinput!("{what} have incompatible types {prev} and {dt}"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds and evaluates a constant AST expression. Errors on column refs,
+    /// including outer query columns.
+    fn evaluate_constant(&self, expr: ast::Expression) -> Result<Value> {
+        Planner::new(self.catalog).build_expression(expr, &Scope::new())?.evaluate(None)
+    }
+}
+
+/// The outer query of a correlated subquery. Column references that can't be
+/// resolved in the subquery's scope are looked up in the outer query's scope,
+/// and recorded as outer values for the subquery expression.
+struct Outer<'a> {
+    /// The outer query's scope.
+    scope: &'a Scope,
+    /// The outer query's own outer query, if it is also a subquery.
+    parent: Option<&'a Outer<'a>>,
+    /// Outer values referenced by the subquery, as expressions in the outer
+    /// scope. OuterColumn expressions index into these.
+    values: RefCell<Vec<Expression>>,
+}
+
+impl Outer<'_> {
+    /// Looks up a column in the outer query, returning its outer value index.
+    /// If the column is also in an outer query of the outer query, it is passed
+    /// through via the outer query's outer values.
+    fn lookup(&self, table: Option<&str>, name: &str) -> Option<usize> {
+        let expr = match self.scope.lookup_column(table, name) {
+            Ok(index) => Expression::Column(index),
+            Err(_) => Expression::OuterColumn(self.parent?.lookup(table, name)?),
+        };
+        let mut values = self.values.borrow_mut();
+        if let Some(index) = values.iter().position(|value| *value == expr) {
+            return Some(index);
+        }
+        values.push(expr);
+        Some(values.len() - 1)
     }
 }
 
 /// A scope maps column/table names to input column indexes, for lookups during
-/// expression construction. It also tracks aggregate and GROUP BY expressions,
-/// as well as hidden columns.
+/// expression construction. It also tracks aggregate, GROUP BY, and window
+/// function expressions, as well as hidden columns.
 ///
 /// Expression evaluation generally happens in the context of an input row. This
 /// row may come directly from a single table, or it may be the result of a long
@@ -593,6 +1489,10 @@ pub struct Scope {
     /// expressions in downstream SELECT, HAVING, and ORDER BY clauses. If the
     /// node contains an (inner) Aggregate node, this is never empty.
     aggregates: HashMap<ast::Expression, usize>,
+    /// Index of window function expressions to column indexes. This is used to
+    /// track output columns of Window nodes and look them up from expressions
+    /// in downstream SELECT and ORDER BY clauses.
+    windows: HashMap<ast::Expression, usize>,
     /// Hidden columns. These are used to pass e.g. ORDER BY and HAVING
     /// expressions through SELECT projection nodes if the expressions aren't
     /// already projected. They should be removed before emitting results.
@@ -608,6 +1508,7 @@ impl Scope {
             qualified: HashMap::new(),
             unqualified: HashMap::new(),
             aggregates: HashMap::new(),
+            windows: HashMap::new(),
             hidden: HashSet::new(),
         }
     }
@@ -640,6 +1541,25 @@ impl Scope {
         Ok(())
     }
 
+    /// Adds a derived table (subquery) to the scope, with the given alias and
+    /// column labels. The alias and column names must be unique.
+    fn add_query(&mut self, alias: &str, labels: &[Label]) -> Result<()> {
+        if self.tables.contains(alias) {
+            return errinput!("duplicate table name {alias}");
+        }
+        let mut names = HashSet::new();
+        for label in labels {
+            if let Label::Qualified(_, name) = label {
+                if !names.insert(name) {
+                    return errinput!("duplicate column name {name} in subquery {alias}");
+                }
+            }
+            self.add_column(label.clone());
+        }
+        self.tables.insert(alias.to_string());
+        Ok(())
+    }
+
     /// Appends a column with the given label to the scope. Returns the column
     /// index.
     fn add_column(&mut self, label: Label) -> usize {
@@ -710,6 +1630,22 @@ impl Scope {
         self.aggregates.get(expr).copied()
     }
 
+    /// Adds a window function expression to the scope, returning the new
+    /// column index or None if the expression already exists.
+    fn add_window(&mut self, expr: &ast::Expression) -> Option<usize> {
+        if self.windows.contains_key(expr) {
+            return None;
+        }
+        let index = self.add_column(Label::None);
+        self.windows.insert(expr.clone(), index);
+        Some(index)
+    }
+
+    /// Looks up a window function column index by expression.
+    fn lookup_window(&self, expr: &ast::Expression) -> Option<usize> {
+        self.windows.get(expr).copied()
+    }
+
     /// Adds a column that passes through a column from the parent scope,
     /// retaining its properties. If hide is true, the column is hidden.
     fn add_passthrough(&mut self, parent: &Scope, parent_index: usize, hide: bool) -> usize {
@@ -719,6 +1655,11 @@ impl Scope {
                 self.aggregates.entry(expr.clone()).or_insert(index);
             }
         }
+        for (expr, i) in &parent.windows {
+            if *i == parent_index {
+                self.windows.entry(expr.clone()).or_insert(index);
+            }
+        }
         if hide || parent.hidden.contains(&parent_index) {
             self.hidden.insert(index);
         }
@@ -740,6 +1681,9 @@ impl Scope {
         for (expr, index) in scope.aggregates {
             self.aggregates.entry(expr).or_insert(index + offset);
         }
+        for (expr, index) in scope.windows {
+            self.windows.entry(expr).or_insert(index + offset);
+        }
         self.hidden.extend(scope.hidden.into_iter().map(|index| index + offset));
         Ok(())
     }
@@ -770,6 +1714,10 @@ impl Scope {
             if !self.aggregates.is_empty() {
                 child.aggregates.entry(expr.clone()).or_insert(index);
             }
+            // Projected window functions can be looked up by e.g. ORDER BY.
+            if self.windows.contains_key(expr) {
+                child.windows.entry(expr.clone()).or_insert(index);
+            }
         }
         child
     }
@@ -800,6 +1748,7 @@ impl Scope {
         self.unqualified.iter_mut().for_each(|(_, vec)| vec.retain(|i| !hidden.contains(i)));
         self.unqualified.retain(|_, vec| !vec.is_empty());
         self.aggregates.retain(|_, index| !hidden.contains(index));
+        self.windows.retain(|_, index| !hidden.contains(index));
         Some(hidden)
     }
 
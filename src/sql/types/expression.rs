@@ -1,9 +1,11 @@
-use super::{Label, Row, Value};
+use super::value::div_round;
+use super::{DataType, Label, Row, Value};
 use crate::errinput;
 use crate::error::Result;
 use crate::sql::planner::Node;
 
 use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
 
 /// An expression, made up of nested operations and values. Values are either
 /// constants or dynamic column references. Evaluates to a final value during
@@ -18,6 +20,13 @@ pub enum Expression {
     Constant(Value),
     /// A column reference. Used as row index when evaluating expressions.
     Column(usize),
+    /// A reference to an outer query value in a correlated subquery plan. Used
+    /// as an index into the outer values of the Exists expression containing
+    /// the plan, and replaced by a constant before the plan is executed.
+    OuterColumn(usize),
+    /// A query parameter placeholder, e.g. $1, numbered from 1. Replaced by a
+    /// constant when the plan is bound to parameter values via Plan::bind.
+    Parameter(usize),
 
     /// Logical AND of two booleans: a AND b.
     And(Box<Expression>, Box<Expression>),
@@ -28,21 +37,33 @@ pub enum Expression {
 
     /// Equality comparison of two values: a = b.
     Equal(Box<Expression>, Box<Expression>),
+    /// NULL-safe inequality comparison of two values: a IS DISTINCT FROM b.
+    /// Like !=, but NULLs are equal to each other and unequal to other values.
+    /// Never yields NULL.
+    Distinct(Box<Expression>, Box<Expression>),
     /// Greater than comparison of two values: a > b.
     GreaterThan(Box<Expression>, Box<Expression>),
     /// Less than comparison of two values: a < b.
     LessThan(Box<Expression>, Box<Expression>),
-    /// Checks for the given value: IS NULL or IS NAN.
+    /// Checks for the given value: IS NULL, IS NAN, IS TRUE, or IS FALSE.
     Is(Box<Expression>, Value),
+    /// Checks if a value equals any of the given values: a IN (b, c).
+    In(Box<Expression>, Vec<Expression>),
 
+    /// The absolute value of a number: abs(a).
+    Absolute(Box<Expression>),
     /// Adds two numbers: a + b.
     Add(Box<Expression>, Box<Expression>),
+    /// Rounds a number up to the nearest integer: ceil(a).
+    Ceil(Box<Expression>),
     /// Divides two numbers: a / b.
     Divide(Box<Expression>, Box<Expression>),
     /// Exponentiates two numbers, i.e. a ^ b.
     Exponentiate(Box<Expression>, Box<Expression>),
     /// Takes the factorial of a number: 4! = 4*3*2*1.
     Factorial(Box<Expression>),
+    /// Rounds a number down to the nearest integer: floor(a).
+    Floor(Box<Expression>),
     /// The identify function, which simply returns the same number: +a.
     Identity(Box<Expression>),
     /// Multiplies two numbers: a * b.
@@ -51,13 +72,75 @@ pub enum Expression {
     Negate(Box<Expression>),
     /// The remainder after dividing two numbers: a % b.
     Remainder(Box<Expression>, Box<Expression>),
+    /// Rounds a number to the given number of decimal places (0 if not given),
+    /// rounding half away from zero: round(a, places).
+    Round(Box<Expression>, Option<Box<Expression>>),
     /// Takes the square root of a number: √a.
     SquareRoot(Box<Expression>),
     /// Subtracts two numbers: a - b.
     Subtract(Box<Expression>, Box<Expression>),
 
-    // Checks if a string matches a pattern: a LIKE b.
+    /// Concatenates two values as strings: a || b. Non-string values are
+    /// converted to strings, and NULL yields NULL.
+    Concat(Box<Expression>, Box<Expression>),
+    /// Concatenates any number of values as strings, skipping NULLs:
+    /// concat(a, b, c).
+    ConcatAll(Vec<Expression>),
+    /// Checks if a string matches a pattern: a LIKE b.
     Like(Box<Expression>, Box<Expression>),
+    /// Checks if a string matches a pattern, ignoring case: a ILIKE b.
+    ILike(Box<Expression>, Box<Expression>),
+    /// Checks if a string matches a regular expression: a ~ b. The match is
+    /// unanchored, i.e. it can match any part of the string.
+    Regex(Box<Expression>, Box<Expression>),
+    /// The number of characters in a string, or elements in an array:
+    /// length(a).
+    Length(Box<Expression>),
+    /// Converts a string to lowercase: lower(a).
+    Lower(Box<Expression>),
+    /// Extracts part of a string, given a 1-based start position and an
+    /// optional length: substring(a, start, length).
+    Substring(Box<Expression>, Box<Expression>, Option<Box<Expression>>),
+    /// Converts a string to uppercase: upper(a).
+    Upper(Box<Expression>),
+
+    /// Builds an array from the given element values: [a, b, c].
+    Array(Vec<Expression>),
+    /// Checks if an array contains a value: contains(a, b).
+    Contains(Box<Expression>, Box<Expression>),
+    /// Looks up an array element by 1-based position: a[b].
+    Index(Box<Expression>, Box<Expression>),
+
+    /// Extracts a JSON object field by key or array element by 0-based
+    /// position, as JSON: a -> b.
+    JsonExtract(Box<Expression>, Box<Expression>),
+    /// Like JsonExtract, but yields the value as a string: a ->> b.
+    JsonExtractText(Box<Expression>, Box<Expression>),
+
+    /// A conditional expression: CASE WHEN a THEN b [...] ELSE c END. Yields
+    /// the result of the first condition that evaluates to true, otherwise the
+    /// ELSE value or NULL. The simple form CASE a WHEN b THEN c END is built
+    /// as CASE WHEN a = b THEN c END.
+    Case(Vec<(Expression, Expression)>, Option<Box<Expression>>),
+    /// Yields the first non-NULL value, or NULL if all values are NULL:
+    /// coalesce(a, b, c).
+    Coalesce(Vec<Expression>),
+    /// Yields NULL if the values are equal, otherwise the first value:
+    /// nullif(a, b).
+    NullIf(Box<Expression>, Box<Expression>),
+    /// Converts a value to the given datatype: CAST(a AS type). See
+    /// Value::cast() for the conversion rules.
+    Cast(Box<Expression>, DataType),
+
+    /// A scalar subquery, which must return a single column and at most one
+    /// row. It is executed and replaced by a constant value before evaluation,
+    /// see execution::execute_node(). Like Exists, the expressions are outer
+    /// query values referenced by OuterColumn in a correlated subquery plan.
+    Subquery(Box<Node>, Vec<Expression>),
+    /// An EXISTS subquery, which returns true if the plan yields any rows. The
+    /// expressions are outer query values referenced by OuterColumn in the
+    /// plan, which is executed for each outer row, see execution::execute_node().
+    Exists(Box<Node>, Vec<Expression>),
 }
 
 impl Expression {
@@ -69,14 +152,21 @@ impl Expression {
         // Precedence levels, for grouping. Matches the parser precedence.
         fn precedence(expr: &Expression) -> u8 {
             match expr {
-                Column(_) | Constant(_) | SquareRoot(_) => 11,
-                Identity(_) | Negate(_) => 10,
-                Factorial(_) => 9,
-                Exponentiate(_, _) => 8,
-                Multiply(_, _) | Divide(_, _) | Remainder(_, _) => 7,
-                Add(_, _) | Subtract(_, _) => 6,
+                Column(_) | Constant(_) | OuterColumn(_) | Parameter(_) | SquareRoot(_) => 12,
+                Absolute(_) | Ceil(_) | Floor(_) | Round(_, _) => 12,
+                ConcatAll(_) | Length(_) | Lower(_) | Substring(_, _, _) | Upper(_) => 12,
+                Case(_, _) | Cast(_, _) | Coalesce(_) | NullIf(_, _) => 12,
+                Exists(_, _) | Subquery(_, _) => 12,
+                Array(_) | Contains(_, _) | Index(_, _) => 12,
+                Identity(_) | Negate(_) => 11,
+                Factorial(_) => 10,
+                Exponentiate(_, _) => 9,
+                Multiply(_, _) | Divide(_, _) | Remainder(_, _) => 8,
+                Add(_, _) | Subtract(_, _) => 7,
+                Concat(_, _) | JsonExtract(_, _) | JsonExtractText(_, _) => 6,
                 GreaterThan(_, _) | LessThan(_, _) => 5,
-                Equal(_, _) | Like(_, _) | Is(_, _) => 4,
+                Equal(_, _) | Distinct(_, _) | Like(_, _) | ILike(_, _) | Regex(_, _) => 4,
+                In(_, _) | Is(_, _) => 4,
                 Not(_) => 3,
                 And(_, _) => 2,
                 Or(_, _) => 1,
@@ -98,33 +188,106 @@ impl Expression {
                 Label::None => format!("#{index}"),
                 label => format!("{label}"),
             },
+            OuterColumn(index) => format!("outer#{index}"),
+            Parameter(index) => format!("${index}"),
 
             And(lhs, rhs) => format!("{} AND {}", format(lhs), format(rhs)),
             Or(lhs, rhs) => format!("{} OR {}", format(lhs), format(rhs)),
             Not(expr) => format!("NOT {}", format(expr)),
 
             Equal(lhs, rhs) => format!("{} = {}", format(lhs), format(rhs)),
+            Distinct(lhs, rhs) => format!("{} IS DISTINCT FROM {}", format(lhs), format(rhs)),
             GreaterThan(lhs, rhs) => format!("{} > {}", format(lhs), format(rhs)),
             LessThan(lhs, rhs) => format!("{} < {}", format(lhs), format(rhs)),
             Is(expr, Value::Null) => format!("{} IS NULL", format(expr)),
             Is(expr, Value::Float(f)) if f.is_nan() => format!("{} IS NAN", format(expr)),
+            Is(expr, Value::Boolean(true)) => format!("{} IS TRUE", format(expr)),
+            Is(expr, Value::Boolean(false)) => format!("{} IS FALSE", format(expr)),
             Is(_, v) => panic!("unexpected IS value {v}"),
+            In(expr, list) => {
+                let list = list.iter().map(|expr| expr.format(node)).collect::<Vec<_>>();
+                format!("{} IN ({})", format(expr), list.join(", "))
+            }
 
+            Absolute(expr) => format!("abs({})", expr.format(node)),
             Add(lhs, rhs) => format!("{} + {}", format(lhs), format(rhs)),
+            Ceil(expr) => format!("ceil({})", expr.format(node)),
             Divide(lhs, rhs) => format!("{} / {}", format(lhs), format(rhs)),
             Exponentiate(lhs, rhs) => format!("{} ^ {}", format(lhs), format(rhs)),
             Factorial(expr) => format!("{}!", format(expr)),
+            Floor(expr) => format!("floor({})", expr.format(node)),
             Identity(expr) => format(expr),
             Multiply(lhs, rhs) => format!("{} * {}", format(lhs), format(rhs)),
             Negate(expr) => format!("-{}", format(expr)),
             Remainder(lhs, rhs) => format!("{} % {}", format(lhs), format(rhs)),
+            Round(expr, None) => format!("round({})", expr.format(node)),
+            Round(expr, Some(places)) => {
+                format!("round({}, {})", expr.format(node), places.format(node))
+            }
             SquareRoot(expr) => format!("sqrt({})", format(expr)),
             Subtract(lhs, rhs) => format!("{} - {}", format(lhs), format(rhs)),
 
+            Concat(lhs, rhs) => format!("{} || {}", format(lhs), format(rhs)),
+            ConcatAll(exprs) => {
+                let args = exprs.iter().map(|expr| expr.format(node)).collect::<Vec<_>>();
+                format!("concat({})", args.join(", "))
+            }
             Like(lhs, rhs) => format!("{} LIKE {}", format(lhs), format(rhs)),
+            ILike(lhs, rhs) => format!("{} ILIKE {}", format(lhs), format(rhs)),
+            Regex(lhs, rhs) => format!("{} ~ {}", format(lhs), format(rhs)),
+            Length(expr) => format!("length({})", expr.format(node)),
+            Lower(expr) => format!("lower({})", expr.format(node)),
+            Substring(expr, start, length) => {
+                let mut args = vec![expr.format(node), start.format(node)];
+                args.extend(length.as_ref().map(|length| length.format(node)));
+                format!("substring({})", args.join(", "))
+            }
+            Upper(expr) => format!("upper({})", expr.format(node)),
+
+            Array(items) => {
+                let items = items.iter().map(|expr| expr.format(node)).collect::<Vec<_>>();
+                format!("[{}]", items.join(", "))
+            }
+            Contains(lhs, rhs) => format!("contains({}, {})", lhs.format(node), rhs.format(node)),
+            Index(expr, index) => format!("{}[{}]", format(expr), index.format(node)),
+
+            JsonExtract(lhs, rhs) => format!("{} -> {}", format(lhs), format(rhs)),
+            JsonExtractText(lhs, rhs) => format!("{} ->> {}", format(lhs), format(rhs)),
+
+            Case(when, r#else) => {
+                let mut string = "CASE".to_string();
+                for (when, then) in when {
+                    string += &format!(" WHEN {} THEN {}", when.format(node), then.format(node));
+                }
+                if let Some(r#else) = r#else {
+                    string += &format!(" ELSE {}", r#else.format(node));
+                }
+                string + " END"
+            }
+            Coalesce(exprs) => {
+                let args = exprs.iter().map(|expr| expr.format(node)).collect::<Vec<_>>();
+                format!("coalesce({})", args.join(", "))
+            }
+            NullIf(lhs, rhs) => format!("nullif({}, {})", lhs.format(node), rhs.format(node)),
+            Cast(expr, datatype) => format!("CAST({} AS {datatype})", expr.format(node)),
+
+            // The subquery plan is displayed as a child of the plan node.
+            Subquery(_, outer) if outer.is_empty() => "(subquery)".to_string(),
+            Subquery(_, outer) => format!("(subquery, {})", Self::format_outer(outer, node)),
+            Exists(_, outer) if outer.is_empty() => "EXISTS (subquery)".to_string(),
+            Exists(_, outer) => format!("EXISTS (subquery, {})", Self::format_outer(outer, node)),
         }
     }
 
+    /// Formats the outer values of a correlated subquery.
+    fn format_outer(outer: &[Expression], node: &Node) -> String {
+        let outer = outer.iter().enumerate();
+        outer
+            .map(|(i, expr)| format!("outer#{i} = {}", expr.format(node)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
     /// Formats a constant expression. Errors on column references.
     pub fn format_constant(&self) -> String {
         self.format(&Node::Nothing { columns: Vec::new() })
@@ -145,22 +308,46 @@ impl Expression {
                 None => panic!("can't reference column {index} with constant evaluation"),
             },
 
+            // Subqueries are executed and replaced by constants before
+            // evaluation, which isn't possible in e.g. LIMIT clauses.
+            Self::Subquery(_, _) | Self::Exists(_, _) => {
+                return errinput!("subquery not allowed here")
+            }
+
+            // Outer column references are replaced by constants before the
+            // subquery plan is executed.
+            Self::OuterColumn(index) => panic!("unresolved outer column {index}"),
+
+            // Parameters are replaced by constants when binding the plan, but
+            // may be evaluated unbound during planning, e.g. in LIMIT clauses.
+            Self::Parameter(index) => return errinput!("unbound parameter ${index}"),
+
             // Logical AND. Inputs must be boolean or NULL. NULLs generally
             // yield NULL, except the special case NULL AND false == false.
-            Self::And(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
-                (Boolean(lhs), Boolean(rhs)) => Boolean(lhs && rhs),
-                (Boolean(b), Null) | (Null, Boolean(b)) if !b => Boolean(false),
-                (Boolean(_), Null) | (Null, Boolean(_)) | (Null, Null) => Null,
-                (lhs, rhs) => return errinput!("can't AND {lhs} and {rhs}"),
+            // Short-circuits, i.e. the right operand isn't evaluated if the
+            // left operand is false.
+            Self::And(lhs, rhs) => match lhs.evaluate(row)? {
+                Boolean(false) => Boolean(false),
+                lhs => match (lhs, rhs.evaluate(row)?) {
+                    (Boolean(lhs), Boolean(rhs)) => Boolean(lhs && rhs),
+                    (Boolean(b), Null) | (Null, Boolean(b)) if !b => Boolean(false),
+                    (Boolean(_), Null) | (Null, Boolean(_)) | (Null, Null) => Null,
+                    (lhs, rhs) => return errinput!("can't AND {lhs} and {rhs}"),
+                },
             },
 
             // Logical OR. Inputs must be boolean or NULL. NULLs generally
             // yield NULL, except the special case NULL OR true == true.
-            Self::Or(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
-                (Boolean(lhs), Boolean(rhs)) => Boolean(lhs || rhs),
-                (Boolean(b), Null) | (Null, Boolean(b)) if b => Boolean(true),
-                (Boolean(_), Null) | (Null, Boolean(_)) | (Null, Null) => Null,
-                (lhs, rhs) => return errinput!("can't OR {lhs} and {rhs}"),
+            // Short-circuits, i.e. the right operand isn't evaluated if the
+            // left operand is true.
+            Self::Or(lhs, rhs) => match lhs.evaluate(row)? {
+                Boolean(true) => Boolean(true),
+                lhs => match (lhs, rhs.evaluate(row)?) {
+                    (Boolean(lhs), Boolean(rhs)) => Boolean(lhs || rhs),
+                    (Boolean(b), Null) | (Null, Boolean(b)) if b => Boolean(true),
+                    (Boolean(_), Null) | (Null, Boolean(_)) | (Null, Null) => Null,
+                    (lhs, rhs) => return errinput!("can't OR {lhs} and {rhs}"),
+                },
             },
 
             // Logical NOT. Input must be boolean or NULL.
@@ -170,46 +357,134 @@ impl Expression {
                 value => return errinput!("can't NOT {value}"),
             },
 
-            // Comparisons. Must be of same type, except floats and integers
-            // which are interchangeable. NULLs yield NULL, NaNs yield NaN.
+            // Comparisons. Must be of same type, except numbers which are
+            // compared exactly by value via compare_mixed_numeric() (e.g.
+            // 1 = 1.0 is TRUE), and strings which are coerced when compared
+            // with timestamps. NULLs yield NULL, NaNs yield NaN. Arrays are
+            // compared lexicographically, see compare_arrays(), and JSON values
+            // as described in Value::cmp().
             //
             // Does not dispatch to Value.cmp() because sorting and comparisons
             // are different for f64 NaN and -0.0 values.
             #[allow(clippy::float_cmp)]
-            Self::Equal(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
-                (Boolean(lhs), Boolean(rhs)) => Boolean(lhs == rhs),
-                (Integer(lhs), Integer(rhs)) => Boolean(lhs == rhs),
-                (Integer(lhs), Float(rhs)) => Boolean(lhs as f64 == rhs),
-                (Float(lhs), Integer(rhs)) => Boolean(lhs == rhs as f64),
-                (Float(lhs), Float(rhs)) => Boolean(lhs == rhs),
-                (String(lhs), String(rhs)) => Boolean(lhs == rhs),
-                (Null, _) | (_, Null) => Null,
-                (lhs, rhs) => return errinput!("can't compare {lhs} and {rhs}"),
-            },
+            Self::Equal(lhs, rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(row)?, rhs.evaluate(row)?);
+                if let Some(ordering) = compare_mixed_numeric(&lhs, &rhs) {
+                    return Ok(Boolean(ordering.is_eq()));
+                }
+                match Value::promote_numeric(lhs, rhs) {
+                    (Boolean(lhs), Boolean(rhs)) => Boolean(lhs == rhs),
+                    (Integer(lhs), Integer(rhs)) => Boolean(lhs == rhs),
+                    (Float(lhs), Float(rhs)) => Boolean(lhs == rhs),
+                    (String(lhs), String(rhs)) => Boolean(lhs == rhs),
+                    (Timestamp(lhs), Timestamp(rhs)) => Boolean(lhs == rhs),
+                    (Timestamp(lhs), rhs @ String(_)) => {
+                        Boolean(Timestamp(lhs) == rhs.coerce(DataType::Timestamp)?)
+                    }
+                    (lhs @ String(_), Timestamp(rhs)) => {
+                        Boolean(lhs.coerce(DataType::Timestamp)? == Timestamp(rhs))
+                    }
+                    (lhs @ Decimal(..), rhs @ Decimal(..)) => Boolean(lhs.cmp(&rhs).is_eq()),
+                    (lhs @ Array(_), rhs @ Array(_)) => {
+                        Boolean(compare_arrays(&lhs, &rhs)?.is_eq())
+                    }
+                    (lhs @ Json(_), rhs @ Json(_)) => Boolean(lhs.cmp(&rhs).is_eq()),
+                    (Null, _) | (_, Null) => Null,
+                    (lhs, rhs) => return errinput!("can't compare {lhs} and {rhs}"),
+                }
+            }
 
-            Self::GreaterThan(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
-                #[allow(clippy::bool_comparison)]
-                (Boolean(lhs), Boolean(rhs)) => Boolean(lhs > rhs),
-                (Integer(lhs), Integer(rhs)) => Boolean(lhs > rhs),
-                (Integer(lhs), Float(rhs)) => Boolean(lhs as f64 > rhs),
-                (Float(lhs), Integer(rhs)) => Boolean(lhs > rhs as f64),
-                (Float(lhs), Float(rhs)) => Boolean(lhs > rhs),
-                (String(lhs), String(rhs)) => Boolean(lhs > rhs),
-                (Null, _) | (_, Null) => Null,
-                (lhs, rhs) => return errinput!("can't compare {lhs} and {rhs}"),
+            // IS DISTINCT FROM is the negation of = semantics, except NULLs
+            // are equal to each other and distinct from other values.
+            Self::Distinct(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
+                (Null, Null) => Boolean(false),
+                (Null, _) | (_, Null) => Boolean(true),
+                (lhs, rhs) => {
+                    match Self::Equal(Self::Constant(lhs).into(), Self::Constant(rhs).into())
+                        .evaluate(None)?
+                    {
+                        Boolean(equal) => Boolean(!equal),
+                        value => panic!("unexpected equality result {value}"),
+                    }
+                }
             },
 
-            Self::LessThan(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
-                #[allow(clippy::bool_comparison)]
-                (Boolean(lhs), Boolean(rhs)) => Boolean(lhs < rhs),
-                (Integer(lhs), Integer(rhs)) => Boolean(lhs < rhs),
-                (Integer(lhs), Float(rhs)) => Boolean((lhs as f64) < rhs),
-                (Float(lhs), Integer(rhs)) => Boolean(lhs < rhs as f64),
-                (Float(lhs), Float(rhs)) => Boolean(lhs < rhs),
-                (String(lhs), String(rhs)) => Boolean(lhs < rhs),
-                (Null, _) | (_, Null) => Null,
-                (lhs, rhs) => return errinput!("can't compare {lhs} and {rhs}"),
-            },
+            // IN compares the value with each list value using = semantics.
+            // Yields TRUE on a match, otherwise NULL if any comparison yielded
+            // NULL (e.g. a NULL value or list value), otherwise FALSE.
+            Self::In(expr, list) => {
+                let value = expr.evaluate(row)?;
+                let mut result = Boolean(false);
+                for item in list {
+                    let equal = Self::Equal(
+                        Self::Constant(value.clone()).into(),
+                        Self::Constant(item.evaluate(row)?).into(),
+                    );
+                    match equal.evaluate(None)? {
+                        Boolean(true) => return Ok(Boolean(true)),
+                        Boolean(false) => {}
+                        Null => result = Null,
+                        value => panic!("unexpected equality result {value}"),
+                    }
+                }
+                result
+            }
+
+            Self::GreaterThan(lhs, rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(row)?, rhs.evaluate(row)?);
+                if let Some(ordering) = compare_mixed_numeric(&lhs, &rhs) {
+                    return Ok(Boolean(ordering.is_gt()));
+                }
+                match Value::promote_numeric(lhs, rhs) {
+                    #[allow(clippy::bool_comparison)]
+                    (Boolean(lhs), Boolean(rhs)) => Boolean(lhs > rhs),
+                    (Integer(lhs), Integer(rhs)) => Boolean(lhs > rhs),
+                    (Float(lhs), Float(rhs)) => Boolean(lhs > rhs),
+                    (String(lhs), String(rhs)) => Boolean(lhs > rhs),
+                    (Timestamp(lhs), Timestamp(rhs)) => Boolean(lhs > rhs),
+                    (Timestamp(lhs), rhs @ String(_)) => {
+                        Boolean(Timestamp(lhs) > rhs.coerce(DataType::Timestamp)?)
+                    }
+                    (lhs @ String(_), Timestamp(rhs)) => {
+                        Boolean(lhs.coerce(DataType::Timestamp)? > Timestamp(rhs))
+                    }
+                    (lhs @ Decimal(..), rhs @ Decimal(..)) => Boolean(lhs.cmp(&rhs).is_gt()),
+                    (lhs @ Array(_), rhs @ Array(_)) => {
+                        Boolean(compare_arrays(&lhs, &rhs)?.is_gt())
+                    }
+                    (lhs @ Json(_), rhs @ Json(_)) => Boolean(lhs.cmp(&rhs).is_gt()),
+                    (Null, _) | (_, Null) => Null,
+                    (lhs, rhs) => return errinput!("can't compare {lhs} and {rhs}"),
+                }
+            }
+
+            Self::LessThan(lhs, rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(row)?, rhs.evaluate(row)?);
+                if let Some(ordering) = compare_mixed_numeric(&lhs, &rhs) {
+                    return Ok(Boolean(ordering.is_lt()));
+                }
+                match Value::promote_numeric(lhs, rhs) {
+                    #[allow(clippy::bool_comparison)]
+                    (Boolean(lhs), Boolean(rhs)) => Boolean(lhs < rhs),
+                    (Integer(lhs), Integer(rhs)) => Boolean(lhs < rhs),
+                    (Float(lhs), Float(rhs)) => Boolean(lhs < rhs),
+                    (String(lhs), String(rhs)) => Boolean(lhs < rhs),
+                    (Timestamp(lhs), Timestamp(rhs)) => Boolean(lhs < rhs),
+                    (Timestamp(lhs), rhs @ String(_)) => {
+                        Boolean(Timestamp(lhs) < rhs.coerce(DataType::Timestamp)?)
+                    }
+                    (lhs @ String(_), Timestamp(rhs)) => {
+                        Boolean(lhs.coerce(DataType::Timestamp)? < Timestamp(rhs))
+                    }
+                    (lhs @ Decimal(..), rhs @ Decimal(..)) => Boolean(lhs.cmp(&rhs).is_lt()),
+                    (lhs @ Array(_), rhs @ Array(_)) => {
+                        Boolean(compare_arrays(&lhs, &rhs)?.is_lt())
+                    }
+                    (lhs @ Json(_), rhs @ Json(_)) => Boolean(lhs.cmp(&rhs).is_lt()),
+                    (Null, _) | (_, Null) => Null,
+                    (lhs, rhs) => return errinput!("can't compare {lhs} and {rhs}"),
+                }
+            }
 
             Self::Is(expr, Null) => Boolean(expr.evaluate(row)? == Null),
             Self::Is(expr, Float(f)) if f.is_nan() => match expr.evaluate(row)? {
@@ -217,12 +492,43 @@ impl Expression {
                 Null => Null,
                 v => return errinput!("IS NAN can't be used with {}", v.datatype().unwrap()),
             },
+            // IS TRUE and IS FALSE never yield NULL.
+            Self::Is(expr, Boolean(b)) => match expr.evaluate(row)? {
+                Boolean(v) => Boolean(v == *b),
+                Null => Boolean(false),
+                v => {
+                    let name = if *b { "TRUE" } else { "FALSE" };
+                    return errinput!("IS {name} can't be used with {}", v.datatype().unwrap());
+                }
+            },
             Self::Is(_, v) => panic!("invalid IS value {v}"), // enforced by parser
 
-            // Mathematical operations. Inputs must be numbers, but integers and
-            // floats are interchangeable (float when mixed). NULLs yield NULL.
-            // Errors on integer overflow, while floats yield infinity or NaN.
+            // Mathematical operations. Inputs must be numbers, but integers,
+            // decimals, and floats are interchangeable (decimal or float when
+            // mixed). NULLs yield NULL. Errors on integer and decimal overflow,
+            // while floats yield infinity or NaN.
+            Self::Absolute(expr) => match expr.evaluate(row)? {
+                Integer(i) => {
+                    Integer(i.checked_abs().map_or_else(|| errinput!("integer overflow"), Ok)?)
+                }
+                Float(f) => Float(f.abs()),
+                Decimal(m, s) => match m.checked_abs() {
+                    Some(m) => Decimal(m, s),
+                    None => return errinput!("decimal overflow"),
+                },
+                Null => Null,
+                value => return errinput!("can't take absolute value of {value}"),
+            },
             Self::Add(lhs, rhs) => lhs.evaluate(row)?.checked_add(&rhs.evaluate(row)?)?,
+            Self::Ceil(expr) => match expr.evaluate(row)? {
+                Integer(i) => Integer(i),
+                Float(f) => Float(f.ceil()),
+                Decimal(m, s) => {
+                    round_decimal(m, s, 0, |m, f| m.div_euclid(f) + (m.rem_euclid(f) > 0) as i128)?
+                }
+                Null => Null,
+                value => return errinput!("can't take ceiling of {value}"),
+            },
             Self::Divide(lhs, rhs) => lhs.evaluate(row)?.checked_div(&rhs.evaluate(row)?)?,
             Self::Exponentiate(lhs, rhs) => lhs.evaluate(row)?.checked_pow(&rhs.evaluate(row)?)?,
             Self::Factorial(expr) => match expr.evaluate(row)? {
@@ -231,72 +537,320 @@ impl Expression {
                 Null => Null,
                 value => return errinput!("can't take factorial of {value}"),
             },
+            Self::Floor(expr) => match expr.evaluate(row)? {
+                Integer(i) => Integer(i),
+                Float(f) => Float(f.floor()),
+                Decimal(m, s) => round_decimal(m, s, 0, i128::div_euclid)?,
+                Null => Null,
+                value => return errinput!("can't take floor of {value}"),
+            },
             Self::Identity(expr) => match expr.evaluate(row)? {
-                v @ (Integer(_) | Float(_) | Null) => v,
+                v @ (Integer(_) | Float(_) | Decimal(..) | Null) => v,
                 expr => return errinput!("can't take the identity of {expr}"),
             },
             Self::Multiply(lhs, rhs) => lhs.evaluate(row)?.checked_mul(&rhs.evaluate(row)?)?,
             Self::Negate(expr) => match expr.evaluate(row)? {
-                Integer(i) => Integer(-i),
+                Integer(i) => match i.checked_neg() {
+                    Some(i) => Integer(i),
+                    None => return errinput!("integer overflow"),
+                },
                 Float(f) => Float(-f),
+                Decimal(m, s) => match m.checked_neg() {
+                    Some(m) => Decimal(m, s),
+                    None => return errinput!("decimal overflow"),
+                },
                 Null => Null,
                 value => return errinput!("can't negate {value}"),
             },
             Self::Remainder(lhs, rhs) => lhs.evaluate(row)?.checked_rem(&rhs.evaluate(row)?)?,
+            Self::Round(expr, places) => {
+                let places = match places {
+                    Some(places) => places.evaluate(row)?,
+                    None => Integer(0),
+                };
+                match (expr.evaluate(row)?, places) {
+                    (Integer(i), Integer(places)) => Integer(round_integer(i, places)?),
+                    (Float(f), Integer(places)) => Float(round_float(f, places)),
+                    (Decimal(m, s), Integer(places)) => round_decimal(m, s, places, div_round)?,
+                    (Null, _) | (_, Null) => Null,
+                    (value, places) => return errinput!("can't round {value} to {places} places"),
+                }
+            }
             Self::SquareRoot(expr) => match expr.evaluate(row)? {
                 Integer(i) if i < 0 => return errinput!("can't take negative square root"),
                 Integer(i) => Float((i as f64).sqrt()),
+                Float(f) if f < 0.0 => return errinput!("can't take negative square root"),
                 Float(f) => Float(f.sqrt()),
+                v @ Decimal(..) => Self::SquareRoot(v.decimal_to_float().into()).evaluate(None)?,
                 Null => Null,
                 value => return errinput!("can't take square root of {value}"),
             },
             Self::Subtract(lhs, rhs) => lhs.evaluate(row)?.checked_sub(&rhs.evaluate(row)?)?,
 
+            // String concatenation. Other values are converted to strings, e.g.
+            // 'a' || 1 is 'a1'. NULLs yield NULL for ||, but are skipped by
+            // concat(), which yields an empty string if all values are NULL.
+            Self::Concat(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
+                (Null, _) | (_, Null) => Null,
+                (lhs, rhs) => String(lhs.to_text() + &rhs.to_text()),
+            },
+            Self::ConcatAll(exprs) => {
+                let mut string = std::string::String::new();
+                for expr in exprs {
+                    match expr.evaluate(row)? {
+                        Null => {}
+                        value => string += &value.to_text(),
+                    }
+                }
+                String(string)
+            }
+
             // LIKE pattern matching, using _ and % as single- and
-            // multi-character wildcards. Inputs must be strings. NULLs yield
-            // NULL. There's no support for escaping an _ and %.
-            Self::Like(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
-                (String(lhs), String(rhs)) => {
-                    // We could precompile the pattern if it's constant, instead
-                    // of recompiling it for every row, but this is fine.
-                    let pattern =
-                        format!("^{}$", regex::escape(&rhs).replace('%', ".*").replace('_', "."));
-                    Boolean(regex::Regex::new(&pattern)?.is_match(&lhs))
+            // multi-character wildcards, which can be escaped with \. Inputs
+            // must be strings. NULLs yield NULL.
+            Self::Like(lhs, rhs) | Self::ILike(lhs, rhs) => {
+                let op = if matches!(self, Self::ILike(..)) { "ILIKE" } else { "LIKE" };
+                match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
+                    (String(lhs), String(rhs)) => {
+                        let regex = like_regex(&rhs, matches!(self, Self::ILike(..)))?;
+                        Boolean(regex.is_match(&lhs))
+                    }
+                    (String(_), Null) | (Null, String(_)) | (Null, Null) => Null,
+                    (lhs, rhs) => return errinput!("can't {op} {lhs} and {rhs}"),
                 }
+            }
+
+            // Regular expression matching, using the regex crate syntax.
+            // Inputs must be strings. NULLs yield NULL.
+            Self::Regex(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
+                (String(lhs), String(rhs)) => Boolean(compile_regex(&rhs, false)?.is_match(&lhs)),
                 (String(_), Null) | (Null, String(_)) | (Null, Null) => Null,
-                (lhs, rhs) => return errinput!("can't LIKE {lhs} and {rhs}"),
+                (lhs, rhs) => return errinput!("can't match {lhs} against {rhs}"),
+            },
+
+            // String functions. Inputs must be strings, NULLs yield NULL.
+            // length() also takes arrays.
+            Self::Length(expr) => match expr.evaluate(row)? {
+                String(s) => Integer(s.chars().count() as i64),
+                Array(items) => Integer(items.len() as i64),
+                Null => Null,
+                value => return errinput!("can't take length of {value}"),
+            },
+            Self::Lower(expr) => match expr.evaluate(row)? {
+                String(s) => String(s.to_lowercase()),
+                Null => Null,
+                value => return errinput!("can't lowercase {value}"),
+            },
+            Self::Upper(expr) => match expr.evaluate(row)? {
+                String(s) => String(s.to_uppercase()),
+                Null => Null,
+                value => return errinput!("can't uppercase {value}"),
+            },
+
+            // Array functions. Arrays must have the same element type (see
+            // Value::array). Positions are 1-based, and must be within the
+            // array. NULL arrays or positions yield NULL.
+            Self::Array(items) => {
+                Value::array(items.iter().map(|item| item.evaluate(row)).collect::<Result<_>>()?)?
+            }
+            Self::Index(expr, index) => match (expr.evaluate(row)?, index.evaluate(row)?) {
+                (Array(items), Integer(index)) => {
+                    match usize::try_from(index).ok().and_then(|i| items.get(i.wrapping_sub(1))) {
+                        Some(item) => item.clone(),
+                        None => {
+                            return errinput!(
+                                "array index {index} out of range for array of length {}",
+                                items.len()
+                            )
+                        }
+                    }
+                }
+                (Array(_) | Null, Null) | (Null, Integer(_)) => Null,
+                (value, index) => return errinput!("can't index {value} with {index}"),
+            },
+
+            // contains() compares the value with each element using IN
+            // semantics, i.e. yields NULL if there's no match but the array
+            // contains NULLs.
+            Self::Contains(lhs, rhs) => match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
+                (Array(items), value) => {
+                    Self::In(value.into(), items.into_iter().map(Self::Constant).collect())
+                        .evaluate(None)?
+                }
+                (Null, _) => Null,
+                (value, _) => return errinput!("can't check if {value} contains a value"),
             },
+
+            // JSON extraction. Object fields are looked up by string key, and
+            // array elements by 0-based integer position. Missing fields or
+            // elements yield NULL, as do NULL operands. ->> yields JSON strings
+            // as-is, JSON null as NULL, and other JSON values as JSON text.
+            Self::JsonExtract(lhs, rhs) | Self::JsonExtractText(lhs, rhs) => {
+                let json = match (lhs.evaluate(row)?, rhs.evaluate(row)?) {
+                    (Json(json), String(key)) => json.get(key.as_str()).cloned(),
+                    (Json(json), Integer(i)) => {
+                        usize::try_from(i).ok().and_then(|i| json.get(i)).cloned()
+                    }
+                    (Json(_), Null) | (Null, String(_) | Integer(_) | Null) => None,
+                    (lhs, rhs) => return errinput!("can't extract {rhs} from {lhs}"),
+                };
+                match (json, self) {
+                    (None, _) => Null,
+                    (Some(json), Self::JsonExtract(..)) => Json(json),
+                    (Some(serde_json::Value::Null), _) => Null,
+                    (Some(serde_json::Value::String(s)), _) => String(s),
+                    (Some(json), _) => String(json.to_string()),
+                }
+            }
+
+            // CASE yields the result of the first true condition. Conditions
+            // must be boolean or NULL, and NULL is not a match. Only the
+            // matching branch is evaluated.
+            Self::Case(when, r#else) => {
+                for (when, then) in when {
+                    match when.evaluate(row)? {
+                        Boolean(true) => return then.evaluate(row),
+                        Boolean(false) | Null => {}
+                        value => {
+                            return errinput!("CASE condition returned {value}, expected boolean")
+                        }
+                    }
+                }
+                match r#else {
+                    Some(r#else) => r#else.evaluate(row)?,
+                    None => Null,
+                }
+            }
+
+            // COALESCE only evaluates values up to the first non-NULL one.
+            Self::Coalesce(exprs) => {
+                for expr in exprs {
+                    match expr.evaluate(row)? {
+                        Null => {}
+                        value => return Ok(value),
+                    }
+                }
+                Null
+            }
+            Self::Cast(expr, datatype) => expr.evaluate(row)?.cast(*datatype)?,
+
+            // NULLIF uses = semantics, so a NULL value never equals anything.
+            Self::NullIf(lhs, rhs) => {
+                let (lhs, rhs) = (lhs.evaluate(row)?, rhs.evaluate(row)?);
+                match Self::Equal(Self::Constant(lhs.clone()).into(), Self::Constant(rhs).into())
+                    .evaluate(None)?
+                {
+                    Boolean(true) => Null,
+                    _ => lhs,
+                }
+            }
+
+            // Substrings use 1-based character positions. Like in PostgreSQL,
+            // the length is counted from the start position even when it's
+            // before the first character, and out-of-range positions are
+            // clamped to the string.
+            Self::Substring(expr, start, length) => {
+                let length = length.as_ref().map(|length| length.evaluate(row)).transpose()?;
+                match (expr.evaluate(row)?, start.evaluate(row)?, length) {
+                    (String(_), Integer(_), Some(Integer(length))) if length < 0 => {
+                        return errinput!("negative substring length {length}")
+                    }
+                    (String(s), Integer(start), length @ (None | Some(Integer(_)))) => {
+                        let end = match length {
+                            Some(Integer(length)) => start.saturating_add(length),
+                            _ => i64::MAX,
+                        };
+                        let start = start.max(1);
+                        let (skip, take) = (start - 1, end.saturating_sub(start).max(0));
+                        String(s.chars().skip(skip as usize).take(take as usize).collect())
+                    }
+                    (Null, _, _) | (_, Null, _) | (_, _, Some(Null)) => Null,
+                    (value, start, None) => {
+                        return errinput!("can't take substring of {value} at {start}")
+                    }
+                    (value, start, Some(length)) => {
+                        return errinput!(
+                            "can't take substring of {value} at {start} length {length}"
+                        )
+                    }
+                }
+            }
         })
     }
 
     /// Recursively walks the expression tree depth-first, calling the given
     /// closure until it returns false. Returns true otherwise.
-    pub fn walk(&self, visitor: &mut impl FnMut(&Expression) -> bool) -> bool {
+    pub fn walk<'a>(&'a self, visitor: &mut impl FnMut(&'a Expression) -> bool) -> bool {
         if !visitor(self) {
             return false;
         }
         match self {
             Self::Add(lhs, rhs)
             | Self::And(lhs, rhs)
+            | Self::Concat(lhs, rhs)
+            | Self::Distinct(lhs, rhs)
             | Self::Divide(lhs, rhs)
             | Self::Equal(lhs, rhs)
             | Self::Exponentiate(lhs, rhs)
             | Self::GreaterThan(lhs, rhs)
+            | Self::ILike(lhs, rhs)
+            | Self::Contains(lhs, rhs)
+            | Self::Index(lhs, rhs)
+            | Self::JsonExtract(lhs, rhs)
+            | Self::JsonExtractText(lhs, rhs)
             | Self::LessThan(lhs, rhs)
             | Self::Like(lhs, rhs)
             | Self::Multiply(lhs, rhs)
+            | Self::NullIf(lhs, rhs)
             | Self::Or(lhs, rhs)
+            | Self::Regex(lhs, rhs)
             | Self::Remainder(lhs, rhs)
             | Self::Subtract(lhs, rhs) => lhs.walk(visitor) && rhs.walk(visitor),
 
-            Self::Factorial(expr)
+            Self::Absolute(expr)
+            | Self::Ceil(expr)
+            | Self::Factorial(expr)
+            | Self::Floor(expr)
             | Self::Identity(expr)
+            | Self::Cast(expr, _)
             | Self::Is(expr, _)
             | Self::Negate(expr)
+            | Self::Length(expr)
+            | Self::Lower(expr)
             | Self::Not(expr)
-            | Self::SquareRoot(expr) => expr.walk(visitor),
+            | Self::SquareRoot(expr)
+            | Self::Upper(expr) => expr.walk(visitor),
+
+            Self::Round(expr, places) => {
+                expr.walk(visitor) && places.as_ref().is_none_or(|places| places.walk(visitor))
+            }
+
+            Self::Substring(expr, start, length) => {
+                expr.walk(visitor)
+                    && start.walk(visitor)
+                    && length.as_ref().is_none_or(|length| length.walk(visitor))
+            }
 
-            Self::Constant(_) | Self::Column(_) => true,
+            Self::In(expr, list) => {
+                expr.walk(visitor) && list.iter().all(|expr| expr.walk(visitor))
+            }
+
+            Self::Array(exprs) | Self::Coalesce(exprs) | Self::ConcatAll(exprs) => {
+                exprs.iter().all(|expr| expr.walk(visitor))
+            }
+
+            Self::Case(when, r#else) => {
+                when.iter().all(|(when, then)| when.walk(visitor) && then.walk(visitor))
+                    && r#else.as_ref().is_none_or(|r#else| r#else.walk(visitor))
+            }
+
+            // Subqueries have their own scope, so we don't descend into them,
+            // only into the outer values of correlated subqueries.
+            Self::Subquery(_, outer) | Self::Exists(_, outer) => {
+                outer.iter().all(|expr| expr.walk(visitor))
+            }
+
+            Self::Constant(_) | Self::Column(_) | Self::OuterColumn(_) | Self::Parameter(_) => true,
         }
     }
 
@@ -324,25 +878,98 @@ impl Expression {
         self = match self {
             Self::Add(lhs, rhs) => Self::Add(xform(lhs)?, xform(rhs)?),
             Self::And(lhs, rhs) => Self::And(xform(lhs)?, xform(rhs)?),
+            Self::Concat(lhs, rhs) => Self::Concat(xform(lhs)?, xform(rhs)?),
+            Self::Distinct(lhs, rhs) => Self::Distinct(xform(lhs)?, xform(rhs)?),
             Self::Divide(lhs, rhs) => Self::Divide(xform(lhs)?, xform(rhs)?),
             Self::Equal(lhs, rhs) => Self::Equal(xform(lhs)?, xform(rhs)?),
             Self::Exponentiate(lhs, rhs) => Self::Exponentiate(xform(lhs)?, xform(rhs)?),
             Self::GreaterThan(lhs, rhs) => Self::GreaterThan(xform(lhs)?, xform(rhs)?),
+            Self::ILike(lhs, rhs) => Self::ILike(xform(lhs)?, xform(rhs)?),
             Self::LessThan(lhs, rhs) => Self::LessThan(xform(lhs)?, xform(rhs)?),
             Self::Like(lhs, rhs) => Self::Like(xform(lhs)?, xform(rhs)?),
             Self::Multiply(lhs, rhs) => Self::Multiply(xform(lhs)?, xform(rhs)?),
             Self::Or(lhs, rhs) => Self::Or(xform(lhs)?, xform(rhs)?),
+            Self::Regex(lhs, rhs) => Self::Regex(xform(lhs)?, xform(rhs)?),
             Self::Remainder(lhs, rhs) => Self::Remainder(xform(lhs)?, xform(rhs)?),
             Self::SquareRoot(expr) => Self::SquareRoot(xform(expr)?),
             Self::Subtract(lhs, rhs) => Self::Subtract(xform(lhs)?, xform(rhs)?),
 
+            Self::Absolute(expr) => Self::Absolute(xform(expr)?),
+            Self::Ceil(expr) => Self::Ceil(xform(expr)?),
             Self::Factorial(expr) => Self::Factorial(xform(expr)?),
+            Self::Floor(expr) => Self::Floor(xform(expr)?),
             Self::Identity(expr) => Self::Identity(xform(expr)?),
             Self::Is(expr, value) => Self::Is(xform(expr)?, value),
+            Self::In(expr, list) => Self::In(
+                xform(expr)?,
+                list.into_iter()
+                    .map(|expr| expr.transform(before, after))
+                    .collect::<Result<_>>()?,
+            ),
             Self::Negate(expr) => Self::Negate(xform(expr)?),
+            Self::Round(expr, places) => Self::Round(xform(expr)?, places.map(xform).transpose()?),
             Self::Not(expr) => Self::Not(xform(expr)?),
 
-            expr @ (Self::Constant(_) | Self::Column(_)) => expr,
+            Self::ConcatAll(exprs) => Self::ConcatAll(
+                exprs
+                    .into_iter()
+                    .map(|expr| expr.transform(before, after))
+                    .collect::<Result<_>>()?,
+            ),
+            Self::Length(expr) => Self::Length(xform(expr)?),
+            Self::Lower(expr) => Self::Lower(xform(expr)?),
+            Self::Substring(expr, start, length) => {
+                Self::Substring(xform(expr)?, xform(start)?, length.map(xform).transpose()?)
+            }
+            Self::Upper(expr) => Self::Upper(xform(expr)?),
+
+            Self::Array(items) => Self::Array(
+                items
+                    .into_iter()
+                    .map(|expr| expr.transform(before, after))
+                    .collect::<Result<_>>()?,
+            ),
+            Self::Contains(lhs, rhs) => Self::Contains(xform(lhs)?, xform(rhs)?),
+            Self::Index(expr, index) => Self::Index(xform(expr)?, xform(index)?),
+            Self::JsonExtract(lhs, rhs) => Self::JsonExtract(xform(lhs)?, xform(rhs)?),
+            Self::JsonExtractText(lhs, rhs) => Self::JsonExtractText(xform(lhs)?, xform(rhs)?),
+
+            Self::Case(when, r#else) => Self::Case(
+                when.into_iter()
+                    .map(|(when, then)| {
+                        Ok((when.transform(before, after)?, then.transform(before, after)?))
+                    })
+                    .collect::<Result<_>>()?,
+                r#else.map(xform).transpose()?,
+            ),
+            Self::Coalesce(exprs) => Self::Coalesce(
+                exprs
+                    .into_iter()
+                    .map(|expr| expr.transform(before, after))
+                    .collect::<Result<_>>()?,
+            ),
+            Self::NullIf(lhs, rhs) => Self::NullIf(xform(lhs)?, xform(rhs)?),
+            Self::Cast(expr, datatype) => Self::Cast(xform(expr)?, datatype),
+
+            Self::Subquery(node, outer) => Self::Subquery(
+                node,
+                outer
+                    .into_iter()
+                    .map(|expr| expr.transform(before, after))
+                    .collect::<Result<_>>()?,
+            ),
+            Self::Exists(node, outer) => Self::Exists(
+                node,
+                outer
+                    .into_iter()
+                    .map(|expr| expr.transform(before, after))
+                    .collect::<Result<_>>()?,
+            ),
+
+            expr @ (Self::Constant(_)
+            | Self::Column(_)
+            | Self::OuterColumn(_)
+            | Self::Parameter(_)) => expr,
         };
         self = after(self)?;
         Ok(self)
@@ -416,7 +1043,7 @@ impl Expression {
     }
 
     /// Checks if an expression is a single column lookup (i.e. a disjunction of
-    /// = or IS NULL/NAN for a single column), returning the column index.
+    /// =, IN or IS NULL/NAN for a single column), returning the column index.
     pub fn is_column_lookup(&self) -> Option<usize> {
         use Expression::*;
         match &self {
@@ -431,6 +1058,11 @@ impl Expression {
                 Column(c) => Some(*c),
                 _ => None,
             },
+            // IN lists of constants can use index lookups.
+            In(expr, list) => match expr.as_ref() {
+                Column(c) if list.iter().all(|e| matches!(e, Constant(_))) => Some(*c),
+                _ => None,
+            },
             // All OR branches must be lookups on the same column:
             // id = 1 OR id = 2 OR id = 3.
             Or(lhs, rhs) => match (lhs.is_column_lookup(), rhs.is_column_lookup()) {
@@ -468,6 +1100,20 @@ impl Expression {
                 }
                 expr => panic!("unexpected expression {expr:?}"),
             },
+            // Like equality, NULL and NAN list values never match.
+            In(expr, list) => match *expr {
+                Column(column) => {
+                    assert_eq!(column, index, "unexpected column");
+                    list.into_iter()
+                        .map(|expr| match expr {
+                            Constant(value) => value,
+                            expr => panic!("unexpected expression {expr:?}"),
+                        })
+                        .filter(|value| !value.is_undefined())
+                        .collect()
+                }
+                expr => panic!("unexpected expression {expr:?}"),
+            },
             Or(lhs, rhs) => {
                 let mut values = lhs.into_column_values(index);
                 values.extend(rhs.into_column_values(index));
@@ -496,6 +1142,154 @@ impl Expression {
     }
 }
 
+/// Compares numbers of different types by value, see Value::numeric_cmp().
+/// This avoids the precision loss of numeric promotion, e.g. the integer
+/// 2^53 + 1 isn't equal to the float 2^53, while the decimal 0.1 is equal to
+/// the float 0.1. Returns None for other values, and for NaN,
+/// which is neither equal to, greater than, nor less than other numbers.
+fn compare_mixed_numeric(lhs: &Value, rhs: &Value) -> Option<std::cmp::Ordering> {
+    let mixed = lhs.is_numeric()
+        && rhs.is_numeric()
+        && std::mem::discriminant(lhs) != std::mem::discriminant(rhs);
+    if !mixed || lhs.is_undefined() || rhs.is_undefined() {
+        return None;
+    }
+    Some(lhs.numeric_cmp(rhs))
+}
+
+/// Compares two arrays lexicographically by element. Unlike scalar comparisons,
+/// NULL elements are equal to each other and less than other values, like when
+/// sorting. The element types must match.
+fn compare_arrays(lhs: &Value, rhs: &Value) -> Result<std::cmp::Ordering> {
+    match (lhs.datatype(), rhs.datatype()) {
+        (Some(l), Some(r)) if l.is_compatible(r) => Ok(lhs.cmp(rhs)),
+        _ => errinput!("can't compare {lhs} and {rhs}"),
+    }
+}
+
+/// Compiles a LIKE pattern into an anchored regular expression. % matches any
+/// number of characters, _ matches a single character, and \ escapes the
+/// following character such that it's matched literally (e.g. \% or \\).
+fn like_regex(pattern: &str, case_insensitive: bool) -> Result<regex::Regex> {
+    let mut re = String::from("^");
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => re.push_str(".*"),
+            '_' => re.push('.'),
+            '\\' => match chars.next() {
+                Some(c) => re.push_str(&regex::escape(&c.to_string())),
+                None => return errinput!("LIKE pattern can't end with escape character \\"),
+            },
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    compile_regex(&re, case_insensitive)
+}
+
+/// Compiles a regular expression. Patterns are typically constant, so the last
+/// compiled regex is cached per thread to avoid recompiling it for every row.
+fn compile_regex(pattern: &str, case_insensitive: bool) -> Result<regex::Regex> {
+    thread_local! {
+        static CACHE: RefCell<Option<(String, bool, regex::Regex)>> = const { RefCell::new(None) };
+    }
+    CACHE.with_borrow_mut(|cache| {
+        if let Some((p, ci, regex)) = cache {
+            if p == pattern && *ci == case_insensitive {
+                return Ok(regex.clone());
+            }
+        }
+        let regex = regex::RegexBuilder::new(pattern)
+            .case_insensitive(case_insensitive)
+            .dot_matches_new_line(true)
+            .build()
+            .or_else(|err| match err {
+                // Syntax errors span multiple lines, with the cause last.
+                regex::Error::Syntax(msg) => {
+                    let cause = msg.lines().last().unwrap_or_default();
+                    let cause = cause.trim_start_matches("error: ");
+                    errinput!("invalid regex '{}': {cause}", pattern.escape_debug())
+                }
+                err => errinput!("invalid regex '{}': {err}", pattern.escape_debug()),
+            })?;
+        *cache = Some((pattern.to_string(), case_insensitive, regex.clone()));
+        Ok(regex)
+    })
+}
+
+/// Rounds an integer to the given number of decimal places, rounding half away
+/// from zero. Only negative places have an effect, e.g. -2 rounds to hundreds.
+fn round_integer(i: i64, places: i64) -> Result<i64> {
+    if places >= 0 {
+        return Ok(i);
+    }
+    // i64 has at most 19 digits, so anything beyond that rounds to 0.
+    let Some(factor) = places_factor(places) else {
+        return Ok(0);
+    };
+    let i = i as i128;
+    let rounded = (i.abs() + factor / 2) / factor * factor * i.signum();
+    i64::try_from(rounded).or_else(|_| errinput!("integer overflow"))
+}
+
+/// Returns 10^|places| for rounding to negative places, or None if it doesn't
+/// fit in an i128. Uses unsigned_abs(), since negating i64::MIN overflows.
+fn places_factor(places: i64) -> Option<i128> {
+    u32::try_from(places.unsigned_abs()).ok().and_then(|p| 10_i128.checked_pow(p))
+}
+
+/// Rounds a float to the given number of decimal places, rounding half away
+/// from zero. Negative places round to tens, hundreds, etc.
+fn round_float(f: f64, places: i64) -> f64 {
+    if !f.is_finite() {
+        return f;
+    }
+    let factor = 10_f64.powi(places.unsigned_abs().min(i32::MAX as u64) as i32);
+    if places < 0 {
+        if !factor.is_finite() {
+            return 0.0_f64.copysign(f);
+        }
+        return (f / factor).round() * factor;
+    }
+    let scaled = f * factor;
+    if !scaled.is_finite() {
+        return f; // the float can't represent that many places anyway
+    }
+    scaled.round() / factor
+}
+
+/// Rounds a decimal to the given number of decimal places, using the given
+/// integer division function to round the mantissa (e.g. rounding half away
+/// from zero, towards negative infinity, or towards positive infinity).
+/// Negative places round to tens, hundreds, etc. The result has the given
+/// scale, or scale 0 for negative places, but the scale is never increased.
+fn round_decimal(
+    mantissa: i128,
+    scale: u64,
+    places: i64,
+    div: impl Fn(i128, i128) -> i128,
+) -> Result<Value> {
+    if places >= 0 && places as u64 >= scale {
+        return Ok(Value::Decimal(mantissa, scale));
+    }
+    // Decimal mantissas have at most 38 digits, so a larger factor rounds to 0.
+    let digits = scale as i128 - places as i128;
+    let rounded = match u32::try_from(digits).ok().and_then(|d| 10_i128.checked_pow(d)) {
+        Some(factor) => div(mantissa, factor),
+        None => 0,
+    };
+    if places >= 0 {
+        return Ok(Value::Decimal(rounded, places as u64));
+    }
+    // For negative places, scale the mantissa back up to a scale of 0.
+    places_factor(places)
+        .and_then(|f| rounded.checked_mul(f))
+        .or_else(|| (rounded == 0).then_some(0))
+        .map(|m| Value::Decimal(m, 0))
+        .map_or_else(|| errinput!("decimal overflow"), Ok)
+}
+
 impl From<Value> for Expression {
     fn from(value: Value) -> Self {
         Expression::Constant(value)
@@ -5,5 +5,5 @@ mod schema;
 mod value;
 
 pub use expression::Expression;
-pub use schema::{Column, Table};
-pub use value::{DataType, Label, Row, Rows, Value};
+pub use schema::{format_id, Column, OnDelete, Table};
+pub use value::{DataType, ElementType, Label, Row, Rows, Value, DECIMAL_MAX_PRECISION};
@@ -1,8 +1,9 @@
-use super::{DataType, Value};
+use super::{DataType, Expression, Label, Row, Value};
 use crate::encoding;
 use crate::errinput;
 use crate::error::Result;
 use crate::sql::engine::{Catalog, Transaction};
+use crate::sql::planner::Node;
 
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
@@ -15,11 +16,20 @@ use std::borrow::Cow;
 pub struct Table {
     /// The table name. Can't be empty.
     pub name: String,
-    /// The primary key column index. A table must have a primary key, and it
-    /// can only be a single column.
-    pub primary_key: usize,
+    /// The primary key column indexes, in key order. A table must have a
+    /// primary key. It is usually a single column, but can be a composite key
+    /// of several columns, in which case rows are ordered by the key tuple.
+    pub primary_key: Vec<usize>,
     /// The table's columns. Must have at least one.
     pub columns: Vec<Column>,
+    /// Multi-column unique constraints, as column indexes. The combination of
+    /// column values must be unique, ignoring rows with any NULL values. The
+    /// first column must have a secondary index, used to find conflicts.
+    /// Single-column constraints use Column.unique instead.
+    pub unique: Vec<Vec<usize>>,
+    /// Table CHECK constraints, which must not evaluate to false for any row.
+    /// NULL passes the check. Column references are row column indexes.
+    pub checks: Vec<Expression>,
 }
 
 impl encoding::Value for Table {}
@@ -31,49 +41,107 @@ pub struct Column {
     pub name: String,
     /// Column datatype.
     pub datatype: DataType,
-    /// Whether the column allows null values. Not legal for primary keys.
+    /// Whether the column allows null values. Not legal for primary key
+    /// columns.
     pub nullable: bool,
     /// The column's default value. If None, the user must specify an explicit
     /// value. Must match the column datatype. Nullable columns require a
     /// default (often Null), and Null is only a valid default when nullable.
     pub default: Option<Value>,
     /// Whether the column should only allow unique values (ignoring NULLs).
-    /// Must be true for a primary key column.
+    /// Must be true for a single-column primary key. Columns of a composite
+    /// primary key are only unique as a tuple, and don't set this.
     pub unique: bool,
     /// Whether the column should have a secondary index. Must be false for
-    /// primary keys, which are the implicit primary index. Must be true for
-    /// unique or reference columns.
+    /// single-column primary keys, which are the implicit primary index. Must
+    /// be true for unique or reference columns otherwise.
     pub index: bool,
+    /// The index name, if given via CREATE INDEX. Index names are unique
+    /// across all tables, and can be used with DROP INDEX. Requires index.
+    pub index_name: Option<String>,
     /// If set, this column is a foreign key reference to the given table's
-    /// primary key. Must be of the same type as the target primary key.
+    /// primary key. Must be of the same type as the target primary key, which
+    /// can't be a composite key.
     pub references: Option<String>,
+    /// The action to take on referencing rows when a referenced row is
+    /// deleted. Must be Restrict if the column isn't a foreign key.
+    pub on_delete: OnDelete,
+    /// A CHECK constraint, which must not evaluate to false for any row. NULL
+    /// passes the check. Column references are row column indexes.
+    pub check: Option<Expression>,
+}
+
+/// A foreign key ON DELETE action, i.e. what to do with referencing rows when
+/// the referenced row is deleted.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq, Deserialize, Serialize)]
+pub enum OnDelete {
+    /// Error if there are referencing rows.
+    #[default]
+    Restrict,
+    /// Delete the referencing rows as well.
+    Cascade,
+    /// Set the referencing column to NULL.
+    SetNull,
+}
+
+impl std::fmt::Display for OnDelete {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Restrict => "RESTRICT",
+            Self::Cascade => "CASCADE",
+            Self::SetNull => "SET NULL",
+        })
+    }
 }
 
 impl std::fmt::Display for Table {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "CREATE TABLE {} (", format_ident(&self.name))?;
+        // Build the column definitions and table constraints as lines, to
+        // separate them with commas.
+        let mut lines = Vec::new();
         for (i, column) in self.columns.iter().enumerate() {
-            write!(f, "  {} {}", format_ident(&column.name), column.datatype)?;
-            if i == self.primary_key {
-                write!(f, " PRIMARY KEY")?;
+            let mut line = format!("{} {}", format_ident(&column.name), column.datatype);
+            if self.primary_key == [i] {
+                line += " PRIMARY KEY";
             } else if !column.nullable {
-                write!(f, " NOT NULL")?;
+                line += " NOT NULL";
             }
             if let Some(default) = &column.default {
-                write!(f, " DEFAULT {default}")?;
+                line += &format!(" DEFAULT {default}");
             }
-            if i != self.primary_key {
+            if self.primary_key != [i] {
                 if column.unique {
-                    write!(f, " UNIQUE")?;
+                    line += " UNIQUE";
                 }
                 if column.index {
-                    write!(f, " INDEX")?;
+                    line += " INDEX";
                 }
             }
             if let Some(reference) = &column.references {
-                write!(f, " REFERENCES {reference}")?;
+                line += &format!(" REFERENCES {reference}");
+                if column.on_delete != OnDelete::Restrict {
+                    line += &format!(" ON DELETE {}", column.on_delete);
+                }
+            }
+            if let Some(check) = &column.check {
+                line += &format!(" CHECK ({})", self.format_expression(check));
             }
-            if i < self.columns.len() - 1 {
+            lines.push(line);
+        }
+        if self.primary_key.len() > 1 {
+            lines.push(format!("PRIMARY KEY ({})", self.format_columns(&self.primary_key)));
+        }
+        for unique in &self.unique {
+            lines.push(format!("UNIQUE ({})", self.format_columns(unique)));
+        }
+        for check in &self.checks {
+            lines.push(format!("CHECK ({})", self.format_expression(check)));
+        }
+
+        writeln!(f, "CREATE TABLE {} (", format_ident(&self.name))?;
+        for (i, line) in lines.iter().enumerate() {
+            write!(f, "  {line}")?;
+            if i < lines.len() - 1 {
                 write!(f, ",")?;
             }
             writeln!(f)?;
@@ -84,7 +152,9 @@ impl std::fmt::Display for Table {
 
 impl Table {
     /// Validates the table schema, using the catalog to validate foreign key
-    /// references.
+    /// references. This is done both when planning CREATE TABLE (such that
+    /// EXPLAIN CREATE TABLE can be used as a dry run), and when creating or
+    /// altering the table in the catalog.
     pub fn validate(&self, catalog: &impl Catalog) -> Result<()> {
         if self.name.is_empty() {
             return errinput!("table name can't be empty");
@@ -92,8 +162,45 @@ impl Table {
         if self.columns.is_empty() {
             return errinput!("table has no columns");
         }
-        if self.columns.get(self.primary_key).is_none() {
-            return errinput!("invalid primary key index");
+        if self.primary_key.is_empty() {
+            return errinput!("table has no primary key");
+        }
+        for (i, index) in self.primary_key.iter().enumerate() {
+            if self.columns.get(*index).is_none() {
+                return errinput!("invalid primary key index");
+            }
+            if self.primary_key[..i].contains(index) {
+                let cname = &self.columns[*index].name;
+                return errinput!("column {cname} given multiple times in primary key");
+            }
+        }
+
+        for check in self.columns.iter().flat_map(|c| c.check.as_ref()).chain(&self.checks) {
+            if check
+                .contains(&|expr| matches!(expr, Expression::Column(i) if *i >= self.columns.len()))
+            {
+                return errinput!("invalid CHECK column index");
+            }
+        }
+
+        for unique in &self.unique {
+            if unique.len() < 2 {
+                return errinput!("unique constraint must have multiple columns");
+            }
+            for (i, index) in unique.iter().enumerate() {
+                let Some(column) = self.columns.get(*index) else {
+                    return errinput!("invalid unique constraint column index");
+                };
+                let cname = &column.name;
+                if unique[..i].contains(index) {
+                    return errinput!("column {cname} given multiple times in unique constraint");
+                }
+                if i == 0 && !column.index && self.primary_key != [*index] {
+                    return errinput!(
+                        "unique constraint column {cname} must have a secondary index"
+                    );
+                }
+            }
         }
 
         for (i, column) in self.columns.iter().enumerate() {
@@ -101,13 +208,37 @@ impl Table {
                 return errinput!("column name can't be empty");
             }
             let (cname, ctype) = (&column.name, &column.datatype); // for formatting convenience
+            if self.columns[..i].iter().any(|c| &c.name == cname) {
+                return errinput!("duplicate column {cname} in table {}", self.name);
+            }
 
-            // Validate primary key.
-            let is_primary_key = i == self.primary_key;
-            if is_primary_key {
-                if column.nullable {
-                    return errinput!("primary key {cname} cannot be nullable");
+            // Validate array and JSON columns. These can't be used in keys,
+            // since the key encoding can't delimit variable-length sequences
+            // (arrays) or preserve their order (JSON).
+            if let DataType::Array(None) = ctype {
+                return errinput!("unknown element type for array column {cname}");
+            }
+            let kind = match ctype {
+                DataType::Array(_) => Some("array"),
+                DataType::Json => Some("JSON"),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                if self.primary_key.contains(&i) {
+                    return errinput!("{kind} column {cname} can't be a primary key");
                 }
+                if column.index {
+                    return errinput!("{kind} column {cname} can't be indexed");
+                }
+            }
+
+            // Validate primary key. Composite key columns are regular columns,
+            // except that they can't be nullable.
+            let is_primary_key = self.primary_key == [i];
+            if self.primary_key.contains(&i) && column.nullable {
+                return errinput!("primary key {cname} cannot be nullable");
+            }
+            if is_primary_key {
                 if !column.unique {
                     return errinput!("primary key {cname} must be unique");
                 }
@@ -124,12 +255,17 @@ impl Table {
                 Some(None) if !column.nullable => {
                     return errinput!("invalid NULL default for non-nullable column {cname}")
                 }
-                Some(Some(vtype)) if vtype != column.datatype => {
+                Some(Some(vtype)) if !vtype.is_compatible(column.datatype) => {
                     return errinput!("invalid default type {vtype} for {ctype} column {cname}");
                 }
                 Some(_) | None => {}
             }
 
+            // Validate index name.
+            if column.index_name.is_some() && !column.index {
+                return errinput!("unindexed column {cname} can't have an index name");
+            }
+
             // Validate unique index.
             if column.unique && !column.index && !is_primary_key {
                 return errinput!("unique column {cname} must have a secondary index");
@@ -140,21 +276,76 @@ impl Table {
                 if !column.index && !is_primary_key {
                     return errinput!("reference column {cname} must have a secondary index");
                 }
-                let reftype = if reference == &self.name {
-                    self.columns[self.primary_key].datatype
+                let reftypes: Vec<DataType> = if reference == &self.name {
+                    self.primary_key.iter().map(|i| self.columns[*i].datatype).collect()
                 } else if let Some(target) = catalog.get_table(reference)? {
-                    target.columns[target.primary_key].datatype
+                    target.primary_key.iter().map(|i| target.columns[*i].datatype).collect()
                 } else {
                     return errinput!("unknown table {reference} referenced by column {cname}");
                 };
+                let [reftype] = reftypes[..] else {
+                    return errinput!(
+                        "can't reference composite primary key of {reference} from column {cname}"
+                    );
+                };
                 if column.datatype != reftype {
                     return errinput!("can't reference {reftype} primary key of {reference} from {ctype} column {cname}");
                 }
+                if column.on_delete == OnDelete::SetNull && !column.nullable {
+                    return errinput!("ON DELETE SET NULL column {cname} must be nullable");
+                }
+            } else if column.on_delete != OnDelete::Restrict {
+                return errinput!("non-reference column {cname} can't have ON DELETE action");
             }
         }
         Ok(())
     }
 
+    /// Formats a CHECK expression, using the table's column names.
+    pub fn format_expression(&self, expr: &Expression) -> String {
+        let columns =
+            self.columns.iter().map(|c| Label::Unqualified(format_ident(&c.name).into())).collect();
+        expr.format(&Node::Nothing { columns })
+    }
+
+    /// Formats a list of column indexes as comma-separated column names.
+    pub fn format_columns(&self, columns: &[usize]) -> String {
+        let names = columns.iter().map(|i| format_ident(&self.columns[*i].name));
+        names.collect::<Vec<_>>().join(", ")
+    }
+
+    /// Returns the primary key of the given row, i.e. the values of the
+    /// primary key columns in key order.
+    pub fn row_id(&self, row: &[Value]) -> Vec<Value> {
+        self.primary_key.iter().map(|i| row[*i].clone()).collect()
+    }
+
+    /// Coerces row values to the column datatypes where allowed (e.g. strings to
+    /// timestamps), and normalizes them. Datatypes are checked by validate_row().
+    pub fn coerce_row(&self, row: Row) -> Result<Row> {
+        if row.len() != self.columns.len() {
+            return errinput!("invalid row size for table {}", self.name);
+        }
+        row.into_iter()
+            .zip(&self.columns)
+            .map(|(value, column)| {
+                let mut value = value.coerce(column.datatype)?;
+                value.normalize();
+                Ok(value)
+            })
+            .collect()
+    }
+
+    /// Pads a stored row with the default values of any columns that were added
+    /// to the table after the row was written. Rows are not rewritten when
+    /// adding columns, so they may be shorter than the schema.
+    pub fn pad_row(&self, mut row: Row) -> Row {
+        for column in self.columns.iter().skip(row.len()) {
+            row.push(column.default.clone().unwrap_or(Value::Null));
+        }
+        row
+    }
+
     /// Validates a row, including uniqueness and reference checks using the
     /// given transaction.
     ///
@@ -170,13 +361,12 @@ impl Table {
         }
 
         // Validate primary key.
-        let id = &row[self.primary_key];
-        let idslice = &row[self.primary_key..=self.primary_key];
-        if id.is_undefined() {
-            return errinput!("invalid primary key {id}");
+        let id = self.row_id(row);
+        if let Some(value) = id.iter().find(|v| v.is_undefined()) {
+            return errinput!("invalid primary key {value}");
         }
-        if !update && !txn.get(&self.name, idslice)?.is_empty() {
-            return errinput!("primary key {id} already exists");
+        if !update && !txn.get(&self.name, std::slice::from_ref(&id))?.is_empty() {
+            return errinput!("primary key {} already exists", format_id(&id));
         }
 
         for (i, (column, value)) in self.columns.iter().zip(row).enumerate() {
@@ -185,7 +375,7 @@ impl Table {
 
             // Validate datatype.
             if let Some(ref vtype) = value.datatype() {
-                if vtype != ctype {
+                if !vtype.is_compatible(*ctype) {
                     return errinput!("invalid datatype {vtype} for {ctype} column {cname}");
                 }
             }
@@ -199,8 +389,8 @@ impl Table {
                     // NB: NaN is not a valid primary key, and not valid as a
                     // missing foreign key marker.
                     Value::Null => {}
-                    v if target == &self.name && v == id => {}
-                    v if txn.get(target, valueslice)?.is_empty() => {
+                    v if target == &self.name && std::slice::from_ref(v) == id => {}
+                    v if txn.get(target, &[vec![v.clone()]])?.is_empty() => {
                         return errinput!("reference {v} not in table {target}");
                     }
                     _ => {}
@@ -208,22 +398,80 @@ impl Table {
             }
 
             // Validate uniqueness constraints. Unique columns are indexed.
-            if column.unique && i != self.primary_key && !value.is_undefined() {
+            if column.unique && self.primary_key != [i] && !value.is_undefined() {
                 let mut index = txn.lookup_index(&self.name, &column.name, valueslice)?;
                 if update {
-                    index.remove(id); // ignore existing version of this row
+                    index.remove(&id); // ignore existing version of this row
                 }
                 if !index.is_empty() {
                     return errinput!("value {value} already in unique column {cname}");
                 }
             }
         }
+
+        // Validate CHECK constraints. NULL results pass the check.
+        let column_checks = self.columns.iter().filter_map(|c| Some((c.check.as_ref()?, Some(c))));
+        let checks = column_checks.chain(self.checks.iter().map(|check| (check, None)));
+        let mut owned_row = None;
+        for (check, column) in checks {
+            let row = owned_row.get_or_insert_with(|| row.to_vec());
+            match check.evaluate(Some(row))? {
+                Value::Boolean(true) | Value::Null => {}
+                Value::Boolean(false) => {
+                    let check = self.format_expression(check);
+                    return match column {
+                        Some(column) => {
+                            errinput!("CHECK ({check}) failed for column {}", column.name)
+                        }
+                        None => errinput!("CHECK ({check}) failed"),
+                    };
+                }
+                value => return errinput!("CHECK returned {value}, expected boolean"),
+            }
+        }
+
+        // Validate multi-column uniqueness constraints. Look up rows with the
+        // same value in the first column (which is indexed, unless it's the
+        // primary key), and compare the remaining columns.
+        for unique in &self.unique {
+            let values: Vec<Value> = unique.iter().map(|i| row[*i].clone()).collect();
+            if values.iter().any(|v| v.is_undefined()) {
+                continue;
+            }
+            let ids = match self.primary_key == unique[..1] {
+                true => vec![values[..1].to_vec()],
+                false => {
+                    let cname = &self.columns[unique[0]].name;
+                    txn.lookup_index(&self.name, cname, &values[..1])?.into_iter().collect()
+                }
+            };
+            for existing in txn.get(&self.name, &ids)? {
+                if (update && self.row_id(&existing) == id)
+                    || unique.iter().zip(&values).any(|(i, v)| &existing[*i] != v)
+                {
+                    continue;
+                }
+                let (values, cnames) = (format_id(&values), self.format_columns(unique));
+                return errinput!("values {values} already in unique columns ({cnames})");
+            }
+        }
         Ok(())
     }
 }
 
+/// Formats a primary key for display. Composite keys are formatted as a
+/// parenthesized tuple, e.g. (1, 'a').
+pub fn format_id(id: &[Value]) -> String {
+    match id {
+        [value] => value.to_string(),
+        values => {
+            format!("({})", values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", "))
+        }
+    }
+}
+
 /// Formats an identifier as valid SQL, quoting it if necessary.
-fn format_ident(ident: &str) -> Cow<str> {
+fn format_ident(ident: &str) -> Cow<'_, str> {
     if crate::sql::parser::is_ident(ident) {
         return ident.into();
     }
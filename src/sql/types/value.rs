@@ -10,7 +10,8 @@ use std::borrow::Cow;
 /// A primitive SQL value.
 ///
 /// For simplicity, only a handful of representative scalar types are supported,
-/// no compound types or more compact variants.
+/// along with arrays of integers and strings and JSON documents, but no other
+/// compound types or more compact variants.
 ///
 /// In SQL, neither Null nor floating point NaN are considered equal to
 /// themselves (they are unknown values). However, in code, we consider them
@@ -33,13 +34,29 @@ pub enum Value {
     Float(f64),
     /// A UTF-8 encoded string.
     String(String),
+    /// A UTC timestamp, as milliseconds since the Unix epoch. String literals
+    /// such as '2024-01-01' or '2024-01-01 12:00:00.123' are coerced to
+    /// timestamps where a timestamp is expected.
+    Timestamp(i64),
+    /// An exact fixed-point decimal number, given as an integer mantissa and a
+    /// decimal scale, i.e. mantissa * 10^-scale. For example, 123.45 is
+    /// represented as Decimal(12345, 2).
+    Decimal(i128, u64),
+    /// An array of values. Elements all have the same datatype (an integer or
+    /// string), but may be NULL. Use Value::array() to construct it.
+    Array(Vec<Value>),
+    /// A JSON document. It's stored as JSON text, since bincode can't decode
+    /// serde_json::Value (it isn't a self-describing format).
+    Json(#[serde(with = "json_text")] serde_json::Value),
 }
 
 impl encoding::Value for Value {}
 
 // In code, consider Null and NaN equal, so that we can detect and process these
 // values (e.g. in index lookups, aggregation groups, etc). SQL expressions
-// handle them specially to provide their undefined value semantics.
+// handle them specially to provide their undefined value semantics. Numbers of
+// different types are equal if they have the same value, e.g. 1 = 1.0, see
+// Value::numeric_cmp().
 impl std::cmp::PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -47,6 +64,12 @@ impl std::cmp::PartialEq for Value {
             (Self::Integer(l), Self::Integer(r)) => l == r,
             (Self::Float(l), Self::Float(r)) => l == r || l.is_nan() && r.is_nan(),
             (Self::String(l), Self::String(r)) => l == r,
+            (Self::Timestamp(l), Self::Timestamp(r)) => l == r,
+            // Decimals are equal regardless of scale, e.g. 1.0 = 1.00.
+            (l @ Self::Decimal(..), r @ Self::Decimal(..)) => l.cmp(r).is_eq(),
+            (Self::Array(l), Self::Array(r)) => l == r,
+            (Self::Json(l), Self::Json(r)) => json_cmp(l, r).is_eq(),
+            (l, r) if l.is_numeric() && r.is_numeric() => l.numeric_cmp(r).is_eq(),
             (l, r) => core::mem::discriminant(l) == core::mem::discriminant(r),
         }
     }
@@ -56,20 +79,30 @@ impl std::cmp::Eq for Value {}
 
 impl std::hash::Hash for Value {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        core::mem::discriminant(self).hash(state);
-        // Normalize to treat +/-0.0 and +/-NAN as equal when hashing.
+        // Numbers of different types can be equal, e.g. 1 = 1.0 = 1.00, so
+        // they're all hashed as their nearest float value, which equal numbers
+        // share (but unequal numbers may too). Treat +/-0.0 and +/-NAN as equal.
+        let float = |f: f64| if f == 0.0 { 0.0_f64 } else { f }.to_bits();
+        if !self.is_numeric() {
+            core::mem::discriminant(self).hash(state);
+        }
         match self.normalize_ref().as_ref() {
             Self::Null => {}
             Self::Boolean(v) => v.hash(state),
-            Self::Integer(v) => v.hash(state),
-            Self::Float(v) => v.to_bits().hash(state),
+            Self::Integer(v) => float(*v as f64).hash(state),
+            Self::Float(v) => float(*v).hash(state),
+            Self::Decimal(mantissa, scale) => float(decimal_to_f64(*mantissa, *scale)).hash(state),
             Self::String(v) => v.hash(state),
+            Self::Timestamp(v) => v.hash(state),
+            Self::Array(v) => v.hash(state),
+            Self::Json(v) => json_hash(v, state),
         }
     }
 }
 
 // For ordering purposes, we consider NULL and NaN equal. We establish a total
 // order across all types, even though mixed types will rarely/never come up.
+// Numbers of different types are ordered by value, see numeric_cmp().
 impl Ord for Value {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         use std::cmp::Ordering::*;
@@ -78,10 +111,14 @@ impl Ord for Value {
             (Null, Null) => Equal,
             (Boolean(a), Boolean(b)) => a.cmp(b),
             (Integer(a), Integer(b)) => a.cmp(b),
-            (Integer(a), Float(b)) => (*a as f64).total_cmp(b),
-            (Float(a), Integer(b)) => a.total_cmp(&(*b as f64)),
             (Float(a), Float(b)) => a.total_cmp(b),
             (String(a), String(b)) => a.cmp(b),
+            (Timestamp(a), Timestamp(b)) => a.cmp(b),
+            (Decimal(a, sa), Decimal(b, sb)) => decimal_cmp((*a, *sa), (*b, *sb)),
+            // Arrays are ordered lexicographically by element.
+            (Array(a), Array(b)) => a.cmp(b),
+            (Json(a), Json(b)) => json_cmp(a, b),
+            (a, b) if a.is_numeric() && b.is_numeric() => a.numeric_cmp(b),
 
             (Null, _) => Less,
             (_, Null) => Greater,
@@ -91,6 +128,14 @@ impl Ord for Value {
             (_, Float(_)) => Greater,
             (Integer(_), _) => Less,
             (_, Integer(_)) => Greater,
+            (Decimal(..), _) => Less,
+            (_, Decimal(..)) => Greater,
+            (Timestamp(_), _) => Less,
+            (_, Timestamp(_)) => Greater,
+            (Array(_), _) => Less,
+            (_, Array(_)) => Greater,
+            (Json(_), _) => Less,
+            (_, Json(_)) => Greater,
             // String is ordered last.
         }
     }
@@ -106,16 +151,21 @@ impl Value {
     /// Adds two values. Errors when invalid.
     pub fn checked_add(&self, other: &Self) -> Result<Self> {
         use Value::*;
-        Ok(match (self, other) {
-            (Integer(lhs), Integer(rhs)) => match lhs.checked_add(*rhs) {
+        let (lhs, rhs) = Self::promote_numeric(self.clone(), other.clone());
+        if let Some(((lhs, rhs), scale)) = Self::decimal_operands(&lhs, &rhs)? {
+            return Ok(match lhs.checked_add(rhs) {
+                Some(mantissa) => Decimal(mantissa, scale),
+                None => return errinput!("decimal overflow"),
+            });
+        }
+        Ok(match (lhs, rhs) {
+            (Integer(lhs), Integer(rhs)) => match lhs.checked_add(rhs) {
                 Some(i) => Integer(i),
                 None => return errinput!("integer overflow"),
             },
-            (Integer(lhs), Float(rhs)) => Float(*lhs as f64 + rhs),
-            (Float(lhs), Integer(rhs)) => Float(lhs + *rhs as f64),
             (Float(lhs), Float(rhs)) => Float(lhs + rhs),
-            (Null, Integer(_) | Float(_) | Null) => Null,
-            (Integer(_) | Float(_), Null) => Null,
+            (Null, Integer(_) | Float(_) | Decimal(..) | Null) => Null,
+            (Integer(_) | Float(_) | Decimal(..), Null) => Null,
             (lhs, rhs) => return errinput!("can't add {lhs} and {rhs}"),
         })
     }
@@ -123,14 +173,27 @@ impl Value {
     /// Divides two values. Errors when invalid.
     pub fn checked_div(&self, other: &Self) -> Result<Self> {
         use Value::*;
-        Ok(match (self, other) {
+        let (lhs, rhs) = Self::promote_numeric(self.clone(), other.clone());
+        if let Some(((lhs, rhs), scale)) = Self::decimal_operands(&lhs, &rhs)? {
+            if rhs == 0 {
+                return errinput!("can't divide by zero");
+            }
+            // Both operands have the same scale, so scale up the dividend to
+            // retain the scale in the quotient.
+            let Some(lhs) = decimal_rescale(lhs, 0, scale) else {
+                return errinput!("decimal overflow");
+            };
+            return Ok(Decimal(div_round(lhs, rhs), scale));
+        }
+        Ok(match (lhs, rhs) {
             (Integer(_), Integer(0)) => return errinput!("can't divide by zero"),
-            (Integer(lhs), Integer(rhs)) => Integer(lhs / rhs),
-            (Integer(lhs), Float(rhs)) => Float(*lhs as f64 / rhs),
-            (Float(lhs), Integer(rhs)) => Float(lhs / *rhs as f64),
+            (Integer(lhs), Integer(rhs)) => match lhs.checked_div(rhs) {
+                Some(i) => Integer(i),
+                None => return errinput!("integer overflow"),
+            },
             (Float(lhs), Float(rhs)) => Float(lhs / rhs),
-            (Null, Integer(_) | Float(_) | Null) => Null,
-            (Integer(_) | Float(_), Null) => Null,
+            (Null, Integer(_) | Float(_) | Decimal(..) | Null) => Null,
+            (Integer(_) | Float(_) | Decimal(..), Null) => Null,
             (lhs, rhs) => return errinput!("can't divide {lhs} and {rhs}"),
         })
     }
@@ -138,16 +201,23 @@ impl Value {
     /// Multiplies two values. Errors when invalid.
     pub fn checked_mul(&self, other: &Self) -> Result<Self> {
         use Value::*;
-        Ok(match (self, other) {
-            (Integer(lhs), Integer(rhs)) => match lhs.checked_mul(*rhs) {
+        let (lhs, rhs) = Self::promote_numeric(self.clone(), other.clone());
+        if let (Decimal(l, ls), Decimal(r, rs)) = (&lhs, &rhs) {
+            // Keep the exact product, rounding only if it exceeds the maximum
+            // precision. Values are rounded to the column scale when stored.
+            return match decimal_mul((*l, *ls), (*r, *rs)) {
+                Some((mantissa, scale)) => Ok(Decimal(mantissa, scale)),
+                None => errinput!("decimal overflow"),
+            };
+        }
+        Ok(match (lhs, rhs) {
+            (Integer(lhs), Integer(rhs)) => match lhs.checked_mul(rhs) {
                 Some(i) => Integer(i),
                 None => return errinput!("integer overflow"),
             },
-            (Integer(lhs), Float(rhs)) => Float(*lhs as f64 * rhs),
-            (Float(lhs), Integer(rhs)) => Float(lhs * *rhs as f64),
             (Float(lhs), Float(rhs)) => Float(lhs * rhs),
-            (Null, Integer(_) | Float(_) | Null) => Null,
-            (Integer(_) | Float(_), Null) => Null,
+            (Null, Integer(_) | Float(_) | Decimal(..) | Null) => Null,
+            (Integer(_) | Float(_) | Decimal(..), Null) => Null,
             (lhs, rhs) => return errinput!("can't multiply {lhs} and {rhs}"),
         })
     }
@@ -155,20 +225,26 @@ impl Value {
     /// Exponentiates two values. Errors when invalid.
     pub fn checked_pow(&self, other: &Self) -> Result<Self> {
         use Value::*;
-        Ok(match (self, other) {
-            (Integer(lhs), Integer(rhs)) if *rhs >= 0 => {
-                let rhs = (*rhs).try_into().or_else(|_| errinput!("integer overflow"))?;
+        Ok(match Self::promote_numeric(self.clone(), other.clone()) {
+            (Integer(lhs), Integer(rhs)) if rhs >= 0 => {
+                let rhs = rhs.try_into().or_else(|_| errinput!("integer overflow"))?;
                 match lhs.checked_pow(rhs) {
                     Some(i) => Integer(i),
                     None => return errinput!("integer overflow"),
                 }
             }
-            (Integer(lhs), Integer(rhs)) => Float((*lhs as f64).powf(*rhs as f64)),
-            (Integer(lhs), Float(rhs)) => Float((*lhs as f64).powf(*rhs)),
-            (Float(lhs), Integer(rhs)) => Float((lhs).powi(*rhs as i32)),
-            (Float(lhs), Float(rhs)) => Float((lhs).powf(*rhs)),
-            (Integer(_) | Float(_), Null) => Null,
-            (Null, Integer(_) | Float(_) | Null) => Null,
+            (Integer(lhs), Integer(rhs)) => Float((lhs as f64).powf(rhs as f64)),
+            // A negative base with a fractional exponent has a complex result.
+            (Float(lhs), Float(rhs)) if lhs < 0.0 && rhs.is_finite() && rhs.fract() != 0.0 => {
+                return errinput!("can't raise negative number {self} to fractional power {other}")
+            }
+            (Float(lhs), Float(rhs)) => Float(lhs.powf(rhs)),
+            // Decimal exponentiation is performed with floats.
+            (lhs @ Decimal(..), rhs @ Decimal(..)) => {
+                return lhs.decimal_to_float().checked_pow(&rhs.decimal_to_float());
+            }
+            (Integer(_) | Float(_) | Decimal(..), Null) => Null,
+            (Null, Integer(_) | Float(_) | Decimal(..) | Null) => Null,
             (lhs, rhs) => return errinput!("can't exponentiate {lhs} and {rhs}"),
         })
     }
@@ -180,14 +256,20 @@ impl Value {
     /// positive value (modulo).
     pub fn checked_rem(&self, other: &Self) -> Result<Self> {
         use Value::*;
-        Ok(match (self, other) {
+        let (lhs, rhs) = Self::promote_numeric(self.clone(), other.clone());
+        if let Some(((lhs, rhs), scale)) = Self::decimal_operands(&lhs, &rhs)? {
+            if rhs == 0 {
+                return errinput!("can't divide by zero");
+            }
+            return Ok(Decimal(lhs % rhs, scale));
+        }
+        Ok(match (lhs, rhs) {
             (Integer(_), Integer(0)) => return errinput!("can't divide by zero"),
-            (Integer(lhs), Integer(rhs)) => Integer(lhs % rhs),
-            (Integer(lhs), Float(rhs)) => Float(*lhs as f64 % rhs),
-            (Float(lhs), Integer(rhs)) => Float(lhs % *rhs as f64),
+            // i64::MIN % -1 overflows, but the remainder is always 0.
+            (Integer(lhs), Integer(rhs)) => Integer(lhs.wrapping_rem(rhs)),
             (Float(lhs), Float(rhs)) => Float(lhs % rhs),
-            (Integer(_) | Float(_) | Null, Null) => Null,
-            (Null, Integer(_) | Float(_)) => Null,
+            (Integer(_) | Float(_) | Decimal(..) | Null, Null) => Null,
+            (Null, Integer(_) | Float(_) | Decimal(..)) => Null,
             (lhs, rhs) => return errinput!("can't take remainder of {lhs} and {rhs}"),
         })
     }
@@ -195,21 +277,114 @@ impl Value {
     /// Subtracts two values. Errors when invalid.
     pub fn checked_sub(&self, other: &Self) -> Result<Self> {
         use Value::*;
-        Ok(match (self, other) {
-            (Integer(lhs), Integer(rhs)) => match lhs.checked_sub(*rhs) {
+        let (lhs, rhs) = Self::promote_numeric(self.clone(), other.clone());
+        if let Some(((lhs, rhs), scale)) = Self::decimal_operands(&lhs, &rhs)? {
+            return Ok(match lhs.checked_sub(rhs) {
+                Some(mantissa) => Decimal(mantissa, scale),
+                None => return errinput!("decimal overflow"),
+            });
+        }
+        Ok(match (lhs, rhs) {
+            (Integer(lhs), Integer(rhs)) => match lhs.checked_sub(rhs) {
                 Some(i) => Integer(i),
                 None => return errinput!("integer overflow"),
             },
-            (Integer(lhs), Float(rhs)) => Float(*lhs as f64 - rhs),
-            (Float(lhs), Integer(rhs)) => Float(lhs - *rhs as f64),
             (Float(lhs), Float(rhs)) => Float(lhs - rhs),
-            (Null, Integer(_) | Float(_) | Null) => Null,
-            (Integer(_) | Float(_), Null) => Null,
+            (Null, Integer(_) | Float(_) | Decimal(..) | Null) => Null,
+            (Integer(_) | Float(_) | Decimal(..), Null) => Null,
             (lhs, rhs) => return errinput!("can't subtract {lhs} and {rhs}"),
         })
     }
 
-    /// Returns the value's datatype, or None for null values.
+    /// Promotes numeric operands of different types to a common type, such
+    /// that they can be compared or combined. This is used both for comparisons
+    /// and arithmetic. Integers are promoted to decimals or floats, and
+    /// decimals to floats, i.e. INTEGER → DECIMAL → FLOAT. For example, 1 and
+    /// 1.0 are promoted to the floats 1.0 and 1.0, and are thus equal. Integers
+    /// beyond ±2^53 can lose precision when promoted to floats. Other values,
+    /// including NULLs, are returned as-is.
+    pub fn promote_numeric(lhs: Self, rhs: Self) -> (Self, Self) {
+        use Value::*;
+        match (lhs, rhs) {
+            (Integer(lhs), rhs @ Float(_)) => (Float(lhs as f64), rhs),
+            (lhs @ Float(_), Integer(rhs)) => (lhs, Float(rhs as f64)),
+            (Integer(lhs), rhs @ Decimal(..)) => (Decimal(lhs as i128, 0), rhs),
+            (lhs @ Decimal(..), Integer(rhs)) => (lhs, Decimal(rhs as i128, 0)),
+            (lhs @ Decimal(..), rhs @ Float(_)) => (lhs.decimal_to_float(), rhs),
+            (lhs @ Float(_), rhs @ Decimal(..)) => (lhs, rhs.decimal_to_float()),
+            (lhs, rhs) => (lhs, rhs),
+        }
+    }
+
+    /// Compares two numbers of any numeric type by value, without the
+    /// precision loss of numeric promotion. Floats are compared as their
+    /// shortest round-trip decimal representation, i.e. the digits they're
+    /// written with, such that the float 0.1 equals the decimal 0.1 even though
+    /// its binary value is slightly larger. The integer 9007199254740993 is
+    /// thus greater than the float 9007199254740992.0, even though the integer
+    /// promotes to that float. Floats that can't be represented as a DECIMAL
+    /// are compared by their exact binary value, and NaN is ordered after all
+    /// other numbers, like for floats. Non-numeric values use Value::cmp().
+    pub fn numeric_cmp(&self, other: &Self) -> std::cmp::Ordering {
+        use Value::*;
+        match (self, other) {
+            (Integer(l), Float(r)) => match float_decimal(*r) {
+                Some(r) => decimal_cmp((*l as i128, 0), r),
+                None => integer_float_cmp(*l, *r),
+            },
+            (Decimal(l, ls), Float(r)) => match float_decimal(*r) {
+                Some(r) => decimal_cmp((*l, *ls), r),
+                None => decimal_float_cmp((*l, *ls), *r),
+            },
+            (Float(_), Integer(_) | Decimal(..)) => other.numeric_cmp(self).reverse(),
+            (Integer(l), Decimal(r, rs)) => decimal_cmp((*l as i128, 0), (*r, *rs)),
+            (Decimal(l, ls), Integer(r)) => decimal_cmp((*l, *ls), (*r as i128, 0)),
+            (l, r) => l.cmp(r),
+        }
+    }
+
+    /// Converts the operands of a decimal arithmetic operation to mantissas of
+    /// the same scale, i.e. the larger of the operand scales. Returns None if
+    /// the operands aren't both decimals (integers must be promoted first).
+    fn decimal_operands(lhs: &Self, rhs: &Self) -> Result<Option<((i128, i128), u64)>> {
+        let (Self::Decimal(l, ls), Self::Decimal(r, rs)) = (lhs, rhs) else {
+            return Ok(None);
+        };
+        let scale = (*ls).max(*rs);
+        match (decimal_rescale(*l, *ls, scale), decimal_rescale(*r, *rs, scale)) {
+            (Some(lhs), Some(rhs)) => Ok(Some(((lhs, rhs), scale))),
+            _ => errinput!("decimal overflow"),
+        }
+    }
+
+    /// Converts a decimal to the nearest float. Other values are returned as-is.
+    pub fn decimal_to_float(&self) -> Self {
+        match self {
+            Self::Decimal(mantissa, scale) => Self::Float(decimal_to_f64(*mantissa, *scale)),
+            value => value.clone(),
+        }
+    }
+
+    /// Builds an array from the given elements, which must have the same
+    /// datatype (ignoring NULLs), and be integers or strings.
+    pub fn array(items: Vec<Value>) -> Result<Self> {
+        let mut element: Option<DataType> = None;
+        for datatype in items.iter().filter_map(|item| item.datatype()) {
+            match element {
+                None => element = Some(ElementType::try_from(datatype)?.into()),
+                Some(element) if element == datatype => {}
+                Some(element) => {
+                    return errinput!(
+                        "array elements have different types {element} and {datatype}"
+                    )
+                }
+            }
+        }
+        Ok(Self::Array(items))
+    }
+
+    /// Returns the value's datatype, or None for null values. The element type
+    /// of an array without any non-NULL elements is unknown.
     pub fn datatype(&self) -> Option<DataType> {
         match self {
             Self::Null => None,
@@ -217,9 +392,223 @@ impl Value {
             Self::Integer(_) => Some(DataType::Integer),
             Self::Float(_) => Some(DataType::Float),
             Self::String(_) => Some(DataType::String),
+            Self::Timestamp(_) => Some(DataType::Timestamp),
+            Self::Decimal(_, scale) => Some(DataType::Decimal(DECIMAL_MAX_PRECISION, *scale as u8)),
+            Self::Array(items) => Some(DataType::Array(
+                items.iter().find_map(|item| ElementType::try_from(item.datatype()?).ok()),
+            )),
+            Self::Json(_) => Some(DataType::Json),
+        }
+    }
+
+    /// Implicitly converts the value to the given datatype where allowed.
+    /// Strings can be converted to timestamps and JSON (by parsing them), and
+    /// numbers and strings to decimals (rounded to the decimal scale). Other
+    /// values are returned as-is, and datatypes must be validated by the caller.
+    pub fn coerce(self, datatype: DataType) -> Result<Self> {
+        Ok(match (self, datatype) {
+            (Self::String(s), DataType::Timestamp) => Self::Timestamp(parse_timestamp(&s)?),
+            (Self::String(s), DataType::Json) => match serde_json::from_str(&s) {
+                Ok(json) => Self::Json(json),
+                Err(_) => return errinput!("invalid JSON {}", Self::String(s)),
+            },
+            (
+                value @ (Self::Integer(_) | Self::Float(_) | Self::String(_) | Self::Decimal(..)),
+                DataType::Decimal(precision, scale),
+            ) => {
+                let (mantissa, from) = match &value {
+                    Self::Integer(i) => (*i as i128, 0),
+                    Self::Float(f) => match decimal_from_f64(*f, scale as u64) {
+                        Some(mantissa) => (mantissa, scale as u64),
+                        None => return errinput!("can't convert {value} to {datatype}"),
+                    },
+                    Self::String(s) => match parse_decimal(s) {
+                        Some(decimal) => decimal,
+                        None => return errinput!("invalid decimal {value}"),
+                    },
+                    Self::Decimal(mantissa, scale) => (*mantissa, *scale),
+                    _ => unreachable!(),
+                };
+                match decimal_rescale(mantissa, from, scale as u64) {
+                    Some(mantissa) if mantissa.unsigned_abs() < 10_u128.pow(precision as u32) => {
+                        Self::Decimal(mantissa, scale as u64)
+                    }
+                    _ => return errinput!("value {value} out of range for {datatype}"),
+                }
+            }
+            (value, _) => value,
+        })
+    }
+
+    /// Explicitly converts the value to the given datatype, as with CAST. NULL
+    /// casts to NULL. Strings are parsed (ignoring surrounding whitespace),
+    /// and other values are converted to strings via to_text(). Numbers can be
+    /// converted to each other (rounding half away from zero to integers) and
+    /// to/from booleans, with 0 as false. JSON scalars are cast like the
+    /// corresponding SQL value. Errors on invalid conversions.
+    pub fn cast(self, datatype: DataType) -> Result<Self> {
+        use DataType as T;
+        let invalid = |value: &Self| errinput!("can't cast {value} to {datatype}");
+        Ok(match (self, datatype) {
+            (Self::Null, _) => Self::Null,
+
+            (Self::Boolean(b), T::Boolean) => Self::Boolean(b),
+            (Self::Boolean(b), T::Integer) => Self::Integer(b as i64),
+            (Self::Boolean(b), T::Float) => Self::Float(b as i64 as f64),
+            (Self::Boolean(b), T::Decimal(..)) => Self::Integer(b as i64).coerce(datatype)?,
+            (Self::Integer(i), T::Boolean) => Self::Boolean(i != 0),
+            (Self::Float(f), T::Boolean) => Self::Boolean(f != 0.0),
+            (Self::Decimal(m, _), T::Boolean) => Self::Boolean(m != 0),
+
+            (Self::Integer(i), T::Integer) => Self::Integer(i),
+            (Self::Integer(i), T::Float) => Self::Float(i as f64),
+            (Self::Float(f), T::Float) => Self::Float(f),
+            (Self::Float(f), T::Integer) => {
+                let f = f.round();
+                // i64::MAX as f64 rounds up to 2^63, which is out of range.
+                if !(i64::MIN as f64..i64::MAX as f64).contains(&f) {
+                    return invalid(&Self::Float(f));
+                }
+                Self::Integer(f as i64)
+            }
+            (value @ Self::Decimal(..), T::Float) => value.decimal_to_float(),
+            (value @ Self::Decimal(mantissa, scale), T::Integer) => {
+                match decimal_rescale(mantissa, scale, 0).and_then(|i| i64::try_from(i).ok()) {
+                    Some(i) => Self::Integer(i),
+                    None => return invalid(&value),
+                }
+            }
+            (value @ (Self::Integer(_) | Self::Float(_) | Self::Decimal(..)), T::Decimal(..)) => {
+                value.coerce(datatype)?
+            }
+
+            (Self::Timestamp(t), T::Timestamp) => Self::Timestamp(t),
+
+            (Self::Json(json), T::Json) => Self::Json(json),
+            (Self::Json(json), datatype) if datatype != T::String => match json {
+                serde_json::Value::Null => Self::Null,
+                serde_json::Value::Bool(b) => Self::Boolean(b).cast(datatype)?,
+                serde_json::Value::Number(n) => match n.as_i64() {
+                    Some(i) => Self::Integer(i).cast(datatype)?,
+                    None => Self::Float(n.as_f64().unwrap_or(f64::NAN)).cast(datatype)?,
+                },
+                serde_json::Value::String(s) => Self::String(s).cast(datatype)?,
+                json => return invalid(&Self::Json(json)),
+            },
+
+            (Self::Array(items), T::Array(None)) => Self::Array(items),
+            (Self::Array(items), T::Array(Some(element))) => Self::Array(
+                items.into_iter().map(|item| item.cast(element.into())).collect::<Result<_>>()?,
+            ),
+
+            (Self::String(s), T::String) => Self::String(s),
+            (value, T::String) => Self::String(value.to_text()),
+            (Self::String(s), _) => {
+                let trimmed = s.trim();
+                let value = match datatype {
+                    T::Boolean => match trimmed.to_lowercase().as_str() {
+                        "true" | "t" | "yes" | "y" | "on" | "1" => Some(Self::Boolean(true)),
+                        "false" | "f" | "no" | "n" | "off" | "0" => Some(Self::Boolean(false)),
+                        _ => None,
+                    },
+                    T::Integer => trimmed.parse().ok().map(Self::Integer),
+                    T::Float => trimmed.parse().ok().map(Self::Float),
+                    T::Timestamp | T::Decimal(..) | T::Json => {
+                        Some(Self::String(trimmed.to_string()).coerce(datatype)?)
+                    }
+                    T::Array(_) => None,
+                    T::String => unreachable!("handled above"),
+                };
+                match value {
+                    Some(value) => value,
+                    None => return invalid(&Self::String(s)),
+                }
+            }
+
+            (value, _) => return invalid(&value),
+        })
+    }
+
+    /// Converts the value to a string for string concatenation. This is the
+    /// same as the display format, except strings and timestamps are unquoted.
+    pub fn to_text(&self) -> String {
+        match self {
+            Self::String(string) => string.clone(),
+            Self::Timestamp(millis) => format_timestamp(*millis),
+            value => value.to_string(),
         }
     }
 
+    /// Converts the value to JSON. JSON numbers can't represent NaN or
+    /// infinity, so these floats are given as the strings "NaN", "Infinity",
+    /// and "-Infinity". Decimals are given as strings to retain their exact
+    /// value, and timestamps as strings of the form YYYY-MM-DD HH:MM:SS.sss.
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::Value as Json;
+        match self {
+            Self::Null => Json::Null,
+            Self::Boolean(b) => Json::Bool(*b),
+            Self::Integer(i) => Json::from(*i),
+            Self::Float(f) if f.is_nan() => Json::from("NaN"),
+            Self::Float(f) if *f == f64::INFINITY => Json::from("Infinity"),
+            Self::Float(f) if *f == f64::NEG_INFINITY => Json::from("-Infinity"),
+            Self::Float(f) => Json::from(*f),
+            Self::String(s) => Json::from(s.as_str()),
+            Self::Decimal(..) => Json::from(self.to_string()),
+            Self::Timestamp(millis) => Json::from(format_timestamp(*millis)),
+            Self::Array(items) => Json::Array(items.iter().map(|item| item.to_json()).collect()),
+            Self::Json(json) => json.clone(),
+        }
+    }
+
+    /// Converts a JSON value to a value of the given datatype, inverting
+    /// to_json(). JSON null is NULL for any datatype, and any other JSON value
+    /// is valid for the JSON datatype. Floats and decimals can
+    /// also be given as JSON numbers or strings, but JSON values must otherwise
+    /// match the datatype.
+    pub fn from_json(json: &serde_json::Value, datatype: DataType) -> Result<Self> {
+        use serde_json::Value as Json;
+        let value = match (json, datatype) {
+            (Json::Null, _) => Some(Self::Null),
+            (json, DataType::Json) => Some(Self::Json(json.clone())),
+            (Json::Bool(b), DataType::Boolean) => Some(Self::Boolean(*b)),
+            (Json::Number(n), DataType::Integer) => n.as_i64().map(Self::Integer),
+            (Json::Number(n), DataType::Float) => n.as_f64().map(Self::Float),
+            (Json::String(s), DataType::Float) => match s.as_str() {
+                "NaN" => Some(Self::Float(f64::NAN)),
+                "Infinity" => Some(Self::Float(f64::INFINITY)),
+                "-Infinity" => Some(Self::Float(f64::NEG_INFINITY)),
+                s => s.parse().ok().map(Self::Float),
+            },
+            (Json::String(s), DataType::String) => Some(Self::String(s.clone())),
+            (Json::String(s), DataType::Timestamp) => {
+                Some(Self::String(s.clone()).coerce(datatype)?)
+            }
+            (Json::Number(n), DataType::Decimal(..)) => {
+                Some(Self::String(n.to_string()).coerce(datatype)?)
+            }
+            (Json::String(s), DataType::Decimal(..)) => {
+                Some(Self::String(s.clone()).coerce(datatype)?)
+            }
+            (Json::Array(items), DataType::Array(Some(element))) => Some(Self::Array(
+                items
+                    .iter()
+                    .map(|item| Self::from_json(item, element.into()))
+                    .collect::<Result<_>>()?,
+            )),
+            _ => None,
+        };
+        match value {
+            Some(value) => Ok(value),
+            None => errinput!("invalid JSON value {json} for {datatype}"),
+        }
+    }
+
+    /// Returns true if the value is a number (integer, float, or decimal).
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Self::Integer(_) | Self::Float(_) | Self::Decimal(..))
+    }
+
     /// Returns true if the value is undefined (NULL or NaN).
     pub fn is_undefined(&self) -> bool {
         *self == Self::Null || matches!(self, Self::Float(f) if f.is_nan())
@@ -262,6 +651,32 @@ impl std::fmt::Display for Value {
             Self::Integer(integer) => integer.fmt(f),
             Self::Float(float) => write!(f, "{float:?}"),
             Self::String(string) => write!(f, "'{}'", string.escape_debug()),
+            Self::Decimal(mantissa, scale) => {
+                let digits = mantissa.unsigned_abs().to_string();
+                let scale = *scale as usize;
+                let digits = format!("{digits:0>width$}", width = scale + 1);
+                let (integer, fraction) = digits.split_at(digits.len() - scale);
+                if *mantissa < 0 {
+                    write!(f, "-")?;
+                }
+                write!(f, "{integer}")?;
+                if scale > 0 {
+                    write!(f, ".{fraction}")?;
+                }
+                Ok(())
+            }
+            Self::Timestamp(millis) => write!(f, "'{}'", format_timestamp(*millis)),
+            Self::Json(json) => json.fmt(f),
+            Self::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    item.fmt(f)?;
+                }
+                write!(f, "]")
+            }
         }
     }
 }
@@ -339,7 +754,7 @@ impl<'a> From<&'a Value> for Cow<'a, Value> {
 }
 
 /// A primitive data type.
-#[derive(Clone, Copy, Debug, Hash, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub enum DataType {
     /// A boolean: true or false.
     Boolean,
@@ -349,6 +764,35 @@ pub enum DataType {
     Float,
     /// A UTF-8 encoded string.
     String,
+    /// A UTC timestamp with millisecond precision.
+    Timestamp,
+    /// An exact decimal number with the given precision (total number of
+    /// digits) and scale (number of fractional digits).
+    Decimal(u8, u8),
+    /// An array of the given element type, e.g. INTEGER[]. The element type is
+    /// None for array values without any non-NULL elements, which are
+    /// compatible with any array type.
+    Array(Option<ElementType>),
+    /// A JSON document.
+    Json,
+}
+
+impl DataType {
+    /// Returns true if a value of this datatype can be stored in a column of
+    /// the given datatype. Decimals are compatible regardless of precision and
+    /// scale, since values are coerced to the column's scale before storage.
+    pub fn is_compatible(self, column: DataType) -> bool {
+        match (self, column) {
+            (Self::Decimal(..), Self::Decimal(..)) => true,
+            (Self::Array(None), Self::Array(_)) | (Self::Array(_), Self::Array(None)) => true,
+            (datatype, column) => datatype == column,
+        }
+    }
+
+    /// Returns true if this is a numeric datatype.
+    pub fn is_numeric(self) -> bool {
+        matches!(self, Self::Integer | Self::Float | Self::Decimal(..))
+    }
 }
 
 impl std::fmt::Display for DataType {
@@ -358,8 +802,526 @@ impl std::fmt::Display for DataType {
             Self::Integer => write!(f, "INTEGER"),
             Self::Float => write!(f, "FLOAT"),
             Self::String => write!(f, "STRING"),
+            Self::Timestamp => write!(f, "TIMESTAMP"),
+            Self::Decimal(precision, scale) => write!(f, "DECIMAL({precision},{scale})"),
+            Self::Array(Some(element)) => write!(f, "{}[]", DataType::from(*element)),
+            Self::Array(None) => write!(f, "ARRAY"),
+            Self::Json => write!(f, "JSON"),
+        }
+    }
+}
+
+/// An array element datatype. Only integer and string arrays are supported.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum ElementType {
+    Integer,
+    String,
+}
+
+impl From<ElementType> for DataType {
+    fn from(element: ElementType) -> Self {
+        match element {
+            ElementType::Integer => Self::Integer,
+            ElementType::String => Self::String,
+        }
+    }
+}
+
+impl TryFrom<DataType> for ElementType {
+    type Error = Error;
+
+    fn try_from(datatype: DataType) -> Result<Self> {
+        match datatype {
+            DataType::Integer => Ok(Self::Integer),
+            DataType::String => Ok(Self::String),
+            datatype => errinput!("unsupported array element type {datatype}"),
+        }
+    }
+}
+
+/// Serializes JSON values as JSON text, since serde_json::Value can only be
+/// deserialized from self-describing formats.
+mod json_text {
+    use serde::{Deserialize as _, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(json: &serde_json::Value, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&json.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<serde_json::Value, D::Error> {
+        serde_json::from_str(&String::deserialize(d)?).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Compares two JSON values. Values of different JSON types are ordered as
+/// null < boolean < number < string < array < object. Numbers are compared by
+/// value (e.g. 1 = 1.0), strings by byte value, arrays lexicographically by
+/// element, and objects lexicographically by key/value pairs in key order.
+fn json_cmp(lhs: &serde_json::Value, rhs: &serde_json::Value) -> std::cmp::Ordering {
+    use serde_json::Value as Json;
+    use std::cmp::Ordering;
+
+    fn rank(json: &Json) -> u8 {
+        match json {
+            Json::Null => 0,
+            Json::Bool(_) => 1,
+            Json::Number(_) => 2,
+            Json::String(_) => 3,
+            Json::Array(_) => 4,
+            Json::Object(_) => 5,
+        }
+    }
+
+    match (lhs, rhs) {
+        (Json::Bool(l), Json::Bool(r)) => l.cmp(r),
+        (Json::Number(l), Json::Number(r)) => match (l.as_i64(), r.as_i64()) {
+            (Some(l), Some(r)) => l.cmp(&r),
+            // JSON numbers can't be NaN, so they're always comparable.
+            _ => json_f64(l).partial_cmp(&json_f64(r)).unwrap_or(Ordering::Equal),
+        },
+        (Json::String(l), Json::String(r)) => l.cmp(r),
+        (Json::Array(l), Json::Array(r)) => {
+            for (l, r) in l.iter().zip(r) {
+                match json_cmp(l, r) {
+                    Ordering::Equal => {}
+                    ordering => return ordering,
+                }
+            }
+            l.len().cmp(&r.len())
+        }
+        (Json::Object(l), Json::Object(r)) => {
+            for ((lk, lv), (rk, rv)) in l.iter().zip(r) {
+                match lk.cmp(rk).then_with(|| json_cmp(lv, rv)) {
+                    Ordering::Equal => {}
+                    ordering => return ordering,
+                }
+            }
+            l.len().cmp(&r.len())
+        }
+        (l, r) => rank(l).cmp(&rank(r)),
+    }
+}
+
+/// Hashes a JSON value consistently with json_cmp(). Numbers are hashed as
+/// floats, which equal numbers share.
+fn json_hash<H: std::hash::Hasher>(json: &serde_json::Value, state: &mut H) {
+    use serde_json::Value as Json;
+    use std::hash::Hash as _;
+    core::mem::discriminant(json).hash(state);
+    match json {
+        Json::Null => {}
+        Json::Bool(b) => b.hash(state),
+        Json::Number(n) => match json_f64(n) {
+            0.0 => 0.0_f64.to_bits().hash(state), // -0.0 and 0.0 are equal
+            f => f.to_bits().hash(state),
+        },
+        Json::String(s) => s.hash(state),
+        Json::Array(items) => {
+            items.len().hash(state);
+            items.iter().for_each(|item| json_hash(item, state));
+        }
+        Json::Object(object) => {
+            object.len().hash(state);
+            for (key, value) in object {
+                key.hash(state);
+                json_hash(value, state);
+            }
+        }
+    }
+}
+
+/// Converts a JSON number to a float.
+fn json_f64(n: &serde_json::Number) -> f64 {
+    n.as_f64().unwrap_or(f64::NAN) // only None with arbitrary_precision
+}
+
+/// The maximum decimal precision, i.e. number of digits. An i128 can hold any
+/// 38-digit number.
+pub const DECIMAL_MAX_PRECISION: u8 = 38;
+
+/// Rescales a decimal mantissa from one scale to another, rounding half away
+/// from zero when reducing the scale. Returns None on overflow.
+fn decimal_rescale(mantissa: i128, from: u64, to: u64) -> Option<i128> {
+    if to >= from {
+        let exp = u32::try_from(to - from).ok()?;
+        return mantissa.checked_mul(10_i128.checked_pow(exp)?);
+    }
+    match u32::try_from(from - to).ok().and_then(|exp| 10_i128.checked_pow(exp)) {
+        Some(divisor) => Some(div_round(mantissa, divisor)),
+        None => Some(0), // the divisor is larger than any mantissa
+    }
+}
+
+/// Divides two integers, rounding half away from zero.
+pub(super) fn div_round(dividend: i128, divisor: i128) -> i128 {
+    let (quotient, remainder) = (dividend / divisor, dividend % divisor);
+    if remainder.unsigned_abs() >= divisor.unsigned_abs() - remainder.unsigned_abs() {
+        // Round away from zero, in the direction of the exact result.
+        if (dividend < 0) != (divisor < 0) {
+            return quotient - 1;
+        }
+        return quotient + 1;
+    }
+    quotient
+}
+
+/// Multiplies two decimals, given as (mantissa, scale). The exact product has
+/// the sum of the operand scales. If it has more than 38 fractional digits, or
+/// its mantissa doesn't fit in an i128, it's rounded half away from zero to
+/// fewer fractional digits. Returns None if the integer part doesn't fit.
+fn decimal_mul(lhs: (i128, u64), rhs: (i128, u64)) -> Option<(i128, u64)> {
+    let mut product = BigUint::from(lhs.0.unsigned_abs()).mul(&BigUint::from(rhs.0.unsigned_abs()));
+    let mut scale = lhs.1.checked_add(rhs.1)?;
+    // The last removed digit is the most significant one, and determines the
+    // rounding direction.
+    let mut round_up = false;
+    let mut mantissa = product.to_u128().and_then(|m| i128::try_from(m).ok());
+    while scale > DECIMAL_MAX_PRECISION as u64 || mantissa.is_none() {
+        if scale == 0 {
+            return None;
+        }
+        round_up = product.div_small(10) >= 5;
+        scale -= 1;
+        mantissa = product.to_u128().and_then(|m| i128::try_from(m).ok());
+    }
+    let mantissa = mantissa?.checked_add(round_up as i128)?;
+    match lhs.0.signum() * rhs.0.signum() {
+        -1 => Some((-mantissa, scale)),
+        _ => Some((mantissa, scale)),
+    }
+}
+
+/// Compares two decimals exactly, given as (mantissa, scale).
+fn decimal_cmp(lhs: (i128, u64), rhs: (i128, u64)) -> std::cmp::Ordering {
+    let scale = lhs.1.max(rhs.1);
+    match (decimal_rescale(lhs.0, lhs.1, scale), decimal_rescale(rhs.0, rhs.1, scale)) {
+        (Some(lhs), Some(rhs)) => lhs.cmp(&rhs),
+        // If rescaling overflows, that value has the larger magnitude.
+        (None, _) => lhs.0.cmp(&0),
+        (_, None) => 0.cmp(&rhs.0),
+    }
+}
+
+/// Converts a float to its shortest round-trip decimal representation, as
+/// (mantissa, scale), e.g. 19.99 rather than its binary value
+/// 19.989999999999998436805981327779591083526611328125. This is the value
+/// the float was most likely written as. Returns None if the float isn't
+/// finite, or its mantissa or scale exceed the DECIMAL limits.
+fn float_decimal(f: f64) -> Option<(i128, u64)> {
+    if !f.is_finite() {
+        return None;
+    }
+    parse_decimal(&f.to_string()).filter(|(_, scale)| *scale <= DECIMAL_MAX_PRECISION as u64)
+}
+
+/// Compares an integer and a float exactly. NaN is greater than all integers.
+fn integer_float_cmp(i: i64, f: f64) -> std::cmp::Ordering {
+    use std::cmp::Ordering::*;
+    if !f.is_finite() {
+        return (i as f64).total_cmp(&f);
+    }
+    // Floats outside of the i64 range [-2^63, 2^63) are larger in magnitude
+    // than any integer. Otherwise, the truncated float is an exact integer, and
+    // any fractional part breaks ties.
+    if f >= 9223372036854775808.0 {
+        return Less;
+    } else if f < -9223372036854775808.0 {
+        return Greater;
+    }
+    let trunc = f.trunc();
+    i.cmp(&(trunc as i64)).then(trunc.partial_cmp(&f).expect("finite float"))
+}
+
+/// Compares a decimal, given as (mantissa, scale), and a float exactly. NaN is
+/// greater than all decimals.
+fn decimal_float_cmp((mantissa, scale): (i128, u64), f: f64) -> std::cmp::Ordering {
+    if !f.is_finite() {
+        return decimal_to_f64(mantissa, scale).total_cmp(&f);
+    }
+    let sign = if f > 0.0 {
+        1
+    } else if f < 0.0 {
+        -1
+    } else {
+        0
+    };
+    if mantissa.signum() != sign || sign == 0 {
+        return mantissa.signum().cmp(&sign);
+    }
+    // Both have the same sign, compare their magnitudes. Decimals with more
+    // than 400 fractional digits are smaller than the smallest float (~5e-324).
+    let ordering = if scale > 400 {
+        std::cmp::Ordering::Less
+    } else {
+        // Decompose the float as k * 2^e, and compare mantissa / 10^scale with
+        // it as mantissa vs. k * 5^scale * 2^(e + scale).
+        let bits = f.abs().to_bits();
+        let (exponent, fraction) = ((bits >> 52) as i64, bits & ((1 << 52) - 1));
+        let (k, e) = match exponent {
+            0 => (fraction, -1074),
+            _ => (fraction | 1 << 52, exponent - 1075),
+        };
+        let mut lhs = BigUint::from(mantissa.unsigned_abs());
+        let mut rhs = BigUint::from(k as u128);
+        (0..scale).for_each(|_| rhs.mul_small(5));
+        match e + scale as i64 {
+            shift if shift >= 0 => rhs.shl(shift as u64),
+            shift => lhs.shl(shift.unsigned_abs()),
+        }
+        lhs.cmp(&rhs)
+    };
+    if sign < 0 {
+        return ordering.reverse();
+    }
+    ordering
+}
+
+/// A minimal arbitrary-precision unsigned integer, used to compare decimals and
+/// floats and multiply decimals exactly. Stored as little-endian u32 limbs.
+#[derive(PartialEq, Eq)]
+struct BigUint(Vec<u32>);
+
+impl From<u128> for BigUint {
+    fn from(n: u128) -> Self {
+        let mut limbs = (0..4).map(|i| (n >> (32 * i)) as u32).collect::<Vec<_>>();
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        Self(limbs)
+    }
+}
+
+impl BigUint {
+    /// Multiplies the integer by a small factor.
+    fn mul_small(&mut self, factor: u32) {
+        let mut carry = 0;
+        for limb in self.0.iter_mut() {
+            let product = *limb as u64 * factor as u64 + carry;
+            (*limb, carry) = (product as u32, product >> 32);
+        }
+        if carry > 0 {
+            self.0.push(carry as u32);
+        }
+    }
+
+    /// Multiplies two integers.
+    fn mul(&self, other: &Self) -> Self {
+        let mut limbs = vec![0; self.0.len() + other.0.len()];
+        for (i, &l) in self.0.iter().enumerate() {
+            let mut carry = 0;
+            for (j, &r) in other.0.iter().enumerate() {
+                let product = l as u64 * r as u64 + limbs[i + j] as u64 + carry;
+                (limbs[i + j], carry) = (product as u32, product >> 32);
+            }
+            limbs[i + other.0.len()] = carry as u32;
+        }
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        Self(limbs)
+    }
+
+    /// Divides the integer by a small divisor, returning the remainder.
+    fn div_small(&mut self, divisor: u32) -> u32 {
+        let mut remainder = 0;
+        for limb in self.0.iter_mut().rev() {
+            let dividend = remainder << 32 | *limb as u64;
+            (*limb, remainder) = ((dividend / divisor as u64) as u32, dividend % divisor as u64);
+        }
+        while self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+        remainder as u32
+    }
+
+    /// Converts the integer to a u128, or None if it doesn't fit.
+    fn to_u128(&self) -> Option<u128> {
+        if self.0.len() > 4 {
+            return None;
+        }
+        Some(self.0.iter().rev().fold(0, |n, &limb| n << 32 | limb as u128))
+    }
+
+    /// Shifts the integer left by the given number of bits.
+    fn shl(&mut self, bits: u64) {
+        if self.0.is_empty() {
+            return;
+        }
+        let (limbs, bits) = ((bits / 32) as usize, bits % 32);
+        if bits > 0 {
+            let mut carry = 0;
+            for limb in self.0.iter_mut() {
+                (*limb, carry) = (*limb << bits | carry, *limb >> (32 - bits));
+            }
+            if carry > 0 {
+                self.0.push(carry);
+            }
+        }
+        self.0.splice(0..0, std::iter::repeat_n(0, limbs));
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // Limbs are normalized without leading zeroes.
+        self.0.len().cmp(&other.0.len()).then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Converts a decimal to the nearest float. This is correctly rounded, such
+/// that equal numbers (e.g. 1.5, 1.50, and 1.5 as a float) yield the same float.
+fn decimal_to_f64(mantissa: i128, scale: u64) -> f64 {
+    format!("{mantissa}e-{scale}").parse().expect("invalid decimal float")
+}
+
+/// Converts a float to a decimal mantissa with the given scale, rounding half
+/// away from zero. Returns None for infinity, NaN, and out-of-range values.
+fn decimal_from_f64(f: f64, scale: u64) -> Option<i128> {
+    let scaled = (f * 10_f64.powi(scale.min(i32::MAX as u64) as i32)).round();
+    if !scaled.is_finite() || scaled.abs() >= 1e38 {
+        return None;
+    }
+    Some(scaled as i128)
+}
+
+/// Parses a decimal string such as -123.45, returning its (mantissa, scale).
+fn parse_decimal(s: &str) -> Option<(i128, u64)> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    if integer.is_empty() && fraction.is_empty()
+        || !integer.bytes().chain(fraction.bytes()).all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let mut mantissa: i128 = 0;
+    for b in integer.bytes().chain(fraction.bytes()) {
+        mantissa = mantissa.checked_mul(10)?.checked_add((b - b'0') as i128)?;
+    }
+    Some((if negative { -mantissa } else { mantissa }, fraction.len() as u64))
+}
+
+const MILLIS_PER_DAY: i64 = 86_400_000;
+
+/// Parses a timestamp string of the form YYYY-MM-DD, optionally followed by a
+/// time HH:MM:SS with optional milliseconds, separated by a space or T. The
+/// time is taken to be UTC. Returns milliseconds since the Unix epoch.
+fn parse_timestamp(s: &str) -> Result<i64> {
+    let invalid = || errinput!("invalid timestamp '{}'", s.escape_debug());
+
+    // Parses a fixed-length number, which must have exactly the given number
+    // of ASCII digits.
+    fn number(s: &str, len: usize) -> Option<i64> {
+        if s.len() != len || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        s.parse().ok()
+    }
+
+    let (date, time) = match s.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (s, None),
+    };
+
+    let mut parts = date.splitn(3, '-');
+    let (Some(year), Some(month), Some(day)) = (
+        parts.next().and_then(|s| number(s, 4)),
+        parts.next().and_then(|s| number(s, 2)),
+        parts.next().and_then(|s| number(s, 2)),
+    ) else {
+        return invalid();
+    };
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return invalid();
+    }
+    let mut millis = days_from_civil(year, month, day) * MILLIS_PER_DAY;
+
+    if let Some(time) = time {
+        let (time, fraction) = match time.split_once('.') {
+            Some((time, fraction)) => (time, Some(fraction)),
+            None => (time, None),
+        };
+        let mut parts = time.splitn(3, ':');
+        let (Some(hour), Some(minute), Some(second)) = (
+            parts.next().and_then(|s| number(s, 2)),
+            parts.next().and_then(|s| number(s, 2)),
+            parts.next().and_then(|s| number(s, 2)),
+        ) else {
+            return invalid();
+        };
+        if hour > 23 || minute > 59 || second > 59 {
+            return invalid();
+        }
+        millis += (hour * 3600 + minute * 60 + second) * 1000;
+        if let Some(fraction) = fraction {
+            let Some(ms) = number(fraction, fraction.len()).filter(|_| fraction.len() <= 3) else {
+                return invalid();
+            };
+            millis += ms * 10_i64.pow(3 - fraction.len() as u32);
         }
     }
+    Ok(millis)
+}
+
+/// Formats a timestamp as YYYY-MM-DD HH:MM:SS, with milliseconds if non-zero.
+/// The inverse of parse_timestamp().
+fn format_timestamp(millis: i64) -> String {
+    let days = millis.div_euclid(MILLIS_PER_DAY);
+    let millis = millis.rem_euclid(MILLIS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+    let (hour, minute) = (millis / 3_600_000, millis / 60_000 % 60);
+    let (second, millis) = (millis / 1000 % 60, millis % 1000);
+    let mut s = format!("{year:04}-{month:02}-{day:02} {hour:02}:{minute:02}:{second:02}");
+    if millis > 0 {
+        s.push_str(&format!(".{millis:03}"));
+    }
+    s
+}
+
+/// Returns the number of days in the given month.
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns the number of days since the Unix epoch for the given date in the
+/// proleptic Gregorian calendar. Uses Howard Hinnant's algorithm, see:
+/// <https://howardhinnant.github.io/date_algorithms.html#days_from_civil>
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Returns the (year, month, day) date for the given number of days since the
+/// Unix epoch. The inverse of days_from_civil().
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
 }
 
 /// A row of values.
@@ -423,3 +1385,252 @@ impl From<Option<String>> for Label {
         name.map(Label::Unqualified).unwrap_or(Label::None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use test_case::test_case;
+
+    /// Tests Value::to_json() and that Value::from_json() round-trips.
+    #[test_case(Value::Null, DataType::Integer => json!(null); "null")]
+    #[test_case(Value::Boolean(true), DataType::Boolean => json!(true); "boolean")]
+    #[test_case(Value::Integer(-7), DataType::Integer => json!(-7); "integer")]
+    #[test_case(Value::Integer(i64::MAX), DataType::Integer => json!(i64::MAX); "integer max")]
+    #[test_case(Value::Float(2.5), DataType::Float => json!(2.5); "float")]
+    #[test_case(Value::Float(-0.0), DataType::Float => json!(-0.0); "float negative zero")]
+    #[test_case(Value::Float(f64::NAN), DataType::Float => json!("NaN"); "float nan")]
+    #[test_case(Value::Float(f64::INFINITY), DataType::Float => json!("Infinity"); "float infinity")]
+    #[test_case(Value::Float(f64::NEG_INFINITY), DataType::Float => json!("-Infinity"); "float negative infinity")]
+    #[test_case(Value::String("a \"b\"\n".into()), DataType::String => json!("a \"b\"\n"); "string")]
+    #[test_case(Value::String("NaN".into()), DataType::String => json!("NaN"); "string nan")]
+    #[test_case(Value::Timestamp(0), DataType::Timestamp => json!("1970-01-01 00:00:00"); "timestamp")]
+    #[test_case(Value::Timestamp(-1), DataType::Timestamp => json!("1969-12-31 23:59:59.999"); "timestamp negative")]
+    #[test_case(Value::Decimal(-12345, 2), DataType::Decimal(5, 2) => json!("-123.45"); "decimal")]
+    #[test_case(Value::Decimal(-(10_i128.pow(38) - 1), 10), DataType::Decimal(38, 10) => json!("-9999999999999999999999999999.9999999999"); "decimal max")]
+    #[test_case(Value::Array(vec![Value::Integer(1), Value::Null]), DataType::Array(Some(ElementType::Integer)) => json!([1, null]); "integer array")]
+    #[test_case(Value::Array(vec![Value::String("a".into())]), DataType::Array(Some(ElementType::String)) => json!(["a"]); "string array")]
+    #[test_case(Value::Array(vec![]), DataType::Array(Some(ElementType::Integer)) => json!([]); "empty array")]
+    #[test_case(Value::Json(json!({"a": [1, null]})), DataType::Json => json!({"a": [1, null]}); "json")]
+    fn to_json(value: Value, datatype: DataType) -> serde_json::Value {
+        let json = value.to_json();
+        let result = Value::from_json(&json, datatype).expect("from_json failed");
+        assert_eq!(result, value);
+        assert_eq!(result.to_string(), value.to_string()); // e.g. sign and scale
+        json
+    }
+
+    /// Tests Value::promote_numeric() across numeric type pairs. Uses Debug
+    /// formatting to compare datatypes, since Value equality is numeric.
+    #[test_case(Value::Integer(1), Value::Integer(2) => "(Integer(1), Integer(2))"; "integer integer")]
+    #[test_case(Value::Integer(1), Value::Float(2.5) => "(Float(1.0), Float(2.5))"; "integer float")]
+    #[test_case(Value::Float(2.5), Value::Integer(1) => "(Float(2.5), Float(1.0))"; "float integer")]
+    #[test_case(Value::Integer(1), Value::Decimal(25, 1) => "(Decimal(1, 0), Decimal(25, 1))"; "integer decimal")]
+    #[test_case(Value::Decimal(25, 1), Value::Integer(1) => "(Decimal(25, 1), Decimal(1, 0))"; "decimal integer")]
+    #[test_case(Value::Decimal(25, 1), Value::Float(1.0) => "(Float(2.5), Float(1.0))"; "decimal float")]
+    #[test_case(Value::Float(1.0), Value::Decimal(25, 1) => "(Float(1.0), Float(2.5))"; "float decimal")]
+    #[test_case(Value::Integer(1), Value::Null => "(Integer(1), Null)"; "integer null")]
+    #[test_case(Value::Float(1.0), Value::String("1".into()) => "(Float(1.0), String(\"1\"))"; "float string")]
+    fn promote_numeric(lhs: Value, rhs: Value) -> String {
+        format!("{:?}", Value::promote_numeric(lhs, rhs))
+    }
+
+    /// Tests +, -, *, /, %, and ^ across numeric type pairs. Integers and
+    /// floats yield floats, integers and decimals yield decimals, and decimals
+    /// and floats yield floats. Decimal exponentiation yields floats.
+    #[test_case(Value::Integer(7), Value::Integer(2) => "Integer(9) Integer(5) Integer(14) Integer(3) Integer(1) Integer(49)"; "integer integer")]
+    #[test_case(Value::Integer(7), Value::Float(2.0) => "Float(9.0) Float(5.0) Float(14.0) Float(3.5) Float(1.0) Float(49.0)"; "integer float")]
+    #[test_case(Value::Float(7.0), Value::Integer(2) => "Float(9.0) Float(5.0) Float(14.0) Float(3.5) Float(1.0) Float(49.0)"; "float integer")]
+    #[test_case(Value::Float(7.0), Value::Float(2.0) => "Float(9.0) Float(5.0) Float(14.0) Float(3.5) Float(1.0) Float(49.0)"; "float float")]
+    #[test_case(Value::Integer(7), Value::Decimal(20, 1) => "Decimal(90, 1) Decimal(50, 1) Decimal(140, 1) Decimal(35, 1) Decimal(10, 1) Float(49.0)"; "integer decimal")]
+    #[test_case(Value::Decimal(70, 1), Value::Integer(2) => "Decimal(90, 1) Decimal(50, 1) Decimal(140, 1) Decimal(35, 1) Decimal(10, 1) Float(49.0)"; "decimal integer")]
+    #[test_case(Value::Decimal(70, 1), Value::Decimal(200, 2) => "Decimal(900, 2) Decimal(500, 2) Decimal(14000, 3) Decimal(350, 2) Decimal(100, 2) Float(49.0)"; "decimal decimal")]
+    #[test_case(Value::Decimal(70, 1), Value::Float(2.0) => "Float(9.0) Float(5.0) Float(14.0) Float(3.5) Float(1.0) Float(49.0)"; "decimal float")]
+    #[test_case(Value::Float(7.0), Value::Decimal(20, 1) => "Float(9.0) Float(5.0) Float(14.0) Float(3.5) Float(1.0) Float(49.0)"; "float decimal")]
+    #[test_case(Value::Integer(7), Value::Null => "Null Null Null Null Null Null"; "integer null")]
+    #[test_case(Value::Null, Value::Float(2.0) => "Null Null Null Null Null Null"; "null float")]
+    #[test_case(Value::Null, Value::Decimal(20, 1) => "Null Null Null Null Null Null"; "null decimal")]
+    #[test_case(Value::Integer(7), Value::Integer(0) => "Integer(7) Integer(7) Integer(0) InvalidInput(\"can't divide by zero\") InvalidInput(\"can't divide by zero\") Integer(1)"; "integer zero")]
+    #[test_case(Value::Integer(i64::MAX), Value::Integer(2) => "InvalidInput(\"integer overflow\") Integer(9223372036854775805) InvalidInput(\"integer overflow\") Integer(4611686018427387903) Integer(1) InvalidInput(\"integer overflow\")"; "integer max")]
+    #[test_case(Value::Integer(i64::MIN), Value::Integer(-1) => "InvalidInput(\"integer overflow\") Integer(-9223372036854775807) InvalidInput(\"integer overflow\") InvalidInput(\"integer overflow\") Integer(0) Float(-1.0842021724855044e-19)"; "integer min")]
+    #[test_case(Value::Integer(7), Value::Float(0.0) => "Float(7.0) Float(7.0) Float(0.0) Float(inf) Float(NaN) Float(1.0)"; "integer float zero")]
+    #[test_case(Value::Integer(7), Value::String("2".into()) => "InvalidInput(\"can't add 7 and '2'\") InvalidInput(\"can't subtract 7 and '2'\") InvalidInput(\"can't multiply 7 and '2'\") InvalidInput(\"can't divide 7 and '2'\") InvalidInput(\"can't take remainder of 7 and '2'\") InvalidInput(\"can't exponentiate 7 and '2'\")"; "integer string")]
+    fn arithmetic(lhs: Value, rhs: Value) -> String {
+        let results = [
+            lhs.checked_add(&rhs),
+            lhs.checked_sub(&rhs),
+            lhs.checked_mul(&rhs),
+            lhs.checked_div(&rhs),
+            lhs.checked_rem(&rhs),
+            lhs.checked_pow(&rhs),
+        ];
+        results
+            .into_iter()
+            .map(|r| match r {
+                Ok(value) => format!("{value:?}"),
+                Err(err) => format!("{err:?}"),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test_case((15, 1), (15, 1) => Some((225, 2)); "exact")]
+    #[test_case((-15, 1), (15, 2) => Some((-225, 3)); "negative")]
+    #[test_case((-15, 1), (-15, 1) => Some((225, 2)); "negative negative")]
+    #[test_case((5, 20), (1, 19) => Some((1, 38)); "max scale rounds")]
+    #[test_case((-5, 20), (1, 19) => Some((-1, 38)); "max scale rounds negative")]
+    #[test_case((1, 20), (1, 20) => Some((0, 38)); "max scale underflow")]
+    #[test_case((i128::MAX, 38), (15, 0) => Some((25521177519070384759753095557382615859, 36)); "mantissa overflow rounds")]
+    #[test_case((i128::MAX, 0), (2, 0) => None; "overflow")]
+    fn decimal_mul(lhs: (i128, u64), rhs: (i128, u64)) -> Option<(i128, u64)> {
+        super::decimal_mul(lhs, rhs)
+    }
+
+    /// Tests that chained decimal products don't accumulate rounding errors.
+    #[test]
+    fn decimal_mul_chained() -> Result<()> {
+        let mut product = Value::Decimal(15, 1);
+        for _ in 1..20 {
+            product = product.checked_mul(&Value::Decimal(15, 1))?;
+        }
+        assert_eq!(product, Value::Decimal(332525673007965087890625, 20));
+        let product = product.coerce(DataType::Decimal(38, 2))?;
+        assert_eq!(format!("{product:?}"), "Decimal(332526, 2)");
+        Ok(())
+    }
+
+    /// Tests that equality, hashing, and ordering are consistent across
+    /// numeric type pairs, such that e.g. 1 = 1.0 = 1.00, and that they compare
+    /// without losing precision to float promotion. Floats compare as their
+    /// shortest round-trip decimal representation.
+    #[test_case(Value::Integer(1), Value::Float(1.0) => std::cmp::Ordering::Equal; "integer float equal")]
+    #[test_case(Value::Integer(1), Value::Float(1.5) => std::cmp::Ordering::Less; "integer float less")]
+    #[test_case(Value::Float(2.0), Value::Integer(1) => std::cmp::Ordering::Greater; "float integer greater")]
+    #[test_case(Value::Integer(1), Value::Decimal(100, 2) => std::cmp::Ordering::Equal; "integer decimal equal")]
+    #[test_case(Value::Integer(2), Value::Decimal(199, 2) => std::cmp::Ordering::Greater; "integer decimal greater")]
+    #[test_case(Value::Decimal(150, 2), Value::Float(1.5) => std::cmp::Ordering::Equal; "decimal float equal")]
+    #[test_case(Value::Decimal(15, 1), Value::Decimal(150, 2) => std::cmp::Ordering::Equal; "decimal scales equal")]
+    #[test_case(Value::Float(f64::INFINITY), Value::Decimal(i128::MAX, 0) => std::cmp::Ordering::Greater; "infinity decimal")]
+    #[test_case(Value::Float(f64::NAN), Value::Integer(1) => std::cmp::Ordering::Greater; "nan integer")]
+    #[test_case(Value::Integer(9007199254740993), Value::Float(9007199254740992.0) => std::cmp::Ordering::Greater; "integer float precision")]
+    #[test_case(Value::Integer(9007199254740992), Value::Float(9007199254740992.0) => std::cmp::Ordering::Equal; "integer float exact")]
+    #[test_case(Value::Integer(i64::MAX), Value::Float(9223372036854775807.0) => std::cmp::Ordering::Less; "integer max float")]
+    #[test_case(Value::Integer(i64::MIN), Value::Float(-9223372036854775808.0) => std::cmp::Ordering::Greater; "integer min float shortest")]
+    #[test_case(Value::Integer(-1), Value::Float(-0.5) => std::cmp::Ordering::Less; "integer float negative fraction")]
+    #[test_case(Value::Decimal(9007199254740993, 0), Value::Float(9007199254740992.0) => std::cmp::Ordering::Greater; "decimal float precision")]
+    #[test_case(Value::Decimal(1, 1), Value::Float(0.1) => std::cmp::Ordering::Equal; "decimal float inexact")]
+    #[test_case(Value::Decimal(1999, 2), Value::Float(19.99) => std::cmp::Ordering::Equal; "decimal float inexact price")]
+    #[test_case(Value::Decimal(2000, 2), Value::Float(19.99) => std::cmp::Ordering::Greater; "decimal float inexact greater")]
+    #[test_case(Value::Decimal(931322574615478515625, 30), Value::Float(2_f64.powi(-30)) => std::cmp::Ordering::Greater; "decimal float binary value")]
+    #[test_case(Value::Decimal(-15, 1), Value::Float(-1.5) => std::cmp::Ordering::Equal; "decimal float negative")]
+    #[test_case(Value::Decimal(0, 2), Value::Float(-0.0) => std::cmp::Ordering::Equal; "decimal float zero")]
+    #[test_case(Value::Decimal(1, 401), Value::Float(5e-324) => std::cmp::Ordering::Less; "decimal tiny float")]
+    #[test_case(Value::Decimal(-1, 401), Value::Float(-5e-324) => std::cmp::Ordering::Greater; "decimal tiny negative float")]
+    #[test_case(Value::Decimal(i128::MAX, 0), Value::Float(1.7e38) => std::cmp::Ordering::Greater; "decimal max float")]
+    fn numeric_equality(lhs: Value, rhs: Value) -> std::cmp::Ordering {
+        use std::hash::BuildHasher as _;
+        let hasher = std::collections::hash_map::RandomState::new();
+        let ordering = lhs.cmp(&rhs);
+        assert_eq!(rhs.cmp(&lhs), ordering.reverse());
+        assert_eq!(lhs == rhs, ordering.is_eq());
+        if lhs == rhs {
+            assert_eq!(hasher.hash_one(&lhs), hasher.hash_one(&rhs));
+        }
+        ordering
+    }
+
+    /// Tests Value::from_json() conversions that aren't round-trips.
+    #[test_case(json!(null), DataType::Boolean => Ok(Value::Null); "null")]
+    #[test_case(json!(2), DataType::Float => Ok(Value::Float(2.0)); "integer to float")]
+    #[test_case(json!("2.5"), DataType::Float => Ok(Value::Float(2.5)); "string to float")]
+    #[test_case(json!(1.5), DataType::Decimal(4, 2) => Ok(Value::Decimal(150, 2)); "number to decimal")]
+    #[test_case(json!("1.005"), DataType::Decimal(4, 2) => Ok(Value::Decimal(101, 2)); "decimal rounding")]
+    #[test_case(json!("2024-01-01T12:00:00"), DataType::Timestamp => Ok(Value::Timestamp(1_704_110_400_000)); "timestamp separator")]
+    #[test_case(json!(1.5), DataType::Integer => Err(Error::InvalidInput("invalid JSON value 1.5 for INTEGER".into())); "float to integer")]
+    #[test_case(json!(1), DataType::Boolean => Err(Error::InvalidInput("invalid JSON value 1 for BOOLEAN".into())); "integer to boolean")]
+    #[test_case(json!(1), DataType::String => Err(Error::InvalidInput("invalid JSON value 1 for STRING".into())); "integer to string")]
+    #[test_case(json!("inf"), DataType::Float => Ok(Value::Float(f64::INFINITY)); "float inf string")]
+    #[test_case(json!("x"), DataType::Float => Err(Error::InvalidInput("invalid JSON value \"x\" for FLOAT".into())); "invalid float string")]
+    #[test_case(json!("x"), DataType::Timestamp => Err(Error::InvalidInput("invalid timestamp 'x'".into())); "invalid timestamp")]
+    #[test_case(json!([1]), DataType::Integer => Err(Error::InvalidInput("invalid JSON value [1] for INTEGER".into())); "array")]
+    #[test_case(json!({}), DataType::String => Err(Error::InvalidInput("invalid JSON value {} for STRING".into())); "object")]
+    #[test_case(json!([1, "a"]), DataType::Array(Some(ElementType::Integer)) => Err(Error::InvalidInput("invalid JSON value \"a\" for INTEGER".into())); "mixed array")]
+    #[test_case(json!(1), DataType::Array(Some(ElementType::Integer)) => Err(Error::InvalidInput("invalid JSON value 1 for INTEGER[]".into())); "integer to array")]
+    #[test_case(json!({"a": 1}), DataType::Json => Ok(Value::Json(json!({"a": 1}))); "json object")]
+    #[test_case(json!(null), DataType::Json => Ok(Value::Null); "json null")]
+    fn from_json(json: serde_json::Value, datatype: DataType) -> Result<Value> {
+        Value::from_json(&json, datatype)
+    }
+
+    /// Tests that arrays round-trip through the binary value encoding.
+    #[test_case(Value::Array(vec![]); "empty")]
+    #[test_case(Value::Array(vec![Value::Integer(1), Value::Null, Value::Integer(-3)]); "integer")]
+    #[test_case(Value::Array(vec![Value::String("a".into()), Value::String("".into())]); "string")]
+    fn array_encoding(value: Value) {
+        use crate::encoding::Value as _;
+        let decoded = Value::decode(&value.encode()).expect("decode failed");
+        assert_eq!(format!("{decoded:?}"), format!("{value:?}"));
+    }
+
+    /// Tests that JSON values round-trip through the binary value encoding.
+    #[test_case(json!(null); "null")]
+    #[test_case(json!(-1.5); "number")]
+    #[test_case(json!(u64::MAX); "large number")]
+    #[test_case(json!("a \"b\""); "string")]
+    #[test_case(json!({"a": [1, true, {"b": null}]}); "nested")]
+    fn json_encoding(json: serde_json::Value) {
+        use crate::encoding::Value as _;
+        let value = Value::Json(json);
+        let decoded = Value::decode(&value.encode()).expect("decode failed");
+        assert_eq!(format!("{decoded:?}"), format!("{value:?}"));
+    }
+
+    /// Tests that arrays are ordered lexicographically by element, with
+    /// consistent equality and hashing.
+    #[test_case(vec![1, 2], vec![1, 2] => std::cmp::Ordering::Equal; "equal")]
+    #[test_case(vec![1, 2], vec![1, 3] => std::cmp::Ordering::Less; "element less")]
+    #[test_case(vec![2], vec![1, 3] => std::cmp::Ordering::Greater; "first element greater")]
+    #[test_case(vec![1], vec![1, 0] => std::cmp::Ordering::Less; "prefix less")]
+    #[test_case(vec![], vec![i64::MIN] => std::cmp::Ordering::Less; "empty less")]
+    fn array_ordering(lhs: Vec<i64>, rhs: Vec<i64>) -> std::cmp::Ordering {
+        use std::hash::BuildHasher as _;
+        let hasher = std::collections::hash_map::RandomState::new();
+        let lhs = Value::Array(lhs.into_iter().map(Value::Integer).collect());
+        let rhs = Value::Array(rhs.into_iter().map(Value::Integer).collect());
+        let ordering = lhs.cmp(&rhs);
+        assert_eq!(rhs.cmp(&lhs), ordering.reverse());
+        assert_eq!(lhs == rhs, ordering.is_eq());
+        if lhs == rhs {
+            assert_eq!(hasher.hash_one(&lhs), hasher.hash_one(&rhs));
+        }
+        ordering
+    }
+
+    /// Tests that JSON values are ordered by type rank and then by value, with
+    /// consistent equality and hashing.
+    #[test_case(json!(1), json!(1.0) => std::cmp::Ordering::Equal; "integer float equal")]
+    #[test_case(json!(2), json!(10) => std::cmp::Ordering::Less; "number less")]
+    #[test_case(json!(-1), json!(u64::MAX) => std::cmp::Ordering::Less; "number large")]
+    #[test_case(json!(0.0), json!(-0.0) => std::cmp::Ordering::Equal; "zero")]
+    #[test_case(json!("b"), json!("a") => std::cmp::Ordering::Greater; "string greater")]
+    #[test_case(json!(null), json!(false) => std::cmp::Ordering::Less; "null boolean")]
+    #[test_case(json!(true), json!(0) => std::cmp::Ordering::Less; "boolean number")]
+    #[test_case(json!(99), json!("") => std::cmp::Ordering::Less; "number string")]
+    #[test_case(json!("z"), json!([]) => std::cmp::Ordering::Less; "string array")]
+    #[test_case(json!([99]), json!({}) => std::cmp::Ordering::Less; "array object")]
+    #[test_case(json!([1]), json!([1, 0]) => std::cmp::Ordering::Less; "array prefix")]
+    #[test_case(json!({"a": 1, "b": 2}), json!({"b": 2, "a": 1.0}) => std::cmp::Ordering::Equal; "object key order")]
+    #[test_case(json!({"a": 2}), json!({"a": 1, "b": 1}) => std::cmp::Ordering::Greater; "object value")]
+    fn json_ordering(lhs: serde_json::Value, rhs: serde_json::Value) -> std::cmp::Ordering {
+        use std::hash::BuildHasher as _;
+        let hasher = std::collections::hash_map::RandomState::new();
+        let (lhs, rhs) = (Value::Json(lhs), Value::Json(rhs));
+        let ordering = lhs.cmp(&rhs);
+        assert_eq!(rhs.cmp(&lhs), ordering.reverse());
+        assert_eq!(lhs == rhs, ordering.is_eq());
+        if lhs == rhs {
+            assert_eq!(hasher.hash_one(&lhs), hasher.hash_one(&rhs));
+        }
+        ordering
+    }
+}
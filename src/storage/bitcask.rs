@@ -4,6 +4,7 @@ use crate::error::Result;
 use fs4::FileExt;
 use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 /// A very simple variant of BitCask, itself a very simple log-structured
 /// key-value engine used e.g. by the Riak database. It is not compatible with
@@ -25,8 +26,12 @@ use std::path::PathBuf;
 ///   and can exceed the filesystem's file size limit, but ToyDB databases are
 ///   expected to be small.
 ///
-/// - Compactions lock the database for reads and writes. This is ok since ToyDB
-///   only compacts during node startup and files are expected to be small.
+/// - Compactions write the live data to a new log file in a background thread,
+///   while the database keeps serving reads and writes. Once done, keys
+///   written in the meantime are copied into the new file, which is then
+///   atomically renamed over the old one, so a crash during compaction leaves
+///   the original log intact. Only the copying of concurrently written keys
+///   and the file swap block the database.
 ///
 /// - Hint files are not used, the log itself is scanned when opened to
 ///   build the keydir. Hint files only omit values, and ToyDB values are
@@ -46,6 +51,55 @@ pub struct BitCask {
     log: Log,
     /// Maps keys to a value position and length in the log file.
     keydir: KeyDir,
+    /// The number of garbage bytes in the log file, i.e. replaced entries and
+    /// tombstones. Tracked incrementally to avoid scanning the keydir.
+    garbage: u64,
+    /// If given, the log is compacted on flush when the garbage exceeds this
+    /// fraction of the file size and this number of bytes.
+    compact_threshold: Option<(f64, u64)>,
+    /// Controls when flushes fsync the log file to durable storage.
+    durability: Durability,
+    /// The time of the last fsync, used with Durability::Interval.
+    last_fsync: Instant,
+    /// Whether there are writes since the last fsync.
+    unsynced: bool,
+    /// A background compaction in progress, if any.
+    compaction: Option<Compaction>,
+}
+
+/// A background log compaction. The thread writes the live entries of the log
+/// file, as of when the compaction started, to a new log file and returns it
+/// along with its keydir.
+struct Compaction {
+    /// The thread writing the new log file.
+    thread: std::thread::JoinHandle<Result<(Log, KeyDir)>>,
+    /// Keys written or deleted since the compaction started, which must be
+    /// copied into the new log file before it replaces the current one.
+    written: std::collections::BTreeSet<Vec<u8>>,
+}
+
+/// Controls when BitCask fsyncs the log file to durable storage. Writes are
+/// always written through to the OS page cache, which survives process
+/// crashes, but only fsynced writes survive power loss or OS crashes. Fsyncs
+/// happen on Engine::flush(), which the Raft log calls after every append, and
+/// the Raft node calls on every tick.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Durability {
+    /// Fsync on every flush. This is the safest mode, and the default.
+    #[default]
+    Always,
+    /// Fsync on flush at most once per interval. Since the Raft node also
+    /// flushes on every tick, pending writes are fsynced within roughly the
+    /// interval plus a tick even if no further writes arrive. On power loss,
+    /// writes since the last fsync may be lost.
+    Interval(Duration),
+    /// Never fsync, leaving it to the OS to write out dirty pages.
+    ///
+    /// WARNING: on power loss or OS crashes, an arbitrary amount of recent
+    /// writes may be lost, and Raft safety guarantees no longer hold (e.g.
+    /// a node may forget votes or acknowledged log entries). Only use this
+    /// for tests and benchmarks.
+    Never,
 }
 
 /// Maps keys to a value position and length in the log file.
@@ -58,41 +112,136 @@ impl BitCask {
         let mut log = Log::new(path.clone())?;
         let keydir = log.build_keydir()?;
         log::info!("Indexed {} live keys in {}", keydir.len(), path.display());
-        Ok(Self { log, keydir })
+        let mut s = Self {
+            log,
+            keydir,
+            garbage: 0,
+            compact_threshold: None,
+            durability: Durability::default(),
+            last_fsync: Instant::now(),
+            unsynced: false,
+            compaction: None,
+        };
+        s.garbage = s.status()?.garbage_disk_size;
+        Ok(s)
     }
 
     /// Opens a BitCask database, and automatically compacts it if the amount
-    /// of garbage exceeds the given ratio and byte size when opened.
+    /// of garbage exceeds the given ratio and byte size, both when opened and
+    /// when flushed.
     pub fn new_compact(
         path: PathBuf,
         garbage_min_fraction: f64,
         garbage_min_bytes: u64,
     ) -> Result<Self> {
         let mut s = Self::new(path)?;
+        s.compact_threshold = Some((garbage_min_fraction, garbage_min_bytes));
+        s.maybe_compact()?;
+        Ok(s)
+    }
+
+    /// Sets the fsync durability mode. Defaults to Durability::Always.
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// Returns true if a flush should fsync the log file.
+    fn should_fsync(&self) -> bool {
+        match self.durability {
+            Durability::Always => true,
+            Durability::Interval(interval) => self.last_fsync.elapsed() >= interval,
+            Durability::Never => false,
+        }
+    }
 
-        let status = s.status()?;
-        if Self::should_compact(
-            status.garbage_disk_size,
-            status.total_disk_size,
-            garbage_min_fraction,
-            garbage_min_bytes,
-        ) {
+    /// Completes a finished background compaction, if any, and starts a new
+    /// one if the amount of garbage exceeds the compaction threshold, if any.
+    fn maybe_compact(&mut self) -> Result<()> {
+        self.finish_compaction(false)?;
+        let Some((garbage_min_fraction, garbage_min_bytes)) = self.compact_threshold else {
+            return Ok(());
+        };
+        let total_size = self.log.file.metadata()?.len();
+        if self.compaction.is_none()
+            && Self::should_compact(
+                self.garbage,
+                total_size,
+                garbage_min_fraction,
+                garbage_min_bytes,
+            )
+        {
+            let status = self.status()?;
             log::info!(
                 "Compacting {} to remove {:.0}% garbage ({} MB out of {} MB)",
-                s.log.path.display(),
+                self.log.path.display(),
                 status.garbage_percent(),
                 status.garbage_disk_size / 1024 / 1024,
                 status.total_disk_size / 1024 / 1024
             );
-            s.compact()?;
-            log::info!(
-                "Compacted {} to size {} MB",
-                s.log.path.display(),
-                (status.total_disk_size - status.garbage_disk_size) / 1024 / 1024
-            );
+            self.compact()?;
         }
+        Ok(())
+    }
 
-        Ok(s)
+    /// Starts a background compaction, unless one is already in progress. The
+    /// live entries are read from a separate file handle, using a copy of the
+    /// keydir, so the database can keep serving reads and writes meanwhile.
+    fn start_compaction(&mut self) -> Result<()> {
+        if self.compaction.is_some() {
+            return Ok(());
+        }
+        let keydir = self.keydir.clone();
+        let mut log = Log::open_read(self.log.path.clone())?;
+        let mut path = self.log.path.clone();
+        path.set_extension("new");
+        let thread = std::thread::spawn(move || Self::write_log(&mut log, &keydir, path));
+        self.compaction = Some(Compaction { thread, written: Default::default() });
+        Ok(())
+    }
+
+    /// Completes a background compaction, if any. If wait is false, only
+    /// completes it if the background thread has finished. Keys written since
+    /// the compaction started are copied into the new log file, which then
+    /// atomically replaces the current log file.
+    fn finish_compaction(&mut self, wait: bool) -> Result<()> {
+        let Some(compaction) = self.compaction.take_if(|c| wait || c.thread.is_finished()) else {
+            return Ok(());
+        };
+        let (mut new_log, mut new_keydir) =
+            compaction.thread.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))?;
+
+        for key in compaction.written {
+            match self.keydir.get(&key) {
+                Some((value_pos, value_len)) => {
+                    let value = self.log.read_value(*value_pos, *value_len)?;
+                    let (pos, len) = new_log.write_entry(&key, Some(&value))?;
+                    new_keydir.insert(key, (pos + len as u64 - *value_len as u64, *value_len));
+                }
+                None if new_keydir.contains_key(&key) => {
+                    new_log.write_entry(&key, None)?;
+                    new_keydir.remove(&key);
+                }
+                None => {}
+            }
+        }
+
+        // Make sure the new log is durable before replacing the old one.
+        #[cfg(not(test))]
+        new_log.file.sync_all()?;
+        std::fs::rename(&new_log.path, &self.log.path)?;
+        new_log.path = self.log.path.clone();
+
+        self.log = new_log;
+        self.keydir = new_keydir;
+        let status = self.status()?;
+        self.garbage = status.garbage_disk_size;
+        log::info!(
+            "Compacted {} to size {} MB",
+            self.log.path.display(),
+            status.total_disk_size / 1024 / 1024
+        );
+        Ok(())
     }
 
     /// Returns true if the log file should be compacted.
@@ -105,23 +254,52 @@ impl BitCask {
         let garbage_fraction = garbage_size as f64 / total_size as f64;
         garbage_size > 0 && garbage_size >= min_bytes && garbage_fraction >= min_fraction
     }
+
+    /// Returns the on-disk size of a log entry with the given key and value
+    /// length, including the length prefixes.
+    fn entry_size(key: &[u8], value_len: u32) -> u64 {
+        8 + key.len() as u64 + value_len as u64
+    }
 }
 
 impl Engine for BitCask {
     type ScanIterator<'a> = ScanIterator<'a>;
 
+    /// Compacts the current log file by writing out a new log file containing
+    /// only live keys in a background thread. The new file atomically replaces
+    /// the current file on a later flush, once the thread has finished.
+    fn compact(&mut self) -> Result<()> {
+        self.start_compaction()?;
+        // Wait for the compaction in tests, for determinism.
+        #[cfg(test)]
+        self.finish_compaction(true)?;
+        Ok(())
+    }
+
     fn delete(&mut self, key: &[u8]) -> Result<()> {
-        self.log.write_entry(key, None)?;
-        self.keydir.remove(key);
+        let (_, len) = self.log.write_entry(key, None)?;
+        self.unsynced = true;
+        if let Some(compaction) = &mut self.compaction {
+            compaction.written.insert(key.to_vec());
+        }
+        self.garbage += len as u64;
+        if let Some((_, value_len)) = self.keydir.remove(key) {
+            self.garbage += Self::entry_size(key, value_len);
+        }
         Ok(())
     }
 
     fn flush(&mut self) -> Result<()> {
+        self.maybe_compact()?;
         // Don't fsync in tests, to speed them up. We disable this here, instead
         // of setting raft::Log::fsync = false in tests, because we want to
         // assert that the Raft log flushes to disk even if the flush is a noop.
-        #[cfg(not(test))]
-        self.log.file.sync_all()?;
+        if self.unsynced && self.should_fsync() {
+            #[cfg(not(test))]
+            self.log.file.sync_all()?;
+            self.last_fsync = Instant::now();
+            self.unsynced = false;
+        }
         Ok(())
     }
 
@@ -146,8 +324,16 @@ impl Engine for BitCask {
 
     fn set(&mut self, key: &[u8], value: Vec<u8>) -> Result<()> {
         let (pos, len) = self.log.write_entry(key, Some(&*value))?;
+        self.unsynced = true;
+        if let Some(compaction) = &mut self.compaction {
+            compaction.written.insert(key.to_vec());
+        }
         let value_len = value.len() as u32;
-        self.keydir.insert(key.to_vec(), (pos + len as u64 - value_len as u64, value_len));
+        let replaced =
+            self.keydir.insert(key.to_vec(), (pos + len as u64 - value_len as u64, value_len));
+        if let Some((_, value_len)) = replaced {
+            self.garbage += Self::entry_size(key, value_len);
+        }
         Ok(())
     }
 
@@ -198,29 +384,15 @@ impl<'a> DoubleEndedIterator for ScanIterator<'a> {
 }
 
 impl BitCask {
-    /// Compacts the current log file by writing out a new log file containing
-    /// only live keys and replacing the current file with it.
-    pub fn compact(&mut self) -> Result<()> {
-        let mut tmp_path = self.log.path.clone();
-        tmp_path.set_extension("new");
-        let (mut new_log, new_keydir) = self.write_log(tmp_path)?;
-
-        std::fs::rename(&new_log.path, &self.log.path)?;
-        new_log.path = self.log.path.clone();
-
-        self.log = new_log;
-        self.keydir = new_keydir;
-        Ok(())
-    }
-
-    /// Writes out a new log file with the live entries of the current log file
-    /// and returns it along with its keydir. Entries are written in key order.
-    fn write_log(&mut self, path: PathBuf) -> Result<(Log, KeyDir)> {
+    /// Writes out a new log file with the given keydir's entries from the given
+    /// log file and returns it along with its keydir. Entries are written in
+    /// key order.
+    fn write_log(log: &mut Log, keydir: &KeyDir, path: PathBuf) -> Result<(Log, KeyDir)> {
         let mut new_keydir = KeyDir::new();
         let mut new_log = Log::new(path)?;
         new_log.file.set_len(0)?; // truncate file if it exists
-        for (key, (value_pos, value_len)) in self.keydir.iter() {
-            let value = self.log.read_value(*value_pos, *value_len)?;
+        for (key, (value_pos, value_len)) in keydir.iter() {
+            let value = log.read_value(*value_pos, *value_len)?;
             let (pos, len) = new_log.write_entry(key, Some(&value))?;
             new_keydir.insert(key.clone(), (pos + len as u64 - *value_len as u64, *value_len));
         }
@@ -228,12 +400,16 @@ impl BitCask {
     }
 }
 
-/// Attempt to flush the file when the database is closed.
+/// Attempt to flush the file and complete any compaction when the database is
+/// closed.
 impl Drop for BitCask {
     fn drop(&mut self) {
         if let Err(error) = self.flush() {
             log::error!("failed to flush file: {}", error)
         }
+        if let Err(error) = self.finish_compaction(true) {
+            log::error!("failed to compact file: {}", error)
+        }
     }
 }
 
@@ -269,6 +445,13 @@ impl Log {
         Ok(Self { path, file })
     }
 
+    /// Opens an existing log file for reading only, without locking it. This
+    /// allows reading the log concurrently with writes to it.
+    fn open_read(path: PathBuf) -> Result<Self> {
+        let file = std::fs::File::open(&path)?;
+        Ok(Self { path, file })
+    }
+
     /// Builds a keydir by scanning the log file. If an incomplete entry is
     /// encountered, it is assumed to be caused by an incomplete write operation
     /// and the remainder of the file is truncated.
@@ -471,6 +654,144 @@ mod tests {
         Ok(())
     }
 
+    /// Tests that flushes fsync according to the durability mode.
+    #[test]
+    fn durability() -> Result<()> {
+        let dir = tempfile::TempDir::with_prefix("toydb")?;
+        let hour = Duration::from_secs(3600);
+
+        let engine = BitCask::new(dir.path().join("default"))?;
+        assert_eq!(engine.durability, Durability::Always);
+        assert!(engine.should_fsync());
+
+        let engine = BitCask::new(dir.path().join("never"))?.with_durability(Durability::Never);
+        assert!(!engine.should_fsync());
+
+        // An interval fsyncs once it has elapsed since the last fsync, and
+        // fsyncs reset the timer.
+        let mut engine = BitCask::new(dir.path().join("interval"))?
+            .with_durability(Durability::Interval(Duration::ZERO));
+        assert!(engine.should_fsync());
+        engine.flush()?;
+        engine.durability = Durability::Interval(hour);
+        assert!(!engine.should_fsync());
+
+        // A write isn't fsynced until the interval elapses, at which point a
+        // flush without further writes (e.g. a Raft tick) fsyncs it.
+        let last_fsync = engine.last_fsync;
+        engine.set(b"key", vec![1])?;
+        engine.flush()?;
+        assert!(engine.unsynced);
+        assert_eq!(engine.last_fsync, last_fsync);
+
+        engine.durability = Durability::Interval(Duration::ZERO);
+        engine.flush()?;
+        assert!(!engine.unsynced);
+        assert!(engine.last_fsync > last_fsync);
+
+        // Flushes without pending writes don't fsync.
+        let last_fsync = engine.last_fsync;
+        engine.flush()?;
+        assert_eq!(engine.last_fsync, last_fsync);
+
+        Ok(())
+    }
+
+    /// Tests that compaction of a log with many overwrites and deletes retains
+    /// all live data and reclaims the garbage, and that the incrementally
+    /// tracked garbage size matches the status.
+    #[test]
+    fn compact_overwrites() -> Result<()> {
+        let dir = tempfile::TempDir::with_prefix("toydb")?;
+        let path = dir.path().join("bitcask");
+        let mut engine = BitCask::new(path.clone())?;
+        let mut expect = std::collections::BTreeMap::new();
+
+        for round in 0..10_u64 {
+            for i in 0..100_u64 {
+                let key = i.to_be_bytes();
+                if (i + round) % 7 == 0 {
+                    engine.delete(&key)?;
+                    expect.remove(key.as_slice());
+                } else {
+                    let value = (i * round).to_be_bytes().to_vec();
+                    engine.set(&key, value.clone())?;
+                    expect.insert(key.to_vec(), value);
+                }
+            }
+        }
+        let expect = expect.into_iter().collect::<Vec<_>>();
+
+        let status = engine.status()?;
+        assert_eq!(status.garbage_disk_size, engine.garbage);
+        assert!(status.garbage_disk_size > status.live_disk_size);
+        assert_eq!(std::fs::metadata(&path)?.len(), status.total_disk_size);
+
+        engine.compact()?;
+        assert_eq!(engine.scan(..).collect::<Result<Vec<_>>>()?, expect);
+        assert_eq!(engine.garbage, 0);
+        assert_eq!(std::fs::metadata(&path)?.len(), status.live_disk_size);
+
+        // Reopening the compacted log yields the same data.
+        drop(engine);
+        let mut engine = BitCask::new(path.clone())?;
+        assert_eq!(engine.scan(..).collect::<Result<Vec<_>>>()?, expect);
+        assert_eq!(engine.status()?.garbage_disk_size, 0);
+        Ok(())
+    }
+
+    /// Tests that reads and writes are served while a background compaction is
+    /// in progress, and that the writes are retained once it completes.
+    #[test]
+    fn compact_concurrent() -> Result<()> {
+        let dir = tempfile::TempDir::with_prefix("toydb")?;
+        let path = dir.path().join("bitcask");
+        let mut engine = BitCask::new(path.clone())?;
+        let mut expect = std::collections::BTreeMap::new();
+        for i in 0..1000_u64 {
+            engine.set(&i.to_be_bytes(), vec![0; 100])?;
+            engine.set(&i.to_be_bytes(), vec![1; 100])?;
+            expect.insert(i.to_be_bytes().to_vec(), vec![1; 100]);
+        }
+
+        engine.start_compaction()?;
+        assert!(engine.compaction.is_some());
+
+        // Overwrite and delete existing keys, and write and delete new keys.
+        for i in (0..1000_u64).step_by(3) {
+            engine.set(&i.to_be_bytes(), vec![2; 10])?;
+            expect.insert(i.to_be_bytes().to_vec(), vec![2; 10]);
+        }
+        for i in (0..1000_u64).step_by(5) {
+            engine.delete(&i.to_be_bytes())?;
+            expect.remove(i.to_be_bytes().as_slice());
+        }
+        for i in 1000..1100_u64 {
+            engine.set(&i.to_be_bytes(), vec![3; 10])?;
+            expect.insert(i.to_be_bytes().to_vec(), vec![3; 10]);
+        }
+        engine.delete(&1000_u64.to_be_bytes())?;
+        expect.remove(1000_u64.to_be_bytes().as_slice());
+        let expect = expect.into_iter().collect::<Vec<_>>();
+        assert_eq!(engine.scan(..).collect::<Result<Vec<_>>>()?, expect);
+
+        // Completing the compaction retains the writes and removes garbage,
+        // except for the garbage written during the compaction.
+        engine.finish_compaction(true)?;
+        assert!(engine.compaction.is_none());
+        assert_eq!(engine.scan(..).collect::<Result<Vec<_>>>()?, expect);
+        let status = engine.status()?;
+        assert_eq!(status.garbage_disk_size, engine.garbage);
+        assert!(status.garbage_disk_size < status.live_disk_size);
+        assert_eq!(std::fs::metadata(&path)?.len(), status.total_disk_size);
+
+        drop(engine);
+        let mut engine = BitCask::new(path.clone())?;
+        assert_eq!(engine.scan(..).collect::<Result<Vec<_>>>()?, expect);
+        assert_eq!(engine.status()?, status);
+        Ok(())
+    }
+
     /// Tests that should_compact() handles parameters correctly.
     #[test_case(100, 100, -01.0, 0 => true; "ratio negative all garbage")]
     #[test_case(100, 100, 0.0, 0 => true; "ratio 0 all garbage")]
@@ -504,13 +825,6 @@ mod tests {
         fn run(&mut self, command: &goldenscript::Command) -> StdResult<String, Box<dyn StdError>> {
             let mut output = String::new();
             match command.name.as_str() {
-                // compact
-                // Compacts the BitCask entry log.
-                "compact" => {
-                    command.consume_args().reject_rest()?;
-                    self.inner.engine.compact()?;
-                }
-
                 // dump
                 // Dumps the full BitCask entry log.
                 "dump" => {
@@ -19,6 +19,13 @@ pub trait Engine: Send {
     where
         Self: Sized + 'a; // omit in trait objects, for object safety
 
+    /// Compacts the engine's storage, removing garbage such as replaced values
+    /// and tombstones. Engines may compact in the background, completing on a
+    /// later flush. Engines without garbage can use the default noop.
+    fn compact(&mut self) -> Result<()> {
+        Ok(())
+    }
+
     /// Deletes a key, or does nothing if it does not exist.
     fn delete(&mut self, key: &[u8]) -> Result<()>;
 
@@ -115,6 +122,12 @@ pub mod test {
         fn run(&mut self, command: &goldenscript::Command) -> StdResult<String, Box<dyn StdError>> {
             let mut output = String::new();
             match command.name.as_str() {
+                // compact
+                "compact" => {
+                    command.consume_args().reject_rest()?;
+                    self.engine.compact()?;
+                }
+
                 // delete KEY
                 "delete" => {
                     let mut args = command.consume_args();
@@ -123,6 +136,12 @@ pub mod test {
                     self.engine.delete(&key)?;
                 }
 
+                // flush
+                "flush" => {
+                    command.consume_args().reject_rest()?;
+                    self.engine.flush()?;
+                }
+
                 // get KEY
                 "get" => {
                     let mut args = command.consume_args();
@@ -250,6 +269,10 @@ pub mod test {
     impl<E: Engine> Engine for Emit<E> {
         type ScanIterator<'a> = E::ScanIterator<'a> where E: 'a;
 
+        fn compact(&mut self) -> Result<()> {
+            self.inner.compact()
+        }
+
         fn flush(&mut self) -> Result<()> {
             self.inner.flush()?;
             self.tx.send(Operation::Flush)?;
@@ -309,6 +332,11 @@ pub mod test {
             A: 'a,
             B: 'a;
 
+        fn compact(&mut self) -> Result<()> {
+            self.a.compact()?;
+            self.b.compact()
+        }
+
         fn delete(&mut self, key: &[u8]) -> Result<()> {
             self.a.delete(key)?;
             self.b.delete(key)
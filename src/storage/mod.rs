@@ -6,6 +6,6 @@ pub mod engine;
 mod memory;
 pub mod mvcc;
 
-pub use bitcask::BitCask;
+pub use bitcask::{BitCask, Durability};
 pub use engine::{Engine, ScanIterator, Status};
 pub use memory::Memory;
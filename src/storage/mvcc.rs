@@ -88,6 +88,22 @@
 //! not see any of t2's writes, because it's still in its local snapshot of the
 //! active set at the time it began.
 //!
+//! SAVEPOINTS
+//! ==========
+//!
+//! A read-write transaction can create named savepoints, and later roll back to
+//! a savepoint to undo any writes made after it without aborting the entire
+//! transaction. Savepoints form a stack: rolling back to or releasing a
+//! savepoint also removes any later savepoints.
+//!
+//! Savepoints are stored as Key::TxnSavepoint(version, seq), where seq orders
+//! the stack. When a savepoint is created, the current values of all keys
+//! written by the transaction (as found via its TxnWrite records) are copied
+//! into Key::TxnSavepointWrite(version, seq, key). Rolling back to a savepoint
+//! restores these values, and removes any versions and TxnWrite records for
+//! keys that were first written after the savepoint. Savepoint records are
+//! removed when the transaction commits or rolls back.
+//!
 //! READ-ONLY AND TIME TRAVEL QUERIES
 //! =================================
 //!
@@ -133,11 +149,36 @@
 //! GARBAGE COLLECTION
 //! ==================
 //!
-//! Normally, old versions would be garbage collected regularly, when they are
-//! no longer needed by active transactions or time-travel queries. However,
-//! ToyDB does not implement garbage collection, instead keeping all history
-//! forever, both out of laziness and also because it allows unlimited time
-//! travel queries (it's a feature, not a bug!).
+//! Old versions can be garbage collected via MVCC::gc() when they are no
+//! longer needed by active transactions or time-travel queries. By default,
+//! ToyDB keeps all history forever, since it allows unlimited time travel
+//! queries (it's a feature, not a bug!).
+//!
+//! Garbage collection takes a version, and computes a watermark at or below it
+//! such that every version below the watermark is visible to all transactions
+//! at or above the watermark. The watermark is lowered to the oldest active
+//! read-write transaction, as well as to the oldest version in the active set
+//! snapshots of any versions at or above the watermark, since these versions
+//! are invisible to time-travel queries at those versions. For each key, all
+//! versions below the watermark are then removed except the latest one, which
+//! is also removed if it's a tombstone.
+//!
+//! In the following example, GC at version 4 with no active transactions
+//! removes a1, since a2 is the latest version below the watermark, as well as
+//! d1 and the tombstone at d3. A time-travel query at version 4 will still see
+//! a=a2, b=b3, c=c1, and transactions at version 5 see a=a4, b=b3, c=c1.
+//!
+//! Time
+//! 5
+//! 4  a4
+//! 3      b3      x
+//! 2  a2
+//! 1  a1      c1  d1
+//!    a   b   c   d   Keys
+//!
+//! Read-only transactions are not tracked in storage, so the caller must make
+//! sure none are running below the GC version. The watermark is recorded in
+//! Key::GCVersion, and time-travel queries below it return an error.
 
 use super::engine::{self, Engine};
 use crate::encoding::{self, bincode, keycode, Key as _, Value as _};
@@ -193,6 +234,23 @@ pub enum Key<'a> {
         #[serde(borrow)]
         Cow<'a, [u8]>,
     ),
+    /// A savepoint in an active transaction (identified by its version). The
+    /// sequence number orders the transaction's savepoint stack, and the value
+    /// is the savepoint name.
+    TxnSavepoint(Version, u64),
+    /// The raw version value of a key written by an active transaction, as of
+    /// the given savepoint sequence number. Used to restore the key's value
+    /// when rolling back to the savepoint.
+    TxnSavepointWrite(
+        Version,
+        u64,
+        #[serde(with = "serde_bytes")]
+        #[serde(borrow)]
+        Cow<'a, [u8]>,
+    ),
+    /// The garbage collection watermark. Versions below it may have been
+    /// garbage collected, so time-travel queries can't read below it.
+    GCVersion,
 }
 
 impl<'a> encoding::Key<'a> for Key<'a> {}
@@ -211,6 +269,9 @@ enum KeyPrefix<'a> {
         Cow<'a, [u8]>,
     ),
     Unversioned,
+    TxnSavepoint(Version),
+    TxnSavepointWrite(Version),
+    GCVersion,
 }
 
 impl<'a> encoding::Key<'a> for KeyPrefix<'a> {}
@@ -264,6 +325,93 @@ impl<E: Engine> MVCC<E> {
         self.engine.lock()?.set(&Key::Unversioned(key.into()).encode(), value)
     }
 
+    /// Garbage collects old versions that are no longer visible to any
+    /// transaction at or after the given version, returning the number of
+    /// removed versions. See the module documentation for details.
+    ///
+    /// Read-only transactions are not tracked, so the caller must ensure that
+    /// none are running below the given version.
+    pub fn gc(&self, version: Version) -> Result<u64> {
+        let mut engine = self.engine.lock()?;
+
+        // Compute the watermark. It can't exceed the next version, nor any
+        // active read-write transaction.
+        let next_version = match engine.get(&Key::NextVersion.encode())? {
+            Some(ref v) => Version::decode(v)?,
+            None => 1,
+        };
+        let mut watermark = version.min(next_version);
+        if let Some(&active) = Transaction::scan_active(&mut engine)?.first() {
+            watermark = watermark.min(active);
+        }
+
+        // Time-travel queries at or above the watermark can't see versions in
+        // their active set snapshots, so lower the watermark below them. Scan
+        // in reverse order, since lowering the watermark can pull in further
+        // snapshots. Snapshots below the watermark are no longer needed.
+        let mut remove = Vec::new();
+        let mut scan = engine.scan_prefix(&KeyPrefix::TxnActiveSnapshot.encode()).rev();
+        while let Some((key, value)) = scan.next().transpose()? {
+            let version = match Key::decode(&key)? {
+                Key::TxnActiveSnapshot(version) => version,
+                key => return errdata!("expected TxnActiveSnapshot key, got {key:?}"),
+            };
+            if version < watermark {
+                remove.push(key);
+            } else if let Some(&active) = BTreeSet::<Version>::decode(&value)?.first() {
+                watermark = watermark.min(active);
+            }
+        }
+        drop(scan);
+
+        // For each key, remove all versions below the watermark except the
+        // latest, and remove that too if it's a tombstone. Versions are
+        // ordered by key then version, so we keep track of the latest version
+        // seen below the watermark as (key, engine key, is_tombstone).
+        let mut versions = 0;
+        let mut latest: Option<(Vec<u8>, Vec<u8>, bool)> = None;
+        let mut scan = engine.scan((
+            Bound::Included(Key::Version(vec![].into(), 0).encode()),
+            Bound::Excluded(KeyPrefix::Unversioned.encode()),
+        ));
+        while let Some((key, value)) = scan.next().transpose()? {
+            let (userkey, version) = match Key::decode(&key)? {
+                Key::Version(userkey, version) => (userkey.into_owned(), version),
+                key => return errdata!("expected Key::Version got {key:?}"),
+            };
+            if version >= watermark {
+                continue;
+            }
+            if let Some((latest_key, latest_engine_key, tombstone)) = latest.take() {
+                if latest_key == userkey || tombstone {
+                    remove.push(latest_engine_key);
+                    versions += 1;
+                }
+            }
+            let tombstone = bincode::deserialize::<Option<Vec<u8>>>(&value)?.is_none();
+            latest = Some((userkey, key, tombstone));
+        }
+        drop(scan);
+        if let Some((_, key, true)) = latest {
+            remove.push(key);
+            versions += 1;
+        }
+
+        for key in remove {
+            engine.delete(&key)?;
+        }
+
+        // Record the watermark, unless a previous GC used a higher one.
+        let gc_version = match engine.get(&Key::GCVersion.encode())? {
+            Some(ref v) => Version::decode(v)?,
+            None => 0,
+        };
+        if watermark > gc_version {
+            engine.set(&Key::GCVersion.encode(), watermark.encode())?;
+        }
+        Ok(versions)
+    }
+
     /// Returns the status of the MVCC and storage engines.
     pub fn status(&self) -> Result<Status> {
         let mut engine = self.engine.lock()?;
@@ -340,7 +488,7 @@ impl TransactionState {
     /// that version commits its writes. See the module documentation for
     /// details.
     fn is_visible(&self, version: Version) -> bool {
-        if self.active.get(&version).is_some() {
+        if self.active.contains(&version) {
             false
         } else if self.read_only {
             version < self.version
@@ -409,6 +557,11 @@ impl<E: Engine> Transaction<E> {
             if as_of >= version {
                 return errinput!("version {as_of} does not exist");
             }
+            if let Some(ref v) = session.get(&Key::GCVersion.encode())? {
+                if as_of < Version::decode(v)? {
+                    return errinput!("version {as_of} has been garbage collected");
+                }
+            }
             version = as_of;
             if let Some(value) = session.get(&Key::TxnActiveSnapshot(version).encode())? {
                 active = BTreeSet::<Version>::decode(&value)?;
@@ -429,6 +582,16 @@ impl<E: Engine> Transaction<E> {
         if !s.read_only && engine.lock()?.get(&Key::TxnActive(s.version).encode())?.is_none() {
             return errinput!("no active transaction at version {}", s.version);
         }
+        // For read-only transactions, verify that the versions they can see
+        // haven't been garbage collected.
+        if s.read_only {
+            let min_version = s.active.first().copied().unwrap_or(s.version).min(s.version);
+            if let Some(ref v) = engine.lock()?.get(&Key::GCVersion.encode())? {
+                if min_version < Version::decode(v)? {
+                    return errinput!("version {} has been garbage collected", s.version);
+                }
+            }
+        }
         Ok(Self { engine, st: s })
     }
 
@@ -479,6 +642,7 @@ impl<E: Engine> Transaction<E> {
         for key in remove {
             engine.delete(&key)?
         }
+        Self::remove_savepoints(&mut engine, self.st.version, 0)?;
         engine.delete(&Key::TxnActive(self.st.version).encode())
     }
 
@@ -505,9 +669,139 @@ impl<E: Engine> Transaction<E> {
         for key in rollback.into_iter() {
             engine.delete(&key)?;
         }
+        Self::remove_savepoints(&mut engine, self.st.version, 0)?;
         engine.delete(&Key::TxnActive(self.st.version).encode()) // remove from active set
     }
 
+    /// Creates a savepoint with the given name, which the transaction can
+    /// later roll back to. This records the current values of all keys written
+    /// by the transaction. Savepoints form a stack, and names can be reused,
+    /// in which case the latest savepoint with the name is used.
+    pub fn savepoint(&self, name: &str) -> Result<()> {
+        if self.st.read_only {
+            return Err(Error::ReadOnly);
+        }
+        let mut engine = self.engine.lock()?;
+        let seq =
+            match engine.scan_prefix(&KeyPrefix::TxnSavepoint(self.st.version).encode()).last() {
+                Some(result) => match Key::decode(&result?.0)? {
+                    Key::TxnSavepoint(_, seq) => seq + 1,
+                    key => return errdata!("expected TxnSavepoint, got {key:?}"),
+                },
+                None => 1,
+            };
+        let mut writes = Vec::new();
+        let mut scan = engine.scan_prefix(&KeyPrefix::TxnWrite(self.st.version).encode());
+        while let Some((key, _)) = scan.next().transpose()? {
+            match Key::decode(&key)? {
+                Key::TxnWrite(_, key) => writes.push(key.into_owned()),
+                key => return errdata!("expected TxnWrite, got {key:?}"),
+            }
+        }
+        drop(scan);
+        for key in writes {
+            let Some(value) =
+                engine.get(&Key::Version(key.as_slice().into(), self.st.version).encode())?
+            else {
+                return errdata!("missing version for TxnWrite {key:?}");
+            };
+            engine
+                .set(&Key::TxnSavepointWrite(self.st.version, seq, key.into()).encode(), value)?;
+        }
+        engine.set(&Key::TxnSavepoint(self.st.version, seq).encode(), bincode::serialize(&name))
+    }
+
+    /// Rolls back to the latest savepoint with the given name, undoing all
+    /// writes made after it and removing any later savepoints. The savepoint
+    /// itself is kept, and can be rolled back to again.
+    pub fn rollback_to_savepoint(&self, name: &str) -> Result<()> {
+        if self.st.read_only {
+            return Err(Error::ReadOnly);
+        }
+        let mut engine = self.engine.lock()?;
+        let seq = Self::find_savepoint(&mut engine, self.st.version, name)?;
+
+        // Restore keys that were written before the savepoint, and remove
+        // versions and write records for keys first written after it.
+        let mut writes = Vec::new();
+        let mut scan = engine.scan_prefix(&KeyPrefix::TxnWrite(self.st.version).encode());
+        while let Some((key, _)) = scan.next().transpose()? {
+            match Key::decode(&key)? {
+                Key::TxnWrite(_, key) => writes.push(key.into_owned()),
+                key => return errdata!("expected TxnWrite, got {key:?}"),
+            }
+        }
+        drop(scan);
+        for key in writes {
+            let version_key = Key::Version(key.as_slice().into(), self.st.version).encode();
+            let savepoint_key =
+                Key::TxnSavepointWrite(self.st.version, seq, key.as_slice().into()).encode();
+            match engine.get(&savepoint_key)? {
+                Some(value) => engine.set(&version_key, value)?,
+                None => {
+                    engine.delete(&version_key)?;
+                    engine.delete(&Key::TxnWrite(self.st.version, key.into()).encode())?;
+                }
+            }
+        }
+        Self::remove_savepoints(&mut engine, self.st.version, seq + 1)
+    }
+
+    /// Releases the latest savepoint with the given name, along with any later
+    /// savepoints. Their writes are retained.
+    pub fn release_savepoint(&self, name: &str) -> Result<()> {
+        if self.st.read_only {
+            return Err(Error::ReadOnly);
+        }
+        let mut engine = self.engine.lock()?;
+        let seq = Self::find_savepoint(&mut engine, self.st.version, name)?;
+        Self::remove_savepoints(&mut engine, self.st.version, seq)
+    }
+
+    /// Returns the sequence number of the latest savepoint with the given name
+    /// for the transaction at the given version, or errors if none exists.
+    fn find_savepoint(session: &mut MutexGuard<E>, version: Version, name: &str) -> Result<u64> {
+        let mut scan = session.scan_prefix(&KeyPrefix::TxnSavepoint(version).encode()).rev();
+        while let Some((key, value)) = scan.next().transpose()? {
+            match Key::decode(&key)? {
+                Key::TxnSavepoint(_, seq) if bincode::deserialize::<String>(&value)? == name => {
+                    return Ok(seq)
+                }
+                Key::TxnSavepoint(_, _) => {}
+                key => return errdata!("expected TxnSavepoint, got {key:?}"),
+            }
+        }
+        errinput!("savepoint {name} does not exist")
+    }
+
+    /// Removes savepoints with sequence numbers at or above the given one for
+    /// the transaction at the given version, along with their write records.
+    fn remove_savepoints(session: &mut MutexGuard<E>, version: Version, from: u64) -> Result<()> {
+        let mut remove = Vec::new();
+        let mut scan = session.scan_prefix(&KeyPrefix::TxnSavepoint(version).encode());
+        while let Some((key, _)) = scan.next().transpose()? {
+            match Key::decode(&key)? {
+                Key::TxnSavepoint(_, seq) if seq >= from => remove.push(key),
+                Key::TxnSavepoint(_, _) => {}
+                key => return errdata!("expected TxnSavepoint, got {key:?}"),
+            }
+        }
+        drop(scan);
+        let mut scan = session.scan_prefix(&KeyPrefix::TxnSavepointWrite(version).encode());
+        while let Some((key, _)) = scan.next().transpose()? {
+            match Key::decode(&key)? {
+                Key::TxnSavepointWrite(_, seq, _) if seq >= from => remove.push(key),
+                Key::TxnSavepointWrite(_, _, _) => {}
+                key => return errdata!("expected TxnSavepointWrite, got {key:?}"),
+            }
+        }
+        drop(scan);
+        for key in remove {
+            session.delete(&key)?;
+        }
+        Ok(())
+    }
+
     /// Deletes a key.
     pub fn delete(&self, key: &[u8]) -> Result<()> {
         self.write_version(key, None)
@@ -771,6 +1065,9 @@ pub mod tests {
     #[test_case(KeyPrefix::TxnWrite(1), Key::TxnWrite(1, b"foo".as_slice().into()); "TxnWrite")]
     #[test_case(KeyPrefix::Version(b"foo".as_slice().into()), Key::Version(b"foo".as_slice().into(), 1); "Version")]
     #[test_case(KeyPrefix::Unversioned, Key::Unversioned(b"foo".as_slice().into()); "Unversioned")]
+    #[test_case(KeyPrefix::TxnSavepoint(1), Key::TxnSavepoint(1, 2); "TxnSavepoint")]
+    #[test_case(KeyPrefix::TxnSavepointWrite(1), Key::TxnSavepointWrite(1, 2, b"foo".as_slice().into()); "TxnSavepointWrite")]
+    #[test_case(KeyPrefix::GCVersion, Key::GCVersion; "GCVersion")]
     fn key_prefix(prefix: KeyPrefix, key: Key) {
         let prefix = prefix.encode();
         let key = key.encode();
@@ -884,6 +1181,16 @@ pub mod tests {
                     }
                 }
 
+                // gc VERSION
+                "gc" => {
+                    Self::no_txn(command)?;
+                    let mut args = command.consume_args();
+                    let version = args.next_pos().ok_or("version not given")?.parse()?;
+                    args.reject_rest()?;
+                    let removed = self.mvcc.gc(version)?;
+                    writeln!(output, "removed {removed} versions")?;
+                }
+
                 // txn: get KEY...
                 "get" => {
                     let txn = self.get_txn(&command.prefix)?;
@@ -937,6 +1244,15 @@ pub mod tests {
                     txn.commit()?;
                 }
 
+                // txn: release NAME
+                "release" => {
+                    let txn = self.get_txn(&command.prefix)?;
+                    let mut args = command.consume_args();
+                    let name = &args.next_pos().ok_or("savepoint name not given")?.value;
+                    args.reject_rest()?;
+                    txn.release_savepoint(name)?;
+                }
+
                 // txn: resume JSON
                 "resume" => {
                     let name = Self::txn_name(&command.prefix)?;
@@ -956,6 +1272,24 @@ pub mod tests {
                     txn.rollback()?;
                 }
 
+                // txn: rollback_to NAME
+                "rollback_to" => {
+                    let txn = self.get_txn(&command.prefix)?;
+                    let mut args = command.consume_args();
+                    let name = &args.next_pos().ok_or("savepoint name not given")?.value;
+                    args.reject_rest()?;
+                    txn.rollback_to_savepoint(name)?;
+                }
+
+                // txn: savepoint NAME
+                "savepoint" => {
+                    let txn = self.get_txn(&command.prefix)?;
+                    let mut args = command.consume_args();
+                    let name = &args.next_pos().ok_or("savepoint name not given")?.value;
+                    args.reject_rest()?;
+                    txn.savepoint(name)?;
+                }
+
                 // txn: scan [RANGE]
                 "scan" => {
                     let txn = self.get_txn(&command.prefix)?;
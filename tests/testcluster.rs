@@ -114,6 +114,7 @@ impl TestServer {
         writeln!(cfg, "data_dir: {}", dir.to_string_lossy())?;
         writeln!(cfg, "listen_raft: localhost:{raft_port}")?;
         writeln!(cfg, "listen_sql: localhost:{sql_port}")?;
+        writeln!(cfg, "result_cache_size: 100")?;
         writeln!(cfg, "peers: {{")?;
         for (peer_id, (peer_raft_port, _)) in ports.iter().filter(|(peer, _)| **peer != id) {
             writeln!(cfg, "  '{peer_id}': localhost:{peer_raft_port},")?;
@@ -20,15 +20,321 @@ use toydb::{Client, StatementResult};
 test_each_path! { in "tests/scripts" => test_goldenscript }
 
 fn test_goldenscript(path: &std::path::Path) {
-    // We can't run tests concurrently, because the test clusters end up using
-    // the same ports (and we don't want to run a ton of them). We can't use the
-    // #[serial] macro either, since it doesn't work with test_each_path. Just
-    // use a mutex to serialize them, and ignore any poisoning.
+    let _guard = serialize();
+    goldenscript::run(&mut Runner::new(), path).expect("goldenscript failed")
+}
+
+/// Serializes tests that run clusters. We can't run tests concurrently, because
+/// the test clusters end up using the same ports (and we don't want to run a ton
+/// of them). We can't use the #[serial] macro either, since it doesn't work with
+/// test_each_path. Just use a mutex to serialize them, and ignore any poisoning.
+fn serialize() -> std::sync::MutexGuard<'static, ()> {
     static MUTEX: std::sync::OnceLock<std::sync::Mutex<()>> = std::sync::OnceLock::new();
     let mutex = MUTEX.get_or_init(|| std::sync::Mutex::new(()));
-    let _guard = mutex.lock().unwrap_or_else(|error| error.into_inner());
+    mutex.lock().unwrap_or_else(|error| error.into_inner())
+}
 
-    goldenscript::run(&mut Runner::new(), path).expect("goldenscript failed")
+/// Tests that query results are streamed to the client as they're produced,
+/// rather than buffered, and that the client can be reused after dropping a
+/// partially consumed stream.
+#[test]
+fn query_stream() -> Result<(), Box<dyn Error>> {
+    const ROWS: i64 = 10_000;
+    let _guard = serialize();
+    let cluster = TestCluster::run(1)?;
+    let mut client = cluster.connect()?;
+
+    client.execute("CREATE TABLE big (id INTEGER PRIMARY KEY, value STRING)")?;
+    for chunk in &(1..=ROWS).chunks(1000) {
+        let values = chunk.map(|id| format!("({id}, '{}')", "x".repeat(100))).join(", ");
+        client.execute(&format!("INSERT INTO big VALUES {values}"))?;
+    }
+
+    // Stream all rows, checking that they arrive in order.
+    let mut count = 0;
+    let mut rows = client.query("SELECT * FROM big")?;
+    assert_eq!(rows.columns().len(), 2);
+    for row in rows.by_ref() {
+        count += 1;
+        assert_eq!(row?[0], toydb::sql::types::Value::Integer(count));
+    }
+    drop(rows);
+    assert_eq!(count, ROWS);
+
+    // The server's memory use is bounded: it only produces rows as the client
+    // reads them, blocking on the connection otherwise. The first row of a
+    // 100 million row (~20 GB) cross join arrives right away, which wouldn't
+    // be possible if the result was buffered anywhere. Cancel the rest.
+    let canceller = client.canceller();
+    let started = std::time::Instant::now();
+    let mut rows = client.query("SELECT * FROM big a, big b")?;
+    assert!(rows.next().transpose()?.is_some());
+    assert!(started.elapsed() < std::time::Duration::from_secs(5), "first row too slow");
+    canceller.cancel()?;
+    let remaining = rows.by_ref().take_while(|row| row.is_ok()).count();
+    assert!(remaining < 100_000, "read {remaining} rows before cancellation");
+    assert_eq!(rows.next(), None);
+    drop(rows);
+
+    // Dropping a stream after a few rows discards the rest, and the client
+    // can be used again.
+    let mut rows = client.query("SELECT id FROM big")?;
+    assert!(rows.next().transpose()?.is_some());
+    drop(rows);
+    let result: i64 = client.execute("SELECT COUNT(*) FROM big")?.try_into()?;
+    assert_eq!(result, ROWS);
+
+    // Non-queries can't be streamed.
+    assert!(client.query("DELETE FROM big").is_err());
+    Ok(())
+}
+
+/// Tests that a client executes multi-statement scripts, and tracks the
+/// transaction state even when a script fails partway through.
+#[test]
+fn execute_script() -> Result<(), Box<dyn Error>> {
+    let _guard = serialize();
+    let cluster = TestCluster::run(1)?;
+    let mut client = cluster.connect()?;
+
+    let results = client.execute_script(
+        "CREATE TABLE test (id INTEGER PRIMARY KEY); INSERT INTO test VALUES (1), (2);;
+         SELECT COUNT(*) FROM test;",
+    )?;
+    assert_eq!(results.len(), 3);
+    let count: i64 = results.into_iter().last().expect("no result")?.try_into()?;
+    assert_eq!(count, 2);
+
+    // A script that fails partway through returns the results of the prior
+    // statements along with the error. Here, it leaves a transaction open.
+    let results = client.execute_script("BEGIN; INSERT INTO test VALUES (1); SELECT 1")?;
+    assert_eq!(results.len(), 2);
+    assert!(matches!(results[0], Ok(StatementResult::Begin(_))));
+    assert!(results[1].is_err());
+    assert!(client.txn().is_some());
+    client.execute("ROLLBACK")?;
+    assert!(client.txn().is_none());
+    Ok(())
+}
+
+/// Tests that an explicit transaction spans separate requests on the same
+/// connection, and that its writes don't persist unless committed.
+#[test]
+fn connection_transaction() -> Result<(), Box<dyn Error>> {
+    let _guard = serialize();
+    let cluster = TestCluster::run(1)?;
+    let mut client = cluster.connect()?;
+    let mut other = cluster.connect()?;
+
+    client.execute("CREATE TABLE test (id INTEGER PRIMARY KEY)")?;
+
+    // The inserts run in the transaction, and aren't visible to others until
+    // it commits. Rolling back discards them.
+    client.execute("BEGIN")?;
+    assert!(client.txn().is_some());
+    client.execute("INSERT INTO test VALUES (1)")?;
+    client.execute("INSERT INTO test VALUES (2)")?;
+    let count: i64 = client.execute("SELECT COUNT(*) FROM test")?.try_into()?;
+    assert_eq!(count, 2);
+    let count: i64 = other.execute("SELECT COUNT(*) FROM test")?.try_into()?;
+    assert_eq!(count, 0);
+    client.execute("ROLLBACK")?;
+    assert!(client.txn().is_none());
+    let count: i64 = client.execute("SELECT COUNT(*) FROM test")?.try_into()?;
+    assert_eq!(count, 0);
+
+    // COMMIT and ROLLBACK error without an open transaction.
+    assert!(client.execute("COMMIT").is_err());
+    assert!(client.execute("ROLLBACK").is_err());
+
+    // A committed transaction persists.
+    client.execute("BEGIN")?;
+    client.execute("INSERT INTO test VALUES (1)")?;
+    client.execute("COMMIT")?;
+    let count: i64 = other.execute("SELECT COUNT(*) FROM test")?.try_into()?;
+    assert_eq!(count, 1);
+
+    // An open transaction is rolled back when the client disconnects.
+    client.execute("BEGIN")?;
+    client.execute("INSERT INTO test VALUES (2)")?;
+    drop(client);
+    let mut client = cluster.connect()?;
+    let count: i64 = client.execute("SELECT COUNT(*) FROM test")?.try_into()?;
+    assert_eq!(count, 1);
+    Ok(())
+}
+
+/// Tests that a client can cancel a slow query from a different thread, and
+/// that the connection can be used again afterwards.
+#[test]
+fn query_cancel() -> Result<(), Box<dyn Error>> {
+    let _guard = serialize();
+    let cluster = TestCluster::run(1)?;
+    let mut client = cluster.connect()?;
+
+    client.execute("CREATE TABLE test (id INTEGER PRIMARY KEY)")?;
+    let values = (1..=200).map(|id| format!("({id})")).join(", ");
+    client.execute(&format!("INSERT INTO test VALUES {values}"))?;
+
+    // Start a slow query joining 8 million rows, and cancel it mid-flight. It
+    // errors with Error::Cancelled.
+    let canceller = client.canceller();
+    let cancel = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        canceller.cancel()
+    });
+    let started = std::time::Instant::now();
+    let result = client.execute("SELECT COUNT(*) FROM test a, test b, test c");
+    cancel.join().expect("cancel panicked")?;
+    assert_eq!(result, Err(toydb::error::Error::Cancelled));
+    assert!(started.elapsed() < std::time::Duration::from_secs(5), "cancellation too slow");
+
+    // The connection can be used again. A cancellation that arrives when no
+    // request is executing doesn't affect the next request.
+    client.canceller().cancel()?;
+    let count: i64 = client.execute("SELECT COUNT(*) FROM test a, test b")?.try_into()?;
+    assert_eq!(count, 40_000);
+
+    // A cancellation also applies to pipelined requests that are still queued
+    // when it arrives.
+    let canceller = client.canceller();
+    let cancel = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        canceller.cancel()
+    });
+    let started = std::time::Instant::now();
+    let results = client.execute_pipelined(&[
+        "SELECT COUNT(*) FROM test a, test b, test c",
+        "SELECT COUNT(*) FROM test a, test b, test c",
+    ])?;
+    cancel.join().expect("cancel panicked")?;
+    assert_eq!(
+        results,
+        vec![Err(toydb::error::Error::Cancelled), Err(toydb::error::Error::Cancelled)]
+    );
+    assert!(started.elapsed() < std::time::Duration::from_secs(5), "cancellation too slow");
+
+    // Cancelled statements aren't retried by with_retry().
+    let canceller = client.canceller();
+    let cancel = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        canceller.cancel()
+    });
+    let attempts = std::cell::Cell::new(0);
+    let result = client.with_retry(|client| {
+        attempts.set(attempts.get() + 1);
+        client.execute("SELECT COUNT(*) FROM test a, test b, test c")
+    });
+    cancel.join().expect("cancel panicked")?;
+    assert_eq!(result, Err(toydb::error::Error::Cancelled));
+    assert_eq!(attempts.get(), 1);
+    Ok(())
+}
+
+/// Tests that a client can pipeline statements, receiving their results in
+/// order, and that an error in one statement doesn't affect the others.
+#[test]
+fn execute_pipelined() -> Result<(), Box<dyn Error>> {
+    let _guard = serialize();
+    let cluster = TestCluster::run(1)?;
+    let mut client = cluster.connect()?;
+
+    client.execute("CREATE TABLE test (id INTEGER PRIMARY KEY)")?;
+
+    // The second insert fails with a duplicate key, but the statements after
+    // it are still executed.
+    let results = client.execute_pipelined(&[
+        "INSERT INTO test VALUES (1), (2)",
+        "INSERT INTO test VALUES (2)",
+        "SELECT COUNT(*) FROM test",
+    ])?;
+    let mut results = results.into_iter();
+    assert_eq!(results.next(), Some(Ok(StatementResult::Insert { count: 2 })));
+    assert!(matches!(results.next(), Some(Err(toydb::error::Error::InvalidInput(_)))));
+    let count: i64 = results.next().expect("no result")?.try_into()?;
+    assert_eq!(count, 2);
+    assert!(results.next().is_none());
+
+    // Transaction state is tracked across pipelined statements.
+    let results = client.execute_pipelined(&["BEGIN", "INSERT INTO test VALUES (3)"])?;
+    assert!(results.into_iter().all(|r| r.is_ok()));
+    assert!(client.txn().is_some());
+    let results = client.execute_pipelined(&["COMMIT"])?;
+    assert!(results[0].is_ok());
+    assert!(client.txn().is_none());
+
+    // The connection can be used normally afterwards.
+    let count: i64 = client.execute("SELECT COUNT(*) FROM test")?.try_into()?;
+    assert_eq!(count, 3);
+    Ok(())
+}
+
+/// Tests that a client can fetch the server's local Raft node status.
+#[test]
+fn node_status() -> Result<(), Box<dyn Error>> {
+    let _guard = serialize();
+    let cluster = TestCluster::run(1)?;
+    let mut client = cluster.connect()?;
+
+    // A single node is the leader, and has applied at least the initial entry
+    // appended when it became leader.
+    let status = client.node_status()?;
+    assert_eq!(status.id, 1);
+    assert_eq!(status.role, toydb::raft::NodeRole::Leader);
+    assert_eq!(status.leader, Some(1));
+    assert!(status.term > 0);
+    assert!(status.applied_index > 0);
+    assert!(status.last_applied.is_some());
+
+    // Writes advance the commit and applied indexes.
+    client.execute("CREATE TABLE test (id INTEGER PRIMARY KEY)")?;
+    let after = client.node_status()?;
+    assert!(after.commit_index > status.commit_index);
+    assert!(after.applied_index > status.applied_index);
+    assert_eq!(after.term, status.term);
+    Ok(())
+}
+
+/// Benchmarks BitCask write throughput under each durability mode, flushing
+/// after every write like the Raft log does. Timings depend on the hardware,
+/// so this is ignored by default and only prints them. Run with:
+///
+/// cargo test --release --test tests -- --ignored --nocapture storage_durability
+///
+/// The fsync behavior of each mode is tested in storage::bitcask.
+#[test]
+#[ignore]
+fn storage_durability() -> Result<(), Box<dyn Error>> {
+    use toydb::storage::{BitCask, Durability, Engine as _};
+    const WRITES: u64 = 200;
+
+    let dir = tempfile::TempDir::with_prefix("toydb")?;
+    let modes = [
+        ("always", Durability::Always),
+        ("interval", Durability::Interval(std::time::Duration::from_millis(10))),
+        ("never", Durability::Never),
+    ];
+    for (name, durability) in modes {
+        let path = dir.path().join(name);
+        let mut engine = BitCask::new(path.clone())?.with_durability(durability);
+        let start = std::time::Instant::now();
+        for i in 0..WRITES {
+            engine.set(&i.to_be_bytes(), vec![0xaa; 100])?;
+            engine.flush()?;
+        }
+        let elapsed = start.elapsed();
+        println!(
+            "{name:<8} {WRITES} writes in {elapsed:.2?} ({:.0} writes/s)",
+            WRITES as f64 / elapsed.as_secs_f64()
+        );
+
+        drop(engine);
+        let mut engine = BitCask::new(path)?;
+        for i in 0..WRITES {
+            assert_eq!(engine.get(&i.to_be_bytes())?, Some(vec![0xaa; 100]));
+        }
+    }
+    Ok(())
 }
 
 /// Runs Raft goldenscript tests. See run() for available commands.
@@ -134,6 +440,22 @@ impl goldenscript::Runner for Runner {
         let client = self.get_client(&command.prefix)?;
         let input = &command.name;
 
+        // If requested, stream the query result rows.
+        if tags.remove("stream") {
+            let mut rows = client.query(input)?;
+            if tags.remove("header") {
+                writeln!(output, "{}", rows.columns().iter().join(", "))?;
+            }
+            for row in rows.by_ref() {
+                writeln!(output, "{}", row?.into_iter().join(", "))?;
+            }
+            drop(rows);
+            if let Some(tag) = tags.iter().next() {
+                return Err(format!("invalid tag {tag}").into());
+            }
+            return Ok(output);
+        }
+
         // Execute the command and display the result if requested.
         // SELECT and EXPLAIN results are always output.
         let result = client.execute(input)?;
@@ -147,7 +469,10 @@ impl goldenscript::Runner for Runner {
                     writeln!(output, "{}", row.into_iter().join(", "))?;
                 }
             }
-            StatementResult::Explain(root) => writeln!(output, "{root}")?,
+            StatementResult::Explain { plan, unoptimized: None, .. } => writeln!(output, "{plan}")?,
+            StatementResult::Explain { plan, unoptimized: Some(unoptimized), .. } => {
+                writeln!(output, "Unoptimized:\n{unoptimized}\n\nOptimized:\n{plan}")?
+            }
             result if tags.remove("result") => writeln!(output, "{result:?}")?,
             _ => {}
         }